@@ -1,18 +1,153 @@
 use async_trait::async_trait;
+use futures::StreamExt;
+use open_router_blueprint_template_lib::config::LlmConfig;
+use open_router_blueprint_template_lib::correlation::apply_correlation_header;
 use open_router_blueprint_template_lib::llm::{
-    ChatCompletionRequest, ChatCompletionResponse, LlmClient, LlmError, ModelInfo, NodeMetrics,
+    choice_count, create_chat_completion_stream, embed_concurrently, with_request_timeout,
+    BackendVersion, ChatCompletionChunk, ChatCompletionRequest, ChatCompletionResponse,
+    ChatCompletionStream, ChatCompletionStreamChoice, ChatMessage, ChatMessageDelta,
+    EmbeddingResponse, LlmClient, LlmError, MetricsWindow, ModelInfo, NodeInfo, NodeMetrics,
+    StreamingLlmClient, TextCompletionChunk, TextCompletionRequest, TextCompletionStream,
+    TextCompletionStreamChoice, DEFAULT_EMBEDDING_CONCURRENCY, DEFAULT_REQUEST_TIMEOUT,
 };
 use reqwest::Client;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::future::Future;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, OnceCell};
 use tracing::{debug, error, info, trace, warn};
 
 pub struct OllamaLlmClient {
     pub api_url: String,
     pub model: String,
+    /// Operator-supplied model metadata, returned instead of the derived default when set
+    pub models: Vec<ModelInfo>,
+    /// Maximum number of embedding inputs sent to Ollama at the same time
+    pub embedding_concurrency: usize,
     pub metrics: Arc<RwLock<NodeMetrics>>,
+    /// Completed requests, counted into `requests_per_minute`
+    requests: RwLock<MetricsWindow>,
     pub http_client: Client,
+    /// Time a completion or embedding request may take before it fails with `LlmError::Timeout`
+    pub timeout: Duration,
+    /// Version reported by `/api/version`, probed once on first use
+    version: OnceCell<Option<String>>,
+    /// Set once `/api/chat` answered 404, so chat requests go straight to `/api/generate`
+    chat_api_missing: AtomicBool,
+}
+
+/// First Ollama release with the `/api/chat` endpoint; older releases only have `/api/generate`
+pub const OLLAMA_CHAT_API_VERSION: BackendVersion = BackendVersion::new(0, 1, 14);
+
+/// Generation options of an Ollama request
+#[derive(Serialize)]
+struct OllamaOptions {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+}
+
+impl OllamaOptions {
+    /// The options for `request`, if it sets any Ollama takes as an option
+    fn for_request(request: &ChatCompletionRequest) -> Option<Self> {
+        let stop = request.stop.clone()?;
+        Some(Self { stop: Some(stop) })
+    }
+}
+
+/// Body of an Ollama `/api/generate` request
+#[derive(Serialize)]
+struct OllamaGenerateRequest {
+    model: String,
+    prompt: String,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<OllamaOptions>,
+}
+
+/// A chat message as sent to and returned by `/api/chat`
+#[derive(Serialize, Deserialize, Debug)]
+struct OllamaMessage {
+    role: String,
+    content: String,
+    // Reasoning of thinking models, kept separate from the answer by `/api/chat`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    thinking: Option<String>,
+}
+
+/// Body of an Ollama `/api/chat` request
+#[derive(Serialize)]
+struct OllamaChatRequest {
+    model: String,
+    messages: Vec<OllamaMessage>,
+    stream: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    options: Option<OllamaOptions>,
+}
+
+/// A generation result, or one line of a streamed one
+///
+/// `/api/generate` answers in `response`, `/api/chat` in `message`. Streamed lines carry the
+/// next piece of the answer and the last one has `done` set.
+#[derive(Deserialize, Debug)]
+struct OllamaResponse {
+    model: String,
+    #[serde(default)]
+    response: String,
+    #[serde(default)]
+    message: Option<OllamaMessage>,
+    #[serde(default)]
+    done: bool,
+}
+
+impl OllamaResponse {
+    /// Parse the body of a non-streaming reply
+    ///
+    /// Some Ollama builds answer in newline-delimited chunks even with `stream: false`; their
+    /// pieces are joined into a single reply.
+    fn parse_reply(body: &str) -> serde_json::Result<Self> {
+        let error = match serde_json::from_str::<Self>(body) {
+            Ok(reply) => return Ok(reply),
+            Err(e) => e,
+        };
+
+        let mut lines = body.lines().map(str::trim).filter(|line| !line.is_empty());
+        let Some(first) = lines.next() else {
+            return Err(error);
+        };
+        let mut reply: Self = serde_json::from_str(first)?;
+        for line in lines {
+            reply.append(serde_json::from_str(line)?);
+        }
+        Ok(reply)
+    }
+
+    /// Append the next chunk of a reply sent in pieces
+    fn append(&mut self, chunk: Self) {
+        self.response.push_str(&chunk.response);
+        if let Some(next) = chunk.message {
+            match &mut self.message {
+                Some(message) => {
+                    message.content.push_str(&next.content);
+                    message.thinking = match (message.thinking.take(), next.thinking) {
+                        (Some(thinking), Some(next)) => Some(thinking + &next),
+                        (thinking, next) => thinking.or(next),
+                    };
+                }
+                None => self.message = Some(next),
+            }
+        }
+        self.done = chunk.done;
+    }
+
+    /// The answer and reasoning text, whichever endpoint produced them
+    fn into_content(self) -> (String, Option<String>) {
+        match self.message {
+            Some(message) => (message.content, message.thinking),
+            None => (self.response, None),
+        }
+    }
 }
 
 impl OllamaLlmClient {
@@ -24,130 +159,262 @@ impl OllamaLlmClient {
         Self {
             api_url,
             model,
+            models: Vec::new(),
+            embedding_concurrency: DEFAULT_EMBEDDING_CONCURRENCY,
             metrics: Arc::new(RwLock::new(NodeMetrics {
-                cpu_utilization: 0.0,
-                memory_utilization: 0.0,
-                gpu_utilization: None,
-                requests_per_minute: 0,
-                average_response_time_ms: 0,
-                active_requests: 0,
                 last_updated: std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
                     .as_secs(),
+                ..Default::default()
             })),
+            requests: RwLock::new(MetricsWindow::new()),
             http_client: Client::new(),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            version: OnceCell::new(),
+            chat_api_missing: AtomicBool::new(false),
         }
     }
-}
-
-#[async_trait]
-impl LlmClient for OllamaLlmClient {
-    fn get_supported_models(&self) -> Vec<ModelInfo> {
-        debug!("Checking if model '{}' exists in Ollama", self.model);
-        // Check if the model exists in Ollama
-        let valid_model = futures::executor::block_on(async {
-            let url = format!("{}/api/tags", self.api_url);
-            trace!("Sending request to {}", url);
-            let res = self.http_client.get(&url).send().await;
-            if let Ok(response) = res {
-                if response.status().is_success() {
-                    #[derive(Deserialize)]
-                    struct OllamaModels {
-                        models: Vec<OllamaModel>,
-                    }
-
-                    #[derive(Deserialize)]
-                    struct OllamaModel {
-                        name: String,
-                    }
 
-                    if let Ok(models) = response.json::<OllamaModels>().await {
-                        let is_valid = models.models.iter().any(|m| m.name == self.model);
-                        debug!("Model '{}' validation result: {}", self.model, is_valid);
-                        return is_valid;
-                    }
-                }
-            } else if let Err(e) = res {
-                warn!("Failed to get Ollama models: {}", e);
-            }
-            debug!(
-                "Model validation failed, assuming model '{}' is invalid",
-                self.model
+    /// Create a client for the `llm` section of a blueprint configuration
+    ///
+    /// The client serves the first of `models` at `api_url`, with the configured timeout,
+    /// embedding concurrency and HTTP options.
+    pub fn from_config(config: &LlmConfig) -> Self {
+        let model = config
+            .models
+            .first()
+            .map(|model| model.id.clone())
+            .unwrap_or_default();
+        let http_client = config.http_client_builder().build().unwrap_or_else(|e| {
+            warn!(
+                "Failed to build the configured HTTP client, using the default: {}",
+                e
             );
-            false
+            Client::new()
         });
+        Self::new(config.api_url.clone(), model)
+            .with_models(config.models.clone())
+            .with_embedding_concurrency(config.embedding_concurrency)
+            .with_http_client(http_client)
+            .with_timeout(Duration::from_secs(config.timeout_seconds))
+    }
 
-        if !valid_model {
-            // Return empty list for unsupported model
-            warn!(
-                "Model '{}' is not available in Ollama, returning empty model list",
-                self.model
-            );
-            return vec![];
-        }
+    /// Override the model metadata reported by this client.
+    ///
+    /// Each entry is only reported by `get_supported_models` while the model is actually
+    /// available on the Ollama backend.
+    pub fn with_models(mut self, models: Vec<ModelInfo>) -> Self {
+        self.models = models;
+        self
+    }
 
-        info!("Model '{}' is available in Ollama", self.model);
-        vec![ModelInfo {
-            id: self.model.clone(),
-            name: self.model.clone(),
-            max_context_length: 4096,
-            supports_chat: true,
-            supports_text: true,
-            supports_embeddings: false,
-            parameters: Default::default(),
-        }]
+    /// Set how many embedding inputs are sent to Ollama at the same time.
+    ///
+    /// Ollama embeds a single input per request, so multi-input requests fan out in parallel.
+    pub fn with_embedding_concurrency(mut self, embedding_concurrency: usize) -> Self {
+        self.embedding_concurrency = embedding_concurrency.max(1);
+        self
     }
 
-    fn get_capabilities(&self) -> open_router_blueprint_template_lib::llm::LlmCapabilities {
-        open_router_blueprint_template_lib::llm::LlmCapabilities {
-            supports_streaming: false,
-            max_concurrent_requests: 1,
-            supports_batching: false,
-            features: Default::default(),
-        }
+    /// Use a preconfigured HTTP client, e.g. one built from
+    /// `LlmConfig::http_client_builder` to enable HTTP/2 and keep-alive pings.
+    pub fn with_http_client(mut self, http_client: Client) -> Self {
+        self.http_client = http_client;
+        self
     }
 
-    fn get_metrics(&self) -> NodeMetrics {
-        futures::executor::block_on(async { self.metrics.read().await.clone() })
+    /// Fail completion and embedding requests that take longer than `timeout`, e.g. the
+    /// `llm.timeout_seconds` config value. Streamed responses are not limited.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
     }
 
-    async fn chat_completion(
+    /// Run `request` with the request timeout, counting it in the metrics
+    async fn tracked<T>(
         &self,
-        request: ChatCompletionRequest,
-    ) -> Result<ChatCompletionResponse, LlmError> {
-        info!(
-            "Processing chat completion request for model: {}",
-            request.model
-        );
+        request: impl Future<Output = Result<T, LlmError>>,
+    ) -> Result<T, LlmError> {
+        self.record_request_start();
+        let started = Instant::now();
+        let result = with_request_timeout(self.timeout, request).await;
+        self.record_request_end(started.elapsed().as_millis() as u64);
+        result
+    }
 
-        // Check if the requested model is supported
-        let supported_models = self.get_supported_models();
-        let model_supported = supported_models.iter().any(|m| m.id == request.model);
+    fn record_request_start(&self) {
+        let mut metrics = self.metrics.write().unwrap_or_else(PoisonError::into_inner);
+        metrics.active_requests += 1;
+    }
 
-        if !model_supported {
-            error!("Model '{}' is not available in Ollama", request.model);
-            return Err(LlmError::ModelNotSupported(format!(
-                "Model '{}' is not available in Ollama",
-                request.model
-            )));
-        }
+    fn record_request_end(&self, duration_ms: u64) {
+        let mut metrics = self.metrics.write().unwrap_or_else(PoisonError::into_inner);
+        metrics.active_requests = metrics.active_requests.saturating_sub(1);
+
+        // Update average response time with exponential moving average
+        const ALPHA: f64 = 0.1; // Weight for new samples
+        let old_avg = metrics.average_response_time_ms as f64;
+        let new_avg = old_avg * (1.0 - ALPHA) + (duration_ms as f64) * ALPHA;
+        metrics.average_response_time_ms = new_avg as u64;
+
+        let mut requests = self
+            .requests
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        requests.record();
+        metrics.requests_per_minute = requests.count();
+    }
 
-        debug!("Building Ollama API request for model: {}", self.model);
-        // Build Ollama API request
+    /// The version of the Ollama backend, probed with `GET /api/version` on the first call
+    ///
+    /// A failed probe is not retried; features that depend on the version are then detected
+    /// from the answers of their endpoints.
+    pub async fn detect_version(&self) -> Option<BackendVersion> {
+        let version = self
+            .version
+            .get_or_init(|| async {
+                #[derive(Deserialize)]
+                struct OllamaVersion {
+                    version: String,
+                }
+
+                let url = format!("{}/api/version", self.api_url);
+                trace!("Probing Ollama version at {}", url);
+                let version = match self.http_client.get(&url).send().await {
+                    Ok(res) if res.status().is_success() => {
+                        res.json::<OllamaVersion>().await.map(|v| v.version).ok()
+                    }
+                    Ok(res) => {
+                        debug!("Ollama version probe returned {}", res.status());
+                        None
+                    }
+                    Err(e) => {
+                        debug!("Failed to probe Ollama version: {}", e);
+                        None
+                    }
+                };
+                match &version {
+                    Some(version) => info!("Detected Ollama version {}", version),
+                    None => warn!("Could not detect Ollama version"),
+                }
+                version
+            })
+            .await;
+        version.as_deref().and_then(BackendVersion::parse)
+    }
+
+    /// Embed a single input with the Ollama embeddings API
+    async fn embed_one(&self, model: &str, input: String) -> Result<Vec<f32>, LlmError> {
         #[derive(Serialize)]
-        struct OllamaRequest {
-            model: String,
+        struct OllamaEmbeddingRequest<'a> {
+            model: &'a str,
             prompt: String,
-            stream: bool,
         }
 
-        #[derive(Deserialize, Debug)]
-        struct OllamaResponse {
-            model: String,
-            created_at: String,
-            response: String,
-            done: bool,
+        #[derive(Deserialize)]
+        struct OllamaEmbeddingResponse {
+            embedding: Vec<f32>,
+        }
+
+        let url = format!("{}/api/embeddings", self.api_url);
+        trace!("Sending embedding request to {}", url);
+        let res = apply_correlation_header(self.http_client.post(&url))
+            .json(&OllamaEmbeddingRequest {
+                model,
+                prompt: input,
+            })
+            .send()
+            .await
+            .map_err(|e| {
+                error!("Failed to send embedding request to Ollama: {}", e);
+                LlmError::RequestFailed(e.to_string())
+            })?;
+
+        if !res.status().is_success() {
+            let status = res.status();
+            let err_text = res.text().await.unwrap_or_default();
+            error!("Ollama API error ({}): {}", status, err_text);
+            return Err(LlmError::RequestFailed(format!(
+                "Ollama API error ({}): {}",
+                status, err_text
+            )));
+        }
+
+        res.json::<OllamaEmbeddingResponse>()
+            .await
+            .map(|r| r.embedding)
+            .map_err(|e| {
+                error!("Failed to parse Ollama embedding response: {}", e);
+                LlmError::RequestFailed(format!("Failed to parse Ollama embedding response: {}", e))
+            })
+    }
+
+    /// Fail unless Ollama currently serves `model`
+    fn ensure_model_supported(&self, model: &str) -> Result<(), LlmError> {
+        if self.get_supported_models().iter().any(|m| m.id == model) {
+            return Ok(());
+        }
+        error!("Model '{}' is not available in Ollama", model);
+        Err(LlmError::ModelNotSupported(format!(
+            "Model '{}' is not available in Ollama",
+            model
+        )))
+    }
+
+    /// Send a chat request to the generation endpoint of this Ollama release
+    ///
+    /// Messages go to `/api/chat` with their roles, so the model applies its own chat
+    /// template. Releases without that endpoint, known from their version or from a 404
+    /// answer of `/api/chat`, are sent the messages flattened into a `/api/generate` prompt.
+    ///
+    /// Returns the response once Ollama accepted the request; with `stream` set its body is
+    /// newline-delimited JSON.
+    async fn send_chat_request(
+        &self,
+        request: &ChatCompletionRequest,
+        stream: bool,
+    ) -> Result<reqwest::Response, LlmError> {
+        debug!("Building Ollama API request for model: {}", request.model);
+
+        let use_chat_api = !self.chat_api_missing.load(Ordering::Relaxed)
+            && self
+                .detect_version()
+                .await
+                .map_or(true, |version| version >= OLLAMA_CHAT_API_VERSION);
+
+        if use_chat_api {
+            let ollama_req = OllamaChatRequest {
+                model: request.model.clone(),
+                messages: request
+                    .messages
+                    .iter()
+                    .map(|m| OllamaMessage {
+                        role: m.role.clone(),
+                        content: m.content.clone(),
+                        thinking: None,
+                    })
+                    .collect(),
+                stream,
+                options: OllamaOptions::for_request(request),
+            };
+            let url = format!("{}/api/chat", self.api_url);
+            let res = self.post_generation(&url, &ollama_req, &request.model).await?;
+            if res.status() != reqwest::StatusCode::NOT_FOUND {
+                return self.check_generation_status(res, &request.model).await;
+            }
+
+            // Ollama answers 404 for an unknown model as well, naming the model
+            let err_text = res.text().await.unwrap_or_default();
+            if err_text.contains("model") {
+                error!("Model not supported error: {}", err_text);
+                return Err(LlmError::ModelNotSupported(format!(
+                    "Model '{}' not supported: {}",
+                    &request.model, err_text
+                )));
+            }
+            warn!("Ollama has no /api/chat endpoint, falling back to /api/generate");
+            self.chat_api_missing.store(true, Ordering::Relaxed);
         }
 
         // Convert chat messages to a prompt string
@@ -163,92 +430,144 @@ impl LlmClient for OllamaLlmClient {
             request.messages.len()
         );
 
-        let ollama_req = OllamaRequest {
-            model: self.model.clone(),
+        let ollama_req = OllamaGenerateRequest {
+            model: request.model.clone(),
             prompt,
-            stream: false,
+            stream,
+            options: OllamaOptions::for_request(request),
         };
-
-        // Ollama API endpoint
         let url = format!("{}/api/generate", self.api_url);
+        let res = self.post_generation(&url, &ollama_req, &request.model).await?;
+        self.check_generation_status(res, &request.model).await
+    }
+
+    /// Post a generation request body to `url`
+    async fn post_generation(
+        &self,
+        url: &str,
+        body: &impl Serialize,
+        model: &str,
+    ) -> Result<reqwest::Response, LlmError> {
         debug!("Sending request to Ollama API: {}", url);
 
         // Send request to Ollama API
-        let res = self.http_client.post(&url).json(&ollama_req).send().await;
+        let res = apply_correlation_header(self.http_client.post(url))
+            .json(body)
+            .send()
+            .await;
 
-        let res = match res {
-            Ok(response) => response,
+        match res {
+            Ok(response) => Ok(response),
             Err(e) => {
                 // Check if error message indicates model not found
                 let err_msg = e.to_string();
                 error!("Failed to send request to Ollama: {}", err_msg);
 
                 if err_msg.contains("model not found") || err_msg.contains("failed to load model") {
-                    return Err(LlmError::ModelNotSupported(format!(
+                    Err(LlmError::ModelNotSupported(format!(
                         "Model '{}' not found in Ollama",
-                        &self.model
-                    )));
+                        model
+                    )))
                 } else {
-                    return Err(LlmError::RequestFailed(err_msg));
+                    Err(LlmError::RequestFailed(err_msg))
                 }
             }
-        };
+        }
+    }
 
-        // Check response status code
-        if !res.status().is_success() {
-            // Try to parse error response
-            #[derive(Deserialize, Debug)]
-            struct OllamaErrorResponse {
-                error: String,
+    /// Fail for a generation response that does not have a success status
+    async fn check_generation_status(
+        &self,
+        res: reqwest::Response,
+        model: &str,
+    ) -> Result<reqwest::Response, LlmError> {
+        if res.status().is_success() {
+            return Ok(res);
+        }
+
+        let status = res.status();
+        warn!("Ollama API returned non-success status: {}", status);
+
+        let err_text = match res.text().await {
+            Ok(text) => {
+                trace!("Error response body: {}", text);
+                text
+            }
+            Err(e) => {
+                warn!("Failed to read error response body: {}", e);
+                String::default()
             }
+        };
 
-            let status = res.status();
-            warn!("Ollama API returned non-success status: {}", status);
+        // Check for model not found errors
+        if status.as_u16() == 404
+            || status.as_u16() == 400
+            || err_text.contains("model not found")
+            || err_text.contains("failed to load")
+        {
+            error!("Model not supported error: {}", err_text);
+            return Err(LlmError::ModelNotSupported(format!(
+                "Model '{}' not supported: {}",
+                model, err_text
+            )));
+        }
 
-            let err_text = match res.text().await {
-                Ok(text) => {
-                    trace!("Error response body: {}", text);
-                    text
-                }
-                Err(e) => {
-                    warn!("Failed to read error response body: {}", e);
-                    String::default()
-                }
-            };
+        error!("Ollama API error ({}): {}", status, err_text);
+        Err(LlmError::RequestFailed(format!(
+            "Ollama API error ({}): {}",
+            status, err_text
+        )))
+    }
 
-            // Check for model not found errors
-            if status.as_u16() == 404
-                || status.as_u16() == 400
-                || err_text.contains("model not found")
-                || err_text.contains("failed to load")
-            {
-                error!("Model not supported error: {}", err_text);
-                return Err(LlmError::ModelNotSupported(format!(
-                    "Model '{}' not supported: {}",
-                    &self.model, err_text
-                )));
-            }
+    /// Send a chat completion request to Ollama, without a timeout
+    async fn request_chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, LlmError> {
+        info!(
+            "Processing chat completion request for model: {}",
+            request.model
+        );
 
-            error!("Ollama API error ({}): {}", status, err_text);
-            return Err(LlmError::RequestFailed(format!(
-                "Ollama API error ({}): {}",
-                status, err_text
+        // Check if the requested model is supported
+        let supported_models = self.get_supported_models();
+        let model_supported = supported_models.iter().any(|m| m.id == request.model);
+
+        if !model_supported {
+            error!("Model '{}' is not available in Ollama", request.model);
+            return Err(LlmError::ModelNotSupported(format!(
+                "Model '{}' is not available in Ollama",
+                request.model
             )));
         }
 
-        debug!("Successfully received response from Ollama, parsing JSON");
+        ensure_no_tools(&request)?;
 
-        // Parse successful response
-        let ollama_resp = match res.json::<OllamaResponse>().await {
-            Ok(response) => response,
-            Err(e) => {
-                error!("Failed to parse Ollama response: {}", e);
-                return Err(LlmError::RequestFailed(format!(
-                    "Failed to parse Ollama response: {}",
-                    e
-                )));
-            }
-        };
+        // Ollama has no `n`, so every choice is generated by a request of its own
+        let n = choice_count(request.n)?;
+        let replies =
+            futures::future::join_all((0..n).map(|_| self.generate_reply(&request))).await;
+
+        let mut model = request.model.clone();
+        let mut choices = Vec::with_capacity(replies.len());
+        for (index, reply) in replies.into_iter().enumerate() {
+            let reply = reply?;
+            model = reply.model.clone();
+            let (content, reasoning_content) = reply.into_content();
+            choices.push(
+                open_router_blueprint_template_lib::llm::ChatCompletionChoice {
+                    index,
+                    message: open_router_blueprint_template_lib::llm::ChatMessage {
+                        role: "assistant".to_string(),
+                        reasoning_content,
+                        content,
+                        ..Default::default()
+                    },
+                    finish_reason: Some("stop".to_string()),
+                    ..Default::default()
+                },
+            );
+        }
 
         let response_id = uuid::Uuid::new_v4().to_string();
         info!(
@@ -260,23 +579,35 @@ impl LlmClient for OllamaLlmClient {
             id: response_id,
             object: "chat.completion".to_string(),
             created: chrono::Utc::now().timestamp() as u64,
-            model: ollama_resp.model,
-            choices: vec![
-                open_router_blueprint_template_lib::llm::ChatCompletionChoice {
-                    index: 0,
-                    message: open_router_blueprint_template_lib::llm::ChatMessage {
-                        role: "assistant".to_string(),
-                        name: None,
-                        content: ollama_resp.response,
-                    },
-                    finish_reason: Some("stop".to_string()),
-                },
-            ],
+            model,
+            choices,
             usage: None,
+            cost: None,
+            correlation_id: None,
         })
     }
 
-    async fn text_completion(
+    /// Send a non-streaming chat request to Ollama and parse its single reply
+    async fn generate_reply(
+        &self,
+        request: &ChatCompletionRequest,
+    ) -> Result<OllamaResponse, LlmError> {
+        let res = self.send_chat_request(request, false).await?;
+
+        debug!("Successfully received response from Ollama, parsing JSON");
+
+        let body = res.text().await.map_err(|e| {
+            error!("Failed to read Ollama response: {}", e);
+            LlmError::RequestFailed(format!("Failed to read Ollama response: {}", e))
+        })?;
+        OllamaResponse::parse_reply(&body).map_err(|e| {
+            error!("Failed to parse Ollama response: {}", e);
+            LlmError::RequestFailed(format!("Failed to parse Ollama response: {}", e))
+        })
+    }
+
+    /// Send a text completion request to Ollama, without a timeout
+    async fn request_text_completion(
         &self,
         request: open_router_blueprint_template_lib::llm::TextCompletionRequest,
     ) -> Result<open_router_blueprint_template_lib::llm::TextCompletionResponse, LlmError> {
@@ -306,12 +637,18 @@ impl LlmClient for OllamaLlmClient {
             model: request.model.clone(),
             messages: vec![open_router_blueprint_template_lib::llm::ChatMessage {
                 role: "user".to_string(),
-                name: None,
                 content: request.prompt,
+                ..Default::default()
             }],
             max_tokens: None,
             temperature: None,
             top_p: None,
+            stop: request.stop,
+            n: request.n,
+            service_tier: None,
+            session_id: None,
+            tools: None,
+            tool_choice: None,
             stream: None,
             additional_params: std::collections::HashMap::new(),
         };
@@ -325,14 +662,21 @@ impl LlmClient for OllamaLlmClient {
             object: chat_resp.object,
             created: chat_resp.created,
             model: chat_resp.model,
-            choices: vec![
-                open_router_blueprint_template_lib::llm::TextCompletionChoice {
-                    index: 0,
-                    text: chat_resp.choices[0].message.content.clone(),
-                    finish_reason: chat_resp.choices[0].finish_reason.clone(),
-                },
-            ],
-            usage: None,
+            choices: chat_resp
+                .choices
+                .into_iter()
+                .map(
+                    |choice| open_router_blueprint_template_lib::llm::TextCompletionChoice {
+                        index: choice.index,
+                        text: choice.message.content,
+                        finish_reason: choice.finish_reason,
+                        ..Default::default()
+                    },
+                )
+                .collect(),
+            usage: chat_resp.usage,
+            cost: None,
+            correlation_id: None,
         };
 
         info!(
@@ -342,15 +686,342 @@ impl LlmClient for OllamaLlmClient {
         Ok(response)
     }
 
+    /// Embed the inputs of a request with Ollama, without a timeout
+    async fn request_embeddings(
+        &self,
+        request: open_router_blueprint_template_lib::llm::EmbeddingRequest,
+    ) -> Result<EmbeddingResponse, LlmError> {
+        info!(
+            "Processing embedding request for model: {} ({} inputs)",
+            request.model,
+            request.input.len()
+        );
+
+        // Check if the requested model is supported
+        let supported_models = self.get_supported_models();
+        if !supported_models.iter().any(|m| m.id == request.model) {
+            error!(
+                "Model '{}' is not available in Ollama for embeddings",
+                request.model
+            );
+            return Err(LlmError::ModelNotSupported(format!(
+                "Model '{}' is not available in Ollama",
+                request.model
+            )));
+        }
+
+        let model = request.model.as_str();
+        let data = embed_concurrently(request.input.clone(), self.embedding_concurrency, |input| {
+            self.embed_one(model, input)
+        })
+        .await?;
+
+        info!("Successfully embedded {} inputs", data.len());
+        Ok(EmbeddingResponse {
+            object: "list".to_string(),
+            model: request.model,
+            data,
+            usage: None,
+            correlation_id: None,
+        })
+    }
+}
+
+#[async_trait]
+impl LlmClient for OllamaLlmClient {
+    fn get_supported_models(&self) -> Vec<ModelInfo> {
+        debug!("Checking if model '{}' exists in Ollama", self.model);
+        // Fetch the models currently served by Ollama
+        let available_models = futures::executor::block_on(async {
+            let url = format!("{}/api/tags", self.api_url);
+            trace!("Sending request to {}", url);
+            let res = self.http_client.get(&url).send().await;
+            if let Ok(response) = res {
+                if response.status().is_success() {
+                    #[derive(Deserialize)]
+                    struct OllamaModels {
+                        models: Vec<OllamaModel>,
+                    }
+
+                    #[derive(Deserialize)]
+                    struct OllamaModel {
+                        name: String,
+                    }
+
+                    if let Ok(models) = response.json::<OllamaModels>().await {
+                        return models.models.into_iter().map(|m| m.name).collect();
+                    }
+                }
+            } else if let Err(e) = res {
+                warn!("Failed to get Ollama models: {}", e);
+            }
+            Vec::new()
+        });
+
+        if !self.models.is_empty() {
+            // Only report the supplied metadata for models the backend actually serves
+            let models: Vec<ModelInfo> = self
+                .models
+                .iter()
+                .filter(|m| available_models.contains(&m.id))
+                .cloned()
+                .collect();
+            debug!(
+                "{} of {} configured models are available in Ollama",
+                models.len(),
+                self.models.len()
+            );
+            return models;
+        }
+
+        let valid_model = available_models.contains(&self.model);
+        debug!("Model '{}' validation result: {}", self.model, valid_model);
+
+        if !valid_model {
+            // Return empty list for unsupported model
+            warn!(
+                "Model '{}' is not available in Ollama, returning empty model list",
+                self.model
+            );
+            return vec![];
+        }
+
+        info!("Model '{}' is available in Ollama", self.model);
+        vec![ModelInfo {
+            id: self.model.clone(),
+            name: self.model.clone(),
+            max_context_length: 4096,
+            supports_chat: true,
+            supports_text: true,
+            supports_embeddings: true,
+            parameters: Default::default(),
+        }]
+    }
+
+    fn get_capabilities(&self) -> open_router_blueprint_template_lib::llm::LlmCapabilities {
+        open_router_blueprint_template_lib::llm::LlmCapabilities {
+            supports_streaming: true,
+            max_concurrent_requests: 1,
+            supports_batching: false,
+            features: Default::default(),
+        }
+    }
+
+    fn get_metrics(&self) -> NodeMetrics {
+        self.metrics
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+
+    fn get_node_info(&self) -> NodeInfo {
+        NodeInfo {
+            backend: "ollama".to_string(),
+            version: self.version.get().cloned().flatten(),
+        }
+    }
+
+    fn streaming_client(&self) -> Option<&dyn StreamingLlmClient> {
+        Some(self)
+    }
+
+    async fn health_check(&self) -> Result<(), LlmError> {
+        // The root answers "Ollama is running" without touching any model
+        let url = format!("{}/", self.api_url);
+        trace!("Checking Ollama health at {}", url);
+        match self.http_client.get(&url).send().await {
+            Ok(res) if res.status().is_success() => Ok(()),
+            Ok(res) => Err(LlmError::RequestFailed(format!(
+                "Ollama health check returned {}",
+                res.status()
+            ))),
+            Err(e) => Err(LlmError::RequestFailed(format!(
+                "Ollama health check failed: {}",
+                e
+            ))),
+        }
+    }
+
+    async fn chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, LlmError> {
+        self.tracked(self.request_chat_completion(request)).await
+    }
+
+    async fn text_completion(
+        &self,
+        request: open_router_blueprint_template_lib::llm::TextCompletionRequest,
+    ) -> Result<open_router_blueprint_template_lib::llm::TextCompletionResponse, LlmError> {
+        self.tracked(self.request_text_completion(request)).await
+    }
+
     async fn embeddings(
         &self,
         request: open_router_blueprint_template_lib::llm::EmbeddingRequest,
-    ) -> Result<open_router_blueprint_template_lib::llm::EmbeddingResponse, LlmError> {
-        info!("Processing embedding request for model: {}", request.model);
-        warn!("Embeddings are not implemented in this Ollama blueprint example");
+    ) -> Result<EmbeddingResponse, LlmError> {
+        self.tracked(self.request_embeddings(request)).await
+    }
+}
+
+/// Build the Ollama client of an `llm` configuration section, the `LlmClientBuilder` of the
+/// `ollama` backend
+pub fn build_ollama_client(config: &LlmConfig) -> Arc<dyn LlmClient> {
+    Arc::new(OllamaLlmClient::from_config(config))
+}
+
+/// Fail chat requests that offer tools, since this client does not support function calling
+fn ensure_no_tools(request: &ChatCompletionRequest) -> Result<(), LlmError> {
+    if request.tools.is_some() {
+        error!("Rejecting chat request with tools for Ollama");
+        return Err(LlmError::InvalidRequest(
+            "tool calling is not supported by the Ollama client".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+#[async_trait]
+impl StreamingLlmClient for OllamaLlmClient {
+    async fn streaming_chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, LlmError> {
+        info!(
+            "Processing streaming chat completion request for model: {}",
+            request.model
+        );
+        self.ensure_model_supported(&request.model)?;
+        ensure_no_tools(&request)?;
+
+        let res = self.send_chat_request(&request, true).await?;
+        Ok(create_chat_completion_stream(read_ndjson_chunks(res)))
+    }
+
+    async fn streaming_text_completion(
+        &self,
+        request: TextCompletionRequest,
+    ) -> Result<TextCompletionStream, LlmError> {
+        info!(
+            "Processing streaming text completion request for model: {}",
+            request.model
+        );
+
+        // For Ollama, text and chat are equivalent.
+        let chat_req = ChatCompletionRequest {
+            model: request.model,
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: request.prompt,
+                ..Default::default()
+            }],
+            stop: request.stop,
+            ..Default::default()
+        };
+        let stream = self.streaming_chat_completion(chat_req).await?;
 
-        Err(LlmError::NotImplemented(
-            "Ollama embeddings not implemented in this example".to_string(),
-        ))
+        Ok(Box::pin(stream.map(|chunk| {
+            chunk.map(|chunk| TextCompletionChunk {
+                id: chunk.id,
+                object: "text_completion".to_string(),
+                created: chunk.created,
+                model: chunk.model,
+                choices: chunk
+                    .choices
+                    .into_iter()
+                    .map(|choice| TextCompletionStreamChoice {
+                        index: choice.index,
+                        text: choice.delta.content.unwrap_or_default(),
+                        finish_reason: choice.finish_reason,
+                        ..Default::default()
+                    })
+                    .collect(),
+            })
+        })))
     }
 }
+
+/// Number of parsed chunks buffered ahead of the consumer of a stream
+const STREAM_BUFFER: usize = 32;
+
+/// Parse a newline-delimited JSON generation stream into chat completion chunks
+///
+/// Lines are reassembled across network reads before parsing. The channel closes after the
+/// line with `done` set; a read or parse error is sent as the last item.
+fn read_ndjson_chunks(
+    mut res: reqwest::Response,
+) -> mpsc::Receiver<Result<ChatCompletionChunk, LlmError>> {
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(async move {
+        let id = uuid::Uuid::new_v4().to_string();
+        let created = chrono::Utc::now().timestamp() as u64;
+        let mut first = true;
+        let mut buffer: Vec<u8> = Vec::new();
+        loop {
+            let bytes = match res.chunk().await {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = tx
+                        .send(Err(LlmError::RequestFailed(format!(
+                            "Failed to read Ollama stream: {}",
+                            e
+                        ))))
+                        .await;
+                    return;
+                }
+            };
+            buffer.extend_from_slice(&bytes);
+
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let line = line.trim();
+                if line.is_empty() {
+                    continue;
+                }
+
+                let ollama_resp = match serde_json::from_str::<OllamaResponse>(line) {
+                    Ok(ollama_resp) => ollama_resp,
+                    Err(e) => {
+                        let _ = tx
+                            .send(Err(LlmError::RequestFailed(format!(
+                                "Failed to parse Ollama stream line: {}",
+                                e
+                            ))))
+                            .await;
+                        return;
+                    }
+                };
+                let done = ollama_resp.done;
+                let model = ollama_resp.model.clone();
+                let (content, reasoning_content) = ollama_resp.into_content();
+
+                let chunk = ChatCompletionChunk {
+                    id: id.clone(),
+                    object: "chat.completion.chunk".to_string(),
+                    created,
+                    model,
+                    choices: vec![ChatCompletionStreamChoice {
+                        index: 0,
+                        delta: ChatMessageDelta {
+                            role: first.then(|| "assistant".to_string()),
+                            content: (!content.is_empty()).then_some(content),
+                            reasoning_content: reasoning_content.filter(|r| !r.is_empty()),
+                        },
+                        finish_reason: done.then(|| "stop".to_string()),
+                        ..Default::default()
+                    }],
+                };
+                first = false;
+
+                if tx.send(Ok(chunk)).await.is_err() || done {
+                    // The consumer is gone, or Ollama finished the answer
+                    return;
+                }
+            }
+        }
+        trace!("Ollama stream ended without a done line");
+    });
+    rx
+}
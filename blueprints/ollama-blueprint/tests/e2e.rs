@@ -15,12 +15,20 @@
 //!
 //! Expected outcome: The client should return a valid response from the Ollama model, handle errors, and expose metrics/capabilities.
 
-use ollama_blueprint::OllamaLlmClient;
+use backend_test_support::{MockResponse, MockServer};
+use futures::StreamExt;
+use ollama_blueprint::{build_ollama_client, OllamaLlmClient};
+use open_router_blueprint_template_lib::config::{LlmBackend, LlmConfig};
+use open_router_blueprint_template_lib::factory::LlmClientFactory;
 use open_router_blueprint_template_lib::llm::{
-    ChatCompletionRequest, ChatMessage, LlmClient, LlmError, TextCompletionRequest,
+    ChatCompletionRequest, ChatMessage, EmbeddingRequest, LlmClient, LlmClientExt, LlmError,
+    ModelInfo, StreamingLlmClient, TextCompletionRequest,
 };
+use serde_json::json;
 use std::collections::HashMap;
 use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
 // Removed unused import: std::thread::sleep
 use std::time::Duration;
 use tracing::{debug, error, info, warn};
@@ -78,6 +86,612 @@ async fn ensure_ollama_running() {
     }
 }
 
+fn model_info(id: &str, max_context_length: usize) -> ModelInfo {
+    ModelInfo {
+        id: id.to_string(),
+        name: id.to_string(),
+        max_context_length,
+        supports_chat: true,
+        supports_text: true,
+        supports_embeddings: true,
+        parameters: HashMap::new(),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_with_models_overrides_metadata() {
+    let server = MockServer::start(|_| {
+        MockResponse::json(
+            200,
+            json!({ "models": [{ "name": "deepseek-r1" }, { "name": "llama3" }] }),
+        )
+    });
+    let client =
+        OllamaLlmClient::new(server.url.clone(), "deepseek-r1".to_string()).with_models(vec![
+            model_info("deepseek-r1", 65536),
+            model_info("llama3", 8192),
+            model_info("missing", 4096),
+        ]);
+
+    let models = client.get_supported_models();
+
+    let ids: Vec<_> = models.iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, vec!["deepseek-r1", "llama3"]);
+    assert_eq!(models[0].max_context_length, 65536);
+    assert!(models[1].supports_embeddings);
+    assert_eq!(server.requests_to("/api/tags").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_factory_builds_ollama_client_for_ollama_backend() {
+    let server = MockServer::start(|_| {
+        MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] }))
+    });
+    let config = LlmConfig {
+        backend: LlmBackend::Ollama,
+        api_url: server.url.clone(),
+        models: vec![model_info("llama3", 8192)],
+        ..Default::default()
+    };
+    let factory = LlmClientFactory::new().with_backend(LlmBackend::Ollama, build_ollama_client);
+
+    let client = factory.build(&config).unwrap();
+
+    // The capabilities of an Ollama client, not of the template's local one
+    let capabilities = client.get_capabilities();
+    assert!(capabilities.supports_streaming);
+    assert!(!capabilities.supports_batching);
+    assert_eq!(capabilities.max_concurrent_requests, 1);
+    assert_eq!(client.get_supported_models()[0].max_context_length, 8192);
+    assert_eq!(server.requests_to("/api/tags").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_from_config_applies_http2_config() {
+    let server = MockServer::start(|_| MockResponse::json(500, json!({ "error": "unavailable" })));
+    let config = LlmConfig {
+        api_url: server.url.clone(),
+        models: vec![model_info("llama3", 8192)],
+        http2: true,
+        ..Default::default()
+    };
+    let client = OllamaLlmClient::from_config(&config);
+
+    let result = client
+        .text_completion(TextCompletionRequest {
+            model: "llama3".to_string(),
+            prompt: "Hello".to_string(),
+            ..Default::default()
+        })
+        .await;
+
+    // Connections open with the HTTP/2 preface, which this server cannot answer
+    assert!(result.is_err());
+    let preface = server.requests_to("*");
+    assert!(!preface.is_empty());
+    assert!(preface.iter().all(|req| req.method == "PRI"));
+    assert!(server.requests_to("/api/generate").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_embeddings_fan_out_in_input_order() {
+    let in_flight = Arc::new(AtomicUsize::new(0));
+    let max_in_flight = Arc::new(AtomicUsize::new(0));
+    let server = {
+        let in_flight = in_flight.clone();
+        let max_in_flight = max_in_flight.clone();
+        MockServer::start(move |req| match req.path.as_str() {
+            "/api/tags" => {
+                MockResponse::json(200, json!({ "models": [{ "name": "nomic-embed-text" }] }))
+            }
+            _ => {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+                let prompt = req.body_json()["prompt"].as_str().unwrap().to_string();
+                // Answer later inputs faster so responses complete out of order
+                let value: u64 = prompt.parse().unwrap();
+                std::thread::sleep(Duration::from_millis(5 * (12 - value)));
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                MockResponse::json(200, json!({ "embedding": [value as f32, 0.5] }))
+            }
+        })
+    };
+    let client = OllamaLlmClient::new(server.url.clone(), "nomic-embed-text".to_string())
+        .with_embedding_concurrency(3);
+
+    let response = client
+        .embeddings(EmbeddingRequest {
+            model: "nomic-embed-text".to_string(),
+            input: (0..12).map(|i| i.to_string()).collect(),
+            additional_params: HashMap::new(),
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(response.data.len(), 12);
+    for (i, data) in response.data.iter().enumerate() {
+        assert_eq!(data.index, i);
+        assert_eq!(data.embedding, vec![i as f32, 0.5]);
+    }
+    assert_eq!(server.requests_to("/api/embeddings").len(), 12);
+    assert!(max_in_flight.load(Ordering::SeqCst) <= 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_slow_embedding_times_out() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/api/tags" => {
+            MockResponse::json(200, json!({ "models": [{ "name": "nomic-embed-text" }] }))
+        }
+        _ => {
+            std::thread::sleep(Duration::from_millis(500));
+            MockResponse::json(200, json!({ "embedding": [0.5, 0.5] }))
+        }
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "nomic-embed-text".to_string())
+        .with_timeout(Duration::from_millis(100));
+
+    let error = client
+        .embeddings(EmbeddingRequest {
+            model: "nomic-embed-text".to_string(),
+            input: vec!["Hello".to_string()],
+            additional_params: HashMap::new(),
+        })
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, LlmError::Timeout(timeout) if timeout == Duration::from_millis(100)));
+}
+
+/// Send a chat completion to a server reporting the given version and return the completion
+/// endpoint it was called on, with the reply content
+async fn chat_endpoint_for_version(version: &'static str) -> (String, String) {
+    let server = MockServer::start(move |req| match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] })),
+        "/api/version" => MockResponse::json(200, json!({ "version": version })),
+        "/api/chat" => MockResponse::json(
+            200,
+            json!({
+                "model": "llama3",
+                "message": { "role": "assistant", "content": "Hi from chat" },
+                "done": true
+            }),
+        ),
+        _ => MockResponse::json(
+            200,
+            json!({ "model": "llama3", "response": "Hi from generate" }),
+        ),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let response = client
+        .chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(client.get_node_info().version.as_deref(), Some(version));
+    let endpoints: Vec<String> = server
+        .requests()
+        .into_iter()
+        .map(|req| req.path)
+        .filter(|path| path == "/api/chat" || path == "/api/generate")
+        .collect();
+    assert_eq!(endpoints.len(), 1);
+    (
+        endpoints[0].clone(),
+        response.choices[0].message.content.clone(),
+    )
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_chat_endpoint_gated_on_detected_version() {
+    let (endpoint, content) = chat_endpoint_for_version("0.5.7").await;
+    assert_eq!(endpoint, "/api/chat");
+    assert_eq!(content, "Hi from chat");
+
+    // Releases before /api/chat only understand flattened prompts
+    let (endpoint, content) = chat_endpoint_for_version("0.1.10").await;
+    assert_eq!(endpoint, "/api/generate");
+    assert_eq!(content, "Hi from generate");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_chat_completion_keeps_message_roles() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] })),
+        // Without a version the chat endpoint is still used
+        "/api/version" => MockResponse::text(404, "text/plain", "404 page not found"),
+        _ => MockResponse::json(
+            200,
+            json!({
+                "model": "llama3",
+                "message": { "role": "assistant", "content": "Ahoy!" },
+                "done": true
+            }),
+        ),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+    let message = |role: &str, content: &str| ChatMessage {
+        role: role.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    };
+
+    let response = client
+        .chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![
+                message("system", "Talk like a pirate."),
+                message("user", "Hello"),
+            ],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(response.choices[0].message.content, "Ahoy!");
+    let requests = server.requests_to("/api/chat");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].body_json()["messages"],
+        json!([
+            { "role": "system", "content": "Talk like a pirate." },
+            { "role": "user", "content": "Hello" }
+        ])
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_chat_falls_back_to_generate_without_chat_endpoint() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] })),
+        "/api/generate" => MockResponse::json(
+            200,
+            json!({ "model": "llama3", "response": "Hi from generate" }),
+        ),
+        _ => MockResponse::text(404, "text/plain", "404 page not found"),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+    let request = ChatCompletionRequest {
+        model: "llama3".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    for _ in 0..2 {
+        let response = client.chat_completion(request.clone()).await.unwrap();
+        assert_eq!(response.choices[0].message.content, "Hi from generate");
+    }
+
+    // The missing endpoint is remembered rather than tried for every request
+    assert_eq!(server.requests_to("/api/chat").len(), 1);
+    let requests = server.requests_to("/api/generate");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].body_json()["prompt"], "user:\nHello");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_chat_reports_model_missing_from_chat_endpoint() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] })),
+        "/api/version" => MockResponse::json(200, json!({ "version": "0.9.0" })),
+        _ => MockResponse::json(
+            404,
+            json!({ "error": "model \"llama3\" not found, try pulling it first" }),
+        ),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let error = client
+        .chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, LlmError::ModelNotSupported(_)));
+    assert!(server.requests_to("/api/generate").is_empty());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_chat_completion_parses_thinking() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "deepseek-r1" }] })),
+        "/api/version" => MockResponse::json(200, json!({ "version": "0.9.0" })),
+        _ => MockResponse::json(
+            200,
+            json!({
+                "model": "deepseek-r1",
+                "message": {
+                    "role": "assistant",
+                    "thinking": "The user greets me, so I greet back.",
+                    "content": "Hi!"
+                },
+                "done": true
+            }),
+        ),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "deepseek-r1".to_string());
+
+    let response = client
+        .chat_completion(ChatCompletionRequest {
+            model: "deepseek-r1".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let message = &response.choices[0].message;
+    assert_eq!(message.content, "Hi!");
+    assert_eq!(
+        message.reasoning_content.as_deref(),
+        Some("The user greets me, so I greet back.")
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_non_streaming_reply_sent_in_chunks_is_reassembled() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] })),
+        "/api/version" => MockResponse::json(200, json!({ "version": "0.1.10" })),
+        // Some builds stream even when asked not to
+        _ => MockResponse::text(
+            200,
+            "application/x-ndjson",
+            concat!(
+                "{\"model\":\"llama3\",\"response\":\"Once\",\"done\":false}\n",
+                "{\"model\":\"llama3\",\"response\":\" upon\",\"done\":false}\n",
+                "\n",
+                "{\"model\":\"llama3\",\"response\":\" a time\",\"done\":true}\n",
+            ),
+        ),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let response = client
+        .text_completion(TextCompletionRequest {
+            model: "llama3".to_string(),
+            prompt: "Tell me a story".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    assert_eq!(response.choices[0].text, "Once upon a time");
+    let requests = server.requests_to("/api/generate");
+    assert_eq!(requests[0].body_json()["stream"], json!(false));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_streaming_chat_completion_buffers_split_lines() {
+    let server = MockServer::start(|req| {
+        match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] })),
+        "/api/version" => MockResponse::json(200, json!({ "version": "0.9.0" })),
+        _ => MockResponse::chunked(
+            200,
+            "application/x-ndjson",
+            &[
+                "{\"model\":\"llama3\",\"message\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"done\":false}\n{\"model\":\"lla",
+                "ma3\",\"message\":{\"role\":\"assistant\",\"content\":\"lo\"},\"done\":false}\n",
+                "{\"model\":\"llama3\",\"message\":{\"role\":\"assistant\",\"content\":\"\"},\"done\":true}\n",
+            ],
+        ),
+    }
+    });
+    let client: Arc<dyn LlmClient> = Arc::new(OllamaLlmClient::new(
+        server.url.clone(),
+        "llama3".to_string(),
+    ));
+
+    let streaming = client.as_streaming().expect("Ollama supports streaming");
+    let chunks: Vec<_> = streaming
+        .streaming_chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+
+    let deltas: Vec<_> = chunks
+        .iter()
+        .map(|chunk| chunk.choices[0].delta.content.clone().unwrap_or_default())
+        .collect();
+    assert_eq!(deltas, ["Hel", "lo", ""]);
+    assert_eq!(
+        chunks[0].choices[0].delta.role.as_deref(),
+        Some("assistant")
+    );
+    assert_eq!(chunks[1].choices[0].finish_reason, None);
+    assert_eq!(chunks[2].choices[0].finish_reason.as_deref(), Some("stop"));
+    assert!(chunks
+        .iter()
+        .all(|chunk| chunk.created == chunks[0].created));
+    assert!(chunks.iter().all(|chunk| chunk.id == chunks[0].id));
+
+    let requests = server.requests_to("/api/chat");
+    assert_eq!(requests[0].body_json()["stream"], json!(true));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_streaming_text_completion_over_generate() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] })),
+        "/api/version" => MockResponse::json(200, json!({ "version": "0.1.10" })),
+        _ => MockResponse::text(
+            200,
+            "application/x-ndjson",
+            concat!(
+                "{\"model\":\"llama3\",\"response\":\"Once\",\"done\":false}\n",
+                "{\"model\":\"llama3\",\"response\":\" upon\",\"done\":true}\n",
+            ),
+        ),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let chunks: Vec<_> = client
+        .streaming_text_completion(TextCompletionRequest {
+            model: "llama3".to_string(),
+            prompt: "Tell me a story".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .map(|chunk| chunk.unwrap())
+        .collect()
+        .await;
+
+    let text: String = chunks
+        .iter()
+        .map(|chunk| chunk.choices[0].text.as_str())
+        .collect();
+    assert_eq!(text, "Once upon");
+    assert_eq!(chunks[1].choices[0].finish_reason.as_deref(), Some("stop"));
+    assert_eq!(server.requests_to("/api/generate").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_health_check() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/" => MockResponse::text(200, "text/plain", "Ollama is running"),
+        _ => MockResponse::text(404, "text/plain", "404 page not found"),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+    assert!(client.health_check().await.is_ok());
+    assert_eq!(server.requests_to("/").len(), 1);
+
+    let server = MockServer::start(|_| MockResponse::text(503, "text/plain", ""));
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+    let error = client.health_check().await.unwrap_err();
+    assert!(error.to_string().contains("503"), "{}", error);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_choice_count_is_emulated_with_concurrent_requests() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] })),
+        "/api/version" => MockResponse::json(200, json!({ "version": "0.1.10" })),
+        _ => MockResponse::json(
+            200,
+            json!({ "model": "llama3", "response": "Once upon a time" }),
+        ),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+    let request = TextCompletionRequest {
+        model: "llama3".to_string(),
+        prompt: "Tell me a story".to_string(),
+        n: Some(3),
+        ..Default::default()
+    };
+
+    let response = client.text_completion(request.clone()).await.unwrap();
+
+    let indexes: Vec<_> = response.choices.iter().map(|c| c.index).collect();
+    assert_eq!(indexes, vec![0, 1, 2]);
+    assert!(response
+        .choices
+        .iter()
+        .all(|c| c.text == "Once upon a time"));
+    assert_eq!(server.requests_to("/api/generate").len(), 3);
+
+    let error = client
+        .text_completion(TextCompletionRequest {
+            n: Some(0),
+            ..request
+        })
+        .await
+        .unwrap_err();
+    assert!(matches!(error, LlmError::InvalidRequest(_)));
+    assert_eq!(server.requests_to("/api/generate").len(), 3);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_completed_requests_are_counted_in_metrics() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] })),
+        "/api/version" => MockResponse::json(200, json!({ "version": "0.1.10" })),
+        _ => MockResponse::json(200, json!({ "model": "llama3", "response": "Hi" })),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+    let request = TextCompletionRequest {
+        model: "llama3".to_string(),
+        prompt: "Hello".to_string(),
+        ..Default::default()
+    };
+
+    for _ in 0..2 {
+        client.text_completion(request.clone()).await.unwrap();
+    }
+
+    let metrics = client.get_metrics();
+    assert_eq!(metrics.requests_per_minute, 2);
+    assert_eq!(metrics.active_requests, 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_stop_sequences_sent_as_options() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/api/tags" => MockResponse::json(200, json!({ "models": [{ "name": "llama3" }] })),
+        "/api/version" => MockResponse::json(200, json!({ "version": "0.5.7" })),
+        _ => MockResponse::json(
+            200,
+            json!({
+                "model": "llama3",
+                "message": { "role": "assistant", "content": "Hi" },
+                "done": true
+            }),
+        ),
+    });
+    let client = OllamaLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    client
+        .text_completion(TextCompletionRequest {
+            model: "llama3".to_string(),
+            prompt: "Hello".to_string(),
+            stop: Some(vec!["\n\n".to_string()]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let requests = server.requests_to("/api/chat");
+    assert_eq!(requests.len(), 1);
+    let body = requests[0].body_json();
+    assert_eq!(body["options"]["stop"], json!(["\n\n"]));
+    assert!(body.get("stop").is_none());
+}
+
 #[tokio::test]
 async fn test_chat_and_text_completion() {
     // Setup tracing for the test (using info level by default)
@@ -100,12 +714,18 @@ async fn test_chat_and_text_completion() {
         model: model.clone(),
         messages: vec![ChatMessage {
             role: "user".to_string(),
-            name: None,
             content: "Hello, who are you?".to_string(),
+            ..Default::default()
         }],
         max_tokens: None,
         temperature: None,
         top_p: None,
+        stop: None,
+        n: None,
+        service_tier: None,
+        session_id: None,
+        tools: None,
+        tool_choice: None,
         stream: None,
         additional_params: HashMap::new(),
     };
@@ -140,6 +760,8 @@ async fn test_chat_and_text_completion() {
         max_tokens: None,
         temperature: None,
         top_p: None,
+        stop: None,
+        n: None,
         stream: None,
         additional_params: HashMap::new(),
     };
@@ -173,12 +795,18 @@ async fn test_chat_and_text_completion() {
         model: bad_model.clone(),
         messages: vec![ChatMessage {
             role: "user".to_string(),
-            name: None,
             content: "Test".to_string(),
+            ..Default::default()
         }],
         max_tokens: None,
         temperature: None,
         top_p: None,
+        stop: None,
+        n: None,
+        service_tier: None,
+        session_id: None,
+        tools: None,
+        tool_choice: None,
         stream: None,
         additional_params: HashMap::new(),
     };
@@ -208,10 +836,7 @@ async fn test_chat_and_text_completion() {
     info!("Testing capabilities and metrics");
     let caps = client.get_capabilities();
     debug!("Client capabilities: {:?}", caps);
-    assert!(
-        !caps.supports_streaming,
-        "Ollama should not support streaming by default"
-    );
+    assert!(caps.supports_streaming, "Ollama should support streaming");
     assert_eq!(caps.max_concurrent_requests, 1);
 
     let metrics = client.get_metrics();
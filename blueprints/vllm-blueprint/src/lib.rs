@@ -1,18 +1,117 @@
 use async_trait::async_trait;
+use open_router_blueprint_template_lib::config::LlmConfig;
+use open_router_blueprint_template_lib::correlation::apply_correlation_header;
 use open_router_blueprint_template_lib::llm::{
-    ChatCompletionRequest, ChatCompletionResponse, LlmClient, LlmError, ModelInfo, NodeMetrics,
+    create_chat_completion_stream, create_text_completion_stream, passthrough_params, read_json,
+    read_text, with_request_timeout, BackendVersion, BodyCompression, ChatCompletionRequest,
+    ChatCompletionResponse, ChatCompletionStream, EmbeddingData, EmbeddingRequest,
+    EmbeddingResponse, LlmClient, LlmError, LogProbs, MetricsWindow, ModelInfo, NodeInfo,
+    NodeMetrics, StreamingLlmClient, TextCompletionRequest, TextCompletionStream, ToolCall,
+    ToolChoice, ToolDefinition, UsageInfo, DEFAULT_REQUEST_TIMEOUT,
 };
 use reqwest::Client;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::collections::HashMap;
+use std::future::Future;
+use std::sync::{Arc, PoisonError, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::{mpsc, OnceCell};
 use tracing::{debug, error, info, trace, warn};
 
 pub struct VllmLlmClient {
     pub api_url: String,
     pub model: String,
+    /// Operator-supplied model metadata, returned instead of the derived default when set
+    pub models: Vec<ModelInfo>,
+    /// Keys of request `additional_params` merged into the vLLM request body
+    pub passthrough_params: Vec<String>,
+    /// Whether `model` is an embedding model, served by vLLM with `--task embed`
+    pub embedding_model: bool,
     pub metrics: Arc<RwLock<NodeMetrics>>,
+    /// Completed requests, counted into `requests_per_minute`
+    requests: RwLock<MetricsWindow>,
     pub http_client: Client,
+    /// Time a completion or embedding request may take before it fails with `LlmError::Timeout`
+    pub timeout: Duration,
+    /// Version reported by `/version`, probed once on first use
+    version: OnceCell<Option<String>>,
+    /// Gzip compression of request and response bodies
+    compression: BodyCompression,
+}
+
+/// First vLLM release accepting `max_completion_tokens`, which replaces the deprecated
+/// `max_tokens` in chat completion requests
+pub const VLLM_MAX_COMPLETION_TOKENS_VERSION: BackendVersion = BackendVersion::new(0, 6, 2);
+
+/// A chat message in a vLLM chat completion request
+#[derive(Serialize)]
+struct VllmChatMessage {
+    role: String,
+    content: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_calls: Option<Vec<ToolCall>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_call_id: Option<String>,
+}
+
+/// Body of a vLLM `/v1/chat/completions` request
+#[derive(Serialize)]
+struct VllmChatRequest {
+    model: String,
+    messages: Vec<VllmChatMessage>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_completion_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tools: Option<Vec<ToolDefinition>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    tool_choice: Option<ToolChoice>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+/// Body of a vLLM `/v1/embeddings` request
+#[derive(Serialize)]
+struct VllmEmbeddingRequest {
+    model: String,
+    input: Vec<String>,
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
+}
+
+/// Body of a vLLM `/v1/completions` request
+#[derive(Serialize)]
+struct VllmCompletionRequest {
+    model: String,
+    prompt: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    max_tokens: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    temperature: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    top_p: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stop: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    n: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    stream: Option<bool>,
+    #[serde(flatten)]
+    extra: HashMap<String, serde_json::Value>,
 }
 
 impl VllmLlmClient {
@@ -24,95 +123,277 @@ impl VllmLlmClient {
         Self {
             api_url,
             model,
+            models: Vec::new(),
+            passthrough_params: Vec::new(),
+            embedding_model: false,
             metrics: Arc::new(RwLock::new(NodeMetrics {
-                cpu_utilization: 0.0,
-                memory_utilization: 0.0,
-                gpu_utilization: None,
-                requests_per_minute: 0,
-                average_response_time_ms: 0,
-                active_requests: 0,
                 last_updated: std::time::SystemTime::now()
                     .duration_since(std::time::UNIX_EPOCH)
                     .unwrap_or_default()
                     .as_secs(),
+                ..Default::default()
             })),
+            requests: RwLock::new(MetricsWindow::new()),
             http_client: Client::new(),
+            timeout: DEFAULT_REQUEST_TIMEOUT,
+            version: OnceCell::new(),
+            compression: BodyCompression::default(),
         }
     }
-}
 
-#[async_trait]
-impl LlmClient for VllmLlmClient {
-    fn get_supported_models(&self) -> Vec<ModelInfo> {
-        debug!("Checking if model '{}' exists in vLLM", self.model);
-        // Check if the model exists in vLLM
-        let valid_model = futures::executor::block_on(async {
-            let url = format!("{}/v1/models", self.api_url);
-            trace!("Sending request to {}", url);
-            let res = self.http_client.get(&url).send().await;
-            if let Ok(response) = res {
-                if response.status().is_success() {
-                    #[derive(Deserialize)]
-                    struct VllmModelsResponse {
-                        data: Vec<VllmModel>,
-                    }
+    /// Create a client for the `llm` section of a blueprint configuration
+    ///
+    /// The client serves the first of `models` at `api_url`, with the configured timeout,
+    /// passthrough params, compression and HTTP options.
+    pub fn from_config(config: &LlmConfig) -> Self {
+        let model = config
+            .models
+            .first()
+            .map(|model| model.id.clone())
+            .unwrap_or_default();
+        let http_client = config.http_client_builder().build().unwrap_or_else(|e| {
+            warn!(
+                "Failed to build the configured HTTP client, using the default: {}",
+                e
+            );
+            Client::new()
+        });
+        Self::new(config.api_url.clone(), model)
+            .with_models(config.models.clone())
+            .with_passthrough_params(config.passthrough_params.clone())
+            .with_http_client(http_client)
+            .with_timeout(Duration::from_secs(config.timeout_seconds))
+            .with_compression(config.enable_compression)
+    }
 
-                    #[derive(Deserialize)]
-                    struct VllmModel {
-                        id: String,
-                    }
+    /// Override the model metadata reported by this client.
+    ///
+    /// Each entry is only reported by `get_supported_models` while the model is actually
+    /// available on the vLLM backend.
+    pub fn with_models(mut self, models: Vec<ModelInfo>) -> Self {
+        self.models = models;
+        self
+    }
 
-                    if let Ok(models) = response.json::<VllmModelsResponse>().await {
-                        let is_valid = models.data.iter().any(|m| m.id == self.model);
-                        debug!("Model '{}' validation result: {}", self.model, is_valid);
-                        return is_valid;
+    /// Forward the given keys of request `additional_params` to vLLM, e.g. the
+    /// `llm.passthrough_params` config value. Other keys are dropped.
+    pub fn with_passthrough_params(mut self, passthrough_params: Vec<String>) -> Self {
+        self.passthrough_params = passthrough_params;
+        self
+    }
+
+    /// Report `model` as an embedding model rather than a generative one, for vLLM servers
+    /// started with `--task embed`. `/v1/models` does not tell the two apart.
+    pub fn with_embedding_model(mut self, embedding_model: bool) -> Self {
+        self.embedding_model = embedding_model;
+        self
+    }
+
+    /// Use a preconfigured HTTP client, e.g. one built from
+    /// `LlmConfig::http_client_builder` to enable HTTP/2 and keep-alive pings.
+    pub fn with_http_client(mut self, http_client: Client) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    /// Fail completion and embedding requests that take longer than `timeout`, e.g. the
+    /// `llm.timeout_seconds` config value. Streamed responses are not limited.
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Run `request` with the request timeout, counting it in the metrics
+    async fn tracked<T>(
+        &self,
+        request: impl Future<Output = Result<T, LlmError>>,
+    ) -> Result<T, LlmError> {
+        self.record_request_start();
+        let started = Instant::now();
+        let result = with_request_timeout(self.timeout, request).await;
+        self.record_request_end(started.elapsed().as_millis() as u64);
+        result
+    }
+
+    fn record_request_start(&self) {
+        let mut metrics = self.metrics.write().unwrap_or_else(PoisonError::into_inner);
+        metrics.active_requests += 1;
+    }
+
+    fn record_request_end(&self, duration_ms: u64) {
+        let mut metrics = self.metrics.write().unwrap_or_else(PoisonError::into_inner);
+        metrics.active_requests = metrics.active_requests.saturating_sub(1);
+
+        // Update average response time with exponential moving average
+        const ALPHA: f64 = 0.1; // Weight for new samples
+        let old_avg = metrics.average_response_time_ms as f64;
+        let new_avg = old_avg * (1.0 - ALPHA) + (duration_ms as f64) * ALPHA;
+        metrics.average_response_time_ms = new_avg as u64;
+
+        let mut requests = self
+            .requests
+            .write()
+            .unwrap_or_else(PoisonError::into_inner);
+        requests.record();
+        metrics.requests_per_minute = requests.count();
+    }
+
+    /// Gzip large request bodies and accept gzip-encoded responses, e.g. the
+    /// `llm.enable_compression` config value. Request compression is turned off again if vLLM
+    /// (or a proxy in front of it) rejects compressed bodies.
+    pub fn with_compression(mut self, enabled: bool) -> Self {
+        self.compression = BodyCompression::new(enabled);
+        self
+    }
+
+    /// The version of the vLLM server, probed with `GET /version` on the first call
+    ///
+    /// A failed probe is not retried; features that depend on the version then fall back to
+    /// the fields every vLLM release accepts.
+    pub async fn detect_version(&self) -> Option<BackendVersion> {
+        let version = self
+            .version
+            .get_or_init(|| async {
+                #[derive(Deserialize)]
+                struct VllmVersion {
+                    version: String,
+                }
+
+                let url = format!("{}/version", self.api_url);
+                trace!("Probing vLLM version at {}", url);
+                let version = match self.http_client.get(&url).send().await {
+                    Ok(res) if res.status().is_success() => {
+                        res.json::<VllmVersion>().await.map(|v| v.version).ok()
                     }
+                    Ok(res) => {
+                        debug!("vLLM version probe returned {}", res.status());
+                        None
+                    }
+                    Err(e) => {
+                        debug!("Failed to probe vLLM version: {}", e);
+                        None
+                    }
+                };
+                match &version {
+                    Some(version) => info!("Detected vLLM version {}", version),
+                    None => warn!("Could not detect vLLM version, assuming an old release"),
                 }
-            } else if let Err(e) = res {
-                warn!("Failed to get vLLM models: {}", e);
-            }
-            debug!(
-                "Model validation failed, assuming model '{}' is invalid",
-                self.model
-            );
-            false
-        });
+                version
+            })
+            .await;
+        version.as_deref().and_then(BackendVersion::parse)
+    }
 
-        if !valid_model {
-            // Return empty list for unsupported model
-            warn!(
-                "Model '{}' is not available in vLLM, returning empty model list",
-                self.model
-            );
-            return vec![];
+    /// The vLLM request body for a chat completion
+    async fn chat_request_body(
+        &self,
+        request: &ChatCompletionRequest,
+        stream: Option<bool>,
+    ) -> VllmChatRequest {
+        // Newer servers deprecate `max_tokens` for chat; older ones reject its replacement
+        let use_max_completion_tokens = self
+            .detect_version()
+            .await
+            .is_some_and(|version| version >= VLLM_MAX_COMPLETION_TOKENS_VERSION);
+        let (max_tokens, max_completion_tokens) = if use_max_completion_tokens {
+            (None, request.max_tokens)
+        } else {
+            (request.max_tokens, None)
+        };
+
+        VllmChatRequest {
+            model: request.model.clone(),
+            messages: request
+                .messages
+                .iter()
+                .map(|m| VllmChatMessage {
+                    role: m.role.clone(),
+                    content: m.content.clone(),
+                    name: m.name.clone(),
+                    tool_calls: m.tool_calls.clone(),
+                    tool_call_id: m.tool_call_id.clone(),
+                })
+                .collect(),
+            max_tokens,
+            max_completion_tokens,
+            temperature: request.temperature,
+            top_p: request.top_p,
+            stop: request.stop.clone(),
+            n: request.n,
+            tools: request.tools.clone(),
+            tool_choice: request.tool_choice.clone(),
+            stream,
+            extra: passthrough_params(&request.additional_params, &self.passthrough_params),
         }
+    }
 
-        info!("Model '{}' is available in vLLM", self.model);
-        vec![ModelInfo {
-            id: self.model.clone(),
-            name: self.model.clone(),
-            max_context_length: 4096, // Default value, could be model-specific
-            supports_chat: true,
-            supports_text: true,
-            supports_embeddings: false, // vLLM may not support embeddings in all versions
-            parameters: Default::default(),
-        }]
+    /// The vLLM request body for a text completion
+    fn completion_request_body(
+        &self,
+        request: TextCompletionRequest,
+        stream: Option<bool>,
+    ) -> VllmCompletionRequest {
+        VllmCompletionRequest {
+            extra: passthrough_params(&request.additional_params, &self.passthrough_params),
+            model: request.model,
+            prompt: request.prompt,
+            max_tokens: request.max_tokens,
+            temperature: request.temperature,
+            top_p: request.top_p,
+            stop: request.stop,
+            n: request.n,
+            stream,
+        }
     }
 
-    fn get_capabilities(&self) -> open_router_blueprint_template_lib::llm::LlmCapabilities {
-        open_router_blueprint_template_lib::llm::LlmCapabilities {
-            supports_streaming: true,   // vLLM supports streaming
-            max_concurrent_requests: 4, // vLLM can handle multiple concurrent requests
-            supports_batching: true,    // vLLM supports batching
-            features: Default::default(),
+    /// Fail unless vLLM currently serves `model`
+    fn ensure_model_supported(&self, model: &str) -> Result<(), LlmError> {
+        if self.get_supported_models().iter().any(|m| m.id == model) {
+            return Ok(());
         }
+        error!("Model '{}' is not available in vLLM", model);
+        Err(LlmError::ModelNotSupported(format!(
+            "Model '{}' is not available in vLLM",
+            model
+        )))
     }
 
-    fn get_metrics(&self) -> NodeMetrics {
-        futures::executor::block_on(async { self.metrics.read().await.clone() })
+    /// Send a streaming request to `path` and return the response once vLLM accepts it
+    async fn start_stream<B: Serialize>(
+        &self,
+        path: &str,
+        body: &B,
+    ) -> Result<reqwest::Response, LlmError> {
+        let url = format!("{}{}", self.api_url, path);
+        debug!("Sending streaming request to {}", url);
+
+        let resp = self
+            .compression
+            .send_json_streaming(
+                || apply_correlation_header(self.http_client.post(&url)),
+                body,
+            )
+            .await
+            .map_err(|e| {
+                error!("Failed to send request to vLLM API: {}", e);
+                LlmError::RequestFailed(format!("Failed to send request to vLLM API: {}", e))
+            })?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = read_text(resp).await.unwrap_or_default();
+            let message = extract_error_message(&body).unwrap_or_else(|| status.to_string());
+            error!("vLLM API error: {}", message);
+            return Err(LlmError::RequestFailed(format!(
+                "vLLM API error: {}",
+                message
+            )));
+        }
+        Ok(resp)
     }
 
-    async fn chat_completion(
+    /// Send a chat completion request to vLLM, without a timeout
+    async fn request_chat_completion(
         &self,
         request: ChatCompletionRequest,
     ) -> Result<ChatCompletionResponse, LlmError> {
@@ -137,52 +418,19 @@ impl LlmClient for VllmLlmClient {
         }
 
         // Build vLLM API request
-        #[derive(Serialize)]
-        struct VllmChatMessage {
-            role: String,
-            content: String,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            name: Option<String>,
-        }
-
-        #[derive(Serialize)]
-        struct VllmChatRequest {
-            model: String,
-            messages: Vec<VllmChatMessage>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            max_tokens: Option<u32>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            temperature: Option<f32>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            top_p: Option<f32>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            stream: Option<bool>,
-        }
-
-        let vllm_messages = request
-            .messages
-            .iter()
-            .map(|m| VllmChatMessage {
-                role: m.role.clone(),
-                content: m.content.clone(),
-                name: m.name.clone(),
-            })
-            .collect();
-
-        let vllm_request = VllmChatRequest {
-            model: request.model.clone(),
-            messages: vllm_messages,
-            max_tokens: request.max_tokens,
-            temperature: request.temperature,
-            top_p: request.top_p,
-            stream: request.stream,
-        };
+        let vllm_request = self.chat_request_body(&request, request.stream).await;
 
         // Send request to vLLM API
         let url = format!("{}/v1/chat/completions", self.api_url);
         debug!("Sending chat completion request to {}", url);
 
-        let res = self.http_client.post(&url).json(&vllm_request).send().await;
+        let res = self
+            .compression
+            .send_json(
+                || apply_correlation_header(self.http_client.post(&url)),
+                &vllm_request,
+            )
+            .await;
 
         // Parse response
         let response = match res {
@@ -191,9 +439,17 @@ impl LlmClient for VllmLlmClient {
                     #[derive(Deserialize)]
                     struct VllmChatResponseMessage {
                         role: String,
-                        content: String,
+                        // Null when the model only calls tools
+                        #[serde(default)]
+                        content: Option<String>,
                         #[serde(default)]
                         name: Option<String>,
+                        // Set by servers running a reasoning parser, e.g. for deepseek-r1
+                        #[serde(default)]
+                        reasoning_content: Option<String>,
+                        // Set by servers running a tool call parser
+                        #[serde(default)]
+                        tool_calls: Option<Vec<ToolCall>>,
                     }
 
                     #[derive(Deserialize)]
@@ -201,6 +457,15 @@ impl LlmClient for VllmLlmClient {
                         index: usize,
                         message: VllmChatResponseMessage,
                         finish_reason: Option<String>,
+                        // Set when requested with `logprobs`
+                        #[serde(default)]
+                        logprobs: Option<LogProbs>,
+                    }
+
+                    #[derive(Deserialize)]
+                    struct VllmPromptTokensDetails {
+                        #[serde(default)]
+                        cached_tokens: Option<u32>,
                     }
 
                     #[derive(Deserialize)]
@@ -208,6 +473,8 @@ impl LlmClient for VllmLlmClient {
                         prompt_tokens: u32,
                         completion_tokens: u32,
                         total_tokens: u32,
+                        #[serde(default)]
+                        prompt_tokens_details: Option<VllmPromptTokensDetails>,
                     }
 
                     #[derive(Deserialize)]
@@ -216,11 +483,12 @@ impl LlmClient for VllmLlmClient {
                         object: String,
                         created: u64,
                         model: String,
+                        #[serde(default)]
                         choices: Vec<VllmChatResponseChoice>,
                         usage: Option<VllmUsage>,
                     }
 
-                    match resp.json::<VllmChatResponse>().await {
+                    match read_json::<VllmChatResponse>(resp).await {
                         Ok(vllm_resp) => {
                             let choices = vllm_resp
                                 .choices
@@ -231,10 +499,15 @@ impl LlmClient for VllmLlmClient {
                                         message:
                                             open_router_blueprint_template_lib::llm::ChatMessage {
                                                 role: c.message.role,
-                                                content: c.message.content,
+                                                content: c.message.content.unwrap_or_default(),
                                                 name: c.message.name,
+                                                reasoning_content: c.message.reasoning_content,
+                                                tool_calls: c.message.tool_calls.clone(),
+                                                ..Default::default()
                                             },
                                         finish_reason: c.finish_reason,
+                                        logprobs: c.logprobs,
+                                        tool_calls: c.message.tool_calls,
                                     }
                                 })
                                 .collect();
@@ -244,6 +517,9 @@ impl LlmClient for VllmLlmClient {
                                     prompt_tokens: u.prompt_tokens,
                                     completion_tokens: u.completion_tokens,
                                     total_tokens: u.total_tokens,
+                                    prompt_tokens_cached: u
+                                        .prompt_tokens_details
+                                        .and_then(|d| d.cached_tokens),
                                 }
                             });
 
@@ -254,50 +530,26 @@ impl LlmClient for VllmLlmClient {
                                 model: vllm_resp.model,
                                 choices,
                                 usage,
+                                cost: None,
+                                correlation_id: None,
                             })
                         }
                         Err(e) => {
                             error!("Failed to parse vLLM response: {}", e);
-                            Err(LlmError::RequestFailed(format!(
-                                "Failed to parse vLLM response: {}",
-                                e
-                            )))
+                            Err(e)
                         }
                     }
                 } else {
-                    // Try to parse error response
-                    #[derive(Deserialize)]
-                    struct VllmErrorResponse {
-                        error: VllmError,
-                    }
-
-                    #[derive(Deserialize)]
-                    struct VllmError {
-                        message: String,
-                        #[serde(rename = "type")]
-                        error_type: String,
-                    }
-
+                    // Extract whatever error message the server returned
                     let status = resp.status();
-                    match resp.json::<VllmErrorResponse>().await {
-                        Ok(error_resp) => {
-                            error!(
-                                "vLLM API error: {} ({})",
-                                error_resp.error.message, error_resp.error.error_type
-                            );
-                            Err(LlmError::RequestFailed(format!(
-                                "vLLM API error: {} ({})",
-                                error_resp.error.message, error_resp.error.error_type
-                            )))
-                        }
-                        Err(_) => {
-                            error!("vLLM API error: {}", status);
-                            Err(LlmError::RequestFailed(format!(
-                                "vLLM API error: {}",
-                                status
-                            )))
-                        }
-                    }
+                    let body = read_text(resp).await.unwrap_or_default();
+                    let message =
+                        extract_error_message(&body).unwrap_or_else(|| status.to_string());
+                    error!("vLLM API error: {}", message);
+                    Err(LlmError::RequestFailed(format!(
+                        "vLLM API error: {}",
+                        message
+                    )))
                 }
             }
             Err(e) => {
@@ -313,7 +565,8 @@ impl LlmClient for VllmLlmClient {
         response
     }
 
-    async fn text_completion(
+    /// Send a text completion request to vLLM, without a timeout
+    async fn request_text_completion(
         &self,
         request: open_router_blueprint_template_lib::llm::TextCompletionRequest,
     ) -> Result<open_router_blueprint_template_lib::llm::TextCompletionResponse, LlmError> {
@@ -338,34 +591,20 @@ impl LlmClient for VllmLlmClient {
         }
 
         // Build vLLM API request
-        #[derive(Serialize)]
-        struct VllmCompletionRequest {
-            model: String,
-            prompt: String,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            max_tokens: Option<u32>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            temperature: Option<f32>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            top_p: Option<f32>,
-            #[serde(skip_serializing_if = "Option::is_none")]
-            stream: Option<bool>,
-        }
-
-        let vllm_request = VllmCompletionRequest {
-            model: request.model.clone(),
-            prompt: request.prompt,
-            max_tokens: request.max_tokens,
-            temperature: request.temperature,
-            top_p: request.top_p,
-            stream: request.stream,
-        };
+        let stream = request.stream;
+        let vllm_request = self.completion_request_body(request, stream);
 
         // Send request to vLLM API
         let url = format!("{}/v1/completions", self.api_url);
         debug!("Sending text completion request to {}", url);
 
-        let res = self.http_client.post(&url).json(&vllm_request).send().await;
+        let res = self
+            .compression
+            .send_json(
+                || apply_correlation_header(self.http_client.post(&url)),
+                &vllm_request,
+            )
+            .await;
 
         // Parse response
         let response =
@@ -377,6 +616,15 @@ impl LlmClient for VllmLlmClient {
                             index: usize,
                             text: String,
                             finish_reason: Option<String>,
+                            // Set when requested with `logprobs`
+                            #[serde(default)]
+                            logprobs: Option<LogProbs>,
+                        }
+
+                        #[derive(Deserialize)]
+                        struct VllmPromptTokensDetails {
+                            #[serde(default)]
+                            cached_tokens: Option<u32>,
                         }
 
                         #[derive(Deserialize)]
@@ -384,6 +632,8 @@ impl LlmClient for VllmLlmClient {
                             prompt_tokens: u32,
                             completion_tokens: u32,
                             total_tokens: u32,
+                            #[serde(default)]
+                            prompt_tokens_details: Option<VllmPromptTokensDetails>,
                         }
 
                         #[derive(Deserialize)]
@@ -392,11 +642,12 @@ impl LlmClient for VllmLlmClient {
                             object: String,
                             created: u64,
                             model: String,
+                            #[serde(default)]
                             choices: Vec<VllmCompletionChoice>,
                             usage: Option<VllmUsage>,
                         }
 
-                        match resp.json::<VllmCompletionResponse>().await {
+                        match read_json::<VllmCompletionResponse>(resp).await {
                             Ok(vllm_resp) => {
                                 let choices = vllm_resp
                                 .choices
@@ -406,6 +657,7 @@ impl LlmClient for VllmLlmClient {
                                         index: c.index,
                                         text: c.text,
                                         finish_reason: c.finish_reason,
+                                        logprobs: c.logprobs,
                                     }
                                 })
                                 .collect();
@@ -415,6 +667,9 @@ impl LlmClient for VllmLlmClient {
                                         prompt_tokens: u.prompt_tokens,
                                         completion_tokens: u.completion_tokens,
                                         total_tokens: u.total_tokens,
+                                        prompt_tokens_cached: u
+                                            .prompt_tokens_details
+                                            .and_then(|d| d.cached_tokens),
                                     }
                                 });
 
@@ -425,50 +680,26 @@ impl LlmClient for VllmLlmClient {
                                 model: vllm_resp.model,
                                 choices,
                                 usage,
+                                cost: None,
+                                correlation_id: None,
                             })
                             }
                             Err(e) => {
                                 error!("Failed to parse vLLM response: {}", e);
-                                Err(LlmError::RequestFailed(format!(
-                                    "Failed to parse vLLM response: {}",
-                                    e
-                                )))
+                                Err(e)
                             }
                         }
                     } else {
-                        // Try to parse error response
-                        #[derive(Deserialize)]
-                        struct VllmErrorResponse {
-                            error: VllmError,
-                        }
-
-                        #[derive(Deserialize)]
-                        struct VllmError {
-                            message: String,
-                            #[serde(rename = "type")]
-                            error_type: String,
-                        }
-
+                        // Extract whatever error message the server returned
                         let status = resp.status();
-                        match resp.json::<VllmErrorResponse>().await {
-                            Ok(error_resp) => {
-                                error!(
-                                    "vLLM API error: {} ({})",
-                                    error_resp.error.message, error_resp.error.error_type
-                                );
-                                Err(LlmError::RequestFailed(format!(
-                                    "vLLM API error: {} ({})",
-                                    error_resp.error.message, error_resp.error.error_type
-                                )))
-                            }
-                            Err(_) => {
-                                error!("vLLM API error: {}", status);
-                                Err(LlmError::RequestFailed(format!(
-                                    "vLLM API error: {}",
-                                    status
-                                )))
-                            }
-                        }
+                        let body = read_text(resp).await.unwrap_or_default();
+                        let message =
+                            extract_error_message(&body).unwrap_or_else(|| status.to_string());
+                        error!("vLLM API error: {}", message);
+                        Err(LlmError::RequestFailed(format!(
+                            "vLLM API error: {}",
+                            message
+                        )))
                     }
                 }
                 Err(e) => {
@@ -484,32 +715,367 @@ impl LlmClient for VllmLlmClient {
         response
     }
 
-    async fn embeddings(
+    /// Embed the inputs of a request with vLLM, without a timeout
+    ///
+    /// vLLM rejects embedding requests for generative models with 400 Bad Request, which is
+    /// reported as [`LlmError::ModelNotSupported`].
+    async fn request_embeddings(
         &self,
-        request: open_router_blueprint_template_lib::llm::EmbeddingRequest,
-    ) -> Result<open_router_blueprint_template_lib::llm::EmbeddingResponse, LlmError> {
-        info!("Processing embedding request for model: {}", request.model);
+        request: EmbeddingRequest,
+    ) -> Result<EmbeddingResponse, LlmError> {
+        info!(
+            "Processing embedding request for model: {} ({} inputs)",
+            request.model,
+            request.input.len()
+        );
+        self.ensure_model_supported(&request.model)?;
 
-        // Check if the requested model is supported
-        let supported_models = self.get_supported_models();
-        let model_supported = supported_models.iter().any(|m| m.id == request.model);
+        let vllm_request = VllmEmbeddingRequest {
+            extra: passthrough_params(&request.additional_params, &self.passthrough_params),
+            model: request.model,
+            input: request.input,
+        };
 
-        if !model_supported {
-            error!(
-                "Model '{}' is not available in vLLM for embeddings",
-                request.model
-            );
-            return Err(LlmError::ModelNotSupported(format!(
-                "Model '{}' is not available in vLLM",
-                request.model
+        let url = format!("{}/v1/embeddings", self.api_url);
+        debug!("Sending embedding request to {}", url);
+        let resp = self
+            .compression
+            .send_json(
+                || apply_correlation_header(self.http_client.post(&url)),
+                &vllm_request,
+            )
+            .await
+            .map_err(|e| {
+                error!("Failed to send request to vLLM API: {}", e);
+                LlmError::RequestFailed(format!("Failed to send request to vLLM API: {}", e))
+            })?;
+
+        if !resp.status().is_success() {
+            let status = resp.status();
+            let body = read_text(resp).await.unwrap_or_default();
+            let message = extract_error_message(&body).unwrap_or_else(|| status.to_string());
+            error!("vLLM API error: {}", message);
+            if status == reqwest::StatusCode::BAD_REQUEST {
+                return Err(LlmError::ModelNotSupported(format!(
+                    "Model '{}' does not support embeddings in vLLM: {}",
+                    vllm_request.model, message
+                )));
+            }
+            return Err(LlmError::RequestFailed(format!(
+                "vLLM API error: {}",
+                message
             )));
         }
 
-        // vLLM may not support embeddings in all versions
-        warn!("Embeddings are not implemented in this vLLM blueprint example");
+        #[derive(Deserialize)]
+        struct VllmEmbedding {
+            index: usize,
+            embedding: Vec<f32>,
+        }
+
+        #[derive(Deserialize)]
+        struct VllmEmbeddingUsage {
+            prompt_tokens: u32,
+            total_tokens: u32,
+        }
+
+        #[derive(Deserialize)]
+        struct VllmEmbeddingResponse {
+            model: String,
+            data: Vec<VllmEmbedding>,
+            usage: Option<VllmEmbeddingUsage>,
+        }
+
+        let vllm_resp = read_json::<VllmEmbeddingResponse>(resp)
+            .await
+            .inspect_err(|e| error!("Failed to parse vLLM response: {}", e))?;
+
+        info!("Successfully embedded {} inputs", vllm_resp.data.len());
+        Ok(EmbeddingResponse {
+            object: "list".to_string(),
+            model: vllm_resp.model,
+            data: vllm_resp
+                .data
+                .into_iter()
+                .map(|d| EmbeddingData {
+                    index: d.index,
+                    embedding: d.embedding,
+                })
+                .collect(),
+            usage: vllm_resp.usage.map(|u| UsageInfo {
+                prompt_tokens: u.prompt_tokens,
+                completion_tokens: 0,
+                total_tokens: u.total_tokens,
+                prompt_tokens_cached: None,
+            }),
+            correlation_id: None,
+        })
+    }
+}
+
+#[async_trait]
+impl LlmClient for VllmLlmClient {
+    fn get_supported_models(&self) -> Vec<ModelInfo> {
+        debug!("Checking if model '{}' exists in vLLM", self.model);
+        // Fetch the models currently served by vLLM
+        let available_models = futures::executor::block_on(async {
+            let url = format!("{}/v1/models", self.api_url);
+            trace!("Sending request to {}", url);
+            let res = self.http_client.get(&url).send().await;
+            if let Ok(response) = res {
+                if response.status().is_success() {
+                    #[derive(Deserialize)]
+                    struct VllmModelsResponse {
+                        data: Vec<VllmModel>,
+                    }
+
+                    #[derive(Deserialize)]
+                    struct VllmModel {
+                        id: String,
+                    }
+
+                    if let Ok(models) = response.json::<VllmModelsResponse>().await {
+                        return models.data.into_iter().map(|m| m.id).collect();
+                    }
+                }
+            } else if let Err(e) = res {
+                warn!("Failed to get vLLM models: {}", e);
+            }
+            Vec::new()
+        });
+
+        if !self.models.is_empty() {
+            // Only report the supplied metadata for models the backend actually serves
+            let models: Vec<ModelInfo> = self
+                .models
+                .iter()
+                .filter(|m| available_models.contains(&m.id))
+                .cloned()
+                .collect();
+            debug!(
+                "{} of {} configured models are available in vLLM",
+                models.len(),
+                self.models.len()
+            );
+            return models;
+        }
+
+        let valid_model = available_models.contains(&self.model);
+        debug!("Model '{}' validation result: {}", self.model, valid_model);
+
+        if !valid_model {
+            // Return empty list for unsupported model
+            warn!(
+                "Model '{}' is not available in vLLM, returning empty model list",
+                self.model
+            );
+            return vec![];
+        }
+
+        info!("Model '{}' is available in vLLM", self.model);
+        vec![ModelInfo {
+            id: self.model.clone(),
+            name: self.model.clone(),
+            max_context_length: 4096, // Default value, could be model-specific
+            supports_chat: !self.embedding_model,
+            supports_text: !self.embedding_model,
+            supports_embeddings: self.embedding_model,
+            parameters: Default::default(),
+        }]
+    }
+
+    fn get_capabilities(&self) -> open_router_blueprint_template_lib::llm::LlmCapabilities {
+        open_router_blueprint_template_lib::llm::LlmCapabilities {
+            supports_streaming: true,   // vLLM supports streaming
+            max_concurrent_requests: 4, // vLLM can handle multiple concurrent requests
+            supports_batching: true,    // vLLM supports batching
+            features: Default::default(),
+        }
+    }
+
+    fn get_metrics(&self) -> NodeMetrics {
+        self.metrics
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
 
-        Err(LlmError::NotImplemented(
-            "vLLM embeddings not implemented in this example".to_string(),
-        ))
+    fn get_node_info(&self) -> NodeInfo {
+        NodeInfo {
+            backend: "vllm".to_string(),
+            version: self.version.get().cloned().flatten(),
+        }
     }
+
+    fn streaming_client(&self) -> Option<&dyn StreamingLlmClient> {
+        Some(self)
+    }
+
+    async fn health_check(&self) -> Result<(), LlmError> {
+        let url = format!("{}/health", self.api_url);
+        trace!("Checking vLLM health at {}", url);
+        match self.http_client.get(&url).send().await {
+            Ok(res) if res.status().is_success() => Ok(()),
+            Ok(res) => Err(LlmError::RequestFailed(format!(
+                "vLLM health check returned {}",
+                res.status()
+            ))),
+            Err(e) => Err(LlmError::RequestFailed(format!(
+                "vLLM health check failed: {}",
+                e
+            ))),
+        }
+    }
+
+    async fn chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse, LlmError> {
+        self.tracked(self.request_chat_completion(request)).await
+    }
+
+    async fn text_completion(
+        &self,
+        request: open_router_blueprint_template_lib::llm::TextCompletionRequest,
+    ) -> Result<open_router_blueprint_template_lib::llm::TextCompletionResponse, LlmError> {
+        self.tracked(self.request_text_completion(request)).await
+    }
+
+    async fn embeddings(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse, LlmError> {
+        self.tracked(self.request_embeddings(request)).await
+    }
+}
+
+#[async_trait]
+impl StreamingLlmClient for VllmLlmClient {
+    async fn streaming_chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionStream, LlmError> {
+        info!(
+            "Processing streaming chat completion request for model: {}",
+            request.model
+        );
+        self.ensure_model_supported(&request.model)?;
+
+        let body = self.chat_request_body(&request, Some(true)).await;
+        let resp = self.start_stream("/v1/chat/completions", &body).await?;
+        Ok(create_chat_completion_stream(read_sse_chunks(resp)))
+    }
+
+    async fn streaming_text_completion(
+        &self,
+        request: TextCompletionRequest,
+    ) -> Result<TextCompletionStream, LlmError> {
+        info!(
+            "Processing streaming text completion request for model: {}",
+            request.model
+        );
+        self.ensure_model_supported(&request.model)?;
+
+        let body = self.completion_request_body(request, Some(true));
+        let resp = self.start_stream("/v1/completions", &body).await?;
+        Ok(create_text_completion_stream(read_sse_chunks(resp)))
+    }
+}
+
+/// Number of parsed chunks buffered ahead of the consumer of a stream
+const STREAM_BUFFER: usize = 32;
+
+/// Build the vLLM client of an `llm` configuration section, the `LlmClientBuilder` of the
+/// `vllm` backend
+pub fn build_vllm_client(config: &LlmConfig) -> Arc<dyn LlmClient> {
+    Arc::new(VllmLlmClient::from_config(config))
+}
+
+/// Parse the `data:` events of a server-sent event response into chunks
+///
+/// Lines are reassembled across network reads before parsing. The channel closes after the
+/// terminal `data: [DONE]` event; a read or parse error is sent as the last item.
+fn read_sse_chunks<T>(mut resp: reqwest::Response) -> mpsc::Receiver<Result<T, LlmError>>
+where
+    T: DeserializeOwned + Send + 'static,
+{
+    let (tx, rx) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(async move {
+        let mut buffer: Vec<u8> = Vec::new();
+        loop {
+            let bytes = match resp.chunk().await {
+                Ok(Some(bytes)) => bytes,
+                Ok(None) => break,
+                Err(e) => {
+                    let _ = tx
+                        .send(Err(LlmError::RequestFailed(format!(
+                            "Failed to read vLLM stream: {}",
+                            e
+                        ))))
+                        .await;
+                    return;
+                }
+            };
+            buffer.extend_from_slice(&bytes);
+
+            while let Some(end) = buffer.iter().position(|&b| b == b'\n') {
+                let line: Vec<u8> = buffer.drain(..=end).collect();
+                let line = String::from_utf8_lossy(&line);
+                let Some(data) = line.trim().strip_prefix("data:") else {
+                    // Blank separators, comments and other SSE fields carry no chunk
+                    continue;
+                };
+                let data = data.trim();
+                if data == "[DONE]" {
+                    return;
+                }
+                let item = serde_json::from_str::<T>(data).map_err(|e| {
+                    LlmError::RequestFailed(format!("Failed to parse vLLM stream chunk: {}", e))
+                });
+                let failed = item.is_err();
+                if tx.send(item).await.is_err() || failed {
+                    // The consumer is gone, or the stream cannot continue
+                    return;
+                }
+            }
+        }
+        trace!("vLLM stream ended without a [DONE] event");
+    });
+    rx
+}
+
+/// Extract a human-readable message from an OpenAI-compatible error body
+///
+/// Understands `{"error": {"message": ..., "type": ...}}`, `{"error": "..."}`, and FastAPI's
+/// `{"detail": "..."}` / `{"detail": [{"msg": ...}]}`. Any other non-empty body is returned as is.
+fn extract_error_message(body: &str) -> Option<String> {
+    let body = body.trim();
+    if body.is_empty() {
+        return None;
+    }
+
+    let Ok(value) = serde_json::from_str::<serde_json::Value>(body) else {
+        return Some(body.to_string());
+    };
+
+    let message = match (value.get("error"), value.get("detail")) {
+        (Some(serde_json::Value::Object(error)), _) => {
+            let message = error.get("message").and_then(|m| m.as_str());
+            let error_type = error.get("type").and_then(|t| t.as_str());
+            match (message, error_type) {
+                (Some(message), Some(error_type)) => Some(format!("{} ({})", message, error_type)),
+                (Some(message), None) => Some(message.to_string()),
+                _ => None,
+            }
+        }
+        (Some(serde_json::Value::String(error)), _) => Some(error.clone()),
+        (_, Some(serde_json::Value::String(detail))) => Some(detail.clone()),
+        (_, Some(serde_json::Value::Array(details))) => {
+            let messages: Vec<&str> = details
+                .iter()
+                .filter_map(|d| d.get("msg").and_then(|m| m.as_str()))
+                .collect();
+            (!messages.is_empty()).then(|| messages.join("; "))
+        }
+        _ => None,
+    };
+
+    message.or_else(|| Some(body.to_string()))
 }
@@ -1,8 +1,35 @@
+use backend_test_support::{MockResponse, MockServer};
+use futures::StreamExt;
+use open_router_blueprint_template_lib::config::{LlmBackend, LlmConfig};
+use open_router_blueprint_template_lib::correlation::{with_correlation_id, CORRELATION_ID_HEADER};
 use open_router_blueprint_template_lib::llm::{
-    ChatCompletionRequest, ChatMessage, LlmClient, LlmError, ModelInfo, TextCompletionRequest,
+    collect_chat_completion_stream, ChatCompletionRequest, ChatMessage, EmbeddingRequest,
+    FunctionDefinition, LlmClient, LlmClientExt, LlmError, ModelInfo, StreamingLlmClient,
+    TextCompletionRequest, ToolChoice, ToolChoiceMode, ToolDefinition,
 };
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
 use std::time::Duration;
-use vllm_blueprint::VllmLlmClient;
+use open_router_blueprint_template_lib::factory::LlmClientFactory;
+use vllm_blueprint::{build_vllm_client, VllmLlmClient};
+
+fn model_info(id: &str, max_context_length: usize) -> ModelInfo {
+    ModelInfo {
+        id: id.to_string(),
+        name: id.to_string(),
+        max_context_length,
+        supports_chat: true,
+        supports_text: true,
+        supports_embeddings: true,
+        parameters: HashMap::new(),
+    }
+}
+
+fn models_server(available: &[&str]) -> MockServer {
+    let data: Vec<_> = available.iter().map(|id| json!({ "id": id })).collect();
+    MockServer::start(move |_| MockResponse::json(200, json!({ "data": data })))
+}
 
 #[tokio::test]
 async fn test_vllm_client_creation() {
@@ -21,6 +48,920 @@ async fn test_vllm_capabilities() {
     assert!(capabilities.supports_batching);
 }
 
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_factory_builds_vllm_client_for_vllm_backend() {
+    let server = models_server(&["llama3"]);
+    let config = LlmConfig {
+        backend: LlmBackend::Vllm,
+        api_url: server.url.clone(),
+        models: vec![model_info("llama3", 32768)],
+        ..Default::default()
+    };
+    let factory = LlmClientFactory::new().with_backend(LlmBackend::Vllm, build_vllm_client);
+
+    let client = factory.build(&config).unwrap();
+
+    // The capabilities of a vLLM client, not of the template's local one
+    let capabilities = client.get_capabilities();
+    assert!(capabilities.supports_streaming);
+    assert!(capabilities.supports_batching);
+    assert_eq!(capabilities.max_concurrent_requests, 4);
+    assert_eq!(client.get_supported_models()[0].max_context_length, 32768);
+    assert_eq!(server.requests_to("/v1/models").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_with_models_overrides_metadata() {
+    let server = models_server(&["llama3", "mistral"]);
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string()).with_models(vec![
+        model_info("llama3", 32768),
+        model_info("mistral", 8192),
+        model_info("missing", 4096),
+    ]);
+
+    let models = client.get_supported_models();
+
+    let ids: Vec<_> = models.iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, vec!["llama3", "mistral"]);
+    assert_eq!(models[0].max_context_length, 32768);
+    assert!(models[0].supports_embeddings);
+    assert_eq!(server.requests_to("/v1/models").len(), 1);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_without_models_uses_default_metadata() {
+    let server = models_server(&["llama3"]);
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let models = client.get_supported_models();
+
+    assert_eq!(models.len(), 1);
+    assert_eq!(models[0].id, "llama3");
+    assert!(!models[0].supports_embeddings);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_embedding_model_metadata() {
+    let server = models_server(&["bge-m3"]);
+    let client =
+        VllmLlmClient::new(server.url.clone(), "bge-m3".to_string()).with_embedding_model(true);
+
+    let models = client.get_supported_models();
+
+    assert!(models[0].supports_embeddings);
+    assert!(!models[0].supports_chat);
+    assert!(!models[0].supports_text);
+}
+
+fn embedding_request(model: &str) -> EmbeddingRequest {
+    EmbeddingRequest {
+        model: model.to_string(),
+        input: vec!["first".to_string(), "second".to_string()],
+        additional_params: Default::default(),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_embeddings() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "bge-m3" }] })),
+        _ => MockResponse::json(
+            200,
+            json!({
+                "id": "embd-1",
+                "object": "list",
+                "created": 1700000000,
+                "model": "bge-m3",
+                "data": [
+                    { "object": "embedding", "index": 0, "embedding": [0.5, 0.25] },
+                    { "object": "embedding", "index": 1, "embedding": [-1.0, 0.0] }
+                ],
+                "usage": { "prompt_tokens": 6, "total_tokens": 6, "completion_tokens": 0 }
+            }),
+        ),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "bge-m3".to_string());
+
+    let response = client
+        .embeddings(embedding_request("bge-m3"))
+        .await
+        .unwrap();
+
+    let vectors: Vec<_> = response
+        .data
+        .iter()
+        .map(|d| (d.index, d.embedding.clone()))
+        .collect();
+    assert_eq!(vectors, vec![(0, vec![0.5, 0.25]), (1, vec![-1.0, 0.0])]);
+    let usage = response.usage.unwrap();
+    assert_eq!((usage.prompt_tokens, usage.total_tokens), (6, 6));
+
+    let requests = server.requests_to("/v1/embeddings");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(
+        requests[0].body_json(),
+        json!({ "model": "bge-m3", "input": ["first", "second"] })
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_embeddings_with_generative_model() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(
+            400,
+            json!({
+                "object": "error",
+                "message": "The model does not support Embeddings API",
+                "type": "BadRequestError",
+                "code": 400
+            }),
+        ),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let error = client
+        .embeddings(embedding_request("llama3"))
+        .await
+        .unwrap_err();
+
+    match error {
+        LlmError::ModelNotSupported(message) => {
+            assert!(message.contains("llama3"), "{}", message);
+            assert!(message.contains("Embeddings API"), "{}", message);
+        }
+        other => panic!("Expected ModelNotSupported, got {:?}", other),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_chat_completion_reports_cached_prompt_tokens() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(
+            200,
+            json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 1700000000,
+                "model": "llama3",
+                "choices": [{
+                    "index": 0,
+                    "message": { "role": "assistant", "content": "Hi" },
+                    "finish_reason": "stop"
+                }],
+                "usage": {
+                    "prompt_tokens": 20,
+                    "completion_tokens": 1,
+                    "total_tokens": 21,
+                    "prompt_tokens_details": { "cached_tokens": 16 }
+                }
+            }),
+        ),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let response = client
+        .chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let usage = response.usage.unwrap();
+    assert_eq!(usage.prompt_tokens, 20);
+    assert_eq!(usage.prompt_tokens_cached, Some(16));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_health_check() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/health" => MockResponse::text(200, "text/plain", ""),
+        _ => MockResponse::text(404, "text/plain", "Not Found"),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+    assert!(client.health_check().await.is_ok());
+
+    let server = MockServer::start(|_| MockResponse::text(503, "text/plain", ""));
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+    let error = client.health_check().await.unwrap_err();
+    assert!(error.to_string().contains("503"), "{}", error);
+
+    // Nothing listens on the discard port
+    let client = VllmLlmClient::new("http://127.0.0.1:9".to_string(), "llama3".to_string());
+    let error = client.health_check().await.unwrap_err();
+    assert!(error.to_string().contains("vLLM health check failed"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_chat_completion_parses_reasoning_content() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "deepseek-r1" }] })),
+        _ => MockResponse::json(
+            200,
+            json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 1700000000,
+                "model": "deepseek-r1",
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "reasoning_content": "The user greets me, so I greet back.",
+                        "content": "Hi!"
+                    },
+                    "finish_reason": "stop"
+                }]
+            }),
+        ),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "deepseek-r1".to_string());
+
+    let response = client
+        .chat_completion(ChatCompletionRequest {
+            model: "deepseek-r1".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let message = &response.choices[0].message;
+    assert_eq!(message.content, "Hi!");
+    assert_eq!(
+        message.reasoning_content.as_deref(),
+        Some("The user greets me, so I greet back.")
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_streaming_chat_completion() {
+    let server = MockServer::start(|req| {
+        match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::text(
+            200,
+            "text/event-stream",
+            concat!(
+                ": ping\n\n",
+                "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1700000000,\"model\":\"llama3\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"finish_reason\":null}]}\n\n",
+                "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1700000000,\"model\":\"llama3\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\"},\"finish_reason\":\"stop\"}]}\n\n",
+                "data: [DONE]\n\n",
+            ),
+        ),
+    }
+    });
+    let client: Arc<dyn LlmClient> =
+        Arc::new(VllmLlmClient::new(server.url.clone(), "llama3".to_string()));
+
+    let streaming = client.as_streaming().expect("vLLM supports streaming");
+    let chunks: Vec<_> = streaming
+        .streaming_chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap()
+        .collect()
+        .await;
+
+    let deltas: Vec<_> = chunks
+        .into_iter()
+        .map(|chunk| chunk.unwrap().choices[0].delta.content.clone().unwrap())
+        .collect();
+    assert_eq!(deltas, ["Hel", "lo"]);
+
+    let requests = server.requests_to("/v1/chat/completions");
+    assert_eq!(requests[0].body_json()["stream"], json!(true));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_streaming_chat_completion_collects_logprobs() {
+    let server = MockServer::start(|req| {
+        match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::text(
+            200,
+            "text/event-stream",
+            concat!(
+                "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1700000000,\"model\":\"llama3\",\"choices\":[{\"index\":0,\"delta\":{\"role\":\"assistant\",\"content\":\"Hel\"},\"logprobs\":{\"content\":[{\"token\":\"Hel\",\"logprob\":-0.5,\"bytes\":[72,101,108],\"top_logprobs\":[{\"token\":\"Hel\",\"logprob\":-0.5,\"bytes\":[72,101,108]}]}]},\"finish_reason\":null}]}\n\n",
+                "data: {\"id\":\"chatcmpl-1\",\"object\":\"chat.completion.chunk\",\"created\":1700000000,\"model\":\"llama3\",\"choices\":[{\"index\":0,\"delta\":{\"content\":\"lo\"},\"logprobs\":{\"content\":[{\"token\":\"lo\",\"logprob\":-0.125,\"bytes\":[108,111],\"top_logprobs\":[{\"token\":\"lo\",\"logprob\":-0.125,\"bytes\":[108,111]}]}]},\"finish_reason\":\"stop\"}]}\n\n",
+                "data: [DONE]\n\n",
+            ),
+        ),
+    }
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string())
+        .with_passthrough_params(vec!["logprobs".to_string(), "top_logprobs".to_string()]);
+
+    let stream = client
+        .streaming_chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            additional_params: HashMap::from([
+                ("logprobs".to_string(), json!(true)),
+                ("top_logprobs".to_string(), json!(1)),
+            ]),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+    let response = collect_chat_completion_stream(stream).await.unwrap();
+
+    let choice = &response.choices[0];
+    assert_eq!(choice.message.content, "Hello");
+    let logprobs = &choice.logprobs.as_ref().unwrap().content;
+    let tokens: Vec<_> = logprobs.iter().map(|l| l.token.as_str()).collect();
+    assert_eq!(tokens, ["Hel", "lo"]);
+    assert_eq!(logprobs[1].logprob, -0.125);
+    assert_eq!(logprobs[1].top_logprobs[0].token, "lo");
+
+    let requests = server.requests_to("/v1/chat/completions");
+    let body = requests[0].body_json();
+    assert_eq!(body["logprobs"], json!(true));
+    assert_eq!(body["top_logprobs"], json!(1));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_streaming_error_status() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(400, json!({ "error": { "message": "prompt too long" } })),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let result = client
+        .streaming_text_completion(TextCompletionRequest {
+            model: "llama3".to_string(),
+            prompt: "Once upon a time".to_string(),
+            ..Default::default()
+        })
+        .await;
+
+    match result {
+        Err(LlmError::RequestFailed(message)) => assert!(message.contains("prompt too long")),
+        Err(other) => panic!("Unexpected error: {:?}", other),
+        Ok(_) => panic!("Expected the request to fail"),
+    }
+}
+
+/// Send a chat completion to a server that fails with the given error body
+async fn chat_error_for(status: u16, content_type: &'static str, body: &'static str) -> String {
+    let server = MockServer::start(move |req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::text(status, content_type, body),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let result = client
+        .chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            ..Default::default()
+        })
+        .await;
+
+    match result {
+        Err(LlmError::RequestFailed(message)) => message,
+        other => panic!("Expected RequestFailed, got {:?}", other),
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_error_object_body() {
+    let message = chat_error_for(
+        400,
+        "application/json",
+        r#"{"error": {"message": "max_tokens is too large", "type": "invalid_request_error"}}"#,
+    )
+    .await;
+    assert_eq!(
+        message,
+        "vLLM API error: max_tokens is too large (invalid_request_error)"
+    );
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_error_string_body() {
+    let message = chat_error_for(500, "application/json", r#"{"error": "engine is dead"}"#).await;
+    assert_eq!(message, "vLLM API error: engine is dead");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_error_detail_body() {
+    let message = chat_error_for(404, "application/json", r#"{"detail": "Not Found"}"#).await;
+    assert_eq!(message, "vLLM API error: Not Found");
+
+    let message = chat_error_for(
+        422,
+        "application/json",
+        r#"{"detail": [{"loc": ["body", "messages"], "msg": "field required"}]}"#,
+    )
+    .await;
+    assert_eq!(message, "vLLM API error: field required");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_error_raw_and_empty_body() {
+    let message = chat_error_for(502, "text/plain", "upstream connect error").await;
+    assert_eq!(message, "vLLM API error: upstream connect error");
+
+    let message = chat_error_for(503, "text/plain", "").await;
+    assert_eq!(message, "vLLM API error: 503 Service Unavailable");
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_forwards_correlation_id_header() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(500, json!({ "error": "unavailable" })),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+    let request = TextCompletionRequest {
+        model: "llama3".to_string(),
+        prompt: "Hello".to_string(),
+        ..Default::default()
+    };
+
+    let _ = with_correlation_id(
+        "tangle-call-9".to_string(),
+        client.text_completion(request.clone()),
+    )
+    .await;
+    let _ = client.text_completion(request).await;
+
+    let requests = server.requests_to("/v1/completions");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(
+        requests[0].header(CORRELATION_ID_HEADER),
+        Some("tangle-call-9")
+    );
+    assert_eq!(requests[1].header(CORRELATION_ID_HEADER), None);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_http_client_applies_http2_config() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(500, json!({ "error": "unavailable" })),
+    });
+    let request = TextCompletionRequest {
+        model: "llama3".to_string(),
+        prompt: "Hello".to_string(),
+        ..Default::default()
+    };
+
+    // By default requests go out as HTTP/1.1
+    let http_client = LlmConfig::default().http_client_builder().build().unwrap();
+    let client =
+        VllmLlmClient::new(server.url.clone(), "llama3".to_string()).with_http_client(http_client);
+    let _ = client.text_completion(request.clone()).await;
+    assert_eq!(server.requests_to("/v1/completions").len(), 1);
+
+    // With `http2` connections open with the HTTP/2 preface instead, which this server
+    // cannot answer
+    let config = LlmConfig {
+        http2: true,
+        keep_alive_interval_seconds: Some(30),
+        ..Default::default()
+    };
+    let http_client = config.http_client_builder().build().unwrap();
+    let client =
+        VllmLlmClient::new(server.url.clone(), "llama3".to_string()).with_http_client(http_client);
+    let result = client.text_completion(request).await;
+
+    assert!(result.is_err());
+    let preface = server.requests_to("*");
+    assert_eq!(preface.len(), 1);
+    assert_eq!(preface[0].method, "PRI");
+    assert_eq!(server.requests_to("/v1/completions").len(), 1);
+}
+
+/// The body of `req` as JSON, gunzipped if it was sent compressed
+fn decoded_body_json(req: &backend_test_support::RecordedRequest) -> serde_json::Value {
+    use std::io::Read;
+
+    if req.header("content-encoding") != Some("gzip") {
+        return req.body_json();
+    }
+    let mut body = Vec::new();
+    flate2::read::GzDecoder::new(req.body.as_slice())
+        .read_to_end(&mut body)
+        .unwrap();
+    serde_json::from_slice(&body).unwrap()
+}
+
+fn completion_response(text: &str) -> MockResponse {
+    MockResponse::json(
+        200,
+        json!({
+            "id": "cmpl-1",
+            "object": "text_completion",
+            "created": 1700000000,
+            "model": "llama3",
+            "choices": [{ "index": 0, "text": text, "finish_reason": "stop" }]
+        }),
+    )
+}
+
+fn text_request(prompt: String) -> TextCompletionRequest {
+    TextCompletionRequest {
+        model: "llama3".to_string(),
+        prompt,
+        ..Default::default()
+    }
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_compressed_request_is_accepted() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => {
+            let prompt = decoded_body_json(req)["prompt"].as_str().unwrap().len();
+            completion_response(&format!("{} characters", prompt))
+        }
+    });
+    let client =
+        VllmLlmClient::new(server.url.clone(), "llama3".to_string()).with_compression(true);
+    let long_prompt = "Summarize this document. ".repeat(200);
+
+    let response = client
+        .text_completion(text_request(long_prompt.clone()))
+        .await
+        .unwrap();
+    client
+        .text_completion(text_request("Hello".to_string()))
+        .await
+        .unwrap();
+
+    assert_eq!(
+        response.choices[0].text,
+        format!("{} characters", long_prompt.len())
+    );
+    let requests = server.requests_to("/v1/completions");
+    assert_eq!(requests.len(), 2);
+    assert_eq!(requests[0].header("content-encoding"), Some("gzip"));
+    assert_eq!(requests[0].header("accept-encoding"), Some("gzip"));
+    assert!(requests[0].body.len() < long_prompt.len());
+    assert_eq!(
+        decoded_body_json(&requests[0])["prompt"],
+        json!(long_prompt)
+    );
+    // Small bodies are not worth compressing
+    assert_eq!(requests[1].header("content-encoding"), None);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_compression_is_turned_off_when_rejected() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ if req.header("content-encoding").is_some() => {
+            MockResponse::json(415, json!({ "error": "unsupported content encoding" }))
+        }
+        _ => completion_response("ok"),
+    });
+    let client =
+        VllmLlmClient::new(server.url.clone(), "llama3".to_string()).with_compression(true);
+    let long_prompt = "Summarize this document. ".repeat(200);
+
+    for _ in 0..2 {
+        let response = client
+            .text_completion(text_request(long_prompt.clone()))
+            .await
+            .unwrap();
+        assert_eq!(response.choices[0].text, "ok");
+    }
+
+    // Only the first body is sent compressed, and it is resent plain
+    let encodings: Vec<_> = server
+        .requests_to("/v1/completions")
+        .iter()
+        .map(|req| req.header("content-encoding").map(str::to_string))
+        .collect();
+    assert_eq!(encodings, vec![Some("gzip".to_string()), None, None]);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_slow_response_times_out() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => {
+            std::thread::sleep(Duration::from_millis(500));
+            completion_response("too late")
+        }
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string())
+        .with_timeout(Duration::from_millis(100));
+
+    let error = client
+        .text_completion(text_request("Hello".to_string()))
+        .await
+        .unwrap_err();
+
+    assert!(matches!(error, LlmError::Timeout(timeout) if timeout == Duration::from_millis(100)));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_completed_requests_are_counted_in_metrics() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => completion_response("ok"),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    for _ in 0..2 {
+        client
+            .text_completion(text_request("Hello".to_string()))
+            .await
+            .unwrap();
+    }
+
+    let metrics = client.get_metrics();
+    assert_eq!(metrics.requests_per_minute, 2);
+    assert_eq!(metrics.active_requests, 0);
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_chat_completion_forwards_tools_and_parses_tool_calls() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(
+            200,
+            json!({
+                "id": "chatcmpl-1",
+                "object": "chat.completion",
+                "created": 1700000000,
+                "model": "llama3",
+                "choices": [{
+                    "index": 0,
+                    "message": {
+                        "role": "assistant",
+                        "content": null,
+                        "tool_calls": [{
+                            "id": "call_1",
+                            "type": "function",
+                            "function": {
+                                "name": "get_weather",
+                                "arguments": "{\"city\":\"Paris\"}"
+                            }
+                        }]
+                    },
+                    "finish_reason": "tool_calls"
+                }]
+            }),
+        ),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+    let tool = ToolDefinition {
+        kind: "function".to_string(),
+        function: FunctionDefinition {
+            name: "get_weather".to_string(),
+            description: None,
+            parameters: Some(json!({ "type": "object" })),
+        },
+    };
+
+    let response = client
+        .chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Weather in Paris?".to_string(),
+                ..Default::default()
+            }],
+            tools: Some(vec![tool]),
+            tool_choice: Some(ToolChoice::Mode(ToolChoiceMode::Required)),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+
+    let body = server.requests_to("/v1/chat/completions")[0].body_json();
+    assert_eq!(body["tools"][0]["function"]["name"], "get_weather");
+    assert_eq!(body["tool_choice"], "required");
+
+    let choice = &response.choices[0];
+    assert_eq!(choice.message.content, "");
+    let calls = choice.message.tool_calls.as_ref().unwrap();
+    assert_eq!(calls[0].function.name, "get_weather");
+    assert_eq!(calls[0].function.arguments, r#"{"city":"Paris"}"#);
+    assert_eq!(choice.tool_calls.as_ref(), Some(calls));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_forwards_only_allowlisted_params() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(500, json!({ "error": "unavailable" })),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string())
+        .with_passthrough_params(vec!["repetition_penalty".to_string()]);
+    let request = TextCompletionRequest {
+        model: "llama3".to_string(),
+        prompt: "Hello".to_string(),
+        additional_params: HashMap::from([
+            ("repetition_penalty".to_string(), json!(1.1)),
+            ("unsupported_field".to_string(), json!("dropped")),
+        ]),
+        ..Default::default()
+    };
+
+    let _ = client.text_completion(request).await;
+
+    let requests = server.requests_to("/v1/completions");
+    assert_eq!(requests.len(), 1);
+    let body = requests[0].body_json();
+    assert_eq!(body["repetition_penalty"], json!(1.1));
+    assert!(body.get("unsupported_field").is_none());
+    assert!(body.get("additional_params").is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_reserved_params_do_not_override_request_fields() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(500, json!({ "error": "unavailable" })),
+    });
+    // Even an operator allowlisting reserved keys cannot let callers override typed fields
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string())
+        .with_passthrough_params(vec![
+            "model".to_string(),
+            "messages".to_string(),
+            "stream".to_string(),
+            "repetition_penalty".to_string(),
+        ]);
+
+    let _ = client
+        .chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            additional_params: HashMap::from([
+                ("model".to_string(), json!("other-model")),
+                ("messages".to_string(), json!([])),
+                ("stream".to_string(), json!(true)),
+                ("repetition_penalty".to_string(), json!(1.1)),
+            ]),
+            ..Default::default()
+        })
+        .await;
+
+    let requests = server.requests_to("/v1/chat/completions");
+    assert_eq!(requests.len(), 1);
+    let body = requests[0].body_json();
+    assert_eq!(body["model"], json!("llama3"));
+    assert_eq!(body["messages"][0]["content"], json!("Hello"));
+    assert!(body.get("stream").is_none());
+    assert_eq!(body["repetition_penalty"], json!(1.1));
+}
+
+/// Send a chat completion with `max_tokens` to a server reporting the given version and
+/// return the request body it received
+async fn chat_body_for_version(version: &'static str) -> serde_json::Value {
+    let server = MockServer::start(move |req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        "/version" => MockResponse::json(200, json!({ "version": version })),
+        _ => MockResponse::json(500, json!({ "error": "unavailable" })),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let _ = client
+        .chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            max_tokens: Some(64),
+            ..Default::default()
+        })
+        .await;
+
+    assert_eq!(client.get_node_info().version.as_deref(), Some(version));
+    let requests = server.requests_to("/v1/chat/completions");
+    assert_eq!(requests.len(), 1);
+    requests[0].body_json()
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_max_completion_tokens_gated_on_detected_version() {
+    let body = chat_body_for_version("0.6.3.post1").await;
+    assert_eq!(body["max_completion_tokens"], json!(64));
+    assert!(body.get("max_tokens").is_none());
+
+    let body = chat_body_for_version("0.5.4").await;
+    assert_eq!(body["max_tokens"], json!(64));
+    assert!(body.get("max_completion_tokens").is_none());
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_forwards_stop_sequences() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(500, json!({ "error": "unavailable" })),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let _ = client
+        .chat_completion(ChatCompletionRequest {
+            model: "llama3".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                ..Default::default()
+            }],
+            stop: Some(vec!["\n\n".to_string()]),
+            ..Default::default()
+        })
+        .await;
+    let _ = client
+        .text_completion(TextCompletionRequest {
+            model: "llama3".to_string(),
+            prompt: "Hello".to_string(),
+            stop: Some(vec!["\n\n".to_string()]),
+            ..Default::default()
+        })
+        .await;
+
+    let chat = server.requests_to("/v1/chat/completions");
+    assert_eq!(chat.len(), 1);
+    assert_eq!(chat[0].body_json()["stop"], json!(["\n\n"]));
+    let completions = server.requests_to("/v1/completions");
+    assert_eq!(completions.len(), 1);
+    assert_eq!(completions[0].body_json()["stop"], json!(["\n\n"]));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_forwards_choice_count() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(500, json!({ "error": "unavailable" })),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let _ = client
+        .text_completion(TextCompletionRequest {
+            model: "llama3".to_string(),
+            prompt: "Hello".to_string(),
+            n: Some(3),
+            ..Default::default()
+        })
+        .await;
+
+    let requests = server.requests_to("/v1/completions");
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].body_json()["n"], json!(3));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_vllm_omits_unset_stop_sequences() {
+    let server = MockServer::start(|req| match req.path.as_str() {
+        "/v1/models" => MockResponse::json(200, json!({ "data": [{ "id": "llama3" }] })),
+        _ => MockResponse::json(500, json!({ "error": "unavailable" })),
+    });
+    let client = VllmLlmClient::new(server.url.clone(), "llama3".to_string());
+
+    let _ = client
+        .text_completion(TextCompletionRequest {
+            model: "llama3".to_string(),
+            prompt: "Hello".to_string(),
+            ..Default::default()
+        })
+        .await;
+
+    let requests = server.requests_to("/v1/completions");
+    assert_eq!(requests.len(), 1);
+    assert!(requests[0].body_json().get("stop").is_none());
+}
+
 // The following tests require a running vLLM server
 // They are disabled by default and can be enabled with the "integration" feature
 
@@ -45,11 +986,17 @@ async fn test_vllm_chat_completion() {
         messages: vec![ChatMessage {
             role: "user".to_string(),
             content: "Hello, how are you?".to_string(),
-            name: None,
+            ..Default::default()
         }],
         max_tokens: Some(50),
         temperature: Some(0.7),
         top_p: None,
+        stop: None,
+        n: None,
+        service_tier: None,
+        session_id: None,
+        tools: None,
+        tool_choice: None,
         stream: None,
         additional_params: Default::default(),
     };
@@ -74,6 +1021,8 @@ async fn test_vllm_text_completion() {
         max_tokens: Some(50),
         temperature: Some(0.7),
         top_p: None,
+        stop: None,
+        n: None,
         stream: None,
         additional_params: Default::default(),
     };
@@ -89,10 +1038,10 @@ async fn test_vllm_text_completion() {
 
 #[tokio::test]
 #[ignore]
-async fn test_vllm_embeddings_not_implemented() {
+async fn test_vllm_embeddings_generative_model_not_supported() {
     let client = VllmLlmClient::new("http://localhost:8000".to_string(), "llama3".to_string());
 
-    let request = open_router_blueprint_template_lib::llm::EmbeddingRequest {
+    let request = EmbeddingRequest {
         model: "llama3".to_string(),
         input: vec!["Hello, world!".to_string()],
         additional_params: Default::default(),
@@ -100,11 +1049,11 @@ async fn test_vllm_embeddings_not_implemented() {
 
     let response = client.embeddings(request).await;
 
-    // Embeddings are not implemented in this example
+    // This test assumes that "llama3" is served as a generative model, not with --task embed
     assert!(response.is_err());
     match response {
-        Err(LlmError::NotImplemented(_)) => (),
-        _ => panic!("Expected NotImplemented error"),
+        Err(LlmError::ModelNotSupported(_)) => (),
+        _ => panic!("Expected ModelNotSupported error"),
     }
 }
 
@@ -121,11 +1070,17 @@ async fn test_vllm_invalid_model() {
         messages: vec![ChatMessage {
             role: "user".to_string(),
             content: "Hello, how are you?".to_string(),
-            name: None,
+            ..Default::default()
         }],
         max_tokens: Some(50),
         temperature: Some(0.7),
         top_p: None,
+        stop: None,
+        n: None,
+        service_tier: None,
+        session_id: None,
+        tools: None,
+        tool_choice: None,
         stream: None,
         additional_params: Default::default(),
     };
@@ -150,11 +1105,17 @@ async fn test_vllm_server_unavailable() {
         messages: vec![ChatMessage {
             role: "user".to_string(),
             content: "Hello, how are you?".to_string(),
-            name: None,
+            ..Default::default()
         }],
         max_tokens: Some(50),
         temperature: Some(0.7),
         top_p: None,
+        stop: None,
+        n: None,
+        service_tier: None,
+        session_id: None,
+        tools: None,
+        tool_choice: None,
         stream: None,
         additional_params: Default::default(),
     };
@@ -1,6 +1,5 @@
 use blueprint_sdk::build;
-use blueprint_sdk::tangle::blueprint;
-use open_router_blueprint_template_lib::jobs::process_llm_request;
+use open_router_blueprint_template_lib::metadata::blueprint_json;
 use std::path::Path;
 use std::process;
 
@@ -17,19 +16,8 @@ fn main() {
 
     println!("cargo::rerun-if-changed=../open-router-blueprint-template-lib");
 
-    let blueprint = blueprint! {
-        name: "open-router-blueprint",
-        master_manager_revision: "Latest",
-        manager: { Evm = "OpenRouterBlueprint" },
-        jobs: [process_llm_request]
-    };
-
-    match blueprint {
-        Ok(blueprint) => {
-            let json = blueprint_sdk::tangle::metadata::macros::ext::serde_json::to_string_pretty(
-                &blueprint,
-            )
-            .unwrap();
+    match blueprint_json() {
+        Ok(json) => {
             std::fs::write(
                 Path::new(env!("CARGO_WORKSPACE_DIR")).join("blueprint.json"),
                 json.as_bytes(),
@@ -37,7 +25,7 @@ fn main() {
             .unwrap();
         }
         Err(e) => {
-            println!("cargo::error={e:?}");
+            println!("cargo::error={e}");
             process::exit(1);
         }
     }
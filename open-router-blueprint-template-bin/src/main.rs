@@ -12,9 +12,11 @@ use blueprint_sdk::tangle::filters::MatchesServiceId;
 use blueprint_sdk::tangle::layers::TangleLayer;
 use blueprint_sdk::tangle::producer::TangleProducer;
 use open_router_blueprint_template_lib::{
-    OpenRouterContext, PROCESS_LLM_REQUEST_JOB_ID, REPORT_METRICS_JOB_ID, process_llm_request,
-    report_metrics,
+    OpenRouterContext, POLL_LLM_STREAM_JOB_ID, PROCESS_LLM_BATCH_JOB_ID, PROCESS_LLM_REQUEST_JOB_ID,
+    REPORT_METRICS_JOB_ID, REPORT_NODE_JOB_ID, START_LLM_STREAM_JOB_ID, poll_llm_stream,
+    process_llm_batch, process_llm_request, report_metrics, report_node, start_llm_stream,
 };
+use std::path::PathBuf;
 use std::time::Duration;
 use tower::filter::FilterLayer;
 use tracing::level_filters::LevelFilter;
@@ -22,8 +24,25 @@ use tracing::{error, info};
 
 #[tokio::main]
 async fn main() -> Result<(), blueprint_sdk::Error> {
+    // Print before logging is set up, so stdout only carries the JSON
+    if print_blueprint_requested() {
+        let json = open_router_blueprint_template_lib::metadata::blueprint_json()
+            .map_err(blueprint_sdk::Error::Other)?;
+        println!("{json}");
+        return Ok(());
+    }
+
     setup_log();
 
+    if let Some(dir) = dump_schemas_dir().map_err(blueprint_sdk::Error::Other)? {
+        let paths = open_router_blueprint_template_lib::schemas::write_schemas(&dir)
+            .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+        for path in paths {
+            info!("Wrote schema: {}", path.display());
+        }
+        return Ok(());
+    }
+
     let env = BlueprintEnvironment::load()?;
 
     if let Some(data_dir) = env.data_dir.as_ref() {
@@ -57,7 +76,10 @@ async fn main() -> Result<(), blueprint_sdk::Error> {
         "Load balancer strategy: {:?}",
         config.load_balancer.strategy
     );
-    info!("API listening on {}:{}", config.api.host, config.api.port);
+    drop(config);
+
+    // Kept alive for as long as the runner, which stops the server when dropped
+    let _api_server = context.serve_api().await?;
 
     let service_id = env.protocol_settings.tangle()?.service_id.unwrap();
     info!("Using Tangle service ID: {}", service_id);
@@ -79,6 +101,13 @@ async fn main() -> Result<(), blueprint_sdk::Error> {
                     process_llm_request.layer(TangleLayer),
                 )
                 .route(REPORT_METRICS_JOB_ID, report_metrics.layer(TangleLayer))
+                .route(
+                    PROCESS_LLM_BATCH_JOB_ID,
+                    process_llm_batch.layer(TangleLayer),
+                )
+                .route(REPORT_NODE_JOB_ID, report_node.layer(TangleLayer))
+                .route(START_LLM_STREAM_JOB_ID, start_llm_stream.layer(TangleLayer))
+                .route(POLL_LLM_STREAM_JOB_ID, poll_llm_stream.layer(TangleLayer))
                 .layer(FilterLayer::new(MatchesServiceId(service_id)))
                 .with_context(context.clone()),
         )
@@ -97,10 +126,29 @@ async fn main() -> Result<(), blueprint_sdk::Error> {
     Ok(())
 }
 
+/// Parse `--dump-schemas <dir>` from the command line
+fn dump_schemas_dir() -> Result<Option<PathBuf>, String> {
+    let mut args = std::env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == "--dump-schemas" {
+            return args
+                .next()
+                .map(|dir| Some(PathBuf::from(dir)))
+                .ok_or_else(|| "--dump-schemas requires a directory".to_string());
+        }
+    }
+    Ok(None)
+}
+
+/// Whether `--print-blueprint` was given on the command line
+fn print_blueprint_requested() -> bool {
+    std::env::args().skip(1).any(|arg| arg == "--print-blueprint")
+}
+
 pub fn setup_log() {
     use tracing_subscriber::util::SubscriberInitExt;
 
-    let _ = tracing_subscriber::fmt::SubscriberBuilder::default()
+    let subscriber = tracing_subscriber::fmt::SubscriberBuilder::default()
         .without_time()
         .with_span_events(tracing_subscriber::fmt::format::FmtSpan::NONE)
         .with_env_filter(
@@ -108,6 +156,22 @@ pub fn setup_log() {
                 .with_default_directive(LevelFilter::INFO.into())
                 .from_env_lossy(),
         )
-        .finish()
-        .try_init();
+        .finish();
+
+    // Export spans over OTLP as configured by the standard OTEL_* environment variables
+    #[cfg(feature = "otel")]
+    let subscriber = {
+        use tracing_subscriber::layer::SubscriberExt;
+
+        let layer = match open_router_blueprint_template_lib::telemetry::otlp_layer() {
+            Ok((layer, _provider)) => Some(layer),
+            Err(e) => {
+                eprintln!("Failed to set up OpenTelemetry export: {}", e);
+                None
+            }
+        };
+        subscriber.with(layer)
+    };
+
+    let _ = subscriber.try_init();
 }
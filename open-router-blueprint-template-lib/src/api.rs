@@ -0,0 +1,469 @@
+//! OpenAI-compatible HTTP API
+//!
+//! Besides the Tangle jobs, a node serves its models over HTTP with the endpoints of the
+//! OpenAI API: `POST /v1/chat/completions`, `POST /v1/completions`, `POST /v1/embeddings`,
+//! `GET /v1/models` and `GET /v1/models/{id}`. Requests are dispatched through the same
+//! pipeline as `process_llm_request`, across the nodes of the load balancer, and are subject
+//! to the `api` section of the configuration: its authentication, its rate limits and the
+//! usage limits of the caller's key. Errors are answered in the OpenAI error format.
+//!
+//! Operators can query the node under `/admin`, authenticated with the `api.auth_token`
+//! bearer token rather than an API key: `GET /admin/usage` reports the usage of every
+//! principal, and `POST /admin/nodes/{id}/exclude` and `POST /admin/nodes/{id}/include` take
+//! a node out of routing and back, answering the excluded nodes like
+//! `GET /admin/nodes/excluded`. The admin endpoints are disabled while no `auth_token` is
+//! configured.
+//!
+//! Start the server with [`OpenRouterContext::serve_api`].
+
+use std::convert::Infallible;
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::time::Duration;
+
+use futures::StreamExt;
+use hyper::header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_TYPE};
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Method, Request, Response, Server, StatusCode};
+use serde::Serialize;
+use serde_json::json;
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+use tracing::{error, info, info_span, warn, Instrument};
+
+use crate::auth::{bearer_token, constant_time_eq, Principal};
+use crate::context::OpenRouterContext;
+use crate::correlation::{with_correlation_id, CORRELATION_ID_HEADER};
+use crate::jobs::{process_request_for_principal, stream_chat_completion_for_principal};
+use crate::llm::{chat_completion_sse, ChatCompletionRequest, LlmError, LlmRequest, LlmResponse};
+use crate::request_log::RequestLogger;
+use crate::validation::{
+    parse_chat_completion_request, parse_embedding_request, parse_text_completion_request,
+    ValidationError,
+};
+
+/// A running API server, stopped when [`ApiServer::shutdown`] is called or it is dropped
+pub struct ApiServer {
+    local_addr: SocketAddr,
+    shutdown: Option<oneshot::Sender<()>>,
+    task: Option<JoinHandle<hyper::Result<()>>>,
+}
+
+impl ApiServer {
+    /// The address the server listens on
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop accepting connections and wait for the open ones to complete
+    pub async fn shutdown(mut self) -> hyper::Result<()> {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        match self.task.take() {
+            Some(task) => task.await.unwrap_or(Ok(())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl Drop for ApiServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+/// Serve the API of `context` on `addr` in a background task
+///
+/// Binding port 0 picks a free port; see [`ApiServer::local_addr`].
+pub fn serve(context: OpenRouterContext, addr: SocketAddr) -> hyper::Result<ApiServer> {
+    let make_service = make_service_fn(move |_conn| {
+        let context = context.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(context.clone(), request)
+            }))
+        }
+    });
+
+    let server = Server::try_bind(&addr)?;
+    let local_addr = server.local_addr();
+    let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+    let server = server.serve(make_service).with_graceful_shutdown(async {
+        shutdown_rx.await.ok();
+    });
+    let task = tokio::spawn(async move {
+        let result = server.await;
+        if let Err(e) = &result {
+            error!("API server failed: {}", e);
+        }
+        result
+    });
+
+    info!("API listening on http://{}", local_addr);
+    Ok(ApiServer {
+        local_addr,
+        shutdown: Some(shutdown),
+        task: Some(task),
+    })
+}
+
+impl OpenRouterContext {
+    /// Serve the HTTP API on the configured `api.host` and `api.port`
+    ///
+    /// Returns `None` without starting a server if `api.enabled` is off. The server runs until
+    /// the returned [`ApiServer`] is shut down or dropped.
+    pub async fn serve_api(&self) -> Result<Option<ApiServer>, blueprint_sdk::Error> {
+        let (enabled, host, port) = {
+            let config = self.blueprint_config.read().await;
+            (config.api.enabled, config.api.host.clone(), config.api.port)
+        };
+        if !enabled {
+            info!("API server is disabled");
+            return Ok(None);
+        }
+
+        let addr = (host.as_str(), port)
+            .to_socket_addrs()
+            .ok()
+            .and_then(|mut addrs| addrs.next())
+            .ok_or_else(|| {
+                blueprint_sdk::Error::Other(format!("Invalid API address {}:{}", host, port))
+            })?;
+        serve(self.clone(), addr)
+            .map(Some)
+            .map_err(|e| blueprint_sdk::Error::Other(format!("Failed to bind {}: {}", addr, e)))
+    }
+}
+
+async fn handle_request(
+    context: OpenRouterContext,
+    request: Request<Body>,
+) -> Result<Response<Body>, Infallible> {
+    let correlation_id = request
+        .headers()
+        .get(CORRELATION_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .map(str::to_string)
+        .unwrap_or_else(|| format!("api-{}", uuid::Uuid::new_v4()));
+    let span = info_span!(
+        "api_request",
+        method = %request.method(),
+        path = %request.uri().path(),
+        correlation_id = %correlation_id,
+    );
+
+    let response = with_correlation_id(correlation_id, route(context, request))
+        .instrument(span)
+        .await;
+    Ok(response.unwrap_or_else(|e| e.into_response()))
+}
+
+async fn route(
+    context: OpenRouterContext,
+    request: Request<Body>,
+) -> Result<Response<Body>, ApiError> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_string();
+    match (&method, path.as_str()) {
+        (&Method::POST, "/v1/chat/completions") => {
+            dispatch(context, request, |body| {
+                parse_chat_completion_request(body).map(LlmRequest::ChatCompletion)
+            })
+            .await
+        }
+        (&Method::POST, "/v1/completions") => {
+            dispatch(context, request, |body| {
+                parse_text_completion_request(body).map(LlmRequest::TextCompletion)
+            })
+            .await
+        }
+        (&Method::POST, "/v1/embeddings") => {
+            dispatch(context, request, |body| {
+                parse_embedding_request(body).map(LlmRequest::Embedding)
+            })
+            .await
+        }
+        (&Method::GET, "/v1/models") => {
+            authenticate(&context, &request).await?;
+            Ok(json_response(
+                StatusCode::OK,
+                &context.model_catalog().await.to_response(),
+            ))
+        }
+        (&Method::GET, path) if path.starts_with("/v1/models/") => {
+            authenticate(&context, &request).await?;
+            let id = &path["/v1/models/".len()..];
+            match context.model_catalog().await.get(id) {
+                Some(model) => Ok(json_response(StatusCode::OK, model)),
+                None => Err(ApiError::new(
+                    StatusCode::NOT_FOUND,
+                    "invalid_request_error",
+                    "model_not_found",
+                    format!("The model '{}' does not exist", id),
+                )),
+            }
+        }
+        (&Method::GET, "/admin/usage") => {
+            authenticate_admin(&context, &request).await?;
+            Ok(json_response(StatusCode::OK, &context.usage_report()))
+        }
+        (&Method::GET, "/admin/nodes/excluded") => {
+            authenticate_admin(&context, &request).await?;
+            Ok(excluded_nodes_response(&context).await)
+        }
+        (&Method::POST, path) if path.starts_with("/admin/nodes/") => {
+            authenticate_admin(&context, &request).await?;
+            match path["/admin/nodes/".len()..].rsplit_once('/') {
+                Some((id, "exclude")) if !id.is_empty() => {
+                    context.exclude_node(id).await;
+                }
+                Some((id, "include")) if !id.is_empty() => {
+                    context.include_node(id).await;
+                }
+                _ => {
+                    return Err(ApiError::new(
+                        StatusCode::NOT_FOUND,
+                        "invalid_request_error",
+                        "not_found",
+                        format!("Unknown path {}", path),
+                    ))
+                }
+            }
+            Ok(excluded_nodes_response(&context).await)
+        }
+        (_, "/v1/chat/completions" | "/v1/completions" | "/v1/embeddings" | "/v1/models") => {
+            Err(ApiError::new(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "invalid_request_error",
+                "method_not_allowed",
+                format!("{} is not allowed on {}", method, path),
+            ))
+        }
+        _ => Err(ApiError::new(
+            StatusCode::NOT_FOUND,
+            "invalid_request_error",
+            "not_found",
+            format!("Unknown path {}", path),
+        )),
+    }
+}
+
+/// Authenticate, parse and rate limit a request, then dispatch it to the nodes
+async fn dispatch(
+    context: OpenRouterContext,
+    request: Request<Body>,
+    parse: impl FnOnce(&[u8]) -> Result<LlmRequest, ValidationError>,
+) -> Result<Response<Body>, ApiError> {
+    let principal = authenticate(&context, &request).await?;
+    let body = hyper::body::to_bytes(request.into_body())
+        .await
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                "invalid_body",
+                format!("Failed to read the request body: {}", e),
+            )
+        })?;
+    let request = parse(&body).map_err(ApiError::from)?;
+
+    if let Err(e) = context.try_acquire_rate_limit(request.model()).await {
+        warn!("Rejected request: {}", e);
+        return Err(ApiError::from(e));
+    }
+
+    let request = match request {
+        LlmRequest::ChatCompletion(request) if request.stream == Some(true) => {
+            return stream_chat_completion(&context, &principal, request).await;
+        }
+        request => request,
+    };
+
+    let request_logger = {
+        let config = context.blueprint_config.read().await;
+        RequestLogger::from_config(&config.api)
+    };
+    let request_log = request_logger.map(|logger| logger.start(&request));
+    let result = process_request_for_principal(&context, &principal, request).await;
+    if let Some(request_log) = request_log {
+        request_log.finish(&result);
+    }
+
+    match result {
+        Ok(response) => Ok(match &response {
+            LlmResponse::ChatCompletion(response) => json_response(StatusCode::OK, response),
+            LlmResponse::TextCompletion(response) => json_response(StatusCode::OK, response),
+            LlmResponse::Embedding(response) => json_response(StatusCode::OK, response),
+        }),
+        Err(e) => {
+            error!("LLM request failed: {}", e);
+            Err(ApiError::from(e))
+        }
+    }
+}
+
+/// Answer a chat completion with `stream: true` with server-sent events
+///
+/// Every chunk is sent as a `data:` event in the OpenAI chunk format, and the stream ends
+/// with `data: [DONE]`. Errors before the first chunk are answered like those of other
+/// requests; later ones end the stream with an error event.
+async fn stream_chat_completion(
+    context: &OpenRouterContext,
+    principal: &Principal,
+    request: ChatCompletionRequest,
+) -> Result<Response<Body>, ApiError> {
+    let keep_alive = context
+        .blueprint_config
+        .read()
+        .await
+        .api
+        .sse_keep_alive_seconds
+        .map(Duration::from_secs);
+    let stream = stream_chat_completion_for_principal(context, principal, request)
+        .await
+        .map_err(|e| {
+            error!("LLM stream failed: {}", e);
+            ApiError::from(e)
+        })?;
+
+    let events = chat_completion_sse(stream, keep_alive).map(Ok::<_, Infallible>);
+    Response::builder()
+        .status(StatusCode::OK)
+        .header(CONTENT_TYPE, "text/event-stream")
+        .header(CACHE_CONTROL, "no-cache")
+        .body(Body::wrap_stream(events))
+        .map_err(|e| {
+            ApiError::new(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                "api_error",
+                "internal_error",
+                e.to_string(),
+            )
+        })
+}
+
+/// The principal of the caller, from its `Authorization: Bearer` header
+async fn authenticate(
+    context: &OpenRouterContext,
+    request: &Request<Body>,
+) -> Result<Principal, ApiError> {
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token);
+    context.authenticate(token).await.ok_or_else(|| {
+        ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "authentication_error",
+            "invalid_api_key",
+            "Missing or invalid API key".to_string(),
+        )
+    })
+}
+
+/// The ids of the nodes excluded from routing, as `{"excluded": [...]}`
+async fn excluded_nodes_response(context: &OpenRouterContext) -> Response<Body> {
+    let excluded = context.excluded_nodes().await;
+    json_response(StatusCode::OK, &json!({ "excluded": excluded }))
+}
+
+/// Check the caller presents the `api.auth_token` of the admin endpoints
+async fn authenticate_admin(
+    context: &OpenRouterContext,
+    request: &Request<Body>,
+) -> Result<(), ApiError> {
+    let Some(auth_token) = context.blueprint_config.read().await.api.auth_token.clone() else {
+        return Err(ApiError::new(
+            StatusCode::FORBIDDEN,
+            "permission_error",
+            "admin_disabled",
+            "The admin endpoints are disabled without an api.auth_token".to_string(),
+        ));
+    };
+    let token = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(bearer_token);
+    match token {
+        Some(token) if constant_time_eq(token, &auth_token) => Ok(()),
+        _ => Err(ApiError::new(
+            StatusCode::UNAUTHORIZED,
+            "authentication_error",
+            "invalid_admin_token",
+            "Missing or invalid admin token".to_string(),
+        )),
+    }
+}
+
+fn json_response<T: Serialize + ?Sized>(status: StatusCode, body: &T) -> Response<Body> {
+    let body = serde_json::to_vec(body).unwrap_or_default();
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap_or_default()
+}
+
+/// An error answered in the OpenAI error format
+#[derive(Debug)]
+struct ApiError {
+    status: StatusCode,
+    body: serde_json::Value,
+}
+
+impl ApiError {
+    fn new(status: StatusCode, kind: &str, code: &str, message: String) -> Self {
+        Self {
+            status,
+            body: json!({
+                "error": {
+                    "message": message,
+                    "type": kind,
+                    "code": code,
+                }
+            }),
+        }
+    }
+
+    fn into_response(self) -> Response<Body> {
+        json_response(self.status, &self.body)
+    }
+}
+
+impl From<LlmError> for ApiError {
+    fn from(e: LlmError) -> Self {
+        let (status, kind, code) = match &e {
+            LlmError::RateLimited(_) => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "rate_limit_error",
+                "rate_limit_exceeded",
+            ),
+            LlmError::InvalidRequest(_) => (
+                StatusCode::BAD_REQUEST,
+                "invalid_request_error",
+                "invalid_request",
+            ),
+            LlmError::ModelNotSupported(_) => (
+                StatusCode::NOT_FOUND,
+                "invalid_request_error",
+                "model_not_found",
+            ),
+            LlmError::Timeout(_) => (StatusCode::GATEWAY_TIMEOUT, "api_error", "timeout"),
+            _ => (StatusCode::BAD_GATEWAY, "api_error", "upstream_error"),
+        };
+        Self::new(status, kind, code, e.to_string())
+    }
+}
+
+impl From<ValidationError> for ApiError {
+    fn from(e: ValidationError) -> Self {
+        Self {
+            status: StatusCode::BAD_REQUEST,
+            body: e.to_response_body(),
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! Authentication of API callers
+//!
+//! An [`Authenticator`] maps the bearer token of a request to the [`Principal`] it was issued
+//! to, so usage can be attributed per caller. The built-in [`StaticKeyAuthenticator`] accepts
+//! the single `api.api_key`, and [`KeysFileAuthenticator`] accepts every key listed in
+//! `api.keys_file`. Operators with other needs (e.g. an identity provider) can install their
+//! own implementation with `OpenRouterContext::set_authenticator`.
+
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::sync::Arc;
+
+use async_trait::async_trait;
+use serde::{Deserialize, Serialize};
+
+use crate::config::{ApiConfig, ConfigError};
+
+/// Principal of the single `api.api_key`, and of every caller while authentication is disabled
+pub const DEFAULT_PRINCIPAL: &str = "default";
+
+/// The caller a key was issued to
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct Principal {
+    /// Identifier usage is attributed to, e.g. a tenant or team name
+    pub id: String,
+
+    /// Limits on the principal's usage
+    #[serde(default)]
+    pub limits: PrincipalLimits,
+}
+
+impl Principal {
+    /// A principal with the given identifier and no limits
+    pub fn new(id: impl Into<String>) -> Self {
+        Self {
+            id: id.into(),
+            limits: PrincipalLimits::default(),
+        }
+    }
+
+    /// Apply `limits` to this principal
+    pub fn with_limits(mut self, limits: PrincipalLimits) -> Self {
+        self.limits = limits;
+        self
+    }
+}
+
+/// Limits on the usage of a principal; unset limits are unlimited
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct PrincipalLimits {
+    /// Maximum number of requests per minute
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub requests_per_minute: Option<u32>,
+
+    /// Maximum number of tokens per calendar month (UTC)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monthly_token_budget: Option<u64>,
+}
+
+/// Resolves API keys to the principals they were issued to
+#[async_trait]
+pub trait Authenticator: Send + Sync {
+    /// The principal `token` belongs to, or `None` if the token is not a valid key
+    async fn authenticate(&self, token: &str) -> Option<Principal>;
+}
+
+/// Accepts a single static key, issued to [`DEFAULT_PRINCIPAL`]
+#[derive(Debug, Clone)]
+pub struct StaticKeyAuthenticator {
+    key: String,
+}
+
+impl StaticKeyAuthenticator {
+    /// Create an authenticator for `key`
+    pub fn new(key: impl Into<String>) -> Self {
+        Self { key: key.into() }
+    }
+}
+
+#[async_trait]
+impl Authenticator for StaticKeyAuthenticator {
+    async fn authenticate(&self, token: &str) -> Option<Principal> {
+        constant_time_eq(token, &self.key).then(|| Principal::new(DEFAULT_PRINCIPAL))
+    }
+}
+
+/// An entry of a keys file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct KeyEntry {
+    /// The secret presented by the caller
+    pub key: String,
+
+    /// Identifier of the principal the key was issued to
+    pub principal: String,
+
+    /// Limits of the principal; every key of a principal must declare the same limits
+    #[serde(flatten)]
+    pub limits: PrincipalLimits,
+}
+
+/// Accepts every key listed in a keys file, each issued to its own principal
+///
+/// The file is a JSON array of `{"key": ..., "principal": ...}` objects, optionally with the
+/// [`PrincipalLimits`] fields. Several keys may belong to the same principal, e.g. while a key
+/// is rotated; they share its usage and limits.
+#[derive(Debug, Clone)]
+pub struct KeysFileAuthenticator {
+    entries: Vec<KeyEntry>,
+}
+
+impl KeysFileAuthenticator {
+    /// Create an authenticator for the given entries, rejecting empty and duplicate keys and
+    /// principals whose keys declare different limits
+    pub fn new(entries: Vec<KeyEntry>) -> Result<Self, ConfigError> {
+        let mut keys = HashSet::new();
+        let mut limits = HashMap::new();
+        for entry in &entries {
+            if *limits
+                .entry(entry.principal.as_str())
+                .or_insert(entry.limits)
+                != entry.limits
+            {
+                return Err(ConfigError::InvalidValue(format!(
+                    "Keys of principal '{}' declare different limits",
+                    entry.principal
+                )));
+            }
+            if entry.key.is_empty() || entry.principal.is_empty() {
+                return Err(ConfigError::InvalidValue(
+                    "Keys file entries need a non-empty key and principal".to_string(),
+                ));
+            }
+            if !keys.insert(entry.key.as_str()) {
+                return Err(ConfigError::InvalidValue(format!(
+                    "Key of principal '{}' is listed more than once",
+                    entry.principal
+                )));
+            }
+        }
+        Ok(Self { entries })
+    }
+
+    /// Load the entries of a keys file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self, ConfigError> {
+        let contents = std::fs::read_to_string(path.as_ref())?;
+        let entries = serde_json::from_str(&contents).map_err(|e| {
+            ConfigError::ParseError(format!(
+                "Invalid keys file {}: {}",
+                path.as_ref().display(),
+                e
+            ))
+        })?;
+        Self::new(entries)
+    }
+}
+
+#[async_trait]
+impl Authenticator for KeysFileAuthenticator {
+    async fn authenticate(&self, token: &str) -> Option<Principal> {
+        // Compare against every key, so the time taken does not reveal which one matched
+        let mut principal = None;
+        for entry in &self.entries {
+            if constant_time_eq(token, &entry.key) {
+                principal = Some(Principal::new(entry.principal.clone()).with_limits(entry.limits));
+            }
+        }
+        principal
+    }
+}
+
+/// The authenticator configured in `api`, or `None` if authentication is disabled
+///
+/// `api.keys_file` takes precedence over `api.api_key`. Fails if neither is set or the keys
+/// file cannot be loaded, so a broken setup never accepts every caller.
+pub fn from_config(api: &ApiConfig) -> Result<Option<Arc<dyn Authenticator>>, ConfigError> {
+    if !api.auth_enabled {
+        return Ok(None);
+    }
+    if let Some(path) = &api.keys_file {
+        return Ok(Some(Arc::new(KeysFileAuthenticator::from_file(path)?)));
+    }
+    match &api.api_key {
+        Some(key) => Ok(Some(Arc::new(StaticKeyAuthenticator::new(key.clone())))),
+        None => Err(ConfigError::MissingValue(
+            "api.api_key or api.keys_file is required when authentication is enabled".to_string(),
+        )),
+    }
+}
+
+/// The token of an `Authorization: Bearer <token>` header value
+pub fn bearer_token(header: &str) -> Option<&str> {
+    let (scheme, token) = header.trim().split_once(' ')?;
+    scheme
+        .eq_ignore_ascii_case("bearer")
+        .then(|| token.trim())
+        .filter(|token| !token.is_empty())
+}
+
+/// Compare two strings in time that depends only on their lengths
+pub(crate) fn constant_time_eq(a: &str, b: &str) -> bool {
+    a.len() == b.len()
+        && a.bytes()
+            .zip(b.bytes())
+            .fold(0u8, |diff, (x, y)| diff | (x ^ y))
+            == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_static_key() {
+        let authenticator = StaticKeyAuthenticator::new("sk-secret");
+
+        assert_eq!(
+            authenticator.authenticate("sk-secret").await,
+            Some(Principal::new(DEFAULT_PRINCIPAL))
+        );
+        assert_eq!(authenticator.authenticate("sk-secreT").await, None);
+        assert_eq!(authenticator.authenticate("").await, None);
+    }
+
+    #[tokio::test]
+    async fn test_keys_file_maps_keys_to_principals() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("keys.json");
+        std::fs::write(
+            &path,
+            r#"[
+                { "key": "sk-team-a-1", "principal": "team-a" },
+                { "key": "sk-team-a-2", "principal": "team-a" },
+                {
+                    "key": "sk-team-b",
+                    "principal": "team-b",
+                    "requests_per_minute": 30,
+                    "monthly_token_budget": 100000
+                }
+            ]"#,
+        )
+        .unwrap();
+        let authenticator = KeysFileAuthenticator::from_file(&path).unwrap();
+
+        for key in ["sk-team-a-1", "sk-team-a-2"] {
+            assert_eq!(
+                authenticator.authenticate(key).await,
+                Some(Principal::new("team-a"))
+            );
+        }
+        let team_b = authenticator.authenticate("sk-team-b").await.unwrap();
+        assert_eq!(team_b.id, "team-b");
+        assert_eq!(team_b.limits.requests_per_minute, Some(30));
+        assert_eq!(team_b.limits.monthly_token_budget, Some(100_000));
+        assert_eq!(authenticator.authenticate("sk-team-c").await, None);
+    }
+
+    #[test]
+    fn test_invalid_keys_files_are_rejected() {
+        let entry = |key: &str, principal: &str| KeyEntry {
+            key: key.to_string(),
+            principal: principal.to_string(),
+            limits: PrincipalLimits::default(),
+        };
+        assert!(KeysFileAuthenticator::new(vec![entry("", "team-a")]).is_err());
+        assert!(
+            KeysFileAuthenticator::new(vec![entry("sk-1", "team-a"), entry("sk-1", "team-b")])
+                .is_err()
+        );
+        let limited = KeyEntry {
+            limits: PrincipalLimits {
+                requests_per_minute: Some(10),
+                monthly_token_budget: None,
+            },
+            ..entry("sk-2", "team-a")
+        };
+        assert!(KeysFileAuthenticator::new(vec![entry("sk-1", "team-a"), limited]).is_err());
+        assert!(KeysFileAuthenticator::from_file("/nonexistent/keys.json").is_err());
+    }
+
+    #[test]
+    fn test_from_config() {
+        let mut api = ApiConfig::default();
+        assert!(from_config(&api).unwrap().is_none());
+
+        api.auth_enabled = true;
+        assert!(from_config(&api).is_err());
+
+        api.api_key = Some("sk-secret".to_string());
+        assert!(from_config(&api).unwrap().is_some());
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        assert_eq!(bearer_token("Bearer sk-secret"), Some("sk-secret"));
+        assert_eq!(bearer_token("bearer  sk-secret "), Some("sk-secret"));
+        assert_eq!(bearer_token("Basic dXNlcg=="), None);
+        assert_eq!(bearer_token("Bearer "), None);
+    }
+}
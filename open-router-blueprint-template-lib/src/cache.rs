@@ -0,0 +1,107 @@
+//! In-memory cache of responses to deterministic requests
+//!
+//! Only requests with `temperature: 0` are cached, since any other request may legitimately
+//! produce a different completion each time.
+
+use std::collections::{HashMap, VecDeque};
+use std::sync::Mutex;
+
+use crate::llm::{LlmRequest, LlmResponse};
+
+/// A bounded cache of responses keyed by the full request, evicting the oldest entry first
+#[derive(Debug, Default)]
+pub struct ResponseCache {
+    entries: Mutex<CacheEntries>,
+}
+
+#[derive(Debug, Default)]
+struct CacheEntries {
+    responses: HashMap<String, LlmResponse>,
+    insertion_order: VecDeque<String>,
+}
+
+impl ResponseCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The cache key of a request, or `None` if its response must not be cached
+    pub fn key_for(request: &LlmRequest) -> Option<String> {
+        if !request.is_deterministic() {
+            return None;
+        }
+        serde_json::to_value(request)
+            .ok()
+            .map(|value| value.to_string())
+    }
+
+    /// The cached response for `key`, if any
+    pub fn get(&self, key: &str) -> Option<LlmResponse> {
+        self.entries.lock().unwrap().responses.get(key).cloned()
+    }
+
+    /// Cache `response` under `key`, keeping at most `capacity` entries
+    pub fn insert(&self, key: String, response: LlmResponse, capacity: usize) {
+        if capacity == 0 {
+            return;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        if entries.responses.insert(key.clone(), response).is_none() {
+            entries.insertion_order.push_back(key);
+        }
+        while entries.insertion_order.len() > capacity {
+            if let Some(oldest) = entries.insertion_order.pop_front() {
+                entries.responses.remove(&oldest);
+            }
+        }
+    }
+
+    /// Number of cached responses
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().responses.len()
+    }
+
+    /// Whether the cache holds no responses
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::ChatCompletionRequest;
+
+    fn request(temperature: f32, prompt: &str) -> LlmRequest {
+        LlmRequest::ChatCompletion(ChatCompletionRequest {
+            model: prompt.to_string(),
+            temperature: Some(temperature),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_only_deterministic_requests_have_keys() {
+        assert!(ResponseCache::key_for(&request(0.0, "a")).is_some());
+        assert!(ResponseCache::key_for(&request(0.7, "a")).is_none());
+    }
+
+    #[test]
+    fn test_oldest_entry_is_evicted() {
+        let cache = ResponseCache::new();
+        for prompt in ["a", "b", "c"] {
+            let key = ResponseCache::key_for(&request(0.0, prompt)).unwrap();
+            cache.insert(key, LlmResponse::default(), 2);
+        }
+
+        assert_eq!(cache.len(), 2);
+        assert!(cache
+            .get(&ResponseCache::key_for(&request(0.0, "a")).unwrap())
+            .is_none());
+        assert!(cache
+            .get(&ResponseCache::key_for(&request(0.0, "c")).unwrap())
+            .is_some());
+    }
+}
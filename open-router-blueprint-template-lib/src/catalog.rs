@@ -0,0 +1,121 @@
+//! The models this node advertises to OpenRouter
+//!
+//! [`ModelCatalog`] turns the [`ModelInfo`] reported by LLM clients into entries in the
+//! OpenRouter model format, as served by the `/v1/models` endpoints.
+
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+use tracing::error;
+
+use crate::llm::{ModelInfo, Pricing};
+
+/// A model entry in the OpenRouter model format
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OpenRouterModel {
+    /// Unique identifier for the model
+    pub id: String,
+
+    /// Human-readable name of the model
+    pub name: String,
+
+    /// When the model was listed (Unix timestamp in seconds)
+    pub created: u64,
+
+    /// Description of the model, from the `description` parameter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// Maximum context length supported by the model
+    pub context_length: usize,
+
+    /// Maximum number of generated tokens, from the `max_completion_tokens` parameter
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_completion_tokens: Option<usize>,
+
+    /// Quantization of the served weights, from the `quantization` parameter
+    pub quantization: String,
+
+    /// Per-unit prices of the model, omitted when the model advertises none
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub pricing: Option<Pricing>,
+}
+
+impl OpenRouterModel {
+    /// Convert a model into its OpenRouter entry, listed at `created`
+    pub fn from_model_info(model: &ModelInfo, created: u64) -> crate::llm::Result<Self> {
+        Ok(Self {
+            id: model.id.clone(),
+            name: model.name.clone(),
+            created,
+            description: model.parameters.get("description").cloned(),
+            context_length: model.max_context_length,
+            max_completion_tokens: model
+                .parameters
+                .get("max_completion_tokens")
+                .and_then(|v| v.parse::<usize>().ok()),
+            quantization: model
+                .parameters
+                .get("quantization")
+                .cloned()
+                .unwrap_or_else(|| "none".to_string()),
+            pricing: model.openrouter_pricing()?,
+        })
+    }
+}
+
+/// Response body of the `/v1/models` endpoint
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OpenRouterModelsResponse {
+    /// The listed models
+    pub data: Vec<OpenRouterModel>,
+}
+
+/// The OpenRouter entries of a set of models
+#[derive(Debug, Clone, Default)]
+pub struct ModelCatalog {
+    models: Vec<OpenRouterModel>,
+}
+
+impl ModelCatalog {
+    /// Build a catalog from the given models, keeping the first entry for each id
+    ///
+    /// Models with invalid pricing parameters are left out rather than advertised at a
+    /// wrong price; models without pricing parameters are listed without a price.
+    pub fn new(models: Vec<ModelInfo>) -> Self {
+        let created = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+
+        let mut entries: Vec<OpenRouterModel> = Vec::with_capacity(models.len());
+        for model in &models {
+            if entries.iter().any(|entry| entry.id == model.id) {
+                continue;
+            }
+            match OpenRouterModel::from_model_info(model, created) {
+                Ok(entry) => entries.push(entry),
+                Err(e) => error!("Leaving model {} out of the catalog: {}", model.id, e),
+            }
+        }
+
+        Self { models: entries }
+    }
+
+    /// All models in the catalog
+    pub fn models(&self) -> &[OpenRouterModel] {
+        &self.models
+    }
+
+    /// The model with the given id, if it is in the catalog
+    pub fn get(&self, id: &str) -> Option<&OpenRouterModel> {
+        self.models.iter().find(|model| model.id == id)
+    }
+
+    /// The `/v1/models` response listing every model in the catalog
+    pub fn to_response(&self) -> OpenRouterModelsResponse {
+        OpenRouterModelsResponse {
+            data: self.models.clone(),
+        }
+    }
+}
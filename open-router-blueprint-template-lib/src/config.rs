@@ -1,14 +1,19 @@
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 use serde::{Deserialize, Serialize};
 use thiserror::Error;
 use tracing::warn;
 
-use crate::llm::ModelInfo;
-use crate::load_balancer::LoadBalancingStrategy;
+use crate::llm::{
+    ExtraChoicesPolicy, LocalReplyMode, ModelInfo, Pricing, SystemPromptPolicy,
+    DEFAULT_EMBEDDING_CONCURRENCY,
+};
+use crate::load_balancer::{BackoffConfig, CapabilityScoreWeights, LoadBalancingStrategy};
 
 /// Errors that can occur when loading configuration
 #[derive(Debug, Error)]
@@ -29,8 +34,19 @@ pub enum ConfigError {
 /// Result type for configuration operations
 pub type Result<T> = std::result::Result<T, ConfigError>;
 
+/// Outcome of a configuration reload, broadcast to subscribers of
+/// `OpenRouterContext::subscribe_config_events`
+#[derive(Debug, Clone)]
+pub enum ConfigEvent {
+    /// The configuration was reloaded and applied; shared, since every subscriber gets a copy
+    Reloaded(Arc<BlueprintConfig>),
+
+    /// The configuration could not be reloaded; the previous configuration stays in effect
+    ReloadFailed(String),
+}
+
 /// Configuration for the OpenRouter Blueprint
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct BlueprintConfig {
     /// Configuration for the LLM client
     #[serde(default)]
@@ -44,14 +60,68 @@ pub struct BlueprintConfig {
     #[serde(default)]
     pub api: ApiConfig,
 
+    /// Backend nodes registered with the load balancer in addition to the `default` node
+    /// built from `llm`
+    #[serde(default)]
+    pub nodes: Vec<NodeConfig>,
+
     /// Additional configuration parameters
     #[serde(default)]
     pub additional_params: HashMap<String, String>,
 }
 
+/// A backend node declared in the configuration file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeConfig {
+    /// Unique id of the node in the load balancer
+    pub id: String,
+
+    /// Which client serves the node; the template library builds `local` nodes, other
+    /// providers are registered by the blueprint that implements them
+    #[serde(default = "default_node_provider")]
+    pub provider: String,
+
+    /// The base URL of the node's API
+    pub api_url: String,
+
+    /// The models served by the node; when empty, the models of `llm` are used
+    #[serde(default)]
+    pub models: Vec<ModelInfo>,
+
+    /// Relative share of traffic the node should receive under weighted round-robin
+    #[serde(default = "default_node_weight")]
+    pub weight: u32,
+
+    /// Free-form labels, e.g. a region or GPU type
+    #[serde(default)]
+    pub tags: Vec<String>,
+}
+
+/// Provider of nodes built by the template library itself
+pub const LOCAL_NODE_PROVIDER: &str = "local";
+
+/// The kind of backend the default LLM client talks to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LlmBackend {
+    /// The template's `LocalLlmClient`
+    #[default]
+    Local,
+
+    /// A vLLM server, served by the vLLM blueprint's client
+    Vllm,
+
+    /// An Ollama server, served by the Ollama blueprint's client
+    Ollama,
+}
+
 /// Configuration for the LLM client
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmConfig {
+    /// The kind of backend at `api_url`, which decides the client built for it
+    #[serde(default)]
+    pub backend: LlmBackend,
+
     /// The base URL for the LLM API
     #[serde(default = "default_api_url")]
     pub api_url: String,
@@ -68,6 +138,117 @@ pub struct LlmConfig {
     #[serde(default)]
     pub models: Vec<ModelInfo>,
 
+    /// How multiple system messages in chat requests are handled
+    #[serde(default)]
+    pub system_prompt_policy: SystemPromptPolicy,
+
+    /// System message inserted first into chat requests that carry none
+    #[serde(default)]
+    pub default_system_prompt: Option<String>,
+
+    /// Whether `default_system_prompt` also replaces the system messages callers send
+    #[serde(default)]
+    pub override_system_prompt: bool,
+
+    /// Maximum number of embedding inputs sent to a single-input backend at the same time
+    #[serde(default = "default_embedding_concurrency")]
+    pub embedding_concurrency: usize,
+
+    /// Content returned when a backend answers with no choices; without it such responses fail
+    #[serde(default)]
+    pub empty_response_fallback: Option<String>,
+
+    /// How completions with more choices than the request's `n` are handled
+    #[serde(default)]
+    pub extra_choices_policy: ExtraChoicesPolicy,
+
+    /// Keys of request `additional_params` forwarded to the backend; others are dropped
+    #[serde(default)]
+    pub passthrough_params: Vec<String>,
+
+    /// Models tried in order when no node serves the requested model
+    #[serde(default)]
+    pub fallback_models: Vec<String>,
+
+    /// Model names clients may request, mapped to the id of the model serving them
+    #[serde(default)]
+    pub model_aliases: HashMap<String, String>,
+
+    /// Whether responses to a request for an alias report the alias as their model instead
+    /// of the model that served them
+    #[serde(default)]
+    pub echo_requested_model: bool,
+
+    /// Maximum number of cached responses to `temperature: 0` requests
+    ///
+    /// Only used when built with the `response-cache` feature; 0 disables the cache.
+    #[serde(default = "default_response_cache_capacity")]
+    pub response_cache_capacity: usize,
+
+    /// Maximum number of idempotency keys whose responses are kept; 0 disables idempotency
+    #[serde(default = "default_idempotency_cache_capacity")]
+    pub idempotency_cache_capacity: usize,
+
+    /// Whether to talk HTTP/2 to the backend without negotiation (h2c prior knowledge)
+    #[serde(default)]
+    pub http2: bool,
+
+    /// Interval in seconds between HTTP/2 keep-alive pings; pings are disabled when unset
+    #[serde(default)]
+    pub keep_alive_interval_seconds: Option<u64>,
+
+    /// Whether to gzip large request bodies and accept gzip-encoded responses from the backend
+    #[serde(default)]
+    pub enable_compression: bool,
+
+    /// Whether to reject models no node serves instead of passing them to the default client
+    #[serde(default)]
+    pub strict_model_catalog: bool,
+
+    /// Maximum number of messages in a chat request; unlimited when unset
+    #[serde(default)]
+    pub max_messages_per_request: Option<usize>,
+
+    /// Whether to drop the oldest non-system messages of an over-long chat request instead of
+    /// rejecting it
+    #[serde(default)]
+    pub truncate_overflow: bool,
+
+    /// Whether to check a node's health and model list when it is added
+    #[serde(default = "default_true")]
+    pub check_nodes_on_add: bool,
+
+    /// Whether to reject nodes that fail the check instead of adding them with a warning
+    #[serde(default)]
+    pub require_healthy_on_add: bool,
+
+    /// How the template's `local` clients answer requests
+    #[serde(default)]
+    pub local_reply_mode: LocalReplyMode,
+
+    /// Whether to ask for the rest of a completion that stopped at its token limit
+    /// (`finish_reason: "length"`) and append it to the response
+    #[serde(default)]
+    pub auto_continue_on_length: bool,
+
+    /// Maximum number of continuation requests per completion with `auto_continue_on_length`
+    #[serde(default = "default_max_continuations")]
+    pub max_continuations: usize,
+
+    /// Whether to trim the oldest messages, or the head of a text prompt, of a request that
+    /// does not fit the model's context instead of passing it on
+    #[serde(default)]
+    pub auto_truncate: bool,
+
+    /// Whether to reject a completion request that does not fit the context of its model,
+    /// after any `auto_truncate` trimming, instead of passing it on
+    #[serde(default)]
+    pub reject_context_overflow: bool,
+
+    /// Prices of models by model id, taking precedence over the pricing their nodes advertise
+    #[serde(default)]
+    pub model_pricing: HashMap<String, Pricing>,
+
     /// Additional configuration parameters
     #[serde(default)]
     pub additional_params: HashMap<String, String>,
@@ -87,6 +268,27 @@ pub struct LoadBalancerConfig {
     /// Timeout for node selection in milliseconds
     #[serde(default = "default_selection_timeout")]
     pub selection_timeout_ms: u64,
+
+    /// How the capability-based strategy scores nodes
+    #[serde(default)]
+    pub capability_weights: CapabilityScoreWeights,
+
+    /// Consecutive failures after which a node is marked failed and left out of selection;
+    /// any success starts the count over
+    #[serde(default = "default_failure_grace_count")]
+    pub failure_grace_count: u32,
+
+    /// Seconds within which failures must follow each other to count as consecutive
+    #[serde(default = "default_failure_window")]
+    pub failure_window_seconds: u64,
+
+    /// Seconds a failed node is left out of selection before a trial request is sent to it
+    #[serde(default = "default_failure_cooldown")]
+    pub failure_cooldown_seconds: u64,
+
+    /// Delays between the retries of a request that failed on a node
+    #[serde(default)]
+    pub backoff: BackoffConfig,
 }
 
 /// Configuration for the API server
@@ -112,6 +314,11 @@ pub struct ApiConfig {
     #[serde(default)]
     pub api_key: Option<String>,
 
+    /// JSON file listing API keys and the principals they were issued to; takes precedence
+    /// over `api_key`
+    #[serde(default)]
+    pub keys_file: Option<PathBuf>,
+
     /// Whether to enable rate limiting
     #[serde(default = "default_true")]
     pub rate_limiting_enabled: bool,
@@ -120,44 +327,138 @@ pub struct ApiConfig {
     #[serde(default = "default_rate_limit")]
     pub max_requests_per_minute: u32,
 
+    /// Requests per minute admitted for individual models, by model id; other models are
+    /// limited to `max_requests_per_minute` each
+    #[serde(default)]
+    pub model_rate_limits: HashMap<String, u32>,
+
     /// The interval in seconds for reporting metrics
     #[serde(default = "default_metrics_interval")]
     pub metrics_interval_seconds: u64,
 
-    /// The authentication token for API endpoints
+    /// Fraction of successful requests (0.0 - 1.0) that log their summary line
+    #[serde(default = "default_log_sample_rate")]
+    pub log_sample_rate: f32,
+
+    /// Interval in seconds between SSE keep-alive comments while a stream awaits its first
+    /// chunk; `None` disables them
+    #[serde(default = "default_sse_keep_alive")]
+    pub sse_keep_alive_seconds: Option<u64>,
+
+    /// Whether completions report their `cost`, computed from the usage and the pricing of
+    /// the model that served them
+    #[serde(default = "default_false")]
+    pub include_cost: bool,
+
+    /// Whether to emit a structured log event for every LLM request
+    #[serde(default = "default_false")]
+    pub request_logging: bool,
+
+    /// Whether request log events replace the prompt and output with a placeholder
+    #[serde(default = "default_true")]
+    pub redact_content: bool,
+
+    /// Bearer token of the admin endpoints under `/admin`, which are disabled without one
     #[serde(default)]
     pub auth_token: Option<String>,
+
+    /// Content moderation applied to requests
+    #[serde(default)]
+    pub moderation: ModerationConfig,
 }
 
-impl Default for BlueprintConfig {
-    fn default() -> Self {
-        Self {
-            llm: LlmConfig::default(),
-            load_balancer: LoadBalancerConfig::default(),
-            api: ApiConfig::default(),
-            additional_params: HashMap::new(),
-        }
-    }
+/// Configuration for the built-in keyword and pattern moderator
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ModerationConfig {
+    /// Whether to moderate requests
+    #[serde(default)]
+    pub enabled: bool,
+
+    /// Words or phrases that block a request, matched case-insensitively
+    #[serde(default)]
+    pub blocked_keywords: Vec<String>,
+
+    /// Regular expressions that block a request when they match
+    #[serde(default)]
+    pub blocked_patterns: Vec<String>,
+
+    /// Whether to also moderate the content of responses
+    #[serde(default)]
+    pub check_responses: bool,
 }
 
 impl Default for LlmConfig {
     fn default() -> Self {
         Self {
+            backend: LlmBackend::default(),
             api_url: default_api_url(),
             timeout_seconds: default_timeout(),
             max_concurrent_requests: default_max_concurrent(),
             models: default_models(),
+            system_prompt_policy: SystemPromptPolicy::default(),
+            default_system_prompt: None,
+            override_system_prompt: false,
+            embedding_concurrency: default_embedding_concurrency(),
+            empty_response_fallback: None,
+            extra_choices_policy: ExtraChoicesPolicy::default(),
+            passthrough_params: Vec::new(),
+            fallback_models: Vec::new(),
+            model_aliases: HashMap::new(),
+            echo_requested_model: false,
+            response_cache_capacity: default_response_cache_capacity(),
+            idempotency_cache_capacity: default_idempotency_cache_capacity(),
+            http2: false,
+            keep_alive_interval_seconds: None,
+            enable_compression: false,
+            strict_model_catalog: false,
+            max_messages_per_request: None,
+            truncate_overflow: false,
+            check_nodes_on_add: default_true(),
+            require_healthy_on_add: false,
+            local_reply_mode: LocalReplyMode::default(),
+            auto_continue_on_length: false,
+            max_continuations: default_max_continuations(),
+            auto_truncate: false,
+            reject_context_overflow: false,
+            model_pricing: HashMap::new(),
             additional_params: HashMap::new(),
         }
     }
 }
 
+impl LlmConfig {
+    /// An HTTP client builder with the configured HTTP/2 and keep-alive options applied
+    ///
+    /// Keep-alive pings are also sent on idle pooled connections, so a dead connection is
+    /// noticed before a request is sent on it.
+    pub fn http_client_builder(&self) -> reqwest::ClientBuilder {
+        let mut builder = reqwest::Client::builder();
+
+        if self.http2 {
+            builder = builder.http2_prior_knowledge();
+        }
+
+        if let Some(interval) = self.keep_alive_interval_seconds {
+            builder = builder
+                .http2_keep_alive_interval(Duration::from_secs(interval))
+                .http2_keep_alive_while_idle(true);
+        }
+
+        builder
+    }
+}
+
 impl Default for LoadBalancerConfig {
     fn default() -> Self {
         Self {
             strategy: LoadBalancingStrategy::default(),
             max_retries: default_max_retries(),
             selection_timeout_ms: default_selection_timeout(),
+            capability_weights: CapabilityScoreWeights::default(),
+            failure_grace_count: default_failure_grace_count(),
+            failure_window_seconds: default_failure_window(),
+            failure_cooldown_seconds: default_failure_cooldown(),
+            backoff: BackoffConfig::default(),
         }
     }
 }
@@ -170,10 +471,18 @@ impl Default for ApiConfig {
             port: default_port(),
             auth_enabled: default_false(),
             api_key: None,
+            keys_file: None,
             rate_limiting_enabled: default_true(),
             max_requests_per_minute: default_rate_limit(),
+            model_rate_limits: HashMap::new(),
             metrics_interval_seconds: default_metrics_interval(),
+            log_sample_rate: default_log_sample_rate(),
+            sse_keep_alive_seconds: default_sse_keep_alive(),
+            include_cost: default_false(),
+            request_logging: default_false(),
+            redact_content: default_true(),
             auth_token: None,
+            moderation: ModerationConfig::default(),
         }
     }
 }
@@ -227,6 +536,293 @@ impl BlueprintConfig {
             }
         }
 
+        if let Ok(models) = std::env::var("OPENROUTER_LLM_MODELS") {
+            config.llm.models = models
+                .split(',')
+                .map(|m| m.trim())
+                .filter(|m| !m.is_empty())
+                .map(model_from_id)
+                .collect();
+        }
+
+        if let Ok(policy) = std::env::var("OPENROUTER_LLM_SYSTEM_PROMPT_POLICY") {
+            config.llm.system_prompt_policy = match policy.to_lowercase().as_str() {
+                "merge" => SystemPromptPolicy::Merge,
+                "first" => SystemPromptPolicy::First,
+                "passthrough" => SystemPromptPolicy::Passthrough,
+                _ => {
+                    warn!(
+                        "Invalid system prompt policy in environment variable: {}",
+                        policy
+                    );
+                    config.llm.system_prompt_policy
+                }
+            };
+        }
+
+        if let Ok(prompt) = std::env::var("OPENROUTER_LLM_DEFAULT_SYSTEM_PROMPT") {
+            config.llm.default_system_prompt = Some(prompt);
+        }
+
+        if let Ok(override_prompt) = std::env::var("OPENROUTER_LLM_OVERRIDE_SYSTEM_PROMPT") {
+            if let Ok(override_prompt) = override_prompt.parse() {
+                config.llm.override_system_prompt = override_prompt;
+            } else {
+                warn!(
+                    "Invalid override system prompt flag in environment variable: {}",
+                    override_prompt
+                );
+            }
+        }
+
+        if let Ok(concurrency) = std::env::var("OPENROUTER_LLM_EMBEDDING_CONCURRENCY") {
+            if let Ok(concurrency) = concurrency.parse() {
+                config.llm.embedding_concurrency = concurrency;
+            } else {
+                warn!(
+                    "Invalid embedding concurrency in environment variable: {}",
+                    concurrency
+                );
+            }
+        }
+
+        if let Ok(fallback) = std::env::var("OPENROUTER_LLM_EMPTY_RESPONSE_FALLBACK") {
+            config.llm.empty_response_fallback = Some(fallback);
+        }
+
+        if let Ok(policy) = std::env::var("OPENROUTER_LLM_EXTRA_CHOICES_POLICY") {
+            config.llm.extra_choices_policy = match policy.to_lowercase().as_str() {
+                "truncate" => ExtraChoicesPolicy::Truncate,
+                "passthrough" => ExtraChoicesPolicy::Passthrough,
+                _ => {
+                    warn!(
+                        "Invalid extra choices policy in environment variable: {}",
+                        policy
+                    );
+                    config.llm.extra_choices_policy
+                }
+            };
+        }
+
+        if let Ok(params) = std::env::var("OPENROUTER_LLM_PASSTHROUGH_PARAMS") {
+            config.llm.passthrough_params = params
+                .split(',')
+                .map(|p| p.trim().to_string())
+                .filter(|p| !p.is_empty())
+                .collect();
+        }
+
+        if let Ok(models) = std::env::var("OPENROUTER_LLM_FALLBACK_MODELS") {
+            config.llm.fallback_models = models
+                .split(',')
+                .map(|m| m.trim().to_string())
+                .filter(|m| !m.is_empty())
+                .collect();
+        }
+
+        if let Ok(aliases) = std::env::var("OPENROUTER_LLM_MODEL_ALIASES") {
+            for entry in aliases.split(',').filter(|a| !a.trim().is_empty()) {
+                match entry
+                    .split_once('=')
+                    .map(|(alias, model)| (alias.trim(), model.trim()))
+                {
+                    Some((alias, model)) if !alias.is_empty() && !model.is_empty() => {
+                        config
+                            .llm
+                            .model_aliases
+                            .insert(alias.to_string(), model.to_string());
+                    }
+                    _ => warn!("Invalid model alias in environment variable: {}", entry),
+                }
+            }
+        }
+
+        if let Ok(echo) = std::env::var("OPENROUTER_LLM_ECHO_REQUESTED_MODEL") {
+            if let Ok(echo) = echo.parse() {
+                config.llm.echo_requested_model = echo;
+            } else {
+                warn!(
+                    "Invalid echo requested model flag in environment variable: {}",
+                    echo
+                );
+            }
+        }
+
+        if let Ok(capacity) = std::env::var("OPENROUTER_LLM_RESPONSE_CACHE_CAPACITY") {
+            if let Ok(capacity) = capacity.parse() {
+                config.llm.response_cache_capacity = capacity;
+            } else {
+                warn!(
+                    "Invalid response cache capacity in environment variable: {}",
+                    capacity
+                );
+            }
+        }
+
+        if let Ok(capacity) = std::env::var("OPENROUTER_LLM_IDEMPOTENCY_CACHE_CAPACITY") {
+            if let Ok(capacity) = capacity.parse() {
+                config.llm.idempotency_cache_capacity = capacity;
+            } else {
+                warn!(
+                    "Invalid idempotency cache capacity in environment variable: {}",
+                    capacity
+                );
+            }
+        }
+
+        if let Ok(http2) = std::env::var("OPENROUTER_LLM_HTTP2") {
+            if let Ok(http2) = http2.parse() {
+                config.llm.http2 = http2;
+            } else {
+                warn!("Invalid HTTP/2 flag in environment variable: {}", http2);
+            }
+        }
+
+        if let Ok(strict) = std::env::var("OPENROUTER_LLM_STRICT_MODEL_CATALOG") {
+            if let Ok(strict) = strict.parse() {
+                config.llm.strict_model_catalog = strict;
+            } else {
+                warn!(
+                    "Invalid strict model catalog flag in environment variable: {}",
+                    strict
+                );
+            }
+        }
+
+        if let Ok(max_messages) = std::env::var("OPENROUTER_LLM_MAX_MESSAGES_PER_REQUEST") {
+            if let Ok(max_messages) = max_messages.parse() {
+                config.llm.max_messages_per_request = Some(max_messages);
+            } else {
+                warn!(
+                    "Invalid max messages per request in environment variable: {}",
+                    max_messages
+                );
+            }
+        }
+
+        if let Ok(truncate) = std::env::var("OPENROUTER_LLM_TRUNCATE_OVERFLOW") {
+            if let Ok(truncate) = truncate.parse() {
+                config.llm.truncate_overflow = truncate;
+            } else {
+                warn!(
+                    "Invalid truncate overflow flag in environment variable: {}",
+                    truncate
+                );
+            }
+        }
+
+        if let Ok(check) = std::env::var("OPENROUTER_LLM_CHECK_NODES_ON_ADD") {
+            if let Ok(check) = check.parse() {
+                config.llm.check_nodes_on_add = check;
+            } else {
+                warn!(
+                    "Invalid check nodes on add flag in environment variable: {}",
+                    check
+                );
+            }
+        }
+
+        if let Ok(require) = std::env::var("OPENROUTER_LLM_REQUIRE_HEALTHY_ON_ADD") {
+            if let Ok(require) = require.parse() {
+                config.llm.require_healthy_on_add = require;
+            } else {
+                warn!(
+                    "Invalid require healthy on add flag in environment variable: {}",
+                    require
+                );
+            }
+        }
+
+        if let Ok(backend) = std::env::var("OPENROUTER_LLM_BACKEND") {
+            config.llm.backend = match backend.to_lowercase().as_str() {
+                "local" => LlmBackend::Local,
+                "vllm" => LlmBackend::Vllm,
+                "ollama" => LlmBackend::Ollama,
+                _ => {
+                    warn!("Invalid LLM backend in environment variable: {}", backend);
+                    config.llm.backend
+                }
+            };
+        }
+
+        if let Ok(mode) = std::env::var("OPENROUTER_LLM_LOCAL_REPLY_MODE") {
+            config.llm.local_reply_mode = match mode.to_lowercase().as_str() {
+                "echo" => LocalReplyMode::Echo,
+                "backend" => LocalReplyMode::Backend,
+                "unimplemented" => LocalReplyMode::Unimplemented,
+                _ => {
+                    warn!("Invalid local reply mode in environment variable: {}", mode);
+                    config.llm.local_reply_mode
+                }
+            };
+        }
+
+        if let Ok(continue_on_length) = std::env::var("OPENROUTER_LLM_AUTO_CONTINUE_ON_LENGTH") {
+            if let Ok(continue_on_length) = continue_on_length.parse() {
+                config.llm.auto_continue_on_length = continue_on_length;
+            } else {
+                warn!(
+                    "Invalid auto continue on length flag in environment variable: {}",
+                    continue_on_length
+                );
+            }
+        }
+
+        if let Ok(continuations) = std::env::var("OPENROUTER_LLM_MAX_CONTINUATIONS") {
+            if let Ok(continuations) = continuations.parse() {
+                config.llm.max_continuations = continuations;
+            } else {
+                warn!(
+                    "Invalid max continuations in environment variable: {}",
+                    continuations
+                );
+            }
+        }
+
+        if let Ok(truncate) = std::env::var("OPENROUTER_LLM_AUTO_TRUNCATE") {
+            if let Ok(truncate) = truncate.parse() {
+                config.llm.auto_truncate = truncate;
+            } else {
+                warn!(
+                    "Invalid auto truncate flag in environment variable: {}",
+                    truncate
+                );
+            }
+        }
+
+        if let Ok(reject) = std::env::var("OPENROUTER_LLM_REJECT_CONTEXT_OVERFLOW") {
+            if let Ok(reject) = reject.parse() {
+                config.llm.reject_context_overflow = reject;
+            } else {
+                warn!(
+                    "Invalid reject context overflow flag in environment variable: {}",
+                    reject
+                );
+            }
+        }
+
+        if let Ok(interval) = std::env::var("OPENROUTER_LLM_KEEP_ALIVE_INTERVAL") {
+            if let Ok(interval) = interval.parse() {
+                config.llm.keep_alive_interval_seconds = Some(interval);
+            } else {
+                warn!(
+                    "Invalid keep-alive interval in environment variable: {}",
+                    interval
+                );
+            }
+        }
+
+        if let Ok(compression) = std::env::var("OPENROUTER_LLM_ENABLE_COMPRESSION") {
+            if let Ok(compression) = compression.parse() {
+                config.llm.enable_compression = compression;
+            } else {
+                warn!(
+                    "Invalid enable compression flag in environment variable: {}",
+                    compression
+                );
+            }
+        }
+
         // Load balancer configuration
         if let Ok(strategy) = std::env::var("OPENROUTER_LOAD_BALANCER_STRATEGY") {
             config.load_balancer.strategy = match strategy.to_lowercase().as_str() {
@@ -234,6 +830,10 @@ impl BlueprintConfig {
                 "least_loaded" => LoadBalancingStrategy::LeastLoaded,
                 "capability_based" => LoadBalancingStrategy::CapabilityBased,
                 "latency_based" => LoadBalancingStrategy::LatencyBased,
+                "random" => LoadBalancingStrategy::Random,
+                "weighted_round_robin" => LoadBalancingStrategy::WeightedRoundRobin,
+                "power_of_two" => LoadBalancingStrategy::PowerOfTwo,
+                "affinity" => LoadBalancingStrategy::Affinity,
                 _ => config.load_balancer.strategy,
             };
         }
@@ -250,6 +850,24 @@ impl BlueprintConfig {
             }
         }
 
+        if let Ok(count) = std::env::var("OPENROUTER_LOAD_BALANCER_FAILURE_GRACE_COUNT") {
+            if let Ok(count) = count.parse() {
+                config.load_balancer.failure_grace_count = count;
+            }
+        }
+
+        if let Ok(window) = std::env::var("OPENROUTER_LOAD_BALANCER_FAILURE_WINDOW") {
+            if let Ok(window) = window.parse() {
+                config.load_balancer.failure_window_seconds = window;
+            }
+        }
+
+        if let Ok(cooldown) = std::env::var("OPENROUTER_LOAD_BALANCER_FAILURE_COOLDOWN") {
+            if let Ok(cooldown) = cooldown.parse() {
+                config.load_balancer.failure_cooldown_seconds = cooldown;
+            }
+        }
+
         // API configuration
         if let Ok(enabled) = std::env::var("OPENROUTER_API_ENABLED") {
             if let Ok(enabled) = enabled.parse() {
@@ -284,6 +902,10 @@ impl BlueprintConfig {
             config.api.api_key = Some(api_key);
         }
 
+        if let Ok(keys_file) = std::env::var("OPENROUTER_API_KEYS_FILE") {
+            config.api.keys_file = Some(PathBuf::from(keys_file));
+        }
+
         if let Ok(auth_token) = std::env::var("OPENROUTER_API_AUTH_TOKEN") {
             config.api.auth_token = Some(auth_token);
         }
@@ -321,6 +943,97 @@ impl BlueprintConfig {
             }
         }
 
+        if let Ok(sample_rate) = std::env::var("OPENROUTER_API_LOG_SAMPLE_RATE") {
+            if let Ok(sample_rate) = sample_rate.parse::<f32>() {
+                config.api.log_sample_rate = sample_rate;
+            } else {
+                warn!(
+                    "Invalid API log sample rate in environment variable: {}",
+                    sample_rate
+                );
+            }
+        }
+
+        if let Ok(interval) = std::env::var("OPENROUTER_API_SSE_KEEP_ALIVE") {
+            if let Ok(interval) = interval.parse() {
+                config.api.sse_keep_alive_seconds = Some(interval);
+            } else {
+                warn!(
+                    "Invalid SSE keep-alive interval in environment variable: {}",
+                    interval
+                );
+            }
+        }
+
+        if let Ok(limits) = std::env::var("OPENROUTER_API_MODEL_RATE_LIMITS") {
+            for limit in limits.split(',').map(|l| l.trim()).filter(|l| !l.is_empty()) {
+                match limit
+                    .split_once('=')
+                    .and_then(|(model, rate)| Some((model.trim(), rate.trim().parse().ok()?)))
+                {
+                    Some((model, rate)) if !model.is_empty() => {
+                        config.api.model_rate_limits.insert(model.to_string(), rate);
+                    }
+                    _ => warn!(
+                        "Invalid model rate limit in environment variable: {}",
+                        limit
+                    ),
+                }
+            }
+        }
+
+        if let Ok(include_cost) = std::env::var("OPENROUTER_API_INCLUDE_COST") {
+            if let Ok(include_cost) = include_cost.parse::<bool>() {
+                config.api.include_cost = include_cost;
+            } else {
+                warn!(
+                    "Invalid API include cost flag in environment variable: {}",
+                    include_cost
+                );
+            }
+        }
+
+        if let Ok(request_logging) = std::env::var("OPENROUTER_API_REQUEST_LOGGING") {
+            if let Ok(request_logging) = request_logging.parse::<bool>() {
+                config.api.request_logging = request_logging;
+            } else {
+                warn!(
+                    "Invalid API request logging flag in environment variable: {}",
+                    request_logging
+                );
+            }
+        }
+
+        if let Ok(redact_content) = std::env::var("OPENROUTER_API_REDACT_CONTENT") {
+            if let Ok(redact_content) = redact_content.parse::<bool>() {
+                config.api.redact_content = redact_content;
+            } else {
+                warn!(
+                    "Invalid API redact content flag in environment variable: {}",
+                    redact_content
+                );
+            }
+        }
+
+        if let Ok(enabled) = std::env::var("OPENROUTER_API_MODERATION_ENABLED") {
+            if let Ok(enabled) = enabled.parse::<bool>() {
+                config.api.moderation.enabled = enabled;
+            } else {
+                warn!(
+                    "Invalid API moderation enabled flag in environment variable: {}",
+                    enabled
+                );
+            }
+        }
+
+        if let Ok(keywords) = std::env::var("OPENROUTER_API_MODERATION_KEYWORDS") {
+            config.api.moderation.blocked_keywords = keywords
+                .split(',')
+                .map(|k| k.trim().to_string())
+                .filter(|k| !k.is_empty())
+                .collect();
+        }
+
         config
     }
 
@@ -345,6 +1058,114 @@ impl BlueprintConfig {
             config.llm.max_concurrent_requests = env_config.llm.max_concurrent_requests;
         }
 
+        if env_config.llm.models != default_models() {
+            config.llm.models = env_config.llm.models;
+        }
+
+        if env_config.llm.system_prompt_policy != SystemPromptPolicy::default() {
+            config.llm.system_prompt_policy = env_config.llm.system_prompt_policy;
+        }
+
+        if env_config.llm.default_system_prompt.is_some() {
+            config.llm.default_system_prompt = env_config.llm.default_system_prompt;
+        }
+
+        if env_config.llm.override_system_prompt {
+            config.llm.override_system_prompt = env_config.llm.override_system_prompt;
+        }
+
+        if env_config.llm.embedding_concurrency != default_embedding_concurrency() {
+            config.llm.embedding_concurrency = env_config.llm.embedding_concurrency;
+        }
+
+        if env_config.llm.empty_response_fallback.is_some() {
+            config.llm.empty_response_fallback = env_config.llm.empty_response_fallback;
+        }
+
+        if env_config.llm.extra_choices_policy != ExtraChoicesPolicy::default() {
+            config.llm.extra_choices_policy = env_config.llm.extra_choices_policy;
+        }
+
+        if !env_config.llm.passthrough_params.is_empty() {
+            config.llm.passthrough_params = env_config.llm.passthrough_params;
+        }
+
+        if !env_config.llm.fallback_models.is_empty() {
+            config.llm.fallback_models = env_config.llm.fallback_models;
+        }
+
+        if !env_config.llm.model_aliases.is_empty() {
+            config.llm.model_aliases = env_config.llm.model_aliases;
+        }
+
+        if env_config.llm.echo_requested_model {
+            config.llm.echo_requested_model = env_config.llm.echo_requested_model;
+        }
+
+        if env_config.llm.response_cache_capacity != default_response_cache_capacity() {
+            config.llm.response_cache_capacity = env_config.llm.response_cache_capacity;
+        }
+
+        if env_config.llm.idempotency_cache_capacity != default_idempotency_cache_capacity() {
+            config.llm.idempotency_cache_capacity = env_config.llm.idempotency_cache_capacity;
+        }
+
+        if env_config.llm.http2 {
+            config.llm.http2 = env_config.llm.http2;
+        }
+
+        if env_config.llm.keep_alive_interval_seconds.is_some() {
+            config.llm.keep_alive_interval_seconds = env_config.llm.keep_alive_interval_seconds;
+        }
+
+        if env_config.llm.enable_compression {
+            config.llm.enable_compression = env_config.llm.enable_compression;
+        }
+
+        if env_config.llm.strict_model_catalog {
+            config.llm.strict_model_catalog = env_config.llm.strict_model_catalog;
+        }
+
+        if env_config.llm.max_messages_per_request.is_some() {
+            config.llm.max_messages_per_request = env_config.llm.max_messages_per_request;
+        }
+
+        if env_config.llm.truncate_overflow {
+            config.llm.truncate_overflow = env_config.llm.truncate_overflow;
+        }
+
+        if env_config.llm.check_nodes_on_add != default_true() {
+            config.llm.check_nodes_on_add = env_config.llm.check_nodes_on_add;
+        }
+
+        if env_config.llm.require_healthy_on_add {
+            config.llm.require_healthy_on_add = env_config.llm.require_healthy_on_add;
+        }
+
+        if env_config.llm.backend != LlmBackend::default() {
+            config.llm.backend = env_config.llm.backend;
+        }
+
+        if env_config.llm.local_reply_mode != LocalReplyMode::default() {
+            config.llm.local_reply_mode = env_config.llm.local_reply_mode;
+        }
+
+        if env_config.llm.auto_continue_on_length {
+            config.llm.auto_continue_on_length = env_config.llm.auto_continue_on_length;
+        }
+
+        if env_config.llm.max_continuations != default_max_continuations() {
+            config.llm.max_continuations = env_config.llm.max_continuations;
+        }
+
+        if env_config.llm.auto_truncate {
+            config.llm.auto_truncate = env_config.llm.auto_truncate;
+        }
+
+        if env_config.llm.reject_context_overflow {
+            config.llm.reject_context_overflow = env_config.llm.reject_context_overflow;
+        }
+
         if env_config.load_balancer.strategy != LoadBalancingStrategy::default() {
             config.load_balancer.strategy = env_config.load_balancer.strategy;
         }
@@ -358,6 +1179,20 @@ impl BlueprintConfig {
                 env_config.load_balancer.selection_timeout_ms;
         }
 
+        if env_config.load_balancer.failure_grace_count != default_failure_grace_count() {
+            config.load_balancer.failure_grace_count = env_config.load_balancer.failure_grace_count;
+        }
+
+        if env_config.load_balancer.failure_window_seconds != default_failure_window() {
+            config.load_balancer.failure_window_seconds =
+                env_config.load_balancer.failure_window_seconds;
+        }
+
+        if env_config.load_balancer.failure_cooldown_seconds != default_failure_cooldown() {
+            config.load_balancer.failure_cooldown_seconds =
+                env_config.load_balancer.failure_cooldown_seconds;
+        }
+
         if env_config.api.enabled != default_true() {
             config.api.enabled = env_config.api.enabled;
         }
@@ -378,6 +1213,14 @@ impl BlueprintConfig {
             config.api.api_key = env_config.api.api_key;
         }
 
+        if env_config.api.keys_file.is_some() {
+            config.api.keys_file = env_config.api.keys_file;
+        }
+
+        if env_config.api.auth_token.is_some() {
+            config.api.auth_token = env_config.api.auth_token;
+        }
+
         if env_config.api.rate_limiting_enabled != default_true() {
             config.api.rate_limiting_enabled = env_config.api.rate_limiting_enabled;
         }
@@ -386,10 +1229,42 @@ impl BlueprintConfig {
             config.api.max_requests_per_minute = env_config.api.max_requests_per_minute;
         }
 
+        if !env_config.api.model_rate_limits.is_empty() {
+            config.api.model_rate_limits = env_config.api.model_rate_limits;
+        }
+
         if env_config.api.metrics_interval_seconds != default_metrics_interval() {
             config.api.metrics_interval_seconds = env_config.api.metrics_interval_seconds;
         }
 
+        if env_config.api.log_sample_rate != default_log_sample_rate() {
+            config.api.log_sample_rate = env_config.api.log_sample_rate;
+        }
+
+        if env_config.api.sse_keep_alive_seconds != default_sse_keep_alive() {
+            config.api.sse_keep_alive_seconds = env_config.api.sse_keep_alive_seconds;
+        }
+
+        if env_config.api.include_cost != default_false() {
+            config.api.include_cost = env_config.api.include_cost;
+        }
+
+        if env_config.api.request_logging != default_false() {
+            config.api.request_logging = env_config.api.request_logging;
+        }
+
+        if env_config.api.redact_content != default_true() {
+            config.api.redact_content = env_config.api.redact_content;
+        }
+
+        if env_config.api.moderation.enabled {
+            config.api.moderation.enabled = env_config.api.moderation.enabled;
+        }
+
+        if !env_config.api.moderation.blocked_keywords.is_empty() {
+            config.api.moderation.blocked_keywords = env_config.api.moderation.blocked_keywords;
+        }
+
         Ok(config)
     }
 
@@ -412,7 +1287,54 @@ impl BlueprintConfig {
             ));
         }
 
+        if self.llm.override_system_prompt && self.llm.default_system_prompt.is_none() {
+            return Err(ConfigError::MissingValue(
+                "LLM default system prompt is required to override system prompts".to_string(),
+            ));
+        }
+
+        if self.llm.embedding_concurrency == 0 {
+            return Err(ConfigError::InvalidValue(
+                "LLM embedding concurrency must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.llm.keep_alive_interval_seconds == Some(0) {
+            return Err(ConfigError::InvalidValue(
+                "LLM keep-alive interval must be greater than 0".to_string(),
+            ));
+        }
+
+        for (model, pricing) in &self.llm.model_pricing {
+            let rates = [
+                pricing.prompt,
+                pricing.completion,
+                pricing.image,
+                pricing.request,
+            ];
+            if rates.iter().any(|rate| rate.is_sign_negative()) {
+                return Err(ConfigError::InvalidValue(format!(
+                    "LLM pricing of model {} must not be negative",
+                    model
+                )));
+            }
+        }
+
+        if self.llm.max_messages_per_request == Some(0) {
+            return Err(ConfigError::InvalidValue(
+                "LLM max messages per request must be greater than 0".to_string(),
+            ));
+        }
+
         // Validate load balancer configuration
+        if !self.load_balancer.strategy.is_enabled() {
+            return Err(ConfigError::InvalidValue(format!(
+                "Load balancing strategy {:?} is disabled in this build (requires the `{}` feature)",
+                self.load_balancer.strategy,
+                self.load_balancer.strategy.required_feature().unwrap_or_default()
+            )));
+        }
+
         if self.load_balancer.max_retries == 0 {
             return Err(ConfigError::InvalidValue(
                 "Load balancer max retries must be greater than 0".to_string(),
@@ -425,6 +1347,37 @@ impl BlueprintConfig {
             ));
         }
 
+        if self.load_balancer.failure_grace_count == 0 {
+            return Err(ConfigError::InvalidValue(
+                "Load balancer failure grace count must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.load_balancer.failure_window_seconds == 0 {
+            return Err(ConfigError::InvalidValue(
+                "Load balancer failure window must be greater than 0".to_string(),
+            ));
+        }
+
+        if self.load_balancer.failure_cooldown_seconds == 0 {
+            return Err(ConfigError::InvalidValue(
+                "Load balancer failure cooldown must be greater than 0".to_string(),
+            ));
+        }
+
+        if !self.load_balancer.capability_weights.is_valid() {
+            return Err(ConfigError::InvalidValue(
+                "Load balancer capability weights must be non-negative numbers".to_string(),
+            ));
+        }
+
+        if !self.load_balancer.backoff.is_valid() {
+            return Err(ConfigError::InvalidValue(
+                "Load balancer backoff multiplier must be at least 1 and initial_ms at most max_ms"
+                    .to_string(),
+            ));
+        }
+
         // Validate API configuration
         if self.api.enabled {
             if self.api.host.is_empty() {
@@ -437,9 +1390,9 @@ impl BlueprintConfig {
                 ));
             }
 
-            if self.api.auth_enabled && self.api.api_key.is_none() {
+            if self.api.auth_enabled && self.api.api_key.is_none() && self.api.keys_file.is_none() {
                 return Err(ConfigError::MissingValue(
-                    "API key is required when authentication is enabled".to_string(),
+                    "API key or keys file is required when authentication is enabled".to_string(),
                 ));
             }
 
@@ -449,6 +1402,20 @@ impl BlueprintConfig {
                 ));
             }
 
+            if self.api.rate_limiting_enabled {
+                if let Some((model, _)) = self
+                    .api
+                    .model_rate_limits
+                    .iter()
+                    .find(|(_, rate)| **rate == 0)
+                {
+                    return Err(ConfigError::InvalidValue(format!(
+                        "API rate limit of model {} must be greater than 0",
+                        model
+                    )));
+                }
+            }
+
             if self.api.metrics_interval_seconds == 0 {
                 return Err(ConfigError::InvalidValue(
                     "API metrics interval must be greater than 0".to_string(),
@@ -456,6 +1423,55 @@ impl BlueprintConfig {
             }
         }
 
+        if !(0.0..=1.0).contains(&self.api.log_sample_rate) {
+            return Err(ConfigError::InvalidValue(
+                "API log sample rate must be between 0.0 and 1.0".to_string(),
+            ));
+        }
+
+        if self.api.sse_keep_alive_seconds == Some(0) {
+            return Err(ConfigError::InvalidValue(
+                "API SSE keep-alive interval must be greater than 0".to_string(),
+            ));
+        }
+
+        for pattern in &self.api.moderation.blocked_patterns {
+            if let Err(e) = regex::Regex::new(pattern) {
+                return Err(ConfigError::InvalidValue(format!(
+                    "Invalid moderation pattern {:?}: {}",
+                    pattern, e
+                )));
+            }
+        }
+
+        let mut node_ids = std::collections::HashSet::new();
+        for node in &self.nodes {
+            if node.id.is_empty() || node.id == "default" {
+                return Err(ConfigError::InvalidValue(format!(
+                    "Node id {:?} is reserved or empty",
+                    node.id
+                )));
+            }
+            if !node_ids.insert(node.id.as_str()) {
+                return Err(ConfigError::InvalidValue(format!(
+                    "Duplicate node id {:?}",
+                    node.id
+                )));
+            }
+            if node.api_url.is_empty() {
+                return Err(ConfigError::MissingValue(format!(
+                    "API URL of node {:?}",
+                    node.id
+                )));
+            }
+            if node.weight == 0 {
+                return Err(ConfigError::InvalidValue(format!(
+                    "Weight of node {:?} must be greater than 0",
+                    node.id
+                )));
+            }
+        }
+
         Ok(())
     }
 }
@@ -474,6 +1490,10 @@ fn default_max_concurrent() -> usize {
     5
 }
 
+fn default_embedding_concurrency() -> usize {
+    DEFAULT_EMBEDDING_CONCURRENCY
+}
+
 fn default_max_retries() -> usize {
     3
 }
@@ -482,6 +1502,18 @@ fn default_selection_timeout() -> u64 {
     1000
 }
 
+fn default_failure_grace_count() -> u32 {
+    1
+}
+
+fn default_failure_window() -> u64 {
+    60
+}
+
+fn default_failure_cooldown() -> u64 {
+    30
+}
+
 fn default_host() -> String {
     "0.0.0.0".to_string()
 }
@@ -502,6 +1534,19 @@ fn default_false() -> bool {
     false
 }
 
+/// A chat and text completion model known only by its ID, as listed in `OPENROUTER_LLM_MODELS`
+fn model_from_id(id: &str) -> ModelInfo {
+    ModelInfo {
+        id: id.to_string(),
+        name: id.to_string(),
+        max_context_length: 4096,
+        supports_chat: true,
+        supports_text: true,
+        supports_embeddings: false,
+        parameters: HashMap::new(),
+    }
+}
+
 fn default_models() -> Vec<ModelInfo> {
     vec![
         ModelInfo {
@@ -534,6 +1579,34 @@ fn default_models() -> Vec<ModelInfo> {
     ]
 }
 
+fn default_response_cache_capacity() -> usize {
+    256
+}
+
+fn default_idempotency_cache_capacity() -> usize {
+    1024
+}
+
+fn default_max_continuations() -> usize {
+    3
+}
+
 fn default_metrics_interval() -> u64 {
     60
 }
+
+fn default_log_sample_rate() -> f32 {
+    1.0
+}
+
+fn default_node_provider() -> String {
+    LOCAL_NODE_PROVIDER.to_string()
+}
+
+fn default_node_weight() -> u32 {
+    1
+}
+
+fn default_sse_keep_alive() -> Option<u64> {
+    Some(15)
+}
@@ -1,14 +1,37 @@
+use std::collections::BTreeMap;
 use std::sync::Arc;
-use tokio::sync::RwLock;
+use std::time::{Duration, Instant};
+use tokio::sync::{broadcast, RwLock};
 
 use blueprint_sdk::runner::config::BlueprintEnvironment;
-use tracing::info;
+use tracing::{error, info, warn};
 
-use crate::config::BlueprintConfig;
-use crate::llm::{LlmClient, LocalLlmClient, LocalLlmConfig, NodeMetrics};
-use crate::load_balancer::{LoadBalancer, LoadBalancerConfig};
+use crate::auth::{self, Authenticator, Principal, DEFAULT_PRINCIPAL};
+#[cfg(feature = "response-cache")]
+use crate::cache::ResponseCache;
+use crate::catalog::ModelCatalog;
+use crate::config::{
+    BlueprintConfig, ConfigEvent, LlmConfig, ModerationConfig, LOCAL_NODE_PROVIDER,
+};
+use crate::factory::{local_http_client, local_llm_config, LlmClientFactory};
+use crate::idempotency::IdempotencyCache;
+use crate::llm::{
+    LlmCapabilities, LlmClient, LlmError, LlmRequest, LocalLlmClient, LocalLlmConfig,
+    LocalReplyMode, ModelInfo, NodeMetrics,
+};
+use crate::load_balancer::{split_provider_suffix, DrainOutcome, LoadBalancer, LoadBalancerConfig};
+use crate::moderation::{KeywordModerator, Moderator};
+use crate::queue::RequestQueue;
+use crate::rate_limit::RateLimiter;
+use crate::sampling::LogSampler;
+use crate::stream_results::StreamRegistry;
+use crate::tokens::{ApproximateTokenCounter, TokenCounter};
+use crate::usage::{PrincipalUsage, UsageTracker};
 use blueprint_sdk::macros::context::{KeystoreContext, ServicesContext, TangleClientContext};
 
+/// Number of configuration events kept for subscribers that fall behind
+const CONFIG_EVENT_CAPACITY: usize = 16;
+
 /// Context for the OpenRouter Blueprint
 #[derive(Clone, KeystoreContext, TangleClientContext, ServicesContext)]
 pub struct OpenRouterContext {
@@ -30,11 +53,62 @@ pub struct OpenRouterContext {
 
     /// Blueprint configuration
     pub blueprint_config: Arc<RwLock<BlueprintConfig>>,
+
+    /// The last metrics reported to Tangle and when they were reported
+    pub last_metrics_report: Arc<RwLock<Option<(Instant, NodeMetrics)>>>,
+
+    /// Sampler for the per-request summary log line
+    pub log_sampler: Arc<LogSampler>,
+
+    /// Admission queue bounding concurrently dispatched requests
+    pub request_queue: Arc<RequestQueue>,
+
+    /// Moderator applied to requests, if moderation is enabled
+    pub moderator: Arc<RwLock<Option<Arc<dyn Moderator>>>>,
+
+    /// Counter of the tokens requests need, to check them against their model's context
+    pub token_counter: Arc<RwLock<Arc<dyn TokenCounter>>>,
+
+    /// Authenticator for API keys, if authentication is enabled
+    pub authenticator: Arc<RwLock<Option<Arc<dyn Authenticator>>>>,
+
+    /// Request and token usage of authenticated principals
+    pub usage: Arc<UsageTracker>,
+
+    /// Limit of the requests the node admits per minute
+    pub rate_limiter: Arc<RateLimiter>,
+
+    /// Sender for configuration reload events
+    pub config_events: broadcast::Sender<ConfigEvent>,
+
+    /// Responses to deterministic requests
+    #[cfg(feature = "response-cache")]
+    pub response_cache: Arc<ResponseCache>,
+
+    /// Responses to requests sent with an idempotency key
+    pub idempotency_cache: Arc<IdempotencyCache>,
+
+    /// Streams started by `start_llm_stream`, buffered for `poll_llm_stream`
+    pub streams: Arc<StreamRegistry>,
 }
 
 impl OpenRouterContext {
     /// Create a new OpenRouter context
+    ///
+    /// The default client is built for the `local` backend only; blueprints serving other
+    /// backends create their context with [`OpenRouterContext::with_client_factory`].
     pub async fn new(env: BlueprintEnvironment) -> Result<Self, blueprint_sdk::Error> {
+        Self::with_client_factory(env, &LlmClientFactory::default()).await
+    }
+
+    /// Create a new OpenRouter context, building the default client for the configured
+    /// `llm.backend` with `factory`
+    ///
+    /// Fails if `factory` cannot build a client for the configured backend.
+    pub async fn with_client_factory(
+        env: BlueprintEnvironment,
+        factory: &LlmClientFactory,
+    ) -> Result<Self, blueprint_sdk::Error> {
         // Load configuration
         let blueprint_config = {
             // Try to load from the data directory if it exists
@@ -69,33 +143,57 @@ impl OpenRouterContext {
         }
 
         // Create a local LLM config from the blueprint config
-        let local_config = LocalLlmConfig {
-            api_url: blueprint_config.llm.api_url.clone(),
-            timeout_seconds: blueprint_config.llm.timeout_seconds,
-            max_concurrent_requests: blueprint_config.llm.max_concurrent_requests,
-            models: blueprint_config.llm.models.clone(),
-            additional_params: blueprint_config.llm.additional_params.clone(),
-        };
+        let local_config = local_llm_config(&blueprint_config.llm);
 
-        // Create the default LLM client
-        let llm_client = Arc::new(LocalLlmClient::new(local_config.clone()));
+        // Create the default LLM client for the configured backend
+        let llm_client = factory.build(&blueprint_config.llm).map_err(|e| {
+            blueprint_sdk::Error::Other(format!("Failed to create the LLM client: {}", e))
+        })?;
 
         // Get initial metrics
         let metrics = Arc::new(RwLock::new(llm_client.get_metrics()));
 
-        // Create the load balancer with configuration from blueprint config
-        let load_balancer_config = LoadBalancerConfig {
-            strategy: blueprint_config.load_balancer.strategy,
-            max_retries: blueprint_config.load_balancer.max_retries,
-            selection_timeout_ms: blueprint_config.load_balancer.selection_timeout_ms,
-        };
-        let load_balancer = Arc::new(LoadBalancer::new(load_balancer_config));
+        // Create the load balancer with configuration from blueprint config, registering the
+        // nodes it declares that pass the node check
+        let mut nodes = Vec::new();
+        for (id, client) in configured_nodes(&blueprint_config) {
+            if check_node(&id, client.as_ref(), &blueprint_config.llm)
+                .await
+                .is_ok()
+            {
+                nodes.push((id, client));
+            }
+        }
+        let load_balancer = Arc::new(
+            LoadBalancer::with_nodes(load_balancer_config(&blueprint_config), nodes).await,
+        );
+        // Configured nodes weigh what the configuration says, not what their client reports
+        for node in &blueprint_config.nodes {
+            load_balancer.set_node_weight(&node.id, node.weight).await;
+        }
 
         // Add the default LLM client to the load balancer
         load_balancer
             .add_node("default".to_string(), llm_client.clone())
             .await;
 
+        let moderator = Arc::new(RwLock::new(configured_moderator(
+            &blueprint_config.api.moderation,
+        )));
+
+        // Unlike other settings, broken authentication must not fall back to accepting everyone
+        let authenticator = auth::from_config(&blueprint_config.api).map_err(|e| {
+            blueprint_sdk::Error::Other(format!("Failed to configure authentication: {}", e))
+        })?;
+        let authenticator = Arc::new(RwLock::new(authenticator));
+
+        // Requests beyond the configured concurrency wait for a dispatch slot
+        let request_queue = Arc::new(RequestQueue::new(
+            blueprint_config.llm.max_concurrent_requests,
+        ));
+
+        let rate_limiter = Arc::new(RateLimiter::from_config(&blueprint_config.api));
+
         info!("Created OpenRouter context with default LLM client and load balancer");
 
         Ok(Self {
@@ -105,12 +203,27 @@ impl OpenRouterContext {
             config: Arc::new(RwLock::new(local_config)),
             load_balancer,
             blueprint_config: Arc::new(RwLock::new(blueprint_config)),
+            last_metrics_report: Arc::new(RwLock::new(None)),
+            log_sampler: Arc::new(LogSampler::new()),
+            request_queue,
+            moderator,
+            token_counter: Arc::new(RwLock::new(Arc::new(ApproximateTokenCounter))),
+            authenticator,
+            usage: Arc::new(UsageTracker::new()),
+            rate_limiter,
+            config_events: broadcast::channel(CONFIG_EVENT_CAPACITY).0,
+            #[cfg(feature = "response-cache")]
+            response_cache: Arc::new(ResponseCache::new()),
+            idempotency_cache: Arc::new(IdempotencyCache::new()),
+            streams: Arc::new(StreamRegistry::default()),
         })
     }
 
     /// Update the metrics for this node
     pub async fn update_metrics(&self) {
-        let metrics = self.llm_client.get_metrics();
+        let mut metrics = self.llm_client.get_metrics();
+        metrics.queued_requests = self.request_queue.queued_requests();
+        metrics.avg_queue_wait_ms = self.request_queue.avg_wait_ms();
         let mut metrics_lock = self.metrics.write().await;
         *metrics_lock = metrics;
 
@@ -121,8 +234,19 @@ impl OpenRouterContext {
     }
 
     /// Add an LLM node to the load balancer
-    pub async fn add_llm_node(&self, id: String, client: Arc<dyn LlmClient>) {
+    ///
+    /// With `llm.check_nodes_on_add`, the node is checked first. A node failing the check is
+    /// added with a warning, or rejected with the check's error if `llm.require_healthy_on_add`
+    /// is set.
+    pub async fn add_llm_node(
+        &self,
+        id: String,
+        client: Arc<dyn LlmClient>,
+    ) -> Result<(), LlmError> {
+        let llm_config = self.blueprint_config.read().await.llm.clone();
+        check_node(&id, client.as_ref(), &llm_config).await?;
         self.load_balancer.add_node(id, client).await;
+        Ok(())
     }
 
     /// Remove an LLM node from the load balancer
@@ -130,48 +254,355 @@ impl OpenRouterContext {
         self.load_balancer.remove_node(id).await
     }
 
+    /// Remove an LLM node after letting its in-flight requests drain
+    pub async fn remove_llm_node_graceful(&self, id: &str, drain_timeout: Duration) -> bool {
+        self.load_balancer
+            .remove_node_graceful(id, drain_timeout)
+            .await
+    }
+
+    /// Drain an LLM node of its in-flight requests, then remove it, reporting whether it
+    /// drained before `timeout`
+    pub async fn drain_llm_node(&self, id: &str, timeout: Duration) -> DrainOutcome {
+        self.load_balancer.drain_node(id, timeout).await
+    }
+
     /// Get an LLM client for the specified model
     pub async fn get_llm_client_for_model(&self, model: &str) -> Option<Arc<dyn LlmClient>> {
+        self.get_llm_client_for_model_from(model, None).await
+    }
+
+    /// Get an LLM client for the specified model from a node of `provider`, if given
+    pub async fn get_llm_client_for_model_from(
+        &self,
+        model: &str,
+        provider: Option<&str>,
+    ) -> Option<Arc<dyn LlmClient>> {
         // Try to select a node from the load balancer
-        let node = self.load_balancer.select_node_for_model(model).await?;
+        let node = self
+            .load_balancer
+            .select_node_for_model_from(model, provider)
+            .await?;
+        Some(node.client)
+    }
+
+    /// Admit a request for `model` under the node's rate limits, or fail with
+    /// [`LlmError::RateLimited`]
+    ///
+    /// The model is limited under the id it is served as: without its `@provider` suffix and
+    /// with its alias resolved, so neither gets past the limit of the model.
+    pub async fn try_acquire_rate_limit(&self, model: &str) -> Result<(), LlmError> {
+        let (model, _) = split_provider_suffix(model);
+        let resolved = self
+            .blueprint_config
+            .read()
+            .await
+            .llm
+            .model_aliases
+            .get(model)
+            .cloned();
+        self.rate_limiter
+            .try_acquire(resolved.as_deref().unwrap_or(model))
+    }
+
+    /// Get an LLM client to serve `request` with `model`, from a node of `provider` if given
+    ///
+    /// See [`LoadBalancer::node_for_request`].
+    pub async fn get_llm_client_for_request(
+        &self,
+        model: &str,
+        request: &LlmRequest,
+        provider: Option<&str>,
+    ) -> Option<Arc<dyn LlmClient>> {
+        let node = self
+            .load_balancer
+            .node_for_request(model, request, provider)
+            .await?;
+        Some(node.client)
+    }
+
+    /// Get a streaming-capable LLM client for the specified model
+    ///
+    /// Falls back to a client that cannot stream when no streaming-capable node serves it.
+    pub async fn get_streaming_llm_client_for_model(
+        &self,
+        model: &str,
+    ) -> Option<Arc<dyn LlmClient>> {
+        self.get_streaming_llm_client_for_model_from(model, None)
+            .await
+    }
+
+    /// Get a streaming-capable LLM client for the specified model from a node of `provider`,
+    /// if given
+    pub async fn get_streaming_llm_client_for_model_from(
+        &self,
+        model: &str,
+        provider: Option<&str>,
+    ) -> Option<Arc<dyn LlmClient>> {
+        let node = self
+            .load_balancer
+            .select_streaming_node_for_model_from(model, provider)
+            .await?;
         Some(node.client)
     }
 
+    /// The catalog of models served by the active LLM nodes
+    pub async fn model_catalog(&self) -> ModelCatalog {
+        ModelCatalog::new(self.served_models().await)
+    }
+
+    /// The models served by the active LLM nodes, keeping the first entry for each id
+    ///
+    /// Nodes are visited in id order, so the entry kept does not depend on insertion order.
+    pub async fn served_models(&self) -> Vec<ModelInfo> {
+        let mut nodes = self.load_balancer.get_active_nodes().await;
+        nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+        let mut models: Vec<ModelInfo> = Vec::new();
+        for model in nodes
+            .iter()
+            .flat_map(|node| node.client.get_supported_models())
+        {
+            if !models.iter().any(|m| m.id == model.id) {
+                models.push(model);
+            }
+        }
+        models
+    }
+
+    /// The combined capabilities of the active LLM nodes
+    pub async fn capabilities(&self) -> LlmCapabilities {
+        let nodes = self.load_balancer.get_active_nodes().await;
+        LlmCapabilities::combine(nodes.iter().map(|node| node.client.get_capabilities()))
+    }
+
+    /// Moderate requests with a custom moderator instead of the configured one
+    ///
+    /// A configuration reload replaces it with the moderator configured in `api.moderation`.
+    pub async fn set_moderator(&self, moderator: Arc<dyn Moderator>) {
+        *self.moderator.write().await = Some(moderator);
+    }
+
+    /// Count the tokens of requests with a custom counter instead of estimating them
+    ///
+    /// The counter decides which requests `llm.reject_context_overflow` rejects.
+    pub async fn set_token_counter(&self, token_counter: Arc<dyn TokenCounter>) {
+        *self.token_counter.write().await = token_counter;
+    }
+
+    /// Authenticate API keys with a custom authenticator instead of the configured one
+    ///
+    /// A configuration reload replaces it with the authenticator configured in `api`.
+    pub async fn set_authenticator(&self, authenticator: Arc<dyn Authenticator>) {
+        *self.authenticator.write().await = Some(authenticator);
+    }
+
+    /// The principal a caller presenting `token` acts as, or `None` if the caller is rejected
+    ///
+    /// Without an authenticator every caller is [`DEFAULT_PRINCIPAL`]; with one, a missing
+    /// token is rejected.
+    pub async fn authenticate(&self, token: Option<&str>) -> Option<Principal> {
+        let authenticator = self.authenticator.read().await.clone();
+        match (authenticator, token) {
+            (None, _) => Some(Principal::new(DEFAULT_PRINCIPAL)),
+            (Some(authenticator), Some(token)) => authenticator.authenticate(token).await,
+            (Some(_), None) => None,
+        }
+    }
+
+    /// Usage of every principal since the node started, as served by `GET /admin/usage`
+    pub fn usage_report(&self) -> BTreeMap<String, PrincipalUsage> {
+        self.usage.report()
+    }
+
+    /// Stop routing requests to a node without removing it
+    ///
+    /// The exclusion lasts until [`OpenRouterContext::include_node`] lifts it. Returns whether
+    /// the node was not excluded already. Served by `POST /admin/nodes/{id}/exclude`.
+    pub async fn exclude_node(&self, id: &str) -> bool {
+        self.load_balancer.exclude_node(id).await
+    }
+
+    /// Route requests to an excluded node again, returning whether it was excluded
+    ///
+    /// Served by `POST /admin/nodes/{id}/include`.
+    pub async fn include_node(&self, id: &str) -> bool {
+        self.load_balancer.include_node(id).await
+    }
+
+    /// Ids of the nodes excluded from routing, as served by `GET /admin/nodes/excluded`
+    pub async fn excluded_nodes(&self) -> Vec<String> {
+        self.load_balancer.excluded_nodes().await
+    }
+
+    /// Subscribe to configuration reload events
+    ///
+    /// Every call to `reload_config` emits one event, whether it succeeds or fails.
+    pub fn subscribe_config_events(&self) -> broadcast::Receiver<ConfigEvent> {
+        self.config_events.subscribe()
+    }
+
     /// Reload configuration from file
+    ///
+    /// On success the new configuration is applied, including its load balancing strategy.
     pub async fn reload_config(&self) -> Result<(), String> {
-        // Try to load from the data directory
-        if let Some(data_dir) = self.env.data_dir.as_ref() {
-            let config_path = data_dir.join("config.json");
-            if config_path.exists() {
-                match BlueprintConfig::load(&config_path) {
-                    Ok(config) => {
-                        // Validate the configuration
-                        if let Err(e) = config.validate() {
-                            return Err(format!("Configuration validation failed: {}", e));
-                        }
+        let loaded = self.load_config_file().and_then(|config| {
+            let authenticator = auth::from_config(&config.api)
+                .map_err(|e| format!("Failed to configure authentication: {}", e))?;
+            Ok((config, authenticator))
+        });
+        let (config, authenticator) = match loaded {
+            Ok(loaded) => loaded,
+            Err(e) => {
+                // Nobody may be subscribed, which is not an error
+                let _ = self
+                    .config_events
+                    .send(ConfigEvent::ReloadFailed(e.clone()));
+                return Err(e);
+            }
+        };
 
-                        // Update the configuration
-                        *self.blueprint_config.write().await = config;
+        // Update the configuration
+        *self.blueprint_config.write().await = config.clone();
 
-                        // Update the local LLM config
-                        let config = self.blueprint_config.read().await;
-                        let mut local_config = self.config.write().await;
-                        local_config.api_url = config.llm.api_url.clone();
-                        local_config.timeout_seconds = config.llm.timeout_seconds;
-                        local_config.max_concurrent_requests = config.llm.max_concurrent_requests;
-                        local_config.models = config.llm.models.clone();
-                        local_config.additional_params = config.llm.additional_params.clone();
+        // Update the local LLM config
+        *self.config.write().await = local_llm_config(&config.llm);
 
-                        info!("Configuration reloaded successfully");
-                        Ok(())
-                    }
-                    Err(e) => Err(format!("Failed to load configuration: {}", e)),
-                }
-            } else {
-                Err("Configuration file not found".to_string())
+        *self.moderator.write().await = configured_moderator(&config.api.moderation);
+        *self.authenticator.write().await = authenticator;
+        self.rate_limiter.reconfigure(&config.api);
+        self.request_queue
+            .resize(config.llm.max_concurrent_requests);
+
+        // Route subsequent requests with the new strategy and limits
+        self.load_balancer
+            .set_config(load_balancer_config(&config))
+            .await;
+
+        info!("Configuration reloaded successfully");
+        let _ = self
+            .config_events
+            .send(ConfigEvent::Reloaded(Arc::new(config)));
+        Ok(())
+    }
+
+    /// Load and validate the configuration file in the data directory
+    fn load_config_file(&self) -> Result<BlueprintConfig, String> {
+        let data_dir = self
+            .env
+            .data_dir
+            .as_ref()
+            .ok_or_else(|| "Data directory not specified".to_string())?;
+
+        let config_path = data_dir.join("config.json");
+        if !config_path.exists() {
+            return Err("Configuration file not found".to_string());
+        }
+
+        let config = BlueprintConfig::load(&config_path)
+            .map_err(|e| format!("Failed to load configuration: {}", e))?;
+        config
+            .validate()
+            .map_err(|e| format!("Configuration validation failed: {}", e))?;
+        Ok(config)
+    }
+}
+
+/// The load balancer settings of a blueprint configuration
+fn load_balancer_config(config: &BlueprintConfig) -> LoadBalancerConfig {
+    LoadBalancerConfig {
+        strategy: config.load_balancer.strategy,
+        max_retries: config.load_balancer.max_retries,
+        selection_timeout_ms: config.load_balancer.selection_timeout_ms,
+        capability_weights: config.load_balancer.capability_weights,
+        failure_grace_count: config.load_balancer.failure_grace_count,
+        failure_window_seconds: config.load_balancer.failure_window_seconds,
+        failure_cooldown_seconds: config.load_balancer.failure_cooldown_seconds,
+        backoff: config.load_balancer.backoff,
+    }
+}
+
+/// Clients for the `nodes` declared in a blueprint configuration
+///
+/// Only `local` nodes can be built here; nodes of other providers are skipped with a warning
+/// and left to the blueprint implementing the provider to register with `add_llm_node`.
+fn configured_nodes(config: &BlueprintConfig) -> Vec<(String, Arc<dyn LlmClient>)> {
+    config
+        .nodes
+        .iter()
+        .filter_map(|node| {
+            if node.provider != LOCAL_NODE_PROVIDER {
+                warn!(
+                    "Skipping node {}: provider {:?} is not built by the template",
+                    node.id, node.provider
+                );
+                return None;
             }
-        } else {
-            Err("Data directory not specified".to_string())
+            let models = if node.models.is_empty() {
+                config.llm.models.clone()
+            } else {
+                node.models.clone()
+            };
+            let client: Arc<dyn LlmClient> = Arc::new(
+                LocalLlmClient::new(LocalLlmConfig {
+                    api_url: node.api_url.clone(),
+                    timeout_seconds: config.llm.timeout_seconds,
+                    max_concurrent_requests: config.llm.max_concurrent_requests,
+                    models,
+                    additional_params: config.llm.additional_params.clone(),
+                    // A declared node is a backend at its `api_url`; `llm.local_reply_mode`
+                    // only applies to the default client
+                    reply_mode: LocalReplyMode::Backend,
+                })
+                .with_http_client(local_http_client(&config.llm)),
+            );
+            Some((node.id.clone(), client))
+        })
+        .collect()
+}
+
+/// Check a node's health and model list before it is added, as configured in `config`
+///
+/// Fails only if the node fails the check and `require_healthy_on_add` is set; otherwise a
+/// failure is logged as a warning.
+async fn check_node(id: &str, client: &dyn LlmClient, config: &LlmConfig) -> Result<(), LlmError> {
+    if !config.check_nodes_on_add {
+        return Ok(());
+    }
+
+    let timeout = Duration::from_secs(config.timeout_seconds);
+    let result = match tokio::time::timeout(timeout, client.health_check()).await {
+        Ok(Ok(())) if client.get_supported_models().is_empty() => Err(LlmError::RequestFailed(
+            "the backend serves no models".to_string(),
+        )),
+        Ok(result) => result,
+        Err(_) => Err(LlmError::Timeout(timeout)),
+    };
+
+    match result {
+        Ok(()) => Ok(()),
+        Err(e) if config.require_healthy_on_add => {
+            error!("Rejecting LLM node {}: {}", id, e);
+            Err(e)
+        }
+        Err(e) => {
+            warn!("LLM node {} failed its check, adding it anyway: {}", id, e);
+            Ok(())
+        }
+    }
+}
+
+/// The built-in moderator for `config`, or `None` if moderation is disabled
+fn configured_moderator(config: &ModerationConfig) -> Option<Arc<dyn Moderator>> {
+    if !config.enabled {
+        return None;
+    }
+    match KeywordModerator::from_config(config) {
+        Ok(moderator) => Some(Arc::new(moderator)),
+        Err(e) => {
+            // Rejected by config validation, but a config can be built directly
+            error!("Invalid moderation pattern, moderation is disabled: {}", e);
+            None
         }
     }
 }
@@ -0,0 +1,55 @@
+//! Correlation ids linking backend requests to the Tangle job call that carried them
+//!
+//! `process_llm_request` runs each request inside [`with_correlation_id`], so LLM clients can
+//! forward the id to their backend with [`apply_correlation_header`] without any change to the
+//! `LlmClient` trait.
+
+use std::future::Future;
+
+/// HTTP header carrying the correlation id to LLM backends
+pub const CORRELATION_ID_HEADER: &str = "X-Correlation-Id";
+
+tokio::task_local! {
+    static CORRELATION_ID: String;
+}
+
+/// Build the correlation id for a Tangle job call
+pub fn correlation_id_for_call(call_id: u64) -> String {
+    format!("tangle-call-{}", call_id)
+}
+
+/// Run `future` with `id` as the current correlation id
+pub async fn with_correlation_id<F: Future>(id: String, future: F) -> F::Output {
+    CORRELATION_ID.scope(id, future).await
+}
+
+/// The correlation id of the request currently being processed, if any
+pub fn current_correlation_id() -> Option<String> {
+    CORRELATION_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Add the current correlation id, if any, to an outgoing backend request
+pub fn apply_correlation_header(builder: reqwest::RequestBuilder) -> reqwest::RequestBuilder {
+    match current_correlation_id() {
+        Some(id) => builder.header(CORRELATION_ID_HEADER, id),
+        None => builder,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_correlation_id_is_scoped() {
+        assert_eq!(current_correlation_id(), None);
+
+        let id = with_correlation_id(correlation_id_for_call(42), async {
+            current_correlation_id()
+        })
+        .await;
+
+        assert_eq!(id.as_deref(), Some("tangle-call-42"));
+        assert_eq!(current_correlation_id(), None);
+    }
+}
@@ -0,0 +1,165 @@
+//! Building the default LLM client from the `llm` section of a configuration
+//!
+//! The template library only knows how to build its own `LocalLlmClient`. The vLLM and Ollama
+//! clients live in blueprint crates that depend on this library, so a blueprint registers the
+//! builder of its client with [`LlmClientFactory::with_backend`] and creates its context with
+//! `OpenRouterContext::with_client_factory`.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use tracing::warn;
+
+use crate::config::{LlmBackend, LlmConfig};
+use crate::llm::{
+    LlmClient, LlmError, LocalLlmClient, LocalLlmConfig, Result, SYSTEM_METRICS_INTERVAL,
+};
+
+/// Builds the client of one backend from the `llm` section of a configuration
+pub type LlmClientBuilder = fn(&LlmConfig) -> Arc<dyn LlmClient>;
+
+/// The client builders of the backends a blueprint supports, keyed by `llm.backend`
+#[derive(Clone)]
+pub struct LlmClientFactory {
+    builders: HashMap<LlmBackend, LlmClientBuilder>,
+}
+
+impl Default for LlmClientFactory {
+    /// A factory building the template's `local` client only
+    fn default() -> Self {
+        Self {
+            builders: HashMap::from([(
+                LlmBackend::Local,
+                build_local_client as LlmClientBuilder,
+            )]),
+        }
+    }
+}
+
+impl LlmClientFactory {
+    /// Create a factory building the template's `local` client only
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Build clients of `backend` with `builder`, replacing any builder registered before
+    pub fn with_backend(mut self, backend: LlmBackend, builder: LlmClientBuilder) -> Self {
+        self.builders.insert(backend, builder);
+        self
+    }
+
+    /// Whether clients of `backend` can be built
+    pub fn supports(&self, backend: LlmBackend) -> bool {
+        self.builders.contains_key(&backend)
+    }
+
+    /// Build the client of the backend `config` selects
+    ///
+    /// Fails with [`LlmError::NotImplemented`] for a backend without a registered builder.
+    pub fn build(&self, config: &LlmConfig) -> Result<Arc<dyn LlmClient>> {
+        let builder = self.builders.get(&config.backend).ok_or_else(|| {
+            LlmError::NotImplemented(format!(
+                "no client is registered for the {:?} backend",
+                config.backend
+            ))
+        })?;
+        Ok(builder(config))
+    }
+}
+
+/// Build the client of the backend `config` selects with the default factory
+///
+/// Only the `local` backend is built by the template itself; see [`LlmClientFactory`] for the
+/// others.
+pub fn build_llm_client(config: &LlmConfig) -> Result<Arc<dyn LlmClient>> {
+    LlmClientFactory::default().build(config)
+}
+
+/// The `LocalLlmClient` settings of an `llm` configuration section
+pub fn local_llm_config(config: &LlmConfig) -> LocalLlmConfig {
+    LocalLlmConfig {
+        api_url: config.api_url.clone(),
+        timeout_seconds: config.timeout_seconds,
+        max_concurrent_requests: config.max_concurrent_requests,
+        models: config.models.clone(),
+        additional_params: config.additional_params.clone(),
+        reply_mode: config.local_reply_mode.clone(),
+    }
+}
+
+/// Build the template's `local` client
+fn build_local_client(config: &LlmConfig) -> Arc<dyn LlmClient> {
+    let client =
+        LocalLlmClient::new(local_llm_config(config)).with_http_client(local_http_client(config));
+    if tokio::runtime::Handle::try_current().is_ok() {
+        client.spawn_metrics_collector(SYSTEM_METRICS_INTERVAL);
+    }
+    #[cfg(feature = "gpu")]
+    client.start_gpu_metrics(crate::llm::GPU_METRICS_INTERVAL);
+    Arc::new(client)
+}
+
+/// The HTTP client of a `local` client, with the configured HTTP/2 and keep-alive options
+pub(crate) fn local_http_client(config: &LlmConfig) -> reqwest::Client {
+    config.http_client_builder().build().unwrap_or_else(|e| {
+        warn!(
+            "Failed to build the configured HTTP client, using the default: {}",
+            e
+        );
+        reqwest::Client::new()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tests::MockLlmClient;
+
+    #[test]
+    fn test_local_backend_builds_local_client() {
+        let config = LlmConfig {
+            max_concurrent_requests: 3,
+            ..Default::default()
+        };
+        let client = build_llm_client(&config).unwrap();
+
+        let capabilities = client.get_capabilities();
+        assert!(!capabilities.supports_streaming);
+        assert_eq!(capabilities.max_concurrent_requests, 3);
+        assert_eq!(client.get_node_info().backend, "local");
+    }
+
+    #[test]
+    fn test_unregistered_backend_is_not_built() {
+        for backend in [LlmBackend::Vllm, LlmBackend::Ollama] {
+            let config = LlmConfig {
+                backend,
+                ..Default::default()
+            };
+            assert!(matches!(
+                build_llm_client(&config),
+                Err(LlmError::NotImplemented(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_registered_builder_is_used() {
+        fn build_mock(_config: &LlmConfig) -> Arc<dyn LlmClient> {
+            Arc::new(MockLlmClient::new())
+        }
+
+        let factory = LlmClientFactory::new().with_backend(LlmBackend::Vllm, build_mock);
+        assert!(factory.supports(LlmBackend::Local));
+        assert!(factory.supports(LlmBackend::Vllm));
+        assert!(!factory.supports(LlmBackend::Ollama));
+
+        let config = LlmConfig {
+            backend: LlmBackend::Vllm,
+            ..Default::default()
+        };
+        let client = factory.build(&config).unwrap();
+        assert!(client.get_capabilities().supports_streaming);
+        assert_eq!(client.get_capabilities().max_concurrent_requests, 10);
+    }
+}
@@ -0,0 +1,187 @@
+//! Responses of requests sent with an idempotency key
+//!
+//! Callers that lose a response, e.g. to a dropped connection, send the request again with the
+//! same key and receive the response of the first attempt instead of having the completion
+//! generated, and billed, a second time. A retry that arrives while the first attempt is still
+//! being served, including its failover to other nodes, waits for its outcome.
+//!
+//! Keys are scoped to the principal that sent them, so callers cannot read each other's
+//! responses by guessing keys, and a key reused for a different request is rejected rather
+//! than answered with the response of the first one.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::Mutex as AsyncMutex;
+
+use crate::llm::{LlmError, LlmRequest, LlmResponse};
+
+/// The slot of one idempotency key, holding its response once a request succeeded
+///
+/// The slot is locked while a request with its key is served, so a retry waits for the
+/// outcome. A failed request leaves the slot empty and the next retry is served again.
+pub type IdempotencySlot = Arc<AsyncMutex<Option<LlmResponse>>>;
+
+/// An idempotency key with the principal it belongs to, `None` for requests submitted as jobs
+type ScopedKey = (Option<String>, String);
+
+/// A bounded map of idempotency keys to their slots, evicting the oldest key first
+#[derive(Debug, Default)]
+pub struct IdempotencyCache {
+    entries: Mutex<SlotEntries>,
+}
+
+#[derive(Debug, Default)]
+struct SlotEntries {
+    slots: HashMap<ScopedKey, SlotEntry>,
+    insertion_order: VecDeque<ScopedKey>,
+}
+
+#[derive(Debug)]
+struct SlotEntry {
+    /// Fingerprint of the request first sent with the key
+    request_hash: u64,
+    slot: IdempotencySlot,
+}
+
+impl IdempotencyCache {
+    /// Create an empty cache
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// The slot of `key` as sent by `owner` with `request`, created if needed while keeping
+    /// at most `capacity` keys
+    ///
+    /// Fails with [`LlmError::InvalidRequest`] if `owner` sent the key with a different
+    /// request before. An evicted key's slot stays valid for the requests already holding it,
+    /// but later requests with that key are served anew.
+    pub fn slot(
+        &self,
+        owner: Option<&str>,
+        key: &str,
+        request: &LlmRequest,
+        capacity: usize,
+    ) -> Result<IdempotencySlot, LlmError> {
+        let scoped_key = (owner.map(str::to_string), key.to_string());
+        let request_hash = request_hash(request);
+        let mut entries = self.entries.lock().unwrap();
+        if let Some(entry) = entries.slots.get(&scoped_key) {
+            if entry.request_hash != request_hash {
+                return Err(LlmError::InvalidRequest(format!(
+                    "idempotency key {} was already used for a different request",
+                    key
+                )));
+            }
+            return Ok(entry.slot.clone());
+        }
+
+        let slot = IdempotencySlot::default();
+        entries.slots.insert(
+            scoped_key.clone(),
+            SlotEntry {
+                request_hash,
+                slot: slot.clone(),
+            },
+        );
+        entries.insertion_order.push_back(scoped_key);
+        while entries.insertion_order.len() > capacity.max(1) {
+            if let Some(oldest) = entries.insertion_order.pop_front() {
+                entries.slots.remove(&oldest);
+            }
+        }
+        Ok(slot)
+    }
+
+    /// Number of keys with a slot
+    pub fn len(&self) -> usize {
+        self.entries.lock().unwrap().slots.len()
+    }
+
+    /// Whether no key has a slot
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Fingerprint of a request, to tell whether a key is sent again with the same request
+fn request_hash(request: &LlmRequest) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    serde_json::to_value(request)
+        .map(|value| value.to_string())
+        .unwrap_or_default()
+        .hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::TextCompletionRequest;
+
+    fn request(prompt: &str) -> LlmRequest {
+        LlmRequest::TextCompletion(TextCompletionRequest {
+            model: "model".to_string(),
+            prompt: prompt.to_string(),
+            ..Default::default()
+        })
+    }
+
+    /// Store a response in the slot of `key`
+    async fn store(cache: &IdempotencyCache, owner: Option<&str>, key: &str, capacity: usize) {
+        let slot = cache.slot(owner, key, &request("Hello"), capacity).unwrap();
+        *slot.lock().await = Some(LlmResponse::default());
+    }
+
+    /// Whether the slot of `key` holds a response
+    async fn is_stored(cache: &IdempotencyCache, owner: Option<&str>, key: &str) -> bool {
+        let slot = cache.slot(owner, key, &request("Hello"), 4).unwrap();
+        let response = slot.lock().await;
+        response.is_some()
+    }
+
+    #[tokio::test]
+    async fn test_same_key_shares_its_slot() {
+        let cache = IdempotencyCache::new();
+        store(&cache, None, "a", 4).await;
+
+        assert!(is_stored(&cache, None, "a").await);
+        assert!(!is_stored(&cache, None, "b").await);
+        assert_eq!(cache.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_keys_are_scoped_to_their_owner() {
+        let cache = IdempotencyCache::new();
+        store(&cache, Some("alice"), "a", 4).await;
+
+        assert!(is_stored(&cache, Some("alice"), "a").await);
+        assert!(!is_stored(&cache, Some("bob"), "a").await);
+        assert!(!is_stored(&cache, None, "a").await);
+        assert_eq!(cache.len(), 3);
+    }
+
+    #[test]
+    fn test_key_reused_for_another_request_is_rejected() {
+        let cache = IdempotencyCache::new();
+        cache.slot(None, "a", &request("Hello"), 2).unwrap();
+
+        let error = cache.slot(None, "a", &request("Goodbye"), 2).unwrap_err();
+
+        assert!(matches!(error, LlmError::InvalidRequest(_)));
+    }
+
+    #[tokio::test]
+    async fn test_oldest_key_is_evicted() {
+        let cache = IdempotencyCache::new();
+        for key in ["a", "b", "c"] {
+            store(&cache, None, key, 2).await;
+        }
+
+        assert_eq!(cache.len(), 2);
+        assert!(is_stored(&cache, None, "c").await);
+        assert!(!is_stored(&cache, None, "a").await);
+    }
+}
@@ -1,9 +1,29 @@
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
 use blueprint_sdk::extract::Context;
-use blueprint_sdk::tangle::extract::{TangleArg, TangleResult};
-use tracing::{debug, info, warn};
+use blueprint_sdk::tangle::extract::{CallId, TangleArg, TangleResult};
+use futures::StreamExt;
+use tracing::{debug, error, info, info_span, warn, Instrument};
 
+use crate::auth::Principal;
+#[cfg(feature = "response-cache")]
+use crate::cache::ResponseCache;
 use crate::context::OpenRouterContext;
-use crate::llm::{LlmClientExt, LlmRequest, LlmResponse};
+use crate::correlation::{correlation_id_for_call, with_correlation_id};
+use crate::llm::{
+    chat_chunk_from_response, fake_stream_from_response, BatchItemResult, ChatCompletionRequest,
+    ChatCompletionStream, ExtraChoicesPolicy, LlmClient, LlmClientExt, LlmError, LlmRequest,
+    LlmResponse, Pricing,
+};
+use crate::load_balancer::{split_provider_suffix, InFlightRequest};
+use crate::moderation::ModerationResult;
+use crate::queue::QueueSlot;
+use crate::request_log::RequestLogger;
+use crate::stream_results::{
+    text_chunk_from_response, StreamChunk, StreamPage, StreamPoll, StreamRegistry,
+};
 
 /// Job ID for processing LLM requests
 pub const PROCESS_LLM_REQUEST_JOB_ID: u8 = 0;
@@ -11,55 +31,1062 @@ pub const PROCESS_LLM_REQUEST_JOB_ID: u8 = 0;
 /// Job ID for reporting metrics
 pub const REPORT_METRICS_JOB_ID: u8 = 1;
 
+/// Job ID for processing a batch of LLM requests
+pub const PROCESS_LLM_BATCH_JOB_ID: u8 = 2;
+
+/// Job ID for reporting metrics together with the models and capabilities of this node
+pub const REPORT_NODE_JOB_ID: u8 = 3;
+
+/// Job ID for starting a completion streamed through `poll_llm_stream` results
+pub const START_LLM_STREAM_JOB_ID: u8 = 4;
+
+/// Job ID for polling the chunks of a stream started with `start_llm_stream`
+pub const POLL_LLM_STREAM_JOB_ID: u8 = 5;
+
+/// Node id recorded on tracing spans for requests served by the context's default client
+const DEFAULT_CLIENT_NODE_ID: &str = "default";
+
 /// Process an LLM request
 ///
 /// This job handler receives an LLM request from Tangle, processes it
 /// through the selected LLM node, and returns the response.
 ///
 /// # ASCII Diagram
-/// ```
+/// ```text
 /// User -> OpenRouter -> Tangle -> Blueprint -> Load Balancer -> LLM Node
 ///                                    |
 ///                                    v
 /// User <- OpenRouter <- Tangle <- Blueprint <- LLM Node
 /// ```
 ///
+/// The Tangle call id becomes the request's correlation id: it is recorded on the
+/// `llm_request` tracing span, sent to the backend in the `X-Correlation-Id` header, and
+/// returned in the response's `correlation_id` field. Node selection and the backend call
+/// run in child `node_selection` and `backend_call` spans carrying the model and node id.
+///
+/// Failed requests always log an error; successful ones log a summary line for the
+/// `api.log_sample_rate` fraction of requests. With `api.request_logging`, every dispatched
+/// request also emits a structured event; see [`crate::request_log`].
+///
+/// With `api.rate_limiting_enabled`, requests beyond the rate limit of their model, from
+/// `api.model_rate_limits` or else `api.max_requests_per_minute`, fail with
+/// [`LlmError::RateLimited`] before they are dispatched.
+///
 /// # Expected Outcome
 /// The request is processed by the selected LLM node and the response is returned to Tangle.
 #[blueprint_sdk::macros::debug_job]
 pub async fn process_llm_request(
     Context(ctx): Context<OpenRouterContext>,
+    CallId(call_id): CallId,
     TangleArg(request): TangleArg<LlmRequest>,
 ) -> Result<TangleResult<LlmResponse>, blueprint_sdk::Error> {
-    info!("Processing LLM request");
+    let correlation_id = correlation_id_for_call(call_id);
+    let span = info_span!("llm_request", call_id, correlation_id = %correlation_id);
+
+    let (log_sample_rate, request_logger) = {
+        let config = ctx.blueprint_config.read().await;
+        (
+            config.api.log_sample_rate,
+            RequestLogger::from_config(&config.api),
+        )
+    };
+    let log_sampler = ctx.log_sampler.clone();
+
+    if let Err(e) = ctx.try_acquire_rate_limit(request.model()).await {
+        span.in_scope(|| warn!("Rejected request: {}", e));
+        return Err(blueprint_sdk::Error::Other(e.to_string()));
+    }
+
+    let request_log = request_logger.map(|logger| logger.start(&request));
+    let result = with_correlation_id(
+        correlation_id.clone(),
+        moderate_and_dispatch(ctx, None, request),
+    )
+    .instrument(span.clone())
+    .await;
+    if let Some(request_log) = request_log {
+        span.in_scope(|| request_log.finish(&result));
+    }
+
+    // Failures always log; successes only for the sampled fraction of requests
+    span.in_scope(|| match &result {
+        Ok(_) => {
+            if log_sampler.sample(log_sample_rate) {
+                info!("LLM request processed successfully");
+            }
+        }
+        Err(e) => error!("LLM request failed: {}", e),
+    });
+    let mut response = result.map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+    response.set_correlation_id(correlation_id);
+
+    Ok(TangleResult(response))
+}
+
+/// Process a batch of LLM requests
+///
+/// Every request is handled like a `process_llm_request` call, concurrently and subject to
+/// the same rate limits and admission queue. The job returns one [`BatchItemResult`] per request, in request
+/// order: a request that fails, e.g. for a model no node serves, carries its error without
+/// failing the rest of the batch.
+///
+/// # Expected Outcome
+/// The outcome of every request is returned to Tangle, even if some of them failed.
+#[blueprint_sdk::macros::debug_job]
+pub async fn process_llm_batch(
+    Context(ctx): Context<OpenRouterContext>,
+    CallId(call_id): CallId,
+    TangleArg(requests): TangleArg<Vec<LlmRequest>>,
+) -> Result<TangleResult<Vec<BatchItemResult>>, blueprint_sdk::Error> {
+    let correlation_id = correlation_id_for_call(call_id);
+    let span = info_span!(
+        "llm_batch",
+        call_id,
+        correlation_id = %correlation_id,
+        size = requests.len()
+    );
+
+    let results = with_correlation_id(correlation_id.clone(), batch_llm_requests(&ctx, requests))
+        .instrument(span.clone())
+        .await;
+
+    let items: Vec<BatchItemResult> = results
+        .into_iter()
+        .enumerate()
+        .map(|(index, result)| {
+            let result = result.map(|mut response| {
+                response.set_correlation_id(correlation_id.clone());
+                response
+            });
+            BatchItemResult::new(index, result)
+        })
+        .collect();
+
+    span.in_scope(|| {
+        for item in &items {
+            if let Some(e) = &item.error {
+                error!("Batched LLM request {} failed: {}", item.index, e);
+            }
+        }
+        info!(
+            "LLM batch processed: {} of {} requests succeeded",
+            items.iter().filter(|item| item.is_ok()).count(),
+            items.len()
+        );
+    });
+
+    Ok(TangleResult(items))
+}
+
+/// Dispatch every request of a batch concurrently, returning each request's outcome in order
+///
+/// Every request counts against the rate limit of its model; one over the limit fails with
+/// [`LlmError::RateLimited`] without failing the rest of the batch.
+pub async fn batch_llm_requests(
+    ctx: &OpenRouterContext,
+    requests: Vec<LlmRequest>,
+) -> Vec<crate::llm::Result<LlmResponse>> {
+    futures::future::join_all(requests.into_iter().map(|request| async move {
+        if let Err(e) = ctx.try_acquire_rate_limit(request.model()).await {
+            warn!("Rejected batched request: {}", e);
+            return Err(e);
+        }
+        moderate_and_dispatch(ctx.clone(), None, request).await
+    }))
+    .await
+}
+
+/// Start streaming a chat or text completion
+///
+/// The job returns the id of the stream right away, and the completion is generated in the
+/// background; see [`crate::stream_results`]. Its chunks are fetched with `poll_llm_stream`
+/// calls and reassembled with a [`StreamReassembler`](crate::stream_results::StreamReassembler).
+/// Nodes that cannot stream deliver their complete response in a few chunks.
+///
+/// Requests are rate limited, moderated, queued and routed like those of
+/// `process_llm_request`, but are served by a single node without failover; responses are
+/// not moderated.
+///
+/// # Expected Outcome
+/// The id of the stream is returned to Tangle, the call's correlation id.
+#[blueprint_sdk::macros::debug_job]
+pub async fn start_llm_stream(
+    Context(ctx): Context<OpenRouterContext>,
+    CallId(call_id): CallId,
+    TangleArg(mut request): TangleArg<LlmRequest>,
+) -> Result<TangleResult<String>, blueprint_sdk::Error> {
+    let stream_id = correlation_id_for_call(call_id);
+    let span = info_span!("llm_stream", call_id, correlation_id = %stream_id);
+
+    if let LlmRequest::Embedding(_) = request {
+        return Err(blueprint_sdk::Error::Other(
+            LlmError::InvalidRequest("embeddings cannot be streamed".to_string()).to_string(),
+        ));
+    }
+    if let Err(e) = ctx.try_acquire_rate_limit(request.model()).await {
+        span.in_scope(|| warn!("Rejected stream: {}", e));
+        return Err(blueprint_sdk::Error::Other(e.to_string()));
+    }
+    let node = select_stream_node(&ctx, &mut request)
+        .instrument(span.clone())
+        .await
+        .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+
+    if !ctx.streams.open(&stream_id) {
+        return Err(blueprint_sdk::Error::Other(
+            LlmError::InvalidRequest(format!("stream {} was started already", stream_id))
+                .to_string(),
+        ));
+    }
+    span.in_scope(|| info!("Started LLM stream"));
+    let id = stream_id.clone();
+    tokio::spawn(
+        with_correlation_id(stream_id.clone(), async move {
+            // The dispatch slot is held until the stream is complete
+            let _slot = ctx.request_queue.acquire().await;
+            let result = produce_stream(&ctx.streams, &id, &node.llm_client, request).await;
+            if let Err(e) = &result {
+                error!("LLM stream failed: {}", e);
+            }
+            record_stream_outcome(&ctx, &node, result.as_ref().err()).await;
+            ctx.streams.finish(&id, result.err().map(|e| e.to_string()));
+        })
+        .instrument(span),
+    );
+
+    Ok(TangleResult(stream_id))
+}
+
+/// Moderate a request to be streamed and select the node streaming it
+///
+/// The request policies, node selection and context checks are those of
+/// `process_llm_request`.
+async fn select_stream_node(
+    ctx: &OpenRouterContext,
+    request: &mut LlmRequest,
+) -> Result<SelectedNode, LlmError> {
+    let moderator = ctx.moderator.read().await.clone();
+    if let Some(moderator) = moderator {
+        if let ModerationResult::Blocked { reason } = moderator.check_request(request).await {
+            warn!("Blocked stream content: {}", reason);
+            return Err(content_blocked());
+        }
+    }
+
+    apply_request_policies(ctx, request).await?;
+    let node = select_node(ctx, request).await?;
+    fit_to_context(ctx, &node.llm_client, request, None).await?;
+    Ok(node)
+}
+
+/// Record how the node streaming a request served it, then update the metrics
+///
+/// Like failed requests of `process_llm_request`, a stream failing with a retryable error
+/// counts towards opening the node's circuit.
+async fn record_stream_outcome(
+    ctx: &OpenRouterContext,
+    node: &SelectedNode,
+    error: Option<&LlmError>,
+) {
+    if !node.default_client {
+        match error {
+            None => ctx.load_balancer.record_node_success(&node.node_id).await,
+            Some(e) if e.is_retryable() => {
+                ctx.load_balancer.record_node_failure(&node.node_id).await;
+            }
+            Some(_) => {}
+        }
+    }
+    ctx.update_metrics().await;
+}
+
+/// Pass the chunks of `stream` through, holding the dispatch slot `slot` until the stream
+/// ends and then recording its outcome
+fn track_stream(
+    ctx: OpenRouterContext,
+    node: SelectedNode,
+    slot: QueueSlot,
+    stream: ChatCompletionStream,
+) -> ChatCompletionStream {
+    let state = Some((stream, ctx, node, slot));
+    Box::pin(futures::stream::unfold(state, |state| async move {
+        let (mut stream, ctx, node, slot) = state?;
+        match stream.next().await {
+            Some(Ok(chunk)) => Some((Ok(chunk), Some((stream, ctx, node, slot)))),
+            Some(Err(e)) => {
+                record_stream_outcome(&ctx, &node, Some(&e)).await;
+                Some((Err(e), None))
+            }
+            None => {
+                record_stream_outcome(&ctx, &node, None).await;
+                None
+            }
+        }
+    }))
+}
+
+/// Generate the completion of `request` with `llm_client` into the stream `stream_id`
+async fn produce_stream(
+    streams: &StreamRegistry,
+    stream_id: &str,
+    llm_client: &Arc<dyn LlmClient>,
+    request: LlmRequest,
+) -> crate::llm::Result<()> {
+    match request {
+        LlmRequest::ChatCompletion(mut request) => {
+            request.stream = Some(true);
+            let mut stream = match llm_client.as_streaming() {
+                Some(streaming_client) => {
+                    streaming_client.streaming_chat_completion(request).await?
+                }
+                None => fake_stream_from_response(llm_client.chat_completion_ext(request).await?),
+            };
+            while let Some(chunk) = stream.next().await {
+                streams.push(stream_id, StreamChunk::Chat(chunk?));
+            }
+        }
+        LlmRequest::TextCompletion(mut request) => {
+            request.stream = Some(true);
+            match llm_client.as_streaming() {
+                Some(streaming_client) => {
+                    let mut stream = streaming_client.streaming_text_completion(request).await?;
+                    while let Some(chunk) = stream.next().await {
+                        streams.push(stream_id, StreamChunk::Text(chunk?));
+                    }
+                }
+                None => {
+                    let response = llm_client.text_completion_ext(request).await?;
+                    let chunk = text_chunk_from_response(response);
+                    streams.push(stream_id, StreamChunk::Text(chunk));
+                }
+            }
+        }
+        LlmRequest::Embedding(_) => {
+            return Err(LlmError::InvalidRequest(
+                "embeddings cannot be streamed".to_string(),
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Poll the chunks of a stream started with `start_llm_stream`
+///
+/// Returns the chunks from `from_sequence` on that have been generated so far, up to
+/// [`MAX_CHUNKS_PER_PAGE`](crate::stream_results::MAX_CHUNKS_PER_PAGE) of them. The page is
+/// `done` once it reaches the end of a finished stream; until then, poll again from its
+/// `next_sequence`. Streams are kept for a while after they finish, so pages can be polled
+/// again.
+///
+/// # Expected Outcome
+/// A page of the stream is returned to Tangle, or an error for an unknown stream.
+#[blueprint_sdk::macros::debug_job]
+pub async fn poll_llm_stream(
+    Context(ctx): Context<OpenRouterContext>,
+    TangleArg(poll): TangleArg<StreamPoll>,
+) -> Result<TangleResult<StreamPage>, blueprint_sdk::Error> {
+    match ctx.streams.page(&poll.stream_id, poll.from_sequence) {
+        Some(page) => Ok(TangleResult(page)),
+        None => Err(blueprint_sdk::Error::Other(
+            LlmError::InvalidRequest(format!("unknown stream {}", poll.stream_id)).to_string(),
+        )),
+    }
+}
+
+/// Dispatch an LLM request on behalf of an authenticated principal
+///
+/// The request is admitted against the principal's limits first: one over its rate or its
+/// monthly token budget fails with [`LlmError::RateLimited`], which API handlers answer with
+/// HTTP 429. The tokens of the response are then counted towards the principal's usage.
+pub async fn process_request_for_principal(
+    ctx: &OpenRouterContext,
+    principal: &Principal,
+    request: LlmRequest,
+) -> Result<LlmResponse, LlmError> {
+    if let Err(e) = ctx.usage.admit(principal) {
+        warn!("Rejected request: {}", e);
+        return Err(e);
+    }
+
+    let response = moderate_and_dispatch(ctx.clone(), Some(&principal.id), request).await?;
+    if let Some(usage) = response.usage() {
+        ctx.usage.record_tokens(principal, usage);
+    }
+    Ok(response)
+}
+
+/// Stream a chat completion on behalf of an authenticated principal
+///
+/// The request is admitted against the principal's limits, queued, moderated and routed like
+/// [`process_request_for_principal`] does, then served by a single node without failover.
+/// A node that cannot stream completes the request first and yields its response as a
+/// single chunk. Responses are not moderated, and since streamed chunks carry no usage,
+/// their tokens are not counted towards the principal's budget.
+pub async fn stream_chat_completion_for_principal(
+    ctx: &OpenRouterContext,
+    principal: &Principal,
+    mut request: ChatCompletionRequest,
+) -> Result<ChatCompletionStream, LlmError> {
+    if let Err(e) = ctx.usage.admit(principal) {
+        warn!("Rejected request: {}", e);
+        return Err(e);
+    }
+
+    // Wait for a dispatch slot before selecting the node, so that the request does not hold
+    // up a drain of the node while it is queued; the slot is held until the stream is complete
+    let slot = ctx.request_queue.acquire().await;
+
+    request.stream = Some(true);
+    let mut llm_request = LlmRequest::ChatCompletion(request);
+    let node = select_stream_node(ctx, &mut llm_request).await?;
+    let LlmRequest::ChatCompletion(request) = llm_request else {
+        unreachable!("selecting the node keeps the kind of the request");
+    };
 
-    // Get the model name from the request
-    let model = match &request {
-        LlmRequest::ChatCompletion(req) => &req.model,
-        LlmRequest::TextCompletion(req) => &req.model,
-        LlmRequest::Embedding(req) => &req.model,
+    let opened = match node.llm_client.as_streaming() {
+        Some(streaming_client) => streaming_client.streaming_chat_completion(request).await,
+        None => node
+            .llm_client
+            .chat_completion_ext(request)
+            .await
+            .map(|response| {
+                let chunk = chat_chunk_from_response(response);
+                Box::pin(futures::stream::iter([Ok(chunk)])) as ChatCompletionStream
+            }),
     };
+    match opened {
+        Ok(stream) => Ok(track_stream(ctx.clone(), node, slot, stream)),
+        Err(e) => {
+            record_stream_outcome(ctx, &node, Some(&e)).await;
+            Err(e)
+        }
+    }
+}
 
-    // Select an LLM client for this model using the load balancer
-    let llm_client = match ctx.get_llm_client_for_model(model).await {
-        Some(client) => client,
+/// Dispatch an LLM request sent by `owner`, applying the content policy to its prompt and,
+/// if configured, to the response
+///
+/// `owner` is the principal of an API request, or `None` for a request submitted as a job.
+async fn moderate_and_dispatch(
+    ctx: OpenRouterContext,
+    owner: Option<&str>,
+    request: LlmRequest,
+) -> Result<LlmResponse, LlmError> {
+    let moderator = ctx.moderator.read().await.clone();
+    let Some(moderator) = moderator else {
+        return dispatch_llm_request(ctx, owner, request).await;
+    };
+    let check_responses = ctx
+        .blueprint_config
+        .read()
+        .await
+        .api
+        .moderation
+        .check_responses;
+
+    if let ModerationResult::Blocked { reason } = moderator.check_request(&request).await {
+        warn!("Blocked request content: {}", reason);
+        return Err(content_blocked());
+    }
+
+    let response = dispatch_llm_request(ctx, owner, request).await?;
+
+    if check_responses {
+        if let ModerationResult::Blocked { reason } = moderator.check_response(&response).await {
+            warn!("Blocked response content: {}", reason);
+            return Err(content_blocked());
+        }
+    }
+    Ok(response)
+}
+
+/// The context length of `model`, as listed by the client serving it or else by the catalog
+/// merged from all active nodes
+async fn context_length_of(
+    ctx: &OpenRouterContext,
+    llm_client: &Arc<dyn LlmClient>,
+    model: &str,
+) -> Option<usize> {
+    let served = llm_client
+        .get_supported_models()
+        .into_iter()
+        .find(|m| m.id == model)
+        .map(|m| m.max_context_length);
+    match served {
+        Some(context_length) => Some(context_length),
+        None => ctx
+            .model_catalog()
+            .await
+            .get(model)
+            .map(|m| m.context_length),
+    }
+}
+
+/// Whether the client serving `model` lists it as a classifier
+fn is_classifier(llm_client: &Arc<dyn LlmClient>, model: &str) -> bool {
+    llm_client
+        .get_supported_models()
+        .iter()
+        .any(|m| m.id == model && m.is_classifier())
+}
+
+/// The pricing of `model` from `llm.model_pricing`, or else as advertised by the client
+/// serving it or by the catalog merged from all active nodes
+///
+/// A model without pricing parameters, or whose pricing parameters are invalid, has no
+/// pricing.
+async fn pricing_of(
+    ctx: &OpenRouterContext,
+    llm_client: &Arc<dyn LlmClient>,
+    model: &str,
+) -> Option<Pricing> {
+    let configured = ctx
+        .blueprint_config
+        .read()
+        .await
+        .llm
+        .model_pricing
+        .get(model)
+        .copied();
+    if configured.is_some() {
+        return configured;
+    }
+
+    let served = llm_client
+        .get_supported_models()
+        .into_iter()
+        .find(|m| m.id == model);
+    match served {
+        Some(model) => model.openrouter_pricing().ok().flatten(),
+        None => ctx.model_catalog().await.get(model).and_then(|m| m.pricing),
+    }
+}
+
+/// The model id `model` stands for in `llm.model_aliases`, if it is an alias
+async fn resolve_model_alias(ctx: &OpenRouterContext, model: &str) -> Option<String> {
+    ctx.blueprint_config
+        .read()
+        .await
+        .llm
+        .model_aliases
+        .get(model)
+        .cloned()
+}
+
+/// The error returned for content rejected by the moderation policy
+///
+/// The reason stays in the logs so callers cannot probe the policy.
+fn content_blocked() -> LlmError {
+    LlmError::InvalidRequest("content blocked by policy".to_string())
+}
+
+/// Dispatch an LLM request, serving each idempotency key of `owner` at most once
+///
+/// A request carrying the key of one that succeeded gets that response back; one whose key
+/// is being served waits for the outcome, and is served itself only if that failed. A key
+/// sent again with a different request is rejected.
+async fn dispatch_llm_request(
+    ctx: OpenRouterContext,
+    owner: Option<&str>,
+    mut request: LlmRequest,
+) -> Result<LlmResponse, LlmError> {
+    let capacity = ctx
+        .blueprint_config
+        .read()
+        .await
+        .llm
+        .idempotency_cache_capacity;
+    let Some(key) = request.take_idempotency_key().filter(|_| capacity > 0) else {
+        return route_llm_request(ctx, request).await;
+    };
+
+    let slot = ctx
+        .idempotency_cache
+        .slot(owner, &key, &request, capacity)
+        .inspect_err(|e| warn!("Rejected request: {}", e))?;
+    let mut served = slot.lock().await;
+    if let Some(response) = served.as_ref() {
+        info!("Returning the stored response for idempotency key {}", key);
+        return Ok(response.clone());
+    }
+
+    let response = route_llm_request(ctx, request).await?;
+    *served = Some(response.clone());
+    Ok(response)
+}
+
+/// Route an LLM request to a node and return its response
+async fn route_llm_request(
+    ctx: OpenRouterContext,
+    mut request: LlmRequest,
+) -> Result<LlmResponse, LlmError> {
+    debug!("Processing LLM request");
+
+    apply_request_policies(&ctx, &mut request).await?;
+
+    // Greedy decoding needs no nucleus sampling, and its output can be reused
+    request.apply_deterministic_sampling();
+    #[cfg(feature = "response-cache")]
+    let cache_key = ResponseCache::key_for(&request);
+    #[cfg(feature = "response-cache")]
+    if let Some(response) = cache_key
+        .as_deref()
+        .and_then(|key| ctx.response_cache.get(key))
+    {
+        debug!("Returning cached response for deterministic request");
+        return Ok(response);
+    }
+
+    let requested_choices = request.requested_choices();
+    let (auto_continue_on_length, max_continuations) = {
+        let config = ctx.blueprint_config.read().await;
+        (
+            config.llm.auto_continue_on_length,
+            config.llm.max_continuations,
+        )
+    };
+    // The request as sent, to build continuations of a truncated completion from
+    let mut continuation_base = auto_continue_on_length.then(|| request.clone());
+
+    // Wait for a dispatch slot; it is held until the backend has answered
+    let _slot = ctx.request_queue.acquire().await;
+
+    // Check if streaming is requested
+    let streaming = request.is_streaming();
+
+    let SelectedNode {
+        mut llm_client,
+        mut node_id,
+        default_client,
+        in_flight: mut _in_flight,
+        requested_model,
+        provider,
+        alias,
+        served_model,
+    } = select_node(&ctx, &mut request).await?;
+    fit_to_context(&ctx, &llm_client, &mut request, continuation_base.as_mut()).await?;
+
+    // Fail over to another node serving the request when a node fails it; every retryable
+    // failure counts towards the failing node's grace count of consecutive failures, after
+    // which it is marked failed. Retries back off exponentially with jitter so that a
+    // recovering backend is not hit by every failed request at once. A node is tried only
+    // once per request, even while it is still within its grace count.
+    let lb_config = ctx.load_balancer.config().await;
+    let (max_retries, backoff) = (lb_config.max_retries, lb_config.backoff);
+    let mut retries = 0;
+    let mut tried_nodes = HashSet::from([node_id.clone()]);
+    let mut response = loop {
+        let backend_span = info_span!(
+            "backend_call",
+            model = %request.model(),
+            node = %node_id,
+            streaming,
+        );
+        let error = match call_backend(&llm_client, request.clone(), streaming)
+            .instrument(backend_span)
+            .await
+        {
+            Ok(response) => {
+                ctx.load_balancer.record_node_success(&node_id).await;
+                break response;
+            }
+            Err(e) => e,
+        };
+        if !error.is_retryable() || default_client {
+            return Err(error);
+        }
+
+        ctx.load_balancer.record_node_failure(&node_id).await;
+        if retries == max_retries {
+            warn!(
+                "Node {} failed the request, giving up after {} retries: {}",
+                node_id, retries, error
+            );
+            return Err(error);
+        }
+        let Some(node) = ctx
+            .load_balancer
+            .node_for_request_excluding(
+                request.model(),
+                &request,
+                provider.as_deref(),
+                &tried_nodes,
+            )
+            .await
+        else {
+            warn!("No other node serves model {}, giving up", request.model());
+            return Err(error);
+        };
+        retries += 1;
+        let delay = backoff.random_delay(retries as u32);
+        warn!(
+            retry = retries,
+            delay_ms = delay.as_millis() as u64,
+            "Node {} failed the request, retrying on node {} in {:?} ({} of {}): {}",
+            node_id,
+            node.id,
+            delay,
+            retries,
+            max_retries,
+            error
+        );
+        // The request no longer holds up a drain of the failed node
+        _in_flight = Some(node.start_request());
+        tried_nodes.insert(node.id.clone());
+        tokio::time::sleep(delay).await;
+        llm_client = node.client;
+        node_id = node.id;
+    };
+
+    // Never hand back a completion without choices; callers index the first one. Classifier
+    // models are exempt when they generated nothing, as they only return usage.
+    let usage_only = response
+        .usage()
+        .is_some_and(|usage| usage.completion_tokens == 0)
+        && is_classifier(&llm_client, request.model());
+    if response.has_empty_choices() && !usage_only {
+        let fallback = ctx
+            .blueprint_config
+            .read()
+            .await
+            .llm
+            .empty_response_fallback
+            .clone();
+        match fallback {
+            Some(content) => {
+                warn!("Backend returned no choices, using the configured fallback content");
+                response.fill_empty_choices(&content);
+            }
+            None => {
+                warn!("Backend returned no choices");
+                return Err(LlmError::RequestFailed(
+                    "empty response from backend".to_string(),
+                ));
+            }
+        }
+    }
+
+    // Non-conforming backends may produce more choices than were asked for
+    if let Some(n) = requested_choices {
+        let policy = ctx.blueprint_config.read().await.llm.extra_choices_policy;
+        if policy == ExtraChoicesPolicy::Truncate {
+            let dropped = response.truncate_choices(n);
+            if dropped > 0 {
+                warn!(
+                    "Backend returned {} more choices than the {} requested, dropping them",
+                    dropped, n
+                );
+            }
+        }
+    }
+
+    // Ask for the rest of a completion that ran into its token limit
+    if let Some(base) = &continuation_base {
+        for continuation in 1..=max_continuations {
+            let Some(next) = response
+                .length_truncated_output()
+                .and_then(|partial| base.continuation(partial))
+            else {
+                break;
+            };
+            debug!(
+                "Completion stopped at its token limit, requesting continuation {} of at most {}",
+                continuation, max_continuations
+            );
+            let next_response = match next {
+                LlmRequest::ChatCompletion(req) => llm_client
+                    .chat_completion_ext(req)
+                    .await
+                    .map(LlmResponse::ChatCompletion),
+                LlmRequest::TextCompletion(req) => llm_client
+                    .text_completion_ext(req)
+                    .await
+                    .map(LlmResponse::TextCompletion),
+                LlmRequest::Embedding(_) => break,
+            }?;
+            response.append_continuation(next_response);
+        }
+        if response.length_truncated_output().is_some() {
+            warn!(
+                "Completion still stopped at its token limit after {} continuations",
+                max_continuations
+            );
+        }
+    }
+
+    // Report the model that generated the response rather than the one requested
+    if let Some(model) = served_model {
+        info!(
+            "Reporting model {} in place of requested model {}",
+            model, requested_model
+        );
+        response.set_model(model);
+    }
+    // With `llm.echo_requested_model`, a request for an alias reports the alias instead
+    if let Some(alias) = alias {
+        if ctx.blueprint_config.read().await.llm.echo_requested_model {
+            response.set_model(alias);
+        }
+    }
+
+    // Price the completion for clients that bill from the response
+    if ctx.blueprint_config.read().await.api.include_cost {
+        match pricing_of(&ctx, &llm_client, request.model()).await {
+            Some(pricing) => response.apply_pricing(&pricing),
+            None => debug!("Model {} has no valid pricing", request.model()),
+        }
+    }
+
+    #[cfg(feature = "response-cache")]
+    if let Some(key) = cache_key {
+        let capacity = ctx
+            .blueprint_config
+            .read()
+            .await
+            .llm
+            .response_cache_capacity;
+        ctx.response_cache.insert(key, response.clone(), capacity);
+    }
+
+    // Update metrics after processing the request
+    ctx.update_metrics().await;
+
+    Ok(response)
+}
+
+/// Apply the operator's request policies to `request`
+///
+/// The default system prompt is applied and system messages are normalized for backends
+/// that only accept one, then the conversation length and the number of choices are checked.
+async fn apply_request_policies(
+    ctx: &OpenRouterContext,
+    request: &mut LlmRequest,
+) -> Result<(), LlmError> {
+    let (
+        default_system_prompt,
+        override_system_prompt,
+        system_prompt_policy,
+        max_messages,
+        truncate_overflow,
+    ) = {
+        let config = ctx.blueprint_config.read().await;
+        (
+            config.llm.default_system_prompt.clone(),
+            config.llm.override_system_prompt,
+            config.llm.system_prompt_policy,
+            config.llm.max_messages_per_request,
+            config.llm.truncate_overflow,
+        )
+    };
+    if let LlmRequest::ChatCompletion(req) = request {
+        if let Some(prompt) = &default_system_prompt {
+            req.apply_default_system_prompt(prompt, override_system_prompt);
+        }
+        req.apply_system_prompt_policy(system_prompt_policy);
+        if let Some(max_messages) = max_messages {
+            req.enforce_message_limit(max_messages, truncate_overflow)?;
+        }
+    }
+
+    request.check_choice_count()
+}
+
+/// The node selected to serve a request
+struct SelectedNode {
+    llm_client: Arc<dyn LlmClient>,
+    /// Id of the node, or [`DEFAULT_CLIENT_NODE_ID`] for the default client
+    node_id: String,
+    /// Whether no node serves the request; the default client is no node, so there is no
+    /// failure to record or node to fail over from
+    default_client: bool,
+    /// Counts the request in flight on the node until it is served, for drains to wait on
+    in_flight: Option<InFlightRequest>,
+    /// The model requested, without its provider suffix and with its alias resolved
+    requested_model: String,
+    /// The provider a `model@provider` id pins the request to
+    provider: Option<String>,
+    /// The alias the request was sent for
+    alias: Option<String>,
+    /// The fallback model serving the request in place of the requested one
+    served_model: Option<String>,
+}
+
+/// Select the node serving `request`, setting the model it is sent to the backend for
+///
+/// The load balancer picks the node, walking the configured fallback models when no node
+/// serves the requested one for its operation, so that e.g. embeddings never reach a
+/// chat-only node. Streaming requests prefer streaming-capable nodes. A `model@provider` id
+/// pins the request to the nodes of that provider; the backend is sent the bare model id.
+/// Without a node, the request falls back to the default client unless it is pinned or
+/// `llm.strict_model_catalog` is set.
+async fn select_node(
+    ctx: &OpenRouterContext,
+    request: &mut LlmRequest,
+) -> Result<SelectedNode, LlmError> {
+    let (mut requested_model, provider) = match split_provider_suffix(request.model()) {
+        (model, Some(provider)) => (model.to_string(), Some(provider.to_string())),
+        (model, None) => (model.to_string(), None),
+    };
+    if let Some(provider) = &provider {
+        debug!(
+            "Request for model {} is pinned to provider {}",
+            requested_model, provider
+        );
+        request.set_model(requested_model.clone());
+    }
+    // Nodes are selected by the id of the model an alias stands for
+    let alias = match resolve_model_alias(ctx, &requested_model).await {
+        Some(model) => {
+            debug!("Resolved model alias {} to {}", requested_model, model);
+            request.set_model(model.clone());
+            Some(std::mem::replace(&mut requested_model, model))
+        }
+        None => None,
+    };
+    let fallback_models = ctx
+        .blueprint_config
+        .read()
+        .await
+        .llm
+        .fallback_models
+        .clone();
+    let selection_span = info_span!(
+        "node_selection",
+        model = %requested_model,
+        operation = ?request.operation(),
+        node = tracing::field::Empty,
+    );
+    let selected = async {
+        for model in std::iter::once(&requested_model).chain(fallback_models.iter()) {
+            let node = ctx
+                .load_balancer
+                .node_for_request(model, request, provider.as_deref())
+                .await;
+            if let Some(node) = node {
+                tracing::Span::current().record("node", node.id.as_str());
+                return Some((node, model.clone()));
+            }
+        }
+        None
+    }
+    .instrument(selection_span)
+    .await;
+
+    // The default client belongs to no provider, so a pinned request cannot fall back to it
+    if let (None, Some(provider)) = (&selected, &provider) {
+        warn!(
+            "No {} node serves model {}, rejecting the pinned request",
+            provider, requested_model
+        );
+        return Err(LlmError::ModelNotSupported(format!(
+            "{}@{}",
+            requested_model, provider
+        )));
+    }
+
+    let mut served_model = None;
+    let default_client = selected.is_none();
+    let in_flight = selected.as_ref().map(|(node, _)| node.start_request());
+    let (llm_client, node_id) = match selected {
+        Some((node, model)) => {
+            if model != requested_model {
+                debug!(
+                    "No LLM node serves model {}, using fallback model {}",
+                    requested_model, model
+                );
+                request.set_model(model.clone());
+                served_model = Some(model);
+            }
+            (node.client, node.id)
+        }
+        None if ctx.blueprint_config.read().await.llm.strict_model_catalog => {
+            warn!(
+                "Rejecting request for model {}, which is not in the model catalog",
+                requested_model
+            );
+            return Err(LlmError::ModelNotSupported(requested_model));
+        }
         None => {
             // Fall back to the default client if no suitable node is found
             warn!(
                 "No suitable LLM node found for model {}, using default client",
-                model
+                requested_model
             );
-            ctx.llm_client.clone()
+            (ctx.llm_client.clone(), DEFAULT_CLIENT_NODE_ID.to_string())
         }
     };
 
-    // Check if streaming is requested
-    let streaming = match &request {
-        LlmRequest::ChatCompletion(req) => req.stream.unwrap_or(false),
-        LlmRequest::TextCompletion(req) => req.stream.unwrap_or(false),
-        LlmRequest::Embedding(_) => false,
+    Ok(SelectedNode {
+        llm_client,
+        node_id,
+        default_client,
+        in_flight,
+        requested_model,
+        provider,
+        alias,
+        served_model,
+    })
+}
+
+/// Trim a prompt that would overflow the context of the model `llm_client` serves rather
+/// than let the backend fail it, and reject one that still does not fit if configured
+///
+/// `continuation_base` is trimmed along with the request.
+async fn fit_to_context(
+    ctx: &OpenRouterContext,
+    llm_client: &Arc<dyn LlmClient>,
+    request: &mut LlmRequest,
+    continuation_base: Option<&mut LlmRequest>,
+) -> Result<(), LlmError> {
+    let (auto_truncate, reject_context_overflow) = {
+        let config = ctx.blueprint_config.read().await;
+        (config.llm.auto_truncate, config.llm.reject_context_overflow)
     };
+    if !auto_truncate && !reject_context_overflow {
+        return Ok(());
+    }
+    let Some(context_length) = context_length_of(ctx, llm_client, request.model()).await else {
+        return Ok(());
+    };
+
+    if auto_truncate {
+        let dropped = request.truncate_to_context(context_length);
+        if dropped > 0 {
+            info!(
+                "Dropped about {} prompt tokens to fit the {}-token context of {}",
+                dropped,
+                context_length,
+                request.model()
+            );
+            if let Some(base) = continuation_base {
+                base.truncate_to_context(context_length);
+            }
+        }
+    }
+    let needed = ctx.token_counter.read().await.count_context(request);
+    if reject_context_overflow && needed > context_length {
+        warn!(
+            "Rejecting request needing about {} tokens for the {}-token context of {}",
+            needed,
+            context_length,
+            request.model()
+        );
+        return Err(LlmError::InvalidRequest(format!(
+            "the request needs about {} tokens, more than the {}-token context of model {}",
+            needed,
+            context_length,
+            request.model()
+        )));
+    }
+    Ok(())
+}
 
+/// Send `request` to `llm_client`, collecting a streamed response into a single one
+async fn call_backend(
+    llm_client: &Arc<dyn LlmClient>,
+    request: LlmRequest,
+    streaming: bool,
+) -> crate::llm::Result<LlmResponse> {
     // Process the request based on its type
     let response = if streaming {
         // Handle streaming requests if the client supports it
@@ -73,24 +1100,16 @@ pub async fn process_llm_request(
                 // Try to get a streaming client
                 if let Some(streaming_client) = llm_client.as_streaming() {
                     // Use the streaming client
-                    let stream = streaming_client
-                        .streaming_chat_completion(req)
-                        .await
-                        .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+                    let stream = streaming_client.streaming_chat_completion(req).await?;
 
                     // Collect the stream into a single response
-                    let chat_response = crate::llm::collect_chat_completion_stream(stream)
-                        .await
-                        .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+                    let chat_response = crate::llm::collect_chat_completion_stream(stream).await?;
 
                     LlmResponse::ChatCompletion(chat_response)
                 } else {
                     // Fall back to non-streaming if the client doesn't support streaming
                     warn!("Selected LLM client doesn't support streaming, falling back to non-streaming");
-                    let chat_response = llm_client
-                        .chat_completion_ext(req)
-                        .await
-                        .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+                    let chat_response = llm_client.chat_completion_ext(req).await?;
                     LlmResponse::ChatCompletion(chat_response)
                 }
             }
@@ -103,33 +1122,23 @@ pub async fn process_llm_request(
                 // Try to get a streaming client
                 if let Some(streaming_client) = llm_client.as_streaming() {
                     // Use the streaming client
-                    let stream = streaming_client
-                        .streaming_text_completion(req)
-                        .await
-                        .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+                    let stream = streaming_client.streaming_text_completion(req).await?;
 
                     // Collect the stream into a single response
-                    let text_response = crate::llm::collect_text_completion_stream(stream)
-                        .await
-                        .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+                    let text_response = crate::llm::collect_text_completion_stream(stream).await?;
 
                     LlmResponse::TextCompletion(text_response)
                 } else {
                     // Fall back to non-streaming if the client doesn't support streaming
                     warn!("Selected LLM client doesn't support streaming, falling back to non-streaming");
-                    let text_response = llm_client
-                        .text_completion_ext(req)
-                        .await
-                        .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+                    let text_response = llm_client.text_completion_ext(req).await?;
                     LlmResponse::TextCompletion(text_response)
                 }
             }
             LlmRequest::Embedding(req) => {
                 debug!("Processing embedding request for model: {}", req.model);
-                let embedding_response = llm_client
-                    .embeddings_ext(req)
-                    .await
-                    .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+                let mut embedding_response = llm_client.embeddings_ext(req).await?;
+                embedding_response.sort_and_validate()?;
                 LlmResponse::Embedding(embedding_response)
             }
         }
@@ -141,10 +1150,7 @@ pub async fn process_llm_request(
                     "Processing chat completion request for model: {}",
                     req.model
                 );
-                let chat_response = llm_client
-                    .chat_completion_ext(req)
-                    .await
-                    .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+                let chat_response = llm_client.chat_completion_ext(req).await?;
                 LlmResponse::ChatCompletion(chat_response)
             }
             LlmRequest::TextCompletion(req) => {
@@ -152,28 +1158,18 @@ pub async fn process_llm_request(
                     "Processing text completion request for model: {}",
                     req.model
                 );
-                let text_response = llm_client
-                    .text_completion_ext(req)
-                    .await
-                    .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+                let text_response = llm_client.text_completion_ext(req).await?;
                 LlmResponse::TextCompletion(text_response)
             }
             LlmRequest::Embedding(req) => {
                 debug!("Processing embedding request for model: {}", req.model);
-                let embedding_response = llm_client
-                    .embeddings_ext(req)
-                    .await
-                    .map_err(|e| blueprint_sdk::Error::Other(e.to_string()))?;
+                let mut embedding_response = llm_client.embeddings_ext(req).await?;
+                embedding_response.sort_and_validate()?;
                 LlmResponse::Embedding(embedding_response)
             }
         }
     };
-
-    // Update metrics after processing the request
-    ctx.update_metrics().await;
-
-    info!("LLM request processed successfully");
-    Ok(TangleResult(response))
+    Ok(response)
 }
 
 /// Report metrics for this node
@@ -182,13 +1178,16 @@ pub async fn process_llm_request(
 /// This allows Tangle to make informed load balancing decisions.
 ///
 /// # ASCII Diagram
-/// ```
+/// ```text
 /// Tangle -> Blueprint
 ///             |
 ///             v
 /// Tangle <- Blueprint (metrics)
 /// ```
 ///
+/// Reports are rate limited to one per `api.metrics_interval_seconds`; calls within the
+/// interval return the last reported metrics instead of refreshing them.
+///
 /// # Expected Outcome
 /// The current metrics for this node are reported back to Tangle.
 #[blueprint_sdk::macros::debug_job]
@@ -196,13 +1195,62 @@ pub async fn report_metrics(
     Context(ctx): Context<OpenRouterContext>,
 ) -> Result<TangleResult<crate::llm::NodeMetrics>, blueprint_sdk::Error> {
     info!("Reporting metrics");
+    let metrics = coalesced_metrics(&ctx).await;
+    info!("Metrics reported successfully");
+    Ok(TangleResult(metrics))
+}
+
+/// Report metrics, models and capabilities of this node
+///
+/// This job handler gives Tangle the full picture of this node in one call: the metrics of
+/// [`report_metrics`], the models served by its active LLM nodes and their combined
+/// capabilities, so model-aware routing needs no further requests.
+///
+/// # Expected Outcome
+/// A [`NodeReport`](crate::llm::NodeReport) for this node is reported back to Tangle. Its
+/// metrics are rate limited like those of [`report_metrics`].
+#[blueprint_sdk::macros::debug_job]
+pub async fn report_node(
+    Context(ctx): Context<OpenRouterContext>,
+) -> Result<TangleResult<crate::llm::NodeReport>, blueprint_sdk::Error> {
+    info!("Reporting node");
+    let report = crate::llm::NodeReport {
+        metrics: coalesced_metrics(&ctx).await,
+        models: ctx.served_models().await,
+        capabilities: ctx.capabilities().await,
+        version: env!("CARGO_PKG_VERSION").to_string(),
+    };
+    info!(
+        "Node reported successfully with {} models",
+        report.models.len()
+    );
+    Ok(TangleResult(report))
+}
+
+/// The current metrics of this node, refreshed at most once per `api.metrics_interval_seconds`
+async fn coalesced_metrics(ctx: &OpenRouterContext) -> crate::llm::NodeMetrics {
+    let interval = {
+        let config = ctx.blueprint_config.read().await;
+        Duration::from_secs(config.api.metrics_interval_seconds)
+    };
+
+    // Coalesce reports that arrive within the reporting interval
+    let mut last_report = ctx.last_metrics_report.write().await;
+    if let Some((reported_at, metrics)) = last_report.as_ref() {
+        if reported_at.elapsed() < interval {
+            debug!(
+                "Metrics were reported {:?} ago, reusing the last report",
+                reported_at.elapsed()
+            );
+            return metrics.clone();
+        }
+    }
 
     // Update metrics before reporting
     ctx.update_metrics().await;
 
     // Get the current metrics
     let metrics = ctx.metrics.read().await.clone();
-
-    info!("Metrics reported successfully");
-    Ok(TangleResult(metrics))
+    *last_report = Some((Instant::now(), metrics.clone()));
+    metrics
 }
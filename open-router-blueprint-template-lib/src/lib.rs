@@ -1,48 +1,45 @@
 // Export our modules
+pub mod api;
+pub mod auth;
+#[cfg(feature = "response-cache")]
+pub mod cache;
+pub mod catalog;
 pub mod config;
 pub mod context;
+pub mod correlation;
+pub mod factory;
+pub mod idempotency;
 pub mod jobs;
 pub mod llm;
 pub mod load_balancer;
+pub mod metadata;
+pub mod moderation;
+pub mod queue;
+pub mod rate_limit;
+pub mod request_log;
+pub mod sampling;
+pub mod stream_results;
+#[cfg(feature = "schema")]
+pub mod schemas;
+#[cfg(feature = "otel")]
+pub mod telemetry;
+pub mod tokens;
+pub mod usage;
+pub mod validation;
 
 // Re-export key types and functions
-pub use config::{ApiConfig, BlueprintConfig, ConfigError, LlmConfig, Result as ConfigResult};
+pub use config::{
+    ApiConfig, BlueprintConfig, ConfigError, LlmBackend, LlmConfig, ModerationConfig, NodeConfig,
+    Result as ConfigResult,
+};
 pub use context::OpenRouterContext;
+pub use factory::{build_llm_client, LlmClientFactory};
 pub use jobs::{
-    process_llm_request, report_metrics, PROCESS_LLM_REQUEST_JOB_ID, REPORT_METRICS_JOB_ID,
+    poll_llm_stream, process_llm_batch, process_llm_request, report_metrics, report_node,
+    start_llm_stream, POLL_LLM_STREAM_JOB_ID, PROCESS_LLM_BATCH_JOB_ID, PROCESS_LLM_REQUEST_JOB_ID,
+    REPORT_METRICS_JOB_ID, REPORT_NODE_JOB_ID, START_LLM_STREAM_JOB_ID,
 };
 pub use load_balancer::{LoadBalancer, LoadBalancerConfig, LoadBalancingStrategy};
 
 #[cfg(test)]
-mod tests {
-    use super::*;
-    use crate::config::BlueprintConfig;
-
-    #[test]
-    fn test_default_config() {
-        let config = BlueprintConfig::default();
-        assert_eq!(config.llm.api_url, "http://localhost:8000");
-        assert_eq!(config.llm.timeout_seconds, 60);
-        assert_eq!(config.llm.max_concurrent_requests, 5);
-        assert_eq!(config.load_balancer.max_retries, 3);
-        assert_eq!(config.load_balancer.selection_timeout_ms, 1000);
-        assert_eq!(config.api.host, "0.0.0.0");
-        assert_eq!(config.api.port, 3000);
-        assert_eq!(config.api.max_requests_per_minute, 60);
-    }
-
-    #[test]
-    fn test_config_validation() {
-        let mut config = BlueprintConfig::default();
-        assert!(config.validate().is_ok());
-
-        // Test invalid configuration
-        config.llm.api_url = "".to_string();
-        assert!(config.validate().is_err());
-
-        // Reset and test another invalid configuration
-        config = BlueprintConfig::default();
-        config.llm.timeout_seconds = 0;
-        assert!(config.validate().is_err());
-    }
-}
+mod tests;
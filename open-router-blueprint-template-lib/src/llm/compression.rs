@@ -0,0 +1,209 @@
+//! Gzip compression of backend request and response bodies
+//!
+//! A client with [`BodyCompression`] enabled sends JSON bodies of at least
+//! [`MIN_COMPRESSED_BODY_BYTES`] gzip-encoded and asks for gzip-encoded responses, which
+//! [`read_body`] decodes. Not every backend accepts compressed request bodies, so a body the
+//! backend rejects is resent uncompressed, and request compression stays off for that client
+//! once the uncompressed body is accepted.
+
+use std::io::{Read, Write};
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use flate2::read::GzDecoder;
+use flate2::write::GzEncoder;
+use reqwest::header::{ACCEPT_ENCODING, CONTENT_ENCODING, CONTENT_TYPE};
+use reqwest::{RequestBuilder, Response, StatusCode};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use tracing::warn;
+
+use super::{LlmError, Result};
+
+/// Request bodies smaller than this are sent uncompressed, since gzip would not shrink them
+pub const MIN_COMPRESSED_BODY_BYTES: usize = 1024;
+
+/// Gzip-compress `bytes`
+pub fn gzip(bytes: &[u8]) -> Vec<u8> {
+    let mut encoder = GzEncoder::new(Vec::new(), flate2::Compression::default());
+    // Writing to a Vec cannot fail
+    encoder.write_all(bytes).expect("write to Vec");
+    encoder.finish().expect("write to Vec")
+}
+
+/// Decompress gzip-encoded `bytes`
+pub fn gunzip(bytes: &[u8]) -> Result<Vec<u8>> {
+    let mut decoded = Vec::new();
+    GzDecoder::new(bytes)
+        .read_to_end(&mut decoded)
+        .map_err(|e| LlmError::ResponseParseError(format!("Invalid gzip body: {}", e)))?;
+    Ok(decoded)
+}
+
+/// Compression settings of a client, and whether its backend accepts compressed requests
+#[derive(Debug, Default)]
+pub struct BodyCompression {
+    enabled: bool,
+    compress_requests: AtomicBool,
+}
+
+impl BodyCompression {
+    /// Compression as configured by `llm.enable_compression`
+    pub fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            compress_requests: AtomicBool::new(enabled),
+        }
+    }
+
+    /// Whether compression is configured
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Whether request bodies are still sent compressed
+    pub fn compresses_requests(&self) -> bool {
+        self.compress_requests.load(Ordering::Relaxed)
+    }
+
+    /// Send `body` as JSON with the request built by `request`, whose response is read whole
+    pub async fn send_json<B>(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+        body: &B,
+    ) -> reqwest::Result<Response>
+    where
+        B: Serialize + ?Sized,
+    {
+        self.send(request, body, self.enabled).await
+    }
+
+    /// Send `body` as JSON with the request built by `request`, whose response is streamed
+    ///
+    /// Only the request body is compressed, since streamed chunks are parsed as they arrive.
+    pub async fn send_json_streaming<B>(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+        body: &B,
+    ) -> reqwest::Result<Response>
+    where
+        B: Serialize + ?Sized,
+    {
+        self.send(request, body, false).await
+    }
+
+    async fn send<B>(
+        &self,
+        request: impl Fn() -> RequestBuilder,
+        body: &B,
+        accept_gzip: bool,
+    ) -> reqwest::Result<Response>
+    where
+        B: Serialize + ?Sized,
+    {
+        let request = || {
+            let builder = request().header(CONTENT_TYPE, "application/json");
+            if accept_gzip {
+                builder.header(ACCEPT_ENCODING, "gzip")
+            } else {
+                builder
+            }
+        };
+        let json = match serde_json::to_vec(body) {
+            Ok(json) => json,
+            // Let reqwest report the serialization error
+            Err(_) => return request().json(body).send().await,
+        };
+
+        if !self.compresses_requests() || json.len() < MIN_COMPRESSED_BODY_BYTES {
+            return request().body(json).send().await;
+        }
+
+        let response = request()
+            .header(CONTENT_ENCODING, "gzip")
+            .body(gzip(&json))
+            .send()
+            .await?;
+        if !matches!(
+            response.status(),
+            StatusCode::BAD_REQUEST
+                | StatusCode::UNSUPPORTED_MEDIA_TYPE
+                | StatusCode::UNPROCESSABLE_ENTITY
+        ) {
+            return Ok(response);
+        }
+
+        // The backend may not decode compressed bodies; find out by resending this one plain
+        let retry = request().body(json).send().await?;
+        if retry.status().is_success() {
+            warn!(
+                "Backend rejected a gzip-compressed request body ({}), sending request bodies uncompressed",
+                response.status()
+            );
+            self.compress_requests.store(false, Ordering::Relaxed);
+        }
+        Ok(retry)
+    }
+}
+
+/// Read the body of `response`, decompressing it if the backend gzip-encoded it
+pub async fn read_body(response: Response) -> Result<Vec<u8>> {
+    let gzip_encoded = response
+        .headers()
+        .get(CONTENT_ENCODING)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.trim().eq_ignore_ascii_case("gzip"));
+    let bytes = response
+        .bytes()
+        .await
+        .map_err(|e| LlmError::RequestFailed(format!("Failed to read response body: {}", e)))?;
+
+    if gzip_encoded {
+        gunzip(&bytes)
+    } else {
+        Ok(bytes.to_vec())
+    }
+}
+
+/// Read the body of `response` as text, decompressing it if needed
+pub async fn read_text(response: Response) -> Result<String> {
+    let body = read_body(response).await?;
+    Ok(String::from_utf8_lossy(&body).into_owned())
+}
+
+/// Read the body of `response` as JSON, decompressing it if needed
+pub async fn read_json<T: DeserializeOwned>(response: Response) -> Result<T> {
+    let body = read_body(response).await?;
+    serde_json::from_slice(&body).map_err(|e| LlmError::ResponseParseError(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn response(body: Vec<u8>, content_encoding: Option<&str>) -> Response {
+        let mut builder = hyper::Response::builder().status(200);
+        if let Some(encoding) = content_encoding {
+            builder = builder.header("content-encoding", encoding);
+        }
+        Response::from(builder.body(body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_read_json_decodes_gzip_bodies() {
+        let json = br#"{"answer": 42}"#;
+
+        let plain: serde_json::Value = read_json(response(json.to_vec(), None)).await.unwrap();
+        let compressed: serde_json::Value =
+            read_json(response(gzip(json), Some("gzip"))).await.unwrap();
+
+        assert_eq!(plain["answer"], 42);
+        assert_eq!(compressed, plain);
+    }
+
+    #[tokio::test]
+    async fn test_invalid_gzip_body_is_rejected() {
+        let result = read_body(response(b"not gzip".to_vec(), Some("gzip"))).await;
+
+        assert!(matches!(result, Err(LlmError::ResponseParseError(_))));
+    }
+}
@@ -0,0 +1,96 @@
+use std::future::Future;
+
+use futures::stream::{self, StreamExt, TryStreamExt};
+
+use super::{EmbeddingData, Result};
+
+/// Default number of embedding inputs sent to a backend at the same time
+pub const DEFAULT_EMBEDDING_CONCURRENCY: usize = 4;
+
+/// Embed each input with `embed_one`, running at most `concurrency` calls at a time
+///
+/// Intended for backends that only embed a single input per request. The returned data is
+/// ordered by input index regardless of the order in which the calls complete, and the first
+/// error aborts the remaining calls.
+pub async fn embed_concurrently<F, Fut>(
+    inputs: Vec<String>,
+    concurrency: usize,
+    embed_one: F,
+) -> Result<Vec<EmbeddingData>>
+where
+    F: Fn(String) -> Fut,
+    Fut: Future<Output = Result<Vec<f32>>>,
+{
+    let mut data: Vec<EmbeddingData> = stream::iter(inputs.into_iter().enumerate())
+        .map(|(index, input)| {
+            let embedding = embed_one(input);
+            async move {
+                embedding
+                    .await
+                    .map(|embedding| EmbeddingData { index, embedding })
+            }
+        })
+        .buffer_unordered(concurrency.max(1))
+        .try_collect()
+        .await?;
+
+    data.sort_by_key(|d| d.index);
+    Ok(data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::LlmError;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_embed_concurrently_preserves_order_and_bounds_concurrency() {
+        let in_flight = Arc::new(AtomicUsize::new(0));
+        let max_in_flight = Arc::new(AtomicUsize::new(0));
+        let inputs: Vec<String> = (0..20).map(|i| i.to_string()).collect();
+
+        let data = embed_concurrently(inputs, 3, |input| {
+            let in_flight = in_flight.clone();
+            let max_in_flight = max_in_flight.clone();
+            async move {
+                let current = in_flight.fetch_add(1, Ordering::SeqCst) + 1;
+                max_in_flight.fetch_max(current, Ordering::SeqCst);
+
+                // Finish later inputs first so completion order differs from input order
+                let value: u64 = input.parse().unwrap();
+                tokio::time::sleep(Duration::from_millis(20 - value)).await;
+
+                in_flight.fetch_sub(1, Ordering::SeqCst);
+                Ok(vec![value as f32])
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(data.len(), 20);
+        for (i, d) in data.iter().enumerate() {
+            assert_eq!(d.index, i);
+            assert_eq!(d.embedding, vec![i as f32]);
+        }
+        assert_eq!(max_in_flight.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_embed_concurrently_propagates_errors() {
+        let inputs = vec!["ok".to_string(), "fail".to_string()];
+
+        let result = embed_concurrently(inputs, 2, |input| async move {
+            if input == "fail" {
+                Err(LlmError::RequestFailed("backend down".to_string()))
+            } else {
+                Ok(vec![1.0])
+            }
+        })
+        .await;
+
+        assert!(matches!(result, Err(LlmError::RequestFailed(_))));
+    }
+}
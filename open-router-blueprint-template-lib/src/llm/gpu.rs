@@ -0,0 +1,126 @@
+use std::time::Duration;
+
+use tokio::process::Command;
+use tracing::debug;
+
+/// How often `LocalLlmClient` refreshes its GPU metrics
+pub const GPU_METRICS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Arguments of the `nvidia-smi` query the GPU metrics are read from
+const NVIDIA_SMI_ARGS: [&str; 2] = [
+    "--query-gpu=utilization.gpu,memory.used,memory.total",
+    "--format=csv,noheader,nounits",
+];
+
+/// GPU usage of the node, across all of its GPUs
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct GpuMetrics {
+    /// Average utilization of the GPUs (0.0 - 1.0)
+    pub utilization: f32,
+
+    /// Share of the total GPU memory in use (0.0 - 1.0)
+    pub memory_utilization: f32,
+}
+
+/// Why the GPU metrics could not be read
+#[derive(Debug)]
+pub enum GpuQueryError {
+    /// `nvidia-smi` is not installed, so there is no GPU to report on
+    Unavailable,
+
+    /// `nvidia-smi` failed or printed output that could not be parsed
+    Failed(String),
+}
+
+/// Parse the output of `nvidia-smi` queried for `utilization.gpu,memory.used,memory.total`
+/// in `csv,noheader,nounits` format, one line per GPU
+///
+/// Lines that cannot be parsed are skipped. Returns `None` if no line could be.
+pub fn parse_nvidia_smi(output: &str) -> Option<GpuMetrics> {
+    let mut gpus = 0u32;
+    let mut utilization = 0.0f32;
+    let mut memory_used = 0.0f32;
+    let mut memory_total = 0.0f32;
+    for line in output.lines() {
+        let fields: Vec<_> = line.split(',').map(str::trim).collect();
+        let [gpu, used, total] = fields[..] else {
+            continue;
+        };
+        let (Ok(gpu), Ok(used), Ok(total)) =
+            (gpu.parse::<f32>(), used.parse::<f32>(), total.parse::<f32>())
+        else {
+            continue;
+        };
+        gpus += 1;
+        utilization += gpu;
+        memory_used += used;
+        memory_total += total;
+    }
+
+    if gpus == 0 {
+        return None;
+    }
+    let memory_utilization = if memory_total > 0.0 {
+        memory_used / memory_total
+    } else {
+        0.0
+    };
+    Some(GpuMetrics {
+        utilization: (utilization / gpus as f32 / 100.0).clamp(0.0, 1.0),
+        memory_utilization: memory_utilization.clamp(0.0, 1.0),
+    })
+}
+
+/// Read the current GPU metrics with `nvidia-smi`
+pub async fn query_gpu_metrics() -> Result<GpuMetrics, GpuQueryError> {
+    let output = Command::new("nvidia-smi")
+        .args(NVIDIA_SMI_ARGS)
+        .output()
+        .await
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::NotFound => GpuQueryError::Unavailable,
+            _ => GpuQueryError::Failed(format!("failed to run nvidia-smi: {}", e)),
+        })?;
+
+    if !output.status.success() {
+        return Err(GpuQueryError::Failed(format!(
+            "nvidia-smi exited with {}: {}",
+            output.status,
+            String::from_utf8_lossy(&output.stderr).trim()
+        )));
+    }
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    debug!("nvidia-smi reported: {}", stdout.trim());
+    parse_nvidia_smi(&stdout).ok_or_else(|| {
+        GpuQueryError::Failed(format!("unexpected nvidia-smi output: {}", stdout.trim()))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_nvidia_smi_output() {
+        let metrics = parse_nvidia_smi("45, 20480, 81920\n").unwrap();
+        assert!((metrics.utilization - 0.45).abs() < 1e-6);
+        assert!((metrics.memory_utilization - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_parse_nvidia_smi_output_of_several_gpus() {
+        let output = "100, 40960, 81920\n20, 0, 81920\n[N/A], 1, 2\n";
+        let metrics = parse_nvidia_smi(output).unwrap();
+        assert!((metrics.utilization - 0.6).abs() < 1e-6);
+        assert!((metrics.memory_utilization - 0.25).abs() < 1e-6);
+    }
+
+    #[test]
+    fn test_parse_nvidia_smi_rejects_unexpected_output() {
+        assert_eq!(parse_nvidia_smi(""), None);
+        assert_eq!(
+            parse_nvidia_smi("NVIDIA-SMI has failed because it couldn't communicate"),
+            None
+        );
+    }
+}
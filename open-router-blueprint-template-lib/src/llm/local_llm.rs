@@ -1,17 +1,52 @@
 use std::collections::HashMap;
-use std::sync::Arc;
-use std::time::SystemTime;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant, SystemTime};
 
 use async_trait::async_trait;
+use serde::de::DeserializeOwned;
 use serde::{Deserialize, Serialize};
-use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 
+use crate::config::LOCAL_NODE_PROVIDER;
+use crate::correlation::apply_correlation_header;
+
+use super::compression::{read_json, read_text};
 use super::{
-    ChatCompletionRequest, ChatCompletionResponse, EmbeddingRequest, EmbeddingResponse,
-    LlmCapabilities, LlmClient, LlmError, ModelInfo, NodeMetrics, Result, TextCompletionRequest,
-    TextCompletionResponse,
+    with_request_timeout, ChatCompletionChoice, ChatCompletionRequest, ChatCompletionResponse,
+    ChatMessage, EmbeddingData, EmbeddingRequest, EmbeddingResponse, LlmCapabilities, LlmClient,
+    LlmError, MetricsWindow, ModelInfo, NodeInfo, NodeMetrics, Result, SystemMetricsSampler,
+    TextCompletionChoice, TextCompletionRequest, TextCompletionResponse, UsageInfo,
 };
 
+/// Current Unix timestamp in seconds
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Number of dimensions of the placeholder embeddings produced in echo mode
+const ECHO_EMBEDDING_DIMENSIONS: usize = 8;
+
+/// How a `LocalLlmClient` answers requests
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LocalReplyMode {
+    /// Echo the last user message (or the prompt) back as the assistant response
+    #[default]
+    Echo,
+
+    /// Always answer with the given canned reply
+    Canned(String),
+
+    /// Return `LlmError::NotImplemented`, forcing a concrete blueprint to provide the logic
+    Unimplemented,
+
+    /// Forward requests to the OpenAI-compatible backend at `api_url`
+    Backend,
+}
+
 /// Configuration for a local LLM client
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LocalLlmConfig {
@@ -29,6 +64,10 @@ pub struct LocalLlmConfig {
 
     /// Additional configuration parameters
     pub additional_params: HashMap<String, String>,
+
+    /// How requests are answered by the template client
+    #[serde(default)]
+    pub reply_mode: LocalReplyMode,
 }
 
 impl Default for LocalLlmConfig {
@@ -39,6 +78,7 @@ impl Default for LocalLlmConfig {
             max_concurrent_requests: 1,
             models: Vec::new(),
             additional_params: HashMap::new(),
+            reply_mode: LocalReplyMode::default(),
         }
     }
 }
@@ -47,60 +87,198 @@ impl Default for LocalLlmConfig {
 ///
 /// This struct provides the structure and extension points for interacting with any local LLM.
 /// To implement a specific LLM, derive from this template and override the LLM call logic.
+/// With `LocalReplyMode::Backend` it serves requests from any OpenAI-compatible backend as is.
 pub struct LocalLlmClient {
     pub config: LocalLlmConfig,
     pub metrics: Arc<RwLock<NodeMetrics>>,
+    pub http_client: reqwest::Client,
+    /// Completed requests, counted into `requests_per_minute`
+    requests: RwLock<MetricsWindow>,
 }
 
 impl LocalLlmClient {
     /// Create a new local LLM client with the given configuration
     pub fn new(config: LocalLlmConfig) -> Self {
         let metrics = Arc::new(RwLock::new(NodeMetrics {
-            cpu_utilization: 0.0,
-            memory_utilization: 0.0,
-            gpu_utilization: None,
-            requests_per_minute: 0,
-            average_response_time_ms: 0,
-            active_requests: 0,
-            last_updated: SystemTime::now()
-                .duration_since(SystemTime::UNIX_EPOCH)
-                .unwrap_or_default()
-                .as_secs(),
+            last_updated: unix_now(),
+            ..Default::default()
         }));
 
-        Self { config, metrics }
+        Self {
+            config,
+            metrics,
+            http_client: reqwest::Client::new(),
+            requests: RwLock::new(MetricsWindow::new()),
+        }
+    }
+
+    /// Set how this client answers requests
+    pub fn with_reply_mode(mut self, reply_mode: LocalReplyMode) -> Self {
+        self.config.reply_mode = reply_mode;
+        self
+    }
+
+    /// Use a preconfigured HTTP client for `LocalReplyMode::Backend`, e.g. one built from
+    /// `LlmConfig::http_client_builder` to enable HTTP/2 and keep-alive pings.
+    pub fn with_http_client(mut self, http_client: reqwest::Client) -> Self {
+        self.http_client = http_client;
+        self
+    }
+
+    /// Build the reply text for the given input according to the configured reply mode
+    fn reply_for(&self, input: &str, operation: &str) -> Result<String> {
+        match &self.config.reply_mode {
+            LocalReplyMode::Echo => Ok(input.to_string()),
+            LocalReplyMode::Canned(reply) => Ok(reply.clone()),
+            LocalReplyMode::Unimplemented => Err(LlmError::NotImplemented(format!(
+                "{} must be implemented in your blueprint (see LocalLlmClient in template)",
+                operation
+            ))),
+            // Backend replies are forwarded as they are, never built here
+            LocalReplyMode::Backend => Err(LlmError::Internal(format!(
+                "{} replies come from the backend",
+                operation
+            ))),
+        }
     }
 
     /// Update the metrics for this client
-    pub async fn update_metrics(&self, cpu: f32, memory: f32, gpu: Option<f32>) {
-        let mut metrics = self.metrics.write().await;
+    pub fn update_metrics(&self, cpu: f32, memory: f32, gpu: Option<f32>) {
+        let mut metrics = self.metrics.write().unwrap();
         metrics.cpu_utilization = cpu;
         metrics.memory_utilization = memory;
         metrics.gpu_utilization = gpu;
-        metrics.last_updated = SystemTime::now()
-            .duration_since(SystemTime::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs();
-    }
-
-    // async fn record_request_start(&self) {
-    //     let mut metrics = self.metrics.write().await;
-    //     metrics.active_requests += 1;
-    // }
-
-    // async fn record_request_end(&self, duration_ms: u64) {
-    //     let mut metrics = self.metrics.write().await;
-    //     metrics.active_requests = metrics.active_requests.saturating_sub(1);
-
-    //     // Update average response time with exponential moving average
-    //     const ALPHA: f64 = 0.1; // Weight for new samples
-    //     let old_avg = metrics.average_response_time_ms as f64;
-    //     let new_avg = old_avg * (1.0 - ALPHA) + (duration_ms as f64) * ALPHA;
-    //     metrics.average_response_time_ms = new_avg as u64;
-
-    //     // Increment requests per minute (this is simplified and should be improved)
-    //     metrics.requests_per_minute += 1;
-    // }
+        metrics.last_updated = unix_now();
+    }
+
+    /// Sample the CPU and memory usage every `interval` in a background task, updating
+    /// `cpu_utilization` and `memory_utilization`
+    ///
+    /// The usage is that of the whole host, not just the LLM backend process. The task stops
+    /// once the client is dropped, or when the returned handle is aborted. Must be called
+    /// within a Tokio runtime.
+    pub fn spawn_metrics_collector(&self, interval: Duration) -> JoinHandle<()> {
+        let metrics = Arc::downgrade(&self.metrics);
+        tokio::spawn(async move {
+            let mut sampler = SystemMetricsSampler::new();
+            tokio::time::sleep(SystemMetricsSampler::warm_up()).await;
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let (cpu, memory) = sampler.sample();
+                let Some(metrics) = metrics.upgrade() else {
+                    break;
+                };
+                let mut metrics = metrics.write().unwrap();
+                metrics.cpu_utilization = cpu;
+                metrics.memory_utilization = memory;
+                metrics.last_updated = unix_now();
+            }
+        })
+    }
+
+    /// Refresh the GPU metrics from `nvidia-smi` every `interval` in a background task
+    ///
+    /// Leaves `gpu_utilization` and `gpu_memory_utilization` `None` on nodes without
+    /// `nvidia-smi`, and stops once the client is dropped. Does nothing outside a Tokio runtime.
+    #[cfg(feature = "gpu")]
+    pub fn start_gpu_metrics(&self, interval: Duration) {
+        use super::{query_gpu_metrics, GpuQueryError};
+
+        let Ok(runtime) = tokio::runtime::Handle::try_current() else {
+            tracing::warn!("Not refreshing GPU metrics outside a Tokio runtime");
+            return;
+        };
+        let metrics = Arc::downgrade(&self.metrics);
+        runtime.spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                let gpu = match query_gpu_metrics().await {
+                    Ok(gpu) => Some(gpu),
+                    Err(GpuQueryError::Unavailable) => {
+                        tracing::info!("nvidia-smi not found, not reporting GPU metrics");
+                        break;
+                    }
+                    Err(GpuQueryError::Failed(e)) => {
+                        tracing::warn!("Failed to read GPU metrics: {}", e);
+                        None
+                    }
+                };
+                let Some(metrics) = metrics.upgrade() else {
+                    break;
+                };
+                let mut metrics = metrics.write().unwrap();
+                metrics.gpu_utilization = gpu.map(|gpu| gpu.utilization);
+                metrics.gpu_memory_utilization = gpu.map(|gpu| gpu.memory_utilization);
+            }
+        });
+    }
+
+    fn record_request_start(&self) {
+        let mut metrics = self.metrics.write().unwrap();
+        metrics.active_requests += 1;
+    }
+
+    fn record_request_end(&self, duration_ms: u64) {
+        let mut metrics = self.metrics.write().unwrap();
+        metrics.active_requests = metrics.active_requests.saturating_sub(1);
+
+        // Update average response time with exponential moving average
+        const ALPHA: f64 = 0.1; // Weight for new samples
+        let old_avg = metrics.average_response_time_ms as f64;
+        let new_avg = old_avg * (1.0 - ALPHA) + (duration_ms as f64) * ALPHA;
+        metrics.average_response_time_ms = new_avg as u64;
+
+        let mut requests = self.requests.write().unwrap();
+        requests.record();
+        metrics.requests_per_minute = requests.count();
+    }
+
+    /// Send `request` to `path` of the backend and parse its response
+    async fn forward<R: Serialize, T: DeserializeOwned>(
+        &self,
+        path: &str,
+        request: &R,
+    ) -> Result<T> {
+        let body = backend_body(request)?;
+        let url = format!("{}{}", self.config.api_url.trim_end_matches('/'), path);
+
+        let timeout = Duration::from_secs(self.config.timeout_seconds);
+
+        self.record_request_start();
+        let started = Instant::now();
+        let result = with_request_timeout(timeout, self.post_json(&url, &body)).await;
+        self.record_request_end(started.elapsed().as_millis() as u64);
+        result
+    }
+
+    async fn post_json<T: DeserializeOwned>(
+        &self,
+        url: &str,
+        body: &serde_json::Value,
+    ) -> Result<T> {
+        let response = apply_correlation_header(self.http_client.post(url))
+            .json(body)
+            .send()
+            .await
+            .map_err(|e| {
+                LlmError::RequestFailed(format!("Failed to send request to {}: {}", url, e))
+            })?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let body = read_text(response).await.unwrap_or_default();
+            return Err(LlmError::RequestFailed(format!(
+                "Backend returned {}: {}",
+                status,
+                body.trim()
+            )));
+        }
+        read_json(response).await
+    }
 }
 
 #[async_trait]
@@ -119,10 +297,22 @@ impl LlmClient for LocalLlmClient {
     }
 
     fn get_metrics(&self) -> NodeMetrics {
-        futures::executor::block_on(async { self.metrics.read().await.clone() })
+        let mut metrics = self.metrics.read().unwrap().clone();
+        // Let the rate decay while no requests complete
+        metrics.requests_per_minute = self.requests.read().unwrap().count();
+        metrics
+    }
+
+    fn get_node_info(&self) -> NodeInfo {
+        NodeInfo {
+            backend: LOCAL_NODE_PROVIDER.to_string(),
+            version: None,
+        }
     }
 
     /// Template method for chat completion. To use, override this method in your concrete blueprint.
+    ///
+    /// Until then the reply is produced according to the configured `LocalReplyMode`.
     async fn chat_completion(
         &self,
         request: ChatCompletionRequest,
@@ -130,13 +320,50 @@ impl LlmClient for LocalLlmClient {
         if !self.config.models.iter().any(|m| m.id == request.model) {
             return Err(LlmError::ModelNotSupported(request.model));
         }
-        // This is a template method. Implement your LLM call logic in your derived blueprint.
-        Err(LlmError::NotImplemented(
-            "chat_completion must be implemented in your blueprint (see LocalLlmClient in template)".to_string(),
-        ))
+
+        if self.config.reply_mode == LocalReplyMode::Backend {
+            return self.forward("/v1/chat/completions", &request).await;
+        }
+
+        let last_user_message = request
+            .messages
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .map(|m| m.content.as_str())
+            .unwrap_or_default();
+        let content = self.reply_for(last_user_message, "chat_completion")?;
+        let prompt_text = request
+            .messages
+            .iter()
+            .map(|m| m.content.as_str())
+            .collect::<Vec<_>>()
+            .join(" ");
+
+        Ok(ChatCompletionResponse {
+            id: format!("chatcmpl-{}", uuid::Uuid::new_v4()),
+            object: "chat.completion".to_string(),
+            created: unix_timestamp(),
+            usage: Some(approximate_usage(&prompt_text, &content)),
+            cost: None,
+            model: request.model,
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                message: ChatMessage {
+                    role: "assistant".to_string(),
+                    content,
+                    ..Default::default()
+                },
+                finish_reason: Some("stop".to_string()),
+                ..Default::default()
+            }],
+            correlation_id: None,
+        })
     }
 
     /// Template method for text completion. To use, override this method in your concrete blueprint.
+    ///
+    /// Until then the reply is produced according to the configured `LocalReplyMode`.
     async fn text_completion(
         &self,
         request: TextCompletionRequest,
@@ -144,19 +371,134 @@ impl LlmClient for LocalLlmClient {
         if !self.config.models.iter().any(|m| m.id == request.model) {
             return Err(LlmError::ModelNotSupported(request.model));
         }
-        Err(LlmError::NotImplemented(
-            "text_completion must be implemented in your blueprint (see LocalLlmClient in template)".to_string(),
-        ))
+
+        if self.config.reply_mode == LocalReplyMode::Backend {
+            return self.forward("/v1/completions", &request).await;
+        }
+
+        let text = self.reply_for(&request.prompt, "text_completion")?;
+
+        Ok(TextCompletionResponse {
+            id: format!("cmpl-{}", uuid::Uuid::new_v4()),
+            object: "text_completion".to_string(),
+            created: unix_timestamp(),
+            usage: Some(approximate_usage(&request.prompt, &text)),
+            cost: None,
+            model: request.model,
+            choices: vec![TextCompletionChoice {
+                index: 0,
+                text,
+                finish_reason: Some("stop".to_string()),
+                ..Default::default()
+            }],
+            correlation_id: None,
+        })
     }
 
     /// Template method for embeddings. To use, override this method in your concrete blueprint.
+    ///
+    /// Outside of `LocalReplyMode::Unimplemented` this returns small deterministic placeholder
+    /// vectors derived from the input bytes, which is enough to exercise the request path.
     async fn embeddings(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
         if !self.config.models.iter().any(|m| m.id == request.model) {
             return Err(LlmError::ModelNotSupported(request.model));
         }
-        Err(LlmError::NotImplemented(
-            "embeddings must be implemented in your blueprint (see LocalLlmClient in template)"
-                .to_string(),
-        ))
+
+        if self.config.reply_mode == LocalReplyMode::Backend {
+            let mut response: EmbeddingResponse = self.forward("/v1/embeddings", &request).await?;
+            response.sort_and_validate()?;
+            return Ok(response);
+        }
+
+        if self.config.reply_mode == LocalReplyMode::Unimplemented {
+            return Err(LlmError::NotImplemented(
+                "embeddings must be implemented in your blueprint (see LocalLlmClient in template)"
+                    .to_string(),
+            ));
+        }
+
+        let data = request
+            .input
+            .iter()
+            .enumerate()
+            .map(|(index, input)| EmbeddingData {
+                index,
+                embedding: placeholder_embedding(input),
+            })
+            .collect();
+        let prompt_tokens = request
+            .input
+            .iter()
+            .map(|input| approximate_token_count(input))
+            .sum();
+
+        Ok(EmbeddingResponse {
+            object: "list".to_string(),
+            model: request.model,
+            data,
+            usage: Some(UsageInfo {
+                prompt_tokens,
+                completion_tokens: 0,
+                total_tokens: prompt_tokens,
+                prompt_tokens_cached: None,
+            }),
+            correlation_id: None,
+        })
+    }
+}
+
+/// The OpenAI-compatible JSON body of `request`
+///
+/// `additional_params` are sent as top-level fields, `stream` is dropped since this client
+/// reads whole responses, and `session_id` is dropped since it only routes the request.
+fn backend_body<R: Serialize>(request: &R) -> Result<serde_json::Value> {
+    let mut body = serde_json::to_value(request)
+        .map_err(|e| LlmError::Internal(format!("Failed to serialize request: {}", e)))?;
+    if let Some(fields) = body.as_object_mut() {
+        fields.remove("stream");
+        fields.remove("session_id");
+        if let Some(serde_json::Value::Object(params)) = fields.remove("additional_params") {
+            for (key, value) in params {
+                fields.entry(key).or_insert(value);
+            }
+        }
+    }
+    Ok(body)
+}
+
+fn unix_timestamp() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+/// Rough whitespace-based token count used for the template's usage reporting
+fn approximate_token_count(text: &str) -> u32 {
+    text.split_whitespace().count() as u32
+}
+
+fn approximate_usage(prompt: &str, completion: &str) -> UsageInfo {
+    let prompt_tokens = approximate_token_count(prompt);
+    let completion_tokens = approximate_token_count(completion);
+    UsageInfo {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+        prompt_tokens_cached: None,
+    }
+}
+
+/// Fold the input bytes into a fixed number of buckets and normalize the result
+fn placeholder_embedding(input: &str) -> Vec<f32> {
+    let mut embedding = vec![0.0f32; ECHO_EMBEDDING_DIMENSIONS];
+    for (i, byte) in input.bytes().enumerate() {
+        embedding[i % ECHO_EMBEDDING_DIMENSIONS] += byte as f32;
+    }
+
+    let norm = embedding.iter().map(|v| v * v).sum::<f32>().sqrt();
+    if norm > 0.0 {
+        embedding.iter_mut().for_each(|v| *v /= norm);
     }
+    embedding
 }
@@ -0,0 +1,111 @@
+use std::time::Instant;
+
+/// Number of one-second slots a [`MetricsWindow`] counts over
+const WINDOW_SECONDS: usize = 60;
+
+/// Counts events over a sliding window of the last minute, e.g. for
+/// `NodeMetrics::requests_per_minute`
+///
+/// Events are counted in a ring buffer of per-second slots, so memory stays constant however
+/// many events are recorded. A slot is reused once its second has left the window, which makes
+/// the count drop back to zero after a minute without events.
+#[derive(Debug, Clone)]
+pub struct MetricsWindow {
+    origin: Instant,
+    /// Events counted in each slot, with the second since `origin` the slot last counted
+    slots: [(u64, u32); WINDOW_SECONDS],
+}
+
+impl MetricsWindow {
+    /// Create an empty window
+    pub fn new() -> Self {
+        Self::starting_at(Instant::now())
+    }
+
+    /// Create an empty window whose first second starts at `origin`
+    pub fn starting_at(origin: Instant) -> Self {
+        Self {
+            origin,
+            slots: [(0, 0); WINDOW_SECONDS],
+        }
+    }
+
+    /// Count an event now
+    pub fn record(&mut self) {
+        self.record_at(Instant::now());
+    }
+
+    /// Count an event at `now`
+    pub fn record_at(&mut self, now: Instant) {
+        let second = self.second_of(now);
+        let slot = &mut self.slots[second as usize % WINDOW_SECONDS];
+        if slot.0 != second {
+            *slot = (second, 0);
+        }
+        slot.1 = slot.1.saturating_add(1);
+    }
+
+    /// Number of events in the minute up to now
+    pub fn count(&self) -> u32 {
+        self.count_at(Instant::now())
+    }
+
+    /// Number of events in the minute up to `now`
+    pub fn count_at(&self, now: Instant) -> u32 {
+        let second = self.second_of(now);
+        self.slots
+            .iter()
+            .filter(|(slot_second, _)| {
+                *slot_second <= second && second - slot_second < WINDOW_SECONDS as u64
+            })
+            .fold(0u32, |total, (_, count)| total.saturating_add(*count))
+    }
+
+    fn second_of(&self, instant: Instant) -> u64 {
+        instant.saturating_duration_since(self.origin).as_secs()
+    }
+}
+
+impl Default for MetricsWindow {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[test]
+    fn test_counts_events_within_the_last_minute() {
+        let start = Instant::now();
+        let mut window = MetricsWindow::starting_at(start);
+        for second in 0..30 {
+            window.record_at(start + Duration::from_secs(second));
+            window.record_at(start + Duration::from_secs(second));
+        }
+        assert_eq!(window.count_at(start + Duration::from_secs(30)), 60);
+
+        // The events of the first 15 seconds have left the window
+        assert_eq!(window.count_at(start + Duration::from_secs(74)), 30);
+
+        // After a minute without events the rate is back to zero
+        assert_eq!(window.count_at(start + Duration::from_secs(90)), 0);
+        assert_eq!(window.count_at(start + Duration::from_secs(3600)), 0);
+    }
+
+    #[test]
+    fn test_reused_slots_start_from_zero() {
+        let start = Instant::now();
+        let mut window = MetricsWindow::starting_at(start);
+        for _ in 0..5 {
+            window.record_at(start);
+        }
+
+        // Second 60 shares its slot with second 0, whose events have expired
+        let later = start + Duration::from_secs(60);
+        window.record_at(later);
+        assert_eq!(window.count_at(later), 1);
+    }
+}
@@ -11,9 +11,32 @@ pub use models::*;
 mod local_llm;
 pub use local_llm::*;
 
+mod metrics;
+pub use metrics::*;
+
+mod system;
+pub use system::*;
+
+#[cfg(feature = "gpu")]
+mod gpu;
+#[cfg(feature = "gpu")]
+pub use gpu::*;
+
 mod streaming;
 pub use streaming::*;
 
+mod embeddings;
+pub use embeddings::*;
+
+mod compression;
+pub use compression::*;
+
+mod pricing;
+pub use pricing::*;
+
+mod version;
+pub use version::*;
+
 /// Errors that can occur when interacting with an LLM
 #[derive(Debug, Error)]
 pub enum LlmError {
@@ -35,8 +58,41 @@ pub enum LlmError {
     #[error("Internal error: {0}")]
     Internal(String),
 
+    #[error("Failed to parse response: {0}")]
+    ResponseParseError(String),
+
     #[error("Not implemented: {0}")]
     NotImplemented(String),
+
+    /// The caller exceeded one of its limits; API handlers answer with HTTP 429
+    #[error("Rate limit exceeded: {0}")]
+    RateLimited(String),
+}
+
+impl LlmError {
+    /// Whether a request failing with this error may succeed on another node
+    ///
+    /// Only backend failures, timeouts and rate limits are; requests that are invalid or for
+    /// unsupported models fail the same way everywhere.
+    pub fn is_retryable(&self) -> bool {
+        matches!(
+            self,
+            Self::RequestFailed(_) | Self::Timeout(_) | Self::RateLimited(_)
+        )
+    }
+}
+
+/// Timeout of a backend request when none is configured, the default of `llm.timeout_seconds`
+pub const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Await a backend request, failing with [`LlmError::Timeout`] once `timeout` has elapsed
+pub async fn with_request_timeout<T>(
+    timeout: Duration,
+    request: impl std::future::Future<Output = Result<T>>,
+) -> Result<T> {
+    tokio::time::timeout(timeout, request)
+        .await
+        .unwrap_or(Err(LlmError::Timeout(timeout)))
 }
 
 /// Result type for LLM operations
@@ -53,8 +109,38 @@ pub trait LlmClient: Send + Sync {
     fn get_capabilities(&self) -> LlmCapabilities;
 
     /// Get current metrics for this LLM client
+    ///
+    /// Called from async code on the runtime's worker threads, so implementations must return
+    /// without blocking on async locks or I/O; keep the metrics behind a `std::sync::RwLock`.
     fn get_metrics(&self) -> NodeMetrics;
 
+    /// Get information about the backend behind this LLM client
+    ///
+    /// Clients that do not probe their backend report an unknown version.
+    fn get_node_info(&self) -> NodeInfo {
+        NodeInfo::default()
+    }
+
+    /// This client as a [`StreamingLlmClient`], if it implements one
+    ///
+    /// Streaming clients return `Some(self)`, which lets the streaming path reach them
+    /// through an `Arc<dyn LlmClient>`.
+    fn streaming_client(&self) -> Option<&dyn StreamingLlmClient> {
+        None
+    }
+
+    /// Check that the backend behind this LLM client is reachable
+    ///
+    /// Clients without a backend to probe are healthy as long as they serve a model.
+    async fn health_check(&self) -> Result<()> {
+        if self.get_supported_models().is_empty() {
+            return Err(LlmError::RequestFailed(
+                "the backend serves no models".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
     /// Process a chat completion request
     async fn chat_completion(
         &self,
@@ -72,7 +158,7 @@ pub trait LlmClient: Send + Sync {
 }
 
 /// Information about a specific LLM model
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct ModelInfo {
     /// Unique identifier for the model
     pub id: String,
@@ -96,6 +182,35 @@ pub struct ModelInfo {
     pub parameters: HashMap<String, String>,
 }
 
+impl ModelInfo {
+    /// Whether the model can serve `operation`
+    pub fn supports(&self, operation: Operation) -> bool {
+        match operation {
+            Operation::ChatCompletion => self.supports_chat,
+            Operation::TextCompletion => self.supports_text,
+            Operation::Embedding => self.supports_embeddings,
+        }
+    }
+
+    /// Whether the model classifies its input instead of generating text, as marked by a
+    /// `task` parameter of `classify`
+    ///
+    /// Classification and moderation models answer completions with usage but no choices.
+    pub fn is_classifier(&self) -> bool {
+        self.parameters
+            .get("task")
+            .is_some_and(|task| task == "classify")
+    }
+}
+
+/// The kind of work an [`LlmRequest`] asks a model for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    ChatCompletion,
+    TextCompletion,
+    Embedding,
+}
+
 /// Capabilities of an LLM client
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct LlmCapabilities {
@@ -112,8 +227,31 @@ pub struct LlmCapabilities {
     pub features: HashMap<String, bool>,
 }
 
+impl LlmCapabilities {
+    /// The capabilities of several clients serving together
+    ///
+    /// A capability is offered if any client offers it, and concurrency limits add up.
+    pub fn combine(capabilities: impl IntoIterator<Item = LlmCapabilities>) -> Self {
+        let mut combined = Self {
+            supports_streaming: false,
+            max_concurrent_requests: 0,
+            supports_batching: false,
+            features: HashMap::new(),
+        };
+        for capabilities in capabilities {
+            combined.supports_streaming |= capabilities.supports_streaming;
+            combined.max_concurrent_requests += capabilities.max_concurrent_requests;
+            combined.supports_batching |= capabilities.supports_batching;
+            for (feature, enabled) in capabilities.features {
+                *combined.features.entry(feature).or_default() |= enabled;
+            }
+        }
+        combined
+    }
+}
+
 /// Metrics for an LLM node
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct NodeMetrics {
     /// Current CPU utilization (0.0 - 1.0)
     pub cpu_utilization: f32,
@@ -124,6 +262,10 @@ pub struct NodeMetrics {
     /// Current GPU utilization if available (0.0 - 1.0)
     pub gpu_utilization: Option<f32>,
 
+    /// Current share of GPU memory in use if available (0.0 - 1.0)
+    #[serde(default)]
+    pub gpu_memory_utilization: Option<f32>,
+
     /// Number of requests processed in the last minute
     pub requests_per_minute: u32,
 
@@ -133,10 +275,44 @@ pub struct NodeMetrics {
     /// Number of requests currently being processed
     pub active_requests: u32,
 
+    /// Number of requests waiting for a dispatch slot
+    #[serde(default)]
+    pub queued_requests: u32,
+
+    /// Average time requests waited for a dispatch slot, in milliseconds
+    #[serde(default)]
+    pub avg_queue_wait_ms: u64,
+
     /// Timestamp of the last update (Unix timestamp in seconds)
     pub last_updated: u64,
 }
 
+/// Information about the backend serving an LLM node
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NodeInfo {
+    /// Kind of backend, e.g. `vllm` or `ollama`
+    pub backend: String,
+
+    /// Version reported by the backend, if it has been detected
+    pub version: Option<String>,
+}
+
+/// The metrics, models and capabilities of this node, reported to Tangle in one call
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct NodeReport {
+    /// Current metrics of the node
+    pub metrics: NodeMetrics,
+
+    /// Models served by the active LLM nodes, one entry per model id
+    pub models: Vec<ModelInfo>,
+
+    /// Combined capabilities of the active LLM nodes
+    pub capabilities: LlmCapabilities,
+
+    /// Version of the blueprint template library running the node
+    pub version: String,
+}
+
 /// Trait for LLM clients that support streaming responses
 #[allow(async_fn_in_trait)]
 #[async_trait::async_trait]
@@ -189,7 +365,7 @@ impl<T: LlmClient + 'static> LlmClientExt for T {
             return None;
         }
 
-        None // TODO: Implement proper downcasting
+        self.streaming_client()
     }
 
     async fn chat_completion_ext(
@@ -221,7 +397,7 @@ impl LlmClientExt for std::sync::Arc<dyn LlmClient> {
             return None;
         }
 
-        None // TODO: Implement proper downcasting
+        self.as_ref().streaming_client()
     }
 
     async fn chat_completion_ext(
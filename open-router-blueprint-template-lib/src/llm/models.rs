@@ -1,22 +1,145 @@
+use rust_decimal::Decimal;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
 /// A chat message in a conversation
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ChatMessage {
-    /// The role of the message sender (e.g., "system", "user", "assistant")
+    /// The role of the message sender (e.g., "system", "user", "assistant", "tool")
     pub role: String,
 
-    /// The content of the message
+    /// The content of the message; empty for assistant messages that only call tools, which
+    /// OpenAI-compatible backends send with `content: null`
+    #[serde(default, deserialize_with = "null_as_empty")]
     pub content: String,
 
     /// Optional name of the sender
     #[serde(skip_serializing_if = "Option::is_none")]
     pub name: Option<String>,
+
+    /// Reasoning ("thinking") the model produced before its answer, kept out of `content`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>,
+
+    /// Tools the assistant called in this message
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+
+    /// The tool call a `tool` message answers
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_call_id: Option<String>,
+}
+
+/// Deserialize a `null` string as an empty one
+fn null_as_empty<'de, D: serde::Deserializer<'de>>(deserializer: D) -> Result<String, D::Error> {
+    Ok(Option::<String>::deserialize(deserializer)?.unwrap_or_default())
+}
+
+/// A tool the model may call, in the OpenAI `tools` format
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ToolDefinition {
+    /// The type of the tool; only `function` is defined
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    /// The function the tool calls
+    pub function: FunctionDefinition,
+}
+
+/// A function the model may call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FunctionDefinition {
+    /// The name of the function
+    pub name: String,
+
+    /// What the function does, which the model uses to decide when to call it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+
+    /// The parameters the function takes, as a JSON schema
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parameters: Option<serde_json::Value>,
+}
+
+/// A call of a tool made by the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ToolCall {
+    /// The ID of the call, which the `tool` message with its result refers to
+    pub id: String,
+
+    /// The type of the tool; only `function` is defined
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    /// The function called and its arguments
+    pub function: FunctionCall,
+}
+
+/// A function called by the model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FunctionCall {
+    /// The name of the function
+    pub name: String,
+
+    /// The arguments of the call, as a JSON-encoded string
+    pub arguments: String,
+}
+
+/// Which tools the model may call: a mode, or one named function it must call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(untagged)]
+pub enum ToolChoice {
+    /// `none`, `auto` or `required`
+    Mode(ToolChoiceMode),
+
+    /// A function the model must call
+    Function(NamedToolChoice),
+}
+
+/// How freely the model may call tools
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[serde(rename_all = "snake_case")]
+pub enum ToolChoiceMode {
+    /// Never call a tool
+    None,
+
+    /// Decide whether to call tools
+    Auto,
+
+    /// Call at least one tool
+    Required,
+}
+
+/// A function the model is made to call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NamedToolChoice {
+    /// The type of the tool; only `function` is defined
+    #[serde(rename = "type")]
+    pub kind: String,
+
+    /// The function to call
+    pub function: NamedFunction,
+}
+
+/// The name of a function
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NamedFunction {
+    /// The name of the function
+    pub name: String,
 }
 
 /// Request for a chat completion
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ChatCompletionRequest {
     /// The model to use for completion
     pub model: String,
@@ -36,31 +159,361 @@ pub struct ChatCompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub top_p: Option<f32>,
 
+    /// Sequences that end the generation; they are not included in the output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+
+    /// Number of completions to generate, 1 when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+
+    /// OpenAI service tier, e.g. `priority` or `flex`, which sets the request's priority
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub service_tier: Option<String>,
+
+    /// Key of the caller's session, which the affinity strategy keeps on one node
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub session_id: Option<String>,
+
+    /// Tools the model may call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tools: Option<Vec<ToolDefinition>>,
+
+    /// Which of the `tools` the model may call
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tool_choice: Option<ToolChoice>,
+
     /// Whether to stream the response
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
 
     /// Additional model-specific parameters
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub additional_params: HashMap<String, serde_json::Value>,
 }
 
-impl Default for ChatCompletionRequest {
-    fn default() -> Self {
-        Self {
-            model: String::new(),
-            messages: Vec::new(),
-            max_tokens: None,
-            temperature: None,
-            top_p: None,
-            stream: None,
-            additional_params: HashMap::new(),
+impl ChatCompletionRequest {
+    /// The priority requested with `service_tier`
+    ///
+    /// `priority` is high and `flex` is low; no tier, `auto`, `default` and tiers this library
+    /// does not know are normal.
+    pub fn priority(&self) -> RequestPriority {
+        match self.service_tier.as_deref() {
+            Some("priority") => RequestPriority::High,
+            Some("flex") => RequestPriority::Low,
+            _ => RequestPriority::Normal,
+        }
+    }
+
+    /// Insert `prompt` as the first message unless the request already has a system message
+    ///
+    /// With `override_existing`, the caller's system messages are removed and `prompt` is
+    /// inserted in their place.
+    pub fn apply_default_system_prompt(&mut self, prompt: &str, override_existing: bool) {
+        if override_existing {
+            self.messages.retain(|m| m.role != "system");
+        } else if self.messages.iter().any(|m| m.role == "system") {
+            return;
+        }
+        self.messages.insert(
+            0,
+            ChatMessage {
+                role: "system".to_string(),
+                content: prompt.to_string(),
+                ..Default::default()
+            },
+        );
+    }
+
+    /// Normalize the system messages of this request according to `policy`
+    pub fn apply_system_prompt_policy(&mut self, policy: SystemPromptPolicy) {
+        match policy {
+            SystemPromptPolicy::Passthrough => {}
+            SystemPromptPolicy::First => {
+                let mut seen_system = false;
+                self.messages.retain(|m| {
+                    if m.role != "system" {
+                        return true;
+                    }
+                    !std::mem::replace(&mut seen_system, true)
+                });
+            }
+            SystemPromptPolicy::Merge => {
+                let mut merged: Vec<ChatMessage> = Vec::with_capacity(self.messages.len());
+                for message in self.messages.drain(..) {
+                    match merged.last_mut() {
+                        Some(last) if last.role == "system" && message.role == "system" => {
+                            last.content.push_str("\n\n");
+                            last.content.push_str(&message.content);
+                        }
+                        _ => merged.push(message),
+                    }
+                }
+                self.messages = merged;
+            }
+        }
+    }
+
+    /// Limit this request to at most `max_messages` messages
+    ///
+    /// An over-long request is rejected unless `truncate` is set, in which case the oldest
+    /// non-system messages are dropped. System messages are always kept, so a request whose
+    /// system messages alone exceed the limit is rejected either way.
+    pub fn enforce_message_limit(
+        &mut self,
+        max_messages: usize,
+        truncate: bool,
+    ) -> super::Result<()> {
+        let overflow = self.messages.len().saturating_sub(max_messages);
+        if overflow == 0 {
+            return Ok(());
+        }
+
+        let droppable = self.messages.iter().filter(|m| m.role != "system").count();
+        if !truncate || droppable < overflow {
+            return Err(super::LlmError::InvalidRequest(format!(
+                "Request has {} messages, more than the maximum of {}",
+                self.messages.len(),
+                max_messages
+            )));
+        }
+
+        let mut dropped = 0;
+        self.messages.retain(|m| {
+            if dropped < overflow && m.role != "system" {
+                dropped += 1;
+                return false;
+            }
+            true
+        });
+        tracing::debug!(
+            "Dropped the {} oldest messages to fit the limit of {}",
+            dropped,
+            max_messages
+        );
+        Ok(())
+    }
+
+    /// Estimated number of prompt tokens in this request's messages
+    pub fn estimated_prompt_tokens(&self) -> usize {
+        self.messages
+            .iter()
+            .map(|m| estimate_tokens(&m.content))
+            .sum()
+    }
+
+    /// Drop the oldest non-system messages until the estimated prompt fits `max_prompt_tokens`
+    ///
+    /// System messages and the latest message are always kept, so the result can still exceed
+    /// the budget. Returns the estimated number of tokens dropped.
+    pub fn truncate_to_fit(&mut self, max_prompt_tokens: usize) -> usize {
+        let mut excess = self
+            .estimated_prompt_tokens()
+            .saturating_sub(max_prompt_tokens);
+        let last = self.messages.len().saturating_sub(1);
+        let mut dropped = 0;
+        let mut index = 0;
+        self.messages.retain(|m| {
+            let keep = excess == 0 || m.role == "system" || index == last;
+            index += 1;
+            if !keep {
+                let tokens = estimate_tokens(&m.content);
+                excess = excess.saturating_sub(tokens);
+                dropped += tokens;
+            }
+            keep
+        });
+        dropped
+    }
+}
+
+/// The number of choices a completion's `n` asks for, 1 when unset
+///
+/// Fails with [`LlmError::InvalidRequest`](super::LlmError::InvalidRequest) for `n: 0`.
+pub fn choice_count(n: Option<u32>) -> super::Result<u32> {
+    match n {
+        Some(0) => Err(super::LlmError::InvalidRequest(
+            "n must be at least 1".to_string(),
+        )),
+        Some(n) => Ok(n),
+        None => Ok(1),
+    }
+}
+
+/// Rough number of tokens in `text`, at about four characters per token
+///
+/// Used to fit prompts into a model's context before the backend's tokenizer sees them.
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
+/// Request fields that `additional_params` may never override, even when allowlisted
+pub const RESERVED_PARAMS: &[&str] = &["model", "messages", "prompt", "stream", "input"];
+
+/// The `additional_params` whose keys are in `allowed`, for merging into a backend request body
+///
+/// Strict backends reject unknown fields, so anything not allowlisted is dropped. Keys in
+/// [`RESERVED_PARAMS`] are always dropped, since merging them would silently replace the
+/// typed request fields.
+pub fn passthrough_params(
+    params: &HashMap<String, serde_json::Value>,
+    allowed: &[String],
+) -> HashMap<String, serde_json::Value> {
+    params
+        .iter()
+        .filter(|(key, _)| {
+            if RESERVED_PARAMS.contains(&key.as_str()) {
+                tracing::warn!(
+                    "Dropping additional parameter {}, which would override a request field",
+                    key
+                );
+                return false;
+            }
+            let keep = allowed.contains(key);
+            if !keep {
+                tracing::debug!(
+                    "Dropping additional parameter {} not in the passthrough allowlist",
+                    key
+                );
+            }
+            keep
+        })
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect()
+}
+
+/// The `additional_params` key carrying a request's idempotency key
+pub const IDEMPOTENCY_KEY_PARAM: &str = "idempotency_key";
+
+/// How multiple `system` messages in a chat request are handled before dispatch
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SystemPromptPolicy {
+    /// Concatenate consecutive system messages into one
+    Merge,
+
+    /// Keep only the first system message
+    First,
+
+    /// Forward the messages unchanged
+    #[default]
+    Passthrough,
+}
+
+/// How completions with more choices than the request's `n` are handled
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ExtraChoicesPolicy {
+    /// Drop the choices beyond `n`
+    Truncate,
+
+    /// Return every choice the backend produced
+    #[default]
+    Passthrough,
+}
+
+/// How urgently a request should be served
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord)]
+pub enum RequestPriority {
+    /// Latency-insensitive work, e.g. the `flex` service tier
+    Low,
+
+    /// The priority of requests that do not ask for another
+    #[default]
+    Normal,
+
+    /// Latency-sensitive work, e.g. the `priority` service tier
+    High,
+}
+
+/// Log probabilities of the tokens generated for a choice
+///
+/// Chat completions report them in `content`, one entry per token. Text completions use the
+/// legacy layout of parallel `tokens`, `token_logprobs`, `top_logprobs` and `text_offset`
+/// lists, as vLLM and OpenAI return them.
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LogProbs {
+    /// The generated tokens of a chat completion with their log probabilities
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub content: Vec<TokenLogProb>,
+
+    /// The generated tokens of a text completion
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<String>,
+
+    /// The log probability of each of `tokens`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub token_logprobs: Vec<Option<f32>>,
+
+    /// The most likely alternatives at each of `tokens`, with their log probabilities
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub top_logprobs: Vec<Option<HashMap<String, f32>>>,
+
+    /// The character offset of each of `tokens` in the generated text
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub text_offset: Vec<usize>,
+}
+
+impl LogProbs {
+    /// Append the log probabilities of the tokens generated after these, e.g. in the next
+    /// chunk of a stream
+    pub fn append(&mut self, next: LogProbs) {
+        self.content.extend(next.content);
+        self.tokens.extend(next.tokens);
+        self.token_logprobs.extend(next.token_logprobs);
+        self.top_logprobs.extend(next.top_logprobs);
+        self.text_offset.extend(next.text_offset);
+    }
+}
+
+/// A generated token of a chat completion and its log probability
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TokenLogProb {
+    /// The token
+    pub token: String,
+
+    /// The log probability of the token
+    pub logprob: f32,
+
+    /// The UTF-8 bytes of the token, for tokens that are not valid UTF-8 on their own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<Vec<u8>>,
+
+    /// The most likely tokens at this position, with their log probabilities
+    #[serde(default)]
+    pub top_logprobs: Vec<TopLogProb>,
+}
+
+/// One of the most likely tokens at a position of a chat completion
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TopLogProb {
+    /// The token
+    pub token: String,
+
+    /// The log probability of the token
+    pub logprob: f32,
+
+    /// The UTF-8 bytes of the token
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<Vec<u8>>,
+}
+
+/// Append the log probabilities of a chunk or continuation to those collected so far
+pub(crate) fn append_logprobs(collected: &mut Option<LogProbs>, next: Option<LogProbs>) {
+    if let Some(next) = next {
+        match collected {
+            Some(collected) => collected.append(next),
+            None => *collected = Some(next),
         }
     }
 }
 
 /// A chat completion choice
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ChatCompletionChoice {
     /// The index of this choice
     pub index: usize,
@@ -70,10 +523,19 @@ pub struct ChatCompletionChoice {
 
     /// The reason the generation stopped
     pub finish_reason: Option<String>,
+
+    /// The tool calls of `message`, for callers that read them off the choice
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tool_calls: Option<Vec<ToolCall>>,
+
+    /// Log probabilities of the generated tokens, if requested with `logprobs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<LogProbs>,
 }
 
 /// Response from a chat completion request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ChatCompletionResponse {
     /// The ID of the completion
     pub id: String,
@@ -92,23 +554,20 @@ pub struct ChatCompletionResponse {
 
     /// Usage statistics for the completion
     pub usage: Option<UsageInfo>,
-}
 
-impl Default for ChatCompletionResponse {
-    fn default() -> Self {
-        Self {
-            id: String::new(),
-            object: String::new(),
-            created: 0,
-            model: String::new(),
-            choices: Vec::new(),
-            usage: None,
-        }
-    }
+    /// Cost of the completion in USD, reported when `api.include_cost` is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+    pub cost: Option<Decimal>,
+
+    /// Correlation id of the Tangle job call that produced this response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
 }
 
 /// Request for a text completion
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TextCompletionRequest {
     /// The model to use for completion
     pub model: String,
@@ -128,31 +587,46 @@ pub struct TextCompletionRequest {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub top_p: Option<f32>,
 
+    /// Sequences that end the generation; they are not included in the output
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub stop: Option<Vec<String>>,
+
+    /// Number of completions to generate, 1 when unset
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub n: Option<u32>,
+
     /// Whether to stream the response
     #[serde(skip_serializing_if = "Option::is_none")]
     pub stream: Option<bool>,
 
     /// Additional model-specific parameters
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub additional_params: HashMap<String, serde_json::Value>,
 }
 
-impl Default for TextCompletionRequest {
-    fn default() -> Self {
-        Self {
-            model: String::new(),
-            prompt: String::new(),
-            max_tokens: None,
-            temperature: None,
-            top_p: None,
-            stream: None,
-            additional_params: HashMap::new(),
+impl TextCompletionRequest {
+    /// Drop the head of the prompt until its estimated tokens fit `max_prompt_tokens`
+    ///
+    /// Returns the estimated number of tokens dropped.
+    pub fn truncate_to_fit(&mut self, max_prompt_tokens: usize) -> usize {
+        let tokens = estimate_tokens(&self.prompt);
+        if tokens <= max_prompt_tokens {
+            return 0;
         }
+        let excess_chars = self.prompt.chars().count() - max_prompt_tokens * 4;
+        let start = self
+            .prompt
+            .char_indices()
+            .nth(excess_chars)
+            .map_or(self.prompt.len(), |(i, _)| i);
+        self.prompt.drain(..start);
+        tokens - estimate_tokens(&self.prompt)
     }
 }
 
 /// A text completion choice
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TextCompletionChoice {
     /// The index of this choice
     pub index: usize,
@@ -162,10 +636,15 @@ pub struct TextCompletionChoice {
 
     /// The reason the generation stopped
     pub finish_reason: Option<String>,
+
+    /// Log probabilities of the generated tokens, if requested with `logprobs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<LogProbs>,
 }
 
 /// Response from a text completion request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct TextCompletionResponse {
     /// The ID of the completion
     pub id: String,
@@ -184,23 +663,20 @@ pub struct TextCompletionResponse {
 
     /// Usage statistics for the completion
     pub usage: Option<UsageInfo>,
-}
 
-impl Default for TextCompletionResponse {
-    fn default() -> Self {
-        Self {
-            id: String::new(),
-            object: String::new(),
-            created: 0,
-            model: String::new(),
-            choices: Vec::new(),
-            usage: None,
-        }
-    }
+    /// Cost of the completion in USD, reported when `api.include_cost` is enabled
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    #[cfg_attr(feature = "schema", schemars(with = "Option<String>"))]
+    pub cost: Option<Decimal>,
+
+    /// Correlation id of the Tangle job call that produced this response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
 }
 
 /// Request for generating embeddings
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct EmbeddingRequest {
     /// The model to use for embeddings
     pub model: String,
@@ -209,22 +685,13 @@ pub struct EmbeddingRequest {
     pub input: Vec<String>,
 
     /// Additional model-specific parameters
-    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
     pub additional_params: HashMap<String, serde_json::Value>,
 }
 
-impl Default for EmbeddingRequest {
-    fn default() -> Self {
-        Self {
-            model: String::new(),
-            input: Vec::new(),
-            additional_params: HashMap::new(),
-        }
-    }
-}
-
 /// A single embedding result
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct EmbeddingData {
     /// The index of this embedding
     pub index: usize,
@@ -234,7 +701,8 @@ pub struct EmbeddingData {
 }
 
 /// Response from an embedding request
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct EmbeddingResponse {
     /// The type of object (always "list")
     pub object: String,
@@ -247,21 +715,36 @@ pub struct EmbeddingResponse {
 
     /// Usage statistics for the embeddings
     pub usage: Option<UsageInfo>,
+
+    /// Correlation id of the Tangle job call that produced this response
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub correlation_id: Option<String>,
 }
 
-impl Default for EmbeddingResponse {
-    fn default() -> Self {
-        Self {
-            object: String::new(),
-            model: String::new(),
-            data: Vec::new(),
-            usage: None,
+impl EmbeddingResponse {
+    /// Order `data` by input index and check that every embedding has the same dimension
+    pub fn sort_and_validate(&mut self) -> super::Result<()> {
+        self.data.sort_by_key(|d| d.index);
+
+        if let Some(first) = self.data.first() {
+            let dimension = first.embedding.len();
+            if let Some(mismatch) = self.data.iter().find(|d| d.embedding.len() != dimension) {
+                return Err(super::LlmError::ResponseParseError(format!(
+                    "embedding {} has dimension {}, expected {}",
+                    mismatch.index,
+                    mismatch.embedding.len(),
+                    dimension
+                )));
+            }
         }
+
+        Ok(())
     }
 }
 
 /// Usage information for an LLM request
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct UsageInfo {
     /// The number of prompt tokens used
     pub prompt_tokens: u32,
@@ -271,10 +754,15 @@ pub struct UsageInfo {
 
     /// The total number of tokens used
     pub total_tokens: u32,
+
+    /// The number of prompt tokens served from the backend's prefix cache, when reported
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_tokens_cached: Option<u32>,
 }
 
 /// A unified request type that can represent any LLM operation
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "type")]
 pub enum LlmRequest {
     #[serde(rename = "chat.completion")]
@@ -287,6 +775,196 @@ pub enum LlmRequest {
     Embedding(EmbeddingRequest),
 }
 
+impl LlmRequest {
+    /// The model this request asks for
+    pub fn model(&self) -> &str {
+        match self {
+            Self::ChatCompletion(request) => &request.model,
+            Self::TextCompletion(request) => &request.model,
+            Self::Embedding(request) => &request.model,
+        }
+    }
+
+    /// The operation this request asks for
+    pub fn operation(&self) -> super::Operation {
+        match self {
+            Self::ChatCompletion(_) => super::Operation::ChatCompletion,
+            Self::TextCompletion(_) => super::Operation::TextCompletion,
+            Self::Embedding(_) => super::Operation::Embedding,
+        }
+    }
+
+    /// Whether this is a completion that asks for a streamed response
+    pub fn is_streaming(&self) -> bool {
+        match self {
+            Self::ChatCompletion(request) => request.stream.unwrap_or(false),
+            Self::TextCompletion(request) => request.stream.unwrap_or(false),
+            Self::Embedding(_) => false,
+        }
+    }
+
+    /// How urgently this request should be served; only chat requests can ask for a priority
+    pub fn priority(&self) -> RequestPriority {
+        match self {
+            Self::ChatCompletion(request) => request.priority(),
+            Self::TextCompletion(_) | Self::Embedding(_) => RequestPriority::Normal,
+        }
+    }
+
+    /// The session key the affinity strategy routes this request by; only chat requests have
+    /// one
+    pub fn session_id(&self) -> Option<&str> {
+        match self {
+            Self::ChatCompletion(request) => request.session_id.as_deref(),
+            Self::TextCompletion(_) | Self::Embedding(_) => None,
+        }
+    }
+
+    /// Take the key this request is retried under from its `idempotency_key` additional
+    /// parameter
+    ///
+    /// The parameter is removed even if it is not a string, so it never reaches a backend.
+    pub fn take_idempotency_key(&mut self) -> Option<String> {
+        let additional_params = match self {
+            Self::ChatCompletion(request) => &mut request.additional_params,
+            Self::TextCompletion(request) => &mut request.additional_params,
+            Self::Embedding(request) => &mut request.additional_params,
+        };
+        match additional_params.remove(IDEMPOTENCY_KEY_PARAM)? {
+            serde_json::Value::String(key) if !key.is_empty() => Some(key),
+            _ => None,
+        }
+    }
+
+    /// Dispatch this request to a different model
+    pub fn set_model(&mut self, model: String) {
+        match self {
+            Self::ChatCompletion(request) => request.model = model,
+            Self::TextCompletion(request) => request.model = model,
+            Self::Embedding(request) => request.model = model,
+        }
+    }
+
+    /// Whether this is a completion with `temperature: 0`, whose output is deterministic
+    pub fn is_deterministic(&self) -> bool {
+        match self {
+            Self::ChatCompletion(request) => request.temperature == Some(0.0),
+            Self::TextCompletion(request) => request.temperature == Some(0.0),
+            Self::Embedding(_) => false,
+        }
+    }
+
+    /// Drop `top_p` from deterministic completions
+    ///
+    /// Greedy decoding picks the most likely token, which every nucleus contains, so `top_p`
+    /// cannot change the output. Many clients send `top_p: 1.0` by default, and some backends
+    /// reject or mishandle nucleus sampling alongside greedy decoding; dropping every value
+    /// also keeps otherwise identical requests identical for the response cache.
+    pub fn apply_deterministic_sampling(&mut self) {
+        if !self.is_deterministic() {
+            return;
+        }
+        match self {
+            Self::ChatCompletion(request) => request.top_p = None,
+            Self::TextCompletion(request) => request.top_p = None,
+            Self::Embedding(_) => {}
+        }
+    }
+
+    /// Number of choices a completion asks for with the `n` parameter, 1 if it does not
+    ///
+    /// An `n` in `additional_params` counts when the typed field is unset. `None` for
+    /// embedding requests, which have no choices.
+    pub fn requested_choices(&self) -> Option<usize> {
+        let (n, additional_params) = match self {
+            Self::ChatCompletion(request) => (request.n, &request.additional_params),
+            Self::TextCompletion(request) => (request.n, &request.additional_params),
+            Self::Embedding(_) => return None,
+        };
+        let n = n
+            .map(u64::from)
+            .or_else(|| {
+                additional_params
+                    .get("n")
+                    .and_then(serde_json::Value::as_u64)
+            })
+            .unwrap_or(1);
+        Some(n.max(1) as usize)
+    }
+
+    /// Fail with [`LlmError::InvalidRequest`](super::LlmError::InvalidRequest) if this is a
+    /// completion asking for no choices with `n: 0`
+    pub fn check_choice_count(&self) -> super::Result<()> {
+        match self {
+            Self::ChatCompletion(request) => choice_count(request.n).map(|_| ()),
+            Self::TextCompletion(request) => choice_count(request.n).map(|_| ()),
+            Self::Embedding(_) => Ok(()),
+        }
+    }
+
+    /// The estimated number of context tokens a completion needs: its prompt and `max_tokens`
+    ///
+    /// Always 0 for embedding requests.
+    pub fn estimated_context_tokens(&self) -> usize {
+        match self {
+            Self::ChatCompletion(request) => {
+                request.estimated_prompt_tokens() + request.max_tokens.unwrap_or(0) as usize
+            }
+            Self::TextCompletion(request) => {
+                estimate_tokens(&request.prompt) + request.max_tokens.unwrap_or(0) as usize
+            }
+            Self::Embedding(_) => 0,
+        }
+    }
+
+    /// Trim the prompt of a completion so that it and its `max_tokens` fit a context of
+    /// `max_context_length` tokens
+    ///
+    /// See [`ChatCompletionRequest::truncate_to_fit`] and
+    /// [`TextCompletionRequest::truncate_to_fit`]. Returns the estimated number of tokens
+    /// dropped, always 0 for embedding requests.
+    pub fn truncate_to_context(&mut self, max_context_length: usize) -> usize {
+        match self {
+            Self::ChatCompletion(request) => {
+                let reserved = request.max_tokens.unwrap_or(0) as usize;
+                request.truncate_to_fit(max_context_length.saturating_sub(reserved))
+            }
+            Self::TextCompletion(request) => {
+                let reserved = request.max_tokens.unwrap_or(0) as usize;
+                request.truncate_to_fit(max_context_length.saturating_sub(reserved))
+            }
+            Self::Embedding(_) => 0,
+        }
+    }
+
+    /// A request for more output after `partial`, the output so far of a completion that was
+    /// cut off at its token limit
+    ///
+    /// Chat requests get `partial` as a trailing assistant message and text requests have it
+    /// appended to their prompt. Continuations are not streamed. `None` for embedding requests.
+    pub fn continuation(&self, partial: &str) -> Option<LlmRequest> {
+        match self {
+            Self::ChatCompletion(request) => {
+                let mut request = request.clone();
+                request.messages.push(ChatMessage {
+                    role: "assistant".to_string(),
+                    content: partial.to_string(),
+                    ..Default::default()
+                });
+                request.stream = None;
+                Some(Self::ChatCompletion(request))
+            }
+            Self::TextCompletion(request) => {
+                let mut request = request.clone();
+                request.prompt.push_str(partial);
+                request.stream = None;
+                Some(Self::TextCompletion(request))
+            }
+            Self::Embedding(_) => None,
+        }
+    }
+}
+
 impl Default for LlmRequest {
     fn default() -> Self {
         Self::ChatCompletion(ChatCompletionRequest::default())
@@ -295,6 +973,7 @@ impl Default for LlmRequest {
 
 /// A unified response type that can represent any LLM operation result
 #[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[serde(tag = "type")]
 pub enum LlmResponse {
     #[serde(rename = "chat.completion")]
@@ -307,8 +986,629 @@ pub enum LlmResponse {
     Embedding(EmbeddingResponse),
 }
 
+impl LlmResponse {
+    /// Whether this is a chat or text completion without any choices
+    pub fn has_empty_choices(&self) -> bool {
+        match self {
+            Self::ChatCompletion(response) => response.choices.is_empty(),
+            Self::TextCompletion(response) => response.choices.is_empty(),
+            Self::Embedding(_) => false,
+        }
+    }
+
+    /// Token usage reported by the backend, if any
+    pub fn usage(&self) -> Option<&UsageInfo> {
+        match self {
+            Self::ChatCompletion(response) => response.usage.as_ref(),
+            Self::TextCompletion(response) => response.usage.as_ref(),
+            Self::Embedding(response) => response.usage.as_ref(),
+        }
+    }
+
+    /// Give a completion without choices a single choice containing `content`
+    pub fn fill_empty_choices(&mut self, content: &str) {
+        match self {
+            Self::ChatCompletion(response) if response.choices.is_empty() => {
+                response.choices.push(ChatCompletionChoice {
+                    index: 0,
+                    message: ChatMessage {
+                        role: "assistant".to_string(),
+                        content: content.to_string(),
+                        ..Default::default()
+                    },
+                    finish_reason: Some("stop".to_string()),
+                    ..Default::default()
+                });
+            }
+            Self::TextCompletion(response) if response.choices.is_empty() => {
+                response.choices.push(TextCompletionChoice {
+                    index: 0,
+                    text: content.to_string(),
+                    finish_reason: Some("stop".to_string()),
+                    ..Default::default()
+                });
+            }
+            _ => {}
+        }
+    }
+
+    /// Keep only the first `n` choices of a completion, by index, returning how many were dropped
+    pub fn truncate_choices(&mut self, n: usize) -> usize {
+        match self {
+            Self::ChatCompletion(response) => {
+                let extra = response.choices.len().saturating_sub(n);
+                response.choices.sort_by_key(|choice| choice.index);
+                response.choices.truncate(n);
+                extra
+            }
+            Self::TextCompletion(response) => {
+                let extra = response.choices.len().saturating_sub(n);
+                response.choices.sort_by_key(|choice| choice.index);
+                response.choices.truncate(n);
+                extra
+            }
+            Self::Embedding(_) => 0,
+        }
+    }
+
+    /// The output of a single-choice completion that stopped at its token limit
+    ///
+    /// `None` unless the completion has exactly one choice, with finish reason `length`.
+    pub fn length_truncated_output(&self) -> Option<&str> {
+        match self {
+            Self::ChatCompletion(response) => match response.choices.as_slice() {
+                [choice] if choice.finish_reason.as_deref() == Some("length") => {
+                    Some(&choice.message.content)
+                }
+                _ => None,
+            },
+            Self::TextCompletion(response) => match response.choices.as_slice() {
+                [choice] if choice.finish_reason.as_deref() == Some("length") => Some(&choice.text),
+                _ => None,
+            },
+            Self::Embedding(_) => None,
+        }
+    }
+
+    /// Append the output of `continuation`, the response to a [`LlmRequest::continuation`]
+    /// of this completion
+    ///
+    /// The choice takes the finish reason of the continuation, the log probabilities of its
+    /// tokens are appended, and the token usage of both
+    /// responses is added up, since each continuation sends the whole prompt again.
+    pub fn append_continuation(&mut self, continuation: LlmResponse) {
+        let (output, finish_reason, logprobs, usage) = match continuation {
+            Self::ChatCompletion(mut response) if !response.choices.is_empty() => {
+                let choice = response.choices.swap_remove(0);
+                (
+                    choice.message.content,
+                    choice.finish_reason,
+                    choice.logprobs,
+                    response.usage,
+                )
+            }
+            Self::TextCompletion(mut response) if !response.choices.is_empty() => {
+                let choice = response.choices.swap_remove(0);
+                (
+                    choice.text,
+                    choice.finish_reason,
+                    choice.logprobs,
+                    response.usage,
+                )
+            }
+            _ => return,
+        };
+
+        let total_usage = match self {
+            Self::ChatCompletion(response) if !response.choices.is_empty() => {
+                let choice = &mut response.choices[0];
+                choice.message.content.push_str(&output);
+                choice.finish_reason = finish_reason;
+                append_logprobs(&mut choice.logprobs, logprobs);
+                &mut response.usage
+            }
+            Self::TextCompletion(response) if !response.choices.is_empty() => {
+                let choice = &mut response.choices[0];
+                choice.text.push_str(&output);
+                choice.finish_reason = finish_reason;
+                append_logprobs(&mut choice.logprobs, logprobs);
+                &mut response.usage
+            }
+            _ => return,
+        };
+        if let (Some(total), Some(usage)) = (total_usage.as_mut(), usage) {
+            total.prompt_tokens += usage.prompt_tokens;
+            total.completion_tokens += usage.completion_tokens;
+            total.total_tokens += usage.total_tokens;
+            total.prompt_tokens_cached =
+                match (total.prompt_tokens_cached, usage.prompt_tokens_cached) {
+                    (Some(total), Some(cached)) => Some(total + cached),
+                    (total, cached) => total.or(cached),
+                };
+        }
+    }
+
+    /// Report `model` as the model that generated this response
+    pub fn set_model(&mut self, model: String) {
+        match self {
+            Self::ChatCompletion(response) => response.model = model,
+            Self::TextCompletion(response) => response.model = model,
+            Self::Embedding(response) => response.model = model,
+        }
+    }
+
+    /// Attach the correlation id of the job call that produced this response
+    pub fn set_correlation_id(&mut self, id: String) {
+        let correlation_id = match self {
+            Self::ChatCompletion(response) => &mut response.correlation_id,
+            Self::TextCompletion(response) => &mut response.correlation_id,
+            Self::Embedding(response) => &mut response.correlation_id,
+        };
+        *correlation_id = Some(id);
+    }
+}
+
+/// Outcome of one request of a batch
+///
+/// Exactly one of `response` and `error` is set, so a failed request does not fail the rest
+/// of the batch.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct BatchItemResult {
+    /// Position of the request in the batch
+    pub index: usize,
+
+    /// The response, if the request succeeded
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub response: Option<LlmResponse>,
+
+    /// Why the request failed, if it did
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl BatchItemResult {
+    /// Record the outcome of the request at `index`
+    pub fn new<E: std::fmt::Display>(
+        index: usize,
+        result: std::result::Result<LlmResponse, E>,
+    ) -> Self {
+        match result {
+            Ok(response) => Self {
+                index,
+                response: Some(response),
+                error: None,
+            },
+            Err(e) => Self {
+                index,
+                response: None,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+
+    /// Whether the request succeeded
+    pub fn is_ok(&self) -> bool {
+        self.response.is_some()
+    }
+}
+
 impl Default for LlmResponse {
     fn default() -> Self {
         Self::ChatCompletion(ChatCompletionResponse::default())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(role: &str, content: &str) -> ChatMessage {
+        ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+            ..Default::default()
+        }
+    }
+
+    fn request_with_two_system_messages() -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: "test-model".to_string(),
+            messages: vec![
+                message("system", "You are helpful."),
+                message("system", "Answer briefly."),
+                message("user", "Hi"),
+            ],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_merge_policy_combines_system_messages() {
+        let mut request = request_with_two_system_messages();
+        request.apply_system_prompt_policy(SystemPromptPolicy::Merge);
+
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[0].role, "system");
+        assert_eq!(
+            request.messages[0].content,
+            "You are helpful.\n\nAnswer briefly."
+        );
+        assert_eq!(request.messages[1].role, "user");
+    }
+
+    #[test]
+    fn test_usage_prompt_tokens_cached_is_optional() {
+        let usage: UsageInfo = serde_json::from_str(
+            r#"{"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15}"#,
+        )
+        .unwrap();
+        assert_eq!(usage.prompt_tokens_cached, None);
+        assert!(!serde_json::to_string(&usage)
+            .unwrap()
+            .contains("prompt_tokens_cached"));
+
+        let usage: UsageInfo = serde_json::from_str(
+            r#"{"prompt_tokens": 10, "completion_tokens": 5, "total_tokens": 15, "prompt_tokens_cached": 8}"#,
+        )
+        .unwrap();
+        assert_eq!(usage.prompt_tokens_cached, Some(8));
+    }
+
+    #[test]
+    fn test_first_and_passthrough_policies() {
+        let mut request = request_with_two_system_messages();
+        request.apply_system_prompt_policy(SystemPromptPolicy::First);
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[0].content, "You are helpful.");
+
+        let mut request = request_with_two_system_messages();
+        request.apply_system_prompt_policy(SystemPromptPolicy::Passthrough);
+        assert_eq!(request.messages.len(), 3);
+    }
+
+    #[test]
+    fn test_default_system_prompt_is_inserted_when_absent() {
+        let mut request = ChatCompletionRequest {
+            model: "test-model".to_string(),
+            messages: vec![message("user", "Hi")],
+            ..Default::default()
+        };
+        request.apply_default_system_prompt("Be concise.", false);
+
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[0].role, "system");
+        assert_eq!(request.messages[0].content, "Be concise.");
+        assert_eq!(request.messages[1].content, "Hi");
+    }
+
+    #[test]
+    fn test_default_system_prompt_keeps_existing_system_messages() {
+        let mut request = request_with_two_system_messages();
+        request.apply_default_system_prompt("Be concise.", false);
+        assert_eq!(request.messages.len(), 3);
+        assert_eq!(request.messages[0].content, "You are helpful.");
+
+        let mut request = request_with_two_system_messages();
+        request.apply_default_system_prompt("Be concise.", true);
+        let kept: Vec<(&str, &str)> = request
+            .messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()))
+            .collect();
+        assert_eq!(kept, vec![("system", "Be concise."), ("user", "Hi")]);
+    }
+
+    fn conversation(roles: &[&str]) -> ChatCompletionRequest {
+        ChatCompletionRequest {
+            model: "test-model".to_string(),
+            messages: roles
+                .iter()
+                .enumerate()
+                .map(|(i, role)| ChatMessage {
+                    role: role.to_string(),
+                    content: i.to_string(),
+                    ..Default::default()
+                })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_message_limit_rejects_overflow() {
+        let mut request = conversation(&["system", "user", "assistant", "user"]);
+        let result = request.enforce_message_limit(3, false);
+        assert!(matches!(
+            result,
+            Err(crate::llm::LlmError::InvalidRequest(_))
+        ));
+        assert_eq!(request.messages.len(), 4);
+
+        let mut request = conversation(&["system", "user", "assistant"]);
+        assert!(request.enforce_message_limit(3, false).is_ok());
+    }
+
+    #[test]
+    fn test_message_limit_truncates_oldest_non_system_messages() {
+        let mut request = conversation(&["system", "user", "assistant", "system", "user"]);
+        request.enforce_message_limit(3, true).unwrap();
+
+        let kept: Vec<(&str, &str)> = request
+            .messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.as_str()))
+            .collect();
+        assert_eq!(kept, vec![("system", "0"), ("system", "3"), ("user", "4")]);
+
+        // System messages alone over the limit cannot be truncated
+        let mut request = conversation(&["system", "system", "user"]);
+        let result = request.enforce_message_limit(1, true);
+        assert!(matches!(
+            result,
+            Err(crate::llm::LlmError::InvalidRequest(_))
+        ));
+    }
+
+    fn embedding_response(data: Vec<(usize, Vec<f32>)>) -> EmbeddingResponse {
+        EmbeddingResponse {
+            object: "list".to_string(),
+            model: "embed-model".to_string(),
+            data: data
+                .into_iter()
+                .map(|(index, embedding)| EmbeddingData { index, embedding })
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_embedding_data_is_sorted_by_index() {
+        let mut response = embedding_response(vec![
+            (2, vec![2.0, 2.0]),
+            (0, vec![0.0, 0.0]),
+            (1, vec![1.0, 1.0]),
+        ]);
+
+        response.sort_and_validate().unwrap();
+
+        let indices: Vec<usize> = response.data.iter().map(|d| d.index).collect();
+        assert_eq!(indices, vec![0, 1, 2]);
+        assert_eq!(response.data[2].embedding, vec![2.0, 2.0]);
+    }
+
+    #[test]
+    fn test_embedding_dimension_mismatch_is_rejected() {
+        let mut response = embedding_response(vec![(0, vec![0.0, 0.0]), (1, vec![1.0])]);
+
+        let result = response.sort_and_validate();
+
+        assert!(matches!(
+            result,
+            Err(crate::llm::LlmError::ResponseParseError(message)) if message.contains("embedding 1")
+        ));
+    }
+
+    #[test]
+    fn test_passthrough_params_keeps_only_allowlisted_keys() {
+        let params = HashMap::from([
+            ("repetition_penalty".to_string(), serde_json::json!(1.1)),
+            ("unknown_field".to_string(), serde_json::json!(true)),
+        ]);
+
+        let forwarded = passthrough_params(&params, &["repetition_penalty".to_string()]);
+
+        assert_eq!(forwarded.len(), 1);
+        assert_eq!(forwarded["repetition_penalty"], serde_json::json!(1.1));
+    }
+
+    #[test]
+    fn test_deterministic_sampling_drops_top_p() {
+        let mut request = LlmRequest::ChatCompletion(ChatCompletionRequest {
+            temperature: Some(0.0),
+            top_p: Some(1.0),
+            ..Default::default()
+        });
+        request.apply_deterministic_sampling();
+        match &request {
+            LlmRequest::ChatCompletion(req) => assert_eq!(req.top_p, None),
+            other => panic!("Unexpected request type: {:?}", other),
+        }
+
+        let mut request = LlmRequest::TextCompletion(TextCompletionRequest {
+            temperature: Some(0.0),
+            top_p: Some(0.5),
+            ..Default::default()
+        });
+        request.apply_deterministic_sampling();
+        match &request {
+            LlmRequest::TextCompletion(req) => assert_eq!(req.top_p, None),
+            other => panic!("Unexpected request type: {:?}", other),
+        }
+
+        let mut request = LlmRequest::ChatCompletion(ChatCompletionRequest {
+            temperature: Some(0.7),
+            top_p: Some(1.0),
+            ..Default::default()
+        });
+        request.apply_deterministic_sampling();
+        match &request {
+            LlmRequest::ChatCompletion(req) => assert_eq!(req.top_p, Some(1.0)),
+            other => panic!("Unexpected request type: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_tool_definitions_round_trip() {
+        let json = serde_json::json!({
+            "model": "test-model",
+            "messages": [{"role": "user", "content": "Weather in Paris?"}],
+            "tools": [{
+                "type": "function",
+                "function": {
+                    "name": "get_weather",
+                    "description": "Current weather of a city",
+                    "parameters": {
+                        "type": "object",
+                        "properties": {"city": {"type": "string"}},
+                        "required": ["city"]
+                    }
+                }
+            }],
+            "tool_choice": "auto"
+        });
+
+        let request: ChatCompletionRequest = serde_json::from_value(json.clone()).unwrap();
+        let tools = request.tools.as_deref().unwrap();
+        assert_eq!(tools.len(), 1);
+        assert_eq!(tools[0].kind, "function");
+        assert_eq!(tools[0].function.name, "get_weather");
+        assert_eq!(
+            request.tool_choice,
+            Some(ToolChoice::Mode(ToolChoiceMode::Auto))
+        );
+        assert_eq!(serde_json::to_value(&request).unwrap(), json);
+
+        let choice: ToolChoice = serde_json::from_value(serde_json::json!({
+            "type": "function",
+            "function": {"name": "get_weather"}
+        }))
+        .unwrap();
+        assert!(matches!(choice, ToolChoice::Function(f) if f.function.name == "get_weather"));
+    }
+
+    #[test]
+    fn test_tool_call_message_may_have_null_content() {
+        let message: ChatMessage = serde_json::from_value(serde_json::json!({
+            "role": "assistant",
+            "content": null,
+            "tool_calls": [{
+                "id": "call_1",
+                "type": "function",
+                "function": {"name": "get_weather", "arguments": "{\"city\":\"Paris\"}"}
+            }]
+        }))
+        .unwrap();
+
+        assert_eq!(message.content, "");
+        let calls = message.tool_calls.unwrap();
+        assert_eq!(calls[0].id, "call_1");
+        assert_eq!(calls[0].function.arguments, r#"{"city":"Paris"}"#);
+    }
+
+    #[test]
+    fn test_stop_sequences_round_trip_through_llm_request() {
+        let request = LlmRequest::ChatCompletion(ChatCompletionRequest {
+            model: "test-model".to_string(),
+            messages: vec![message("user", "Hi")],
+            stop: Some(vec!["\n\n".to_string()]),
+            ..Default::default()
+        });
+        let json = serde_json::to_value(&request).unwrap();
+        assert_eq!(json["stop"], serde_json::json!(["\n\n"]));
+        match serde_json::from_value(json).unwrap() {
+            LlmRequest::ChatCompletion(req) => assert_eq!(req.stop, Some(vec!["\n\n".to_string()])),
+            other => panic!("Unexpected request type: {:?}", other),
+        }
+
+        let request = LlmRequest::TextCompletion(TextCompletionRequest {
+            model: "test-model".to_string(),
+            prompt: "Hi".to_string(),
+            ..Default::default()
+        });
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("stop").is_none());
+        match serde_json::from_value(json).unwrap() {
+            LlmRequest::TextCompletion(req) => assert_eq!(req.stop, None),
+            other => panic!("Unexpected request type: {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_service_tier_is_omitted_when_unset() {
+        let request = ChatCompletionRequest {
+            model: "test-model".to_string(),
+            messages: vec![message("user", "Hi")],
+            ..Default::default()
+        };
+        let json = serde_json::to_value(&request).unwrap();
+        assert!(json.get("service_tier").is_none());
+        assert_eq!(request.priority(), RequestPriority::Normal);
+
+        let request: ChatCompletionRequest = serde_json::from_value(serde_json::json!({
+            "model": "test-model",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "service_tier": "priority"
+        }))
+        .unwrap();
+        assert_eq!(request.service_tier.as_deref(), Some("priority"));
+        assert_eq!(
+            serde_json::to_value(&request).unwrap()["service_tier"],
+            "priority"
+        );
+        assert_eq!(
+            LlmRequest::ChatCompletion(request).priority(),
+            RequestPriority::High
+        );
+
+        for (tier, priority) in [
+            ("flex", RequestPriority::Low),
+            ("auto", RequestPriority::Normal),
+            ("scale", RequestPriority::Normal),
+        ] {
+            let request = ChatCompletionRequest {
+                service_tier: Some(tier.to_string()),
+                ..Default::default()
+            };
+            assert_eq!(request.priority(), priority, "service tier {}", tier);
+        }
+    }
+
+    #[test]
+    fn test_truncate_to_fit_keeps_system_and_latest_messages() {
+        let mut request = ChatCompletionRequest {
+            messages: vec![
+                message("system", "Be brief."),
+                message("user", &"a".repeat(40)),
+                message("user", &"b".repeat(40)),
+            ],
+            ..Default::default()
+        };
+        // Even a budget the latest message alone exceeds leaves it in place
+        assert_eq!(request.truncate_to_fit(5), 10);
+        assert_eq!(request.messages.len(), 2);
+        assert_eq!(request.messages[0].role, "system");
+        assert_eq!(request.messages[1].content, "b".repeat(40));
+
+        let mut request = TextCompletionRequest {
+            prompt: "é".repeat(40),
+            ..Default::default()
+        };
+        assert_eq!(request.truncate_to_fit(4), 6);
+        assert_eq!(request.prompt, "é".repeat(16));
+        assert_eq!(request.truncate_to_fit(4), 0);
+    }
+
+    #[test]
+    fn test_typed_choice_count_takes_precedence() {
+        let mut request = TextCompletionRequest {
+            additional_params: HashMap::from([("n".to_string(), serde_json::json!(2))]),
+            ..Default::default()
+        };
+        assert_eq!(
+            LlmRequest::TextCompletion(request.clone()).requested_choices(),
+            Some(2)
+        );
+
+        request.n = Some(4);
+        let llm_request = LlmRequest::TextCompletion(request.clone());
+        assert_eq!(llm_request.requested_choices(), Some(4));
+        assert!(llm_request.check_choice_count().is_ok());
+
+        request.n = Some(0);
+        assert!(matches!(
+            LlmRequest::TextCompletion(request).check_choice_count(),
+            Err(crate::llm::LlmError::InvalidRequest(_))
+        ));
+    }
+}
@@ -0,0 +1,280 @@
+use std::str::FromStr;
+
+use rust_decimal::Decimal;
+use serde::{Deserialize, Serialize};
+
+use super::{LlmError, LlmResponse, ModelInfo, Result, UsageInfo};
+
+/// Per-unit prices of a model, in USD
+///
+/// Rates serialize as decimal strings (`"0.000001"`), the format OpenRouter uses for model
+/// pricing, so they round-trip without floating point error. The prompt and completion rates
+/// are required and unknown rates are rejected, so a misspelled rate fails to parse instead
+/// of pricing the model too low; omitted image and request rates are free.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Pricing {
+    /// Price per prompt token
+    pub prompt: Decimal,
+
+    /// Price per completion token
+    pub completion: Decimal,
+
+    /// Price per input image
+    #[serde(default)]
+    pub image: Decimal,
+
+    /// Fixed price per request
+    #[serde(default)]
+    pub request: Decimal,
+}
+
+/// Model parameters holding the OpenRouter pricing of a model
+const PRICING_PARAMETERS: [&str; 4] = [
+    "pricing_prompt",
+    "pricing_completion",
+    "pricing_image",
+    "pricing_request",
+];
+
+impl Pricing {
+    /// Cost of a single request with the given token usage
+    pub fn cost_for(&self, usage: &UsageInfo) -> Decimal {
+        self.prompt * Decimal::from(usage.prompt_tokens)
+            + self.completion * Decimal::from(usage.completion_tokens)
+            + self.request
+    }
+}
+
+impl ModelInfo {
+    /// Pricing advertised to OpenRouter, read from the `pricing_prompt`, `pricing_completion`,
+    /// `pricing_image` and `pricing_request` parameters
+    ///
+    /// A model without `pricing_*` parameters has unknown pricing. A priced model must have
+    /// prompt and completion rates; its missing image and request rates are free. An unknown
+    /// `pricing_*` parameter or a rate that is not a valid decimal is an error.
+    pub fn openrouter_pricing(&self) -> Result<Option<Pricing>> {
+        let mut priced = false;
+        for key in self
+            .parameters
+            .keys()
+            .filter(|key| key.starts_with("pricing_"))
+        {
+            if !PRICING_PARAMETERS.contains(&key.as_str()) {
+                return Err(LlmError::Internal(format!(
+                    "Unknown pricing parameter {} for model {}",
+                    key, self.id
+                )));
+            }
+            priced = true;
+        }
+        if !priced {
+            return Ok(None);
+        }
+
+        let rate = |key: &str, required: bool| -> Result<Decimal> {
+            match self.parameters.get(key) {
+                Some(value) => Decimal::from_str(value.trim()).map_err(|e| {
+                    LlmError::Internal(format!(
+                        "Invalid {} for model {}: {:?} ({})",
+                        key, self.id, value, e
+                    ))
+                }),
+                None if required => Err(LlmError::Internal(format!(
+                    "Missing {} for model {}",
+                    key, self.id
+                ))),
+                None => Ok(Decimal::ZERO),
+            }
+        };
+
+        Ok(Some(Pricing {
+            prompt: rate("pricing_prompt", true)?,
+            completion: rate("pricing_completion", true)?,
+            image: rate("pricing_image", false)?,
+            request: rate("pricing_request", false)?,
+        }))
+    }
+}
+
+impl LlmResponse {
+    /// Report the cost of a chat or text completion, priced by `pricing` for its usage
+    ///
+    /// Embeddings and completions without usage are left without a cost.
+    pub fn apply_pricing(&mut self, pricing: &Pricing) {
+        let (usage, cost) = match self {
+            Self::ChatCompletion(response) => (&response.usage, &mut response.cost),
+            Self::TextCompletion(response) => (&response.usage, &mut response.cost),
+            Self::Embedding(_) => return,
+        };
+        *cost = usage.as_ref().map(|usage| pricing.cost_for(usage));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ChatCompletionResponse, TextCompletionResponse};
+    use std::collections::HashMap;
+
+    fn model_with_pricing(parameters: &[(&str, &str)]) -> ModelInfo {
+        ModelInfo {
+            id: "priced-model".to_string(),
+            name: "Priced Model".to_string(),
+            max_context_length: 4096,
+            supports_chat: true,
+            supports_text: true,
+            supports_embeddings: false,
+            parameters: parameters
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+        }
+    }
+
+    #[test]
+    fn test_cost_for_usage() {
+        let pricing = model_with_pricing(&[
+            ("pricing_prompt", "0.000001"),
+            ("pricing_completion", "0.000002"),
+            ("pricing_request", "0.0005"),
+        ])
+        .openrouter_pricing()
+        .unwrap()
+        .unwrap();
+        assert_eq!(pricing.image, Decimal::ZERO);
+
+        let usage = UsageInfo {
+            prompt_tokens: 1000,
+            completion_tokens: 250,
+            total_tokens: 1250,
+            prompt_tokens_cached: None,
+        };
+
+        // 1000 * 0.000001 + 250 * 0.000002 + 0.0005, exactly
+        assert_eq!(
+            pricing.cost_for(&usage),
+            Decimal::from_str("0.002").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_apply_pricing_to_completion() {
+        let pricing = Pricing {
+            prompt: Decimal::from_str("0.000003").unwrap(),
+            completion: Decimal::from_str("0.000015").unwrap(),
+            ..Default::default()
+        };
+        let mut response = LlmResponse::ChatCompletion(ChatCompletionResponse {
+            usage: Some(UsageInfo {
+                prompt_tokens: 200,
+                completion_tokens: 100,
+                total_tokens: 300,
+                prompt_tokens_cached: None,
+            }),
+            ..Default::default()
+        });
+
+        response.apply_pricing(&pricing);
+
+        let LlmResponse::ChatCompletion(chat) = &response else {
+            panic!("Unexpected response type: {:?}", response);
+        };
+        // 200 * 0.000003 + 100 * 0.000015
+        assert_eq!(chat.cost, Some(Decimal::from_str("0.0021").unwrap()));
+        let json = serde_json::to_value(chat).unwrap();
+        assert_eq!(json["cost"], "0.0021");
+
+        // Without usage there is nothing to price
+        let mut unmetered = LlmResponse::TextCompletion(TextCompletionResponse::default());
+        unmetered.apply_pricing(&pricing);
+        let json = serde_json::to_value(&unmetered).unwrap();
+        assert!(json.get("cost").is_none());
+    }
+
+    #[test]
+    fn test_cost_without_tokens() {
+        let pricing = Pricing {
+            prompt: Decimal::from_str("0.000001").unwrap(),
+            completion: Decimal::from_str("0.000002").unwrap(),
+            ..Default::default()
+        };
+        let usage = UsageInfo {
+            prompt_tokens: 0,
+            completion_tokens: 0,
+            total_tokens: 0,
+            prompt_tokens_cached: None,
+        };
+        assert_eq!(pricing.cost_for(&usage), Decimal::ZERO);
+
+        // The fixed price of a request is due even without tokens
+        let pricing = Pricing {
+            request: Decimal::from_str("0.0005").unwrap(),
+            ..pricing
+        };
+        assert_eq!(
+            pricing.cost_for(&usage),
+            Decimal::from_str("0.0005").unwrap()
+        );
+    }
+
+    #[test]
+    fn test_pricing_requires_token_rates() {
+        let pricing: Pricing = serde_json::from_value(serde_json::json!({
+            "prompt": "0.000001",
+            "completion": "0.000002",
+        }))
+        .unwrap();
+        assert_eq!(pricing.prompt, Decimal::from_str("0.000001").unwrap());
+        assert_eq!(pricing.request, Decimal::ZERO);
+
+        // A missing or misspelled rate is an error rather than free
+        let missing = serde_json::from_value::<Pricing>(serde_json::json!({"prompt": "0.000001"}));
+        assert!(missing.is_err());
+        let misspelled = serde_json::from_value::<Pricing>(serde_json::json!({
+            "prompt": "0.000001",
+            "completion": "0.000002",
+            "requests": "0.001",
+        }));
+        assert!(misspelled.is_err());
+    }
+
+    #[test]
+    fn test_unpriced_model_has_unknown_pricing() {
+        let unpriced = model_with_pricing(&[("quantization", "fp8")]);
+        assert_eq!(unpriced.openrouter_pricing().unwrap(), None);
+
+        let missing = model_with_pricing(&[("pricing_prompt", "0.000001")]).openrouter_pricing();
+        assert!(matches!(missing, Err(LlmError::Internal(_))));
+        let misspelled = model_with_pricing(&[
+            ("pricing_prompt", "0.000001"),
+            ("pricing_completion", "0.000002"),
+            ("pricing_requests", "0.001"),
+        ])
+        .openrouter_pricing();
+        assert!(matches!(misspelled, Err(LlmError::Internal(_))));
+    }
+
+    #[test]
+    fn test_pricing_serializes_as_strings() {
+        let pricing =
+            model_with_pricing(&[("pricing_prompt", "0.000001"), ("pricing_completion", "0")])
+                .openrouter_pricing()
+                .unwrap()
+                .unwrap();
+
+        let json = serde_json::to_value(pricing).unwrap();
+        assert_eq!(json["prompt"], "0.000001");
+        assert_eq!(json["completion"], "0");
+        assert_eq!(json["image"], "0");
+
+        let parsed: Pricing = serde_json::from_value(json).unwrap();
+        assert_eq!(parsed, pricing);
+    }
+
+    #[test]
+    fn test_invalid_rate_is_rejected() {
+        let result = model_with_pricing(&[("pricing_prompt", "cheap")]).openrouter_pricing();
+        assert!(matches!(result, Err(LlmError::Internal(_))));
+    }
+}
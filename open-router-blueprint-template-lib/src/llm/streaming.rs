@@ -1,17 +1,19 @@
 use std::pin::Pin;
+use std::time::Duration;
 // use std::task::{Context, Poll};
 // Removed unused import: async_trait::async_trait
 use futures::stream::{Stream, StreamExt};
+use serde::{Deserialize, Serialize};
 use tokio::sync::mpsc;
 use tokio_stream::wrappers::ReceiverStream;
 
 use super::{
-    ChatCompletionChoice, ChatCompletionResponse, ChatMessage, LlmError, Result,
-    TextCompletionChoice, TextCompletionResponse,
+    append_logprobs, ChatCompletionChoice, ChatCompletionResponse, ChatMessage, LlmError,
+    LogProbs, Result, TextCompletionChoice, TextCompletionResponse,
 };
 
 /// A chunk of a streaming chat completion response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChatCompletionChunk {
     /// The ID of the completion
     pub id: String,
@@ -30,7 +32,7 @@ pub struct ChatCompletionChunk {
 }
 
 /// A choice in a streaming chat completion response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChatCompletionStreamChoice {
     /// The index of this choice
     pub index: usize,
@@ -40,20 +42,30 @@ pub struct ChatCompletionStreamChoice {
 
     /// The reason the generation stopped, if applicable
     pub finish_reason: Option<String>,
+
+    /// Log probabilities of the tokens in this chunk, if requested with `logprobs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<LogProbs>,
 }
 
 /// A delta for a chat message in a streaming response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct ChatMessageDelta {
     /// The role of the message sender, if this is the first chunk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub role: Option<String>,
 
     /// The content delta for this chunk
+    #[serde(default, skip_serializing_if = "Option::is_none")]
     pub content: Option<String>,
+
+    /// The reasoning delta for this chunk, for models that stream their reasoning separately
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reasoning_content: Option<String>,
 }
 
 /// A chunk of a streaming text completion response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TextCompletionChunk {
     /// The ID of the completion
     pub id: String,
@@ -72,7 +84,7 @@ pub struct TextCompletionChunk {
 }
 
 /// A choice in a streaming text completion response
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct TextCompletionStreamChoice {
     /// The index of this choice
     pub index: usize,
@@ -82,11 +94,24 @@ pub struct TextCompletionStreamChoice {
 
     /// The reason the generation stopped, if applicable
     pub finish_reason: Option<String>,
+
+    /// Log probabilities of the tokens in this chunk, if requested with `logprobs`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub logprobs: Option<LogProbs>,
 }
 
 /// A stream of chat completion chunks
 pub type ChatCompletionStream = Pin<Box<dyn Stream<Item = Result<ChatCompletionChunk>> + Send>>;
 
+/// A stream of server-sent event frames, each a complete `\n\n`-terminated event
+pub type SseStream = Pin<Box<dyn Stream<Item = String> + Send>>;
+
+/// Comment frame sent to keep an idle SSE connection open; clients ignore comment lines
+pub const SSE_KEEP_ALIVE: &str = ": keep-alive\n\n";
+
+/// Event frame marking the end of an SSE stream
+pub const SSE_DONE: &str = "data: [DONE]\n\n";
+
 /// A stream of text completion chunks
 pub type TextCompletionStream = Pin<Box<dyn Stream<Item = Result<TextCompletionChunk>> + Send>>;
 
@@ -118,149 +143,616 @@ pub fn create_text_completion_stream(
     Box::pin(ReceiverStream::new(receiver))
 }
 
+/// Frame a chat completion stream as server-sent events
+///
+/// Every chunk becomes a `data:` event and the stream ends with `data: [DONE]`. An error ends
+/// the stream with a `data: {"error": ...}` event instead. While waiting for the first chunk,
+/// a [`SSE_KEEP_ALIVE`] comment is sent every `keep_alive` so proxies and browsers don't close
+/// the connection during a slow prefill; once chunks flow no more comments are sent.
+pub fn chat_completion_sse(
+    stream: ChatCompletionStream,
+    keep_alive: Option<Duration>,
+) -> SseStream {
+    let state = Some((stream, keep_alive));
+    Box::pin(futures::stream::unfold(state, |state| async move {
+        let (mut stream, keep_alive) = state?;
+
+        let next = match keep_alive {
+            Some(interval) => match tokio::time::timeout(interval, stream.next()).await {
+                Ok(next) => next,
+                Err(_) => return Some((SSE_KEEP_ALIVE.to_string(), Some((stream, keep_alive)))),
+            },
+            None => stream.next().await,
+        };
+
+        match next {
+            // Real chunks are flowing, so the connection no longer needs keep-alives
+            Some(Ok(chunk)) => {
+                let frame = match serde_json::to_string(&chunk) {
+                    Ok(json) => format!("data: {}\n\n", json),
+                    Err(e) => return Some((sse_error(&e.to_string()), None)),
+                };
+                Some((frame, Some((stream, None))))
+            }
+            Some(Err(e)) => Some((sse_error(&e.to_string()), None)),
+            None => Some((SSE_DONE.to_string(), None)),
+        }
+    }))
+}
+
+/// An SSE event carrying an OpenAI-style error object
+fn sse_error(message: &str) -> String {
+    format!(
+        "data: {}\n\n",
+        serde_json::json!({ "error": { "message": message } })
+    )
+}
+
+/// Replay a complete chat completion response as a stream of chunks
+///
+/// Lets clients without native streaming serve streaming requests. Like OpenAI, the stream
+/// opens with a role-only chunk (`delta.role` set, no content) for every choice, which many
+/// client libraries need to initialize the message. It is followed by one chunk with each
+/// choice's content and a final chunk carrying the finish reasons.
+pub fn fake_stream_from_response(response: ChatCompletionResponse) -> ChatCompletionStream {
+    let chunk = |choices: Vec<ChatCompletionStreamChoice>| ChatCompletionChunk {
+        id: response.id.clone(),
+        object: "chat.completion.chunk".to_string(),
+        created: response.created,
+        model: response.model.clone(),
+        choices,
+    };
+
+    let role_chunk = chunk(
+        response
+            .choices
+            .iter()
+            .map(|choice| ChatCompletionStreamChoice {
+                index: choice.index,
+                delta: ChatMessageDelta {
+                    role: Some(choice.message.role.clone()),
+                    content: None,
+                    ..Default::default()
+                },
+                finish_reason: None,
+                ..Default::default()
+            })
+            .collect(),
+    );
+    let content_chunk = chunk(
+        response
+            .choices
+            .iter()
+            .map(|choice| ChatCompletionStreamChoice {
+                index: choice.index,
+                delta: ChatMessageDelta {
+                    role: None,
+                    content: Some(choice.message.content.clone()),
+                    reasoning_content: choice.message.reasoning_content.clone(),
+                },
+                finish_reason: None,
+                logprobs: choice.logprobs.clone(),
+            })
+            .collect(),
+    );
+    let finish_chunk = chunk(
+        response
+            .choices
+            .iter()
+            .map(|choice| ChatCompletionStreamChoice {
+                index: choice.index,
+                delta: ChatMessageDelta {
+                    role: None,
+                    content: None,
+                    ..Default::default()
+                },
+                finish_reason: choice.finish_reason.clone(),
+                ..Default::default()
+            })
+            .collect(),
+    );
+
+    Box::pin(futures::stream::iter([
+        Ok(role_chunk),
+        Ok(content_chunk),
+        Ok(finish_chunk),
+    ]))
+}
+
+/// A chat completion as a single chunk, for clients that cannot stream
+///
+/// Unlike [`fake_stream_from_response`], the chunk carries each choice's role, content and
+/// finish reason at once, for callers that emit a buffered response as one event.
+pub fn chat_chunk_from_response(response: ChatCompletionResponse) -> ChatCompletionChunk {
+    ChatCompletionChunk {
+        id: response.id,
+        object: "chat.completion.chunk".to_string(),
+        created: response.created,
+        model: response.model,
+        choices: response
+            .choices
+            .into_iter()
+            .map(|choice| ChatCompletionStreamChoice {
+                index: choice.index,
+                delta: ChatMessageDelta {
+                    role: Some(choice.message.role),
+                    content: Some(choice.message.content),
+                    reasoning_content: choice.message.reasoning_content,
+                },
+                finish_reason: choice.finish_reason,
+                logprobs: choice.logprobs,
+            })
+            .collect(),
+    }
+}
+
 /// Utility to collect a chat completion stream into a single response
+///
+/// Every chunk, including the first, is folded into per-index choice entries. A chunk that
+/// only carries a `finish_reason` for an index that has not been seen yet still creates the
+/// entry, so backends that emit standalone finish chunks are collected correctly. Reasoning
+/// deltas are collected into `reasoning_content`, separate from the answer, and the log
+/// probabilities of every chunk are appended in order. The response keeps
+/// the `created` timestamp of the first chunk, which marks the start of the generation.
 pub async fn collect_chat_completion_stream(
     mut stream: ChatCompletionStream,
 ) -> Result<ChatCompletionResponse> {
-    // let mut id = String::new();
-    // let mut model = String::new();
-    // let mut created = 0;
-    let mut choices = Vec::new();
-
-    // Process the first chunk to get metadata
-    if let Some(first_chunk_result) = stream.next().await {
-        let first_chunk = first_chunk_result?;
-        // id = first_chunk.id;
-        // model = first_chunk.model;
-        // created = first_chunk.created;
-
-        // Initialize choices with empty content
-        for choice in first_chunk.choices {
-            let role = choice.delta.role.unwrap_or_else(|| "assistant".to_string());
-            choices.push((choice.index, role, String::new(), choice.finish_reason));
-        }
-    } else {
-        return Err(LlmError::RequestFailed("Empty stream".to_string()));
-    }
+    let mut choices: Vec<ChatCompletionChoice> = Vec::new();
+    let mut created = None;
 
-    // Process the rest of the chunks
     while let Some(chunk_result) = stream.next().await {
         let chunk = chunk_result?;
+        created.get_or_insert(chunk.created);
 
         for choice in chunk.choices {
-            if let Some(content) = choice.delta.content {
-                if let Some((_, _, content_buffer, _)) = choices
-                    .iter_mut()
-                    .find(|(idx, _, _, _)| *idx == choice.index)
-                {
-                    content_buffer.push_str(&content);
+            let position = match choices.iter().position(|c| c.index == choice.index) {
+                Some(position) => position,
+                None => {
+                    choices.push(ChatCompletionChoice {
+                        index: choice.index,
+                        message: ChatMessage {
+                            role: "assistant".to_string(),
+                            content: String::new(),
+                            ..Default::default()
+                        },
+                        finish_reason: None,
+                        ..Default::default()
+                    });
+                    choices.len() - 1
                 }
+            };
+            let collected = &mut choices[position];
+
+            if let Some(delta_role) = choice.delta.role {
+                collected.message.role = delta_role;
+            }
+
+            if let Some(content) = choice.delta.content {
+                collected.message.content.push_str(&content);
+            }
+
+            if let Some(reasoning) = choice.delta.reasoning_content {
+                collected
+                    .message
+                    .reasoning_content
+                    .get_or_insert_with(String::new)
+                    .push_str(&reasoning);
             }
 
+            append_logprobs(&mut collected.logprobs, choice.logprobs);
+
             if choice.finish_reason.is_some() {
-                if let Some((_, _, _, finish_reason)) = choices
-                    .iter_mut()
-                    .find(|(idx, _, _, _)| *idx == choice.index)
-                {
-                    *finish_reason = choice.finish_reason;
-                }
+                collected.finish_reason = choice.finish_reason;
             }
         }
     }
 
-    // Convert to ChatCompletionResponse
-    let response_choices = choices
-        .into_iter()
-        .map(
-            |(index, role, content, finish_reason)| ChatCompletionChoice {
-                index,
-                message: ChatMessage {
-                    role,
-                    content,
-                    name: None,
-                },
-                finish_reason,
-            },
-        )
-        .collect();
+    let Some(created) = created else {
+        return Err(LlmError::RequestFailed("Empty stream".to_string()));
+    };
+
+    choices.sort_by_key(|choice| choice.index);
 
     Ok(ChatCompletionResponse {
         id: "stream-collected".to_string(),
         object: "chat.completion".to_string(),
-        created: std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs(),
+        created,
         model: "unknown".to_string(),
-        choices: response_choices,
+        choices,
         usage: None, // Usage information is not available when streaming
+        cost: None,
+        correlation_id: None,
     })
 }
 
 /// Utility to collect a text completion stream into a single response
+///
+/// Like [`collect_chat_completion_stream`], finish-only chunks for unseen indices create
+/// their choice entry instead of being dropped, log probabilities are appended in order, and
+/// the response keeps the `created` timestamp of the first chunk.
 pub async fn collect_text_completion_stream(
     mut stream: TextCompletionStream,
 ) -> Result<TextCompletionResponse> {
-    // let mut id = String::new();
-    // let mut model = String::new();
-    // let mut created = 0;
-    let mut choices = Vec::new();
-
-    // Process the first chunk to get metadata
-    if let Some(first_chunk_result) = stream.next().await {
-        let first_chunk = first_chunk_result?;
-        // id = first_chunk.id;
-        // model = first_chunk.model;
-        // created = first_chunk.created;
-
-        // Initialize choices with empty content
-        for choice in first_chunk.choices {
-            choices.push((choice.index, choice.text, choice.finish_reason));
-        }
-    } else {
-        return Err(LlmError::RequestFailed("Empty stream".to_string()));
-    }
+    let mut choices: Vec<TextCompletionChoice> = Vec::new();
+    let mut created = None;
 
-    // Process the rest of the chunks
     while let Some(chunk_result) = stream.next().await {
         let chunk = chunk_result?;
+        created.get_or_insert(chunk.created);
 
         for choice in chunk.choices {
-            if let Some((_, text_buffer, _)) =
-                choices.iter_mut().find(|(idx, _, _)| *idx == choice.index)
-            {
-                text_buffer.push_str(&choice.text);
-            }
+            let position = match choices.iter().position(|c| c.index == choice.index) {
+                Some(position) => position,
+                None => {
+                    choices.push(TextCompletionChoice {
+                        index: choice.index,
+                        text: String::new(),
+                        finish_reason: None,
+                        ..Default::default()
+                    });
+                    choices.len() - 1
+                }
+            };
+            let collected = &mut choices[position];
+
+            collected.text.push_str(&choice.text);
+            append_logprobs(&mut collected.logprobs, choice.logprobs);
 
             if choice.finish_reason.is_some() {
-                if let Some((_, _, finish_reason)) =
-                    choices.iter_mut().find(|(idx, _, _)| *idx == choice.index)
-                {
-                    *finish_reason = choice.finish_reason;
-                }
+                collected.finish_reason = choice.finish_reason;
             }
         }
     }
 
-    // Convert to TextCompletionResponse
-    let response_choices = choices
-        .into_iter()
-        .map(|(index, text, finish_reason)| TextCompletionChoice {
-            index,
-            text,
-            finish_reason,
-        })
-        .collect();
+    let Some(created) = created else {
+        return Err(LlmError::RequestFailed("Empty stream".to_string()));
+    };
+
+    choices.sort_by_key(|choice| choice.index);
 
     Ok(TextCompletionResponse {
         id: "stream-collected".to_string(),
         object: "text_completion".to_string(),
-        created: std::time::SystemTime::now()
-            .duration_since(std::time::UNIX_EPOCH)
-            .unwrap_or_default()
-            .as_secs(),
+        created,
         model: "unknown".to_string(),
-        choices: response_choices,
+        choices,
         usage: None, // Usage information is not available when streaming
+        cost: None,
+        correlation_id: None,
     })
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat_chunk(
+        index: usize,
+        content: Option<&str>,
+        finish: Option<&str>,
+    ) -> ChatCompletionChunk {
+        ChatCompletionChunk {
+            id: "chunk".to_string(),
+            object: "chat.completion.chunk".to_string(),
+            created: 0,
+            model: "test-model".to_string(),
+            choices: vec![ChatCompletionStreamChoice {
+                index,
+                delta: ChatMessageDelta {
+                    role: None,
+                    content: content.map(str::to_string),
+                    ..Default::default()
+                },
+                finish_reason: finish.map(str::to_string),
+                ..Default::default()
+            }],
+        }
+    }
+
+    #[tokio::test]
+    async fn test_collect_chat_stream_with_finish_only_chunk_for_new_index() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(Ok(chat_chunk(0, Some("Hello"), None)))
+            .await
+            .unwrap();
+        tx.send(Ok(chat_chunk(0, Some(" world"), Some("stop"))))
+            .await
+            .unwrap();
+        tx.send(Ok(chat_chunk(1, None, Some("stop"))))
+            .await
+            .unwrap();
+        drop(tx);
+
+        let response = collect_chat_completion_stream(create_chat_completion_stream(rx))
+            .await
+            .unwrap();
+
+        assert_eq!(response.choices.len(), 2);
+        assert_eq!(response.choices[0].message.content, "Hello world");
+        assert_eq!(response.choices[0].finish_reason.as_deref(), Some("stop"));
+        assert_eq!(response.choices[1].index, 1);
+        assert_eq!(response.choices[1].message.role, "assistant");
+        assert_eq!(response.choices[1].message.content, "");
+        assert_eq!(response.choices[1].finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_collect_text_stream_with_finish_only_chunk_for_new_index() {
+        let (tx, rx) = mpsc::channel(4);
+        for (index, text, finish) in [(0, "Once", None), (1, "", Some("stop"))] {
+            tx.send(Ok(TextCompletionChunk {
+                id: "chunk".to_string(),
+                object: "text_completion.chunk".to_string(),
+                created: 0,
+                model: "test-model".to_string(),
+                choices: vec![TextCompletionStreamChoice {
+                    index,
+                    text: text.to_string(),
+                    finish_reason: finish.map(str::to_string),
+                    ..Default::default()
+                }],
+            }))
+            .await
+            .unwrap();
+        }
+        drop(tx);
+
+        let response = collect_text_completion_stream(create_text_completion_stream(rx))
+            .await
+            .unwrap();
+
+        assert_eq!(response.choices.len(), 2);
+        assert_eq!(response.choices[0].text, "Once");
+        assert_eq!(response.choices[1].finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_fake_stream_starts_with_role_only_chunk() {
+        let response = ChatCompletionResponse {
+            id: "chatcmpl-1".to_string(),
+            object: "chat.completion".to_string(),
+            model: "test-model".to_string(),
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                message: ChatMessage {
+                    role: "assistant".to_string(),
+                    content: "Hello world".to_string(),
+                    ..Default::default()
+                },
+                finish_reason: Some("stop".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let chunks: Vec<ChatCompletionChunk> = fake_stream_from_response(response.clone())
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+
+        let first = &chunks[0].choices[0];
+        assert_eq!(first.delta.role.as_deref(), Some("assistant"));
+        assert_eq!(first.delta.content, None);
+        assert_eq!(first.finish_reason, None);
+
+        let collected = collect_chat_completion_stream(fake_stream_from_response(response))
+            .await
+            .unwrap();
+        assert_eq!(collected.choices[0].message.content, "Hello world");
+        assert_eq!(collected.choices[0].finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_single_chunk_from_response() {
+        let response = ChatCompletionResponse {
+            id: "chatcmpl-1".to_string(),
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                message: ChatMessage {
+                    role: "assistant".to_string(),
+                    content: "Hello world".to_string(),
+                    ..Default::default()
+                },
+                finish_reason: Some("stop".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let chunk = chat_chunk_from_response(response);
+        assert_eq!(chunk.object, "chat.completion.chunk");
+        assert_eq!(chunk.choices[0].delta.role.as_deref(), Some("assistant"));
+        assert_eq!(
+            chunk.choices[0].delta.content.as_deref(),
+            Some("Hello world")
+        );
+
+        let stream: ChatCompletionStream = Box::pin(futures::stream::iter([Ok(chunk)]));
+        let collected = collect_chat_completion_stream(stream).await.unwrap();
+        assert_eq!(collected.choices[0].message.content, "Hello world");
+        assert_eq!(collected.choices[0].finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_stream_and_collected_response_share_created() {
+        let response = ChatCompletionResponse {
+            created: 1_700_000_000,
+            choices: vec![ChatCompletionChoice {
+                index: 0,
+                message: ChatMessage {
+                    role: "assistant".to_string(),
+                    content: "Hello".to_string(),
+                    ..Default::default()
+                },
+                finish_reason: Some("stop".to_string()),
+                ..Default::default()
+            }],
+            ..Default::default()
+        };
+
+        let chunks: Vec<ChatCompletionChunk> = fake_stream_from_response(response.clone())
+            .map(|chunk| chunk.unwrap())
+            .collect()
+            .await;
+        assert!(chunks.iter().all(|chunk| chunk.created == 1_700_000_000));
+
+        let collected = collect_chat_completion_stream(fake_stream_from_response(response))
+            .await
+            .unwrap();
+        assert_eq!(collected.created, 1_700_000_000);
+
+        // Later chunks never move the start of the generation
+        let (tx, rx) = mpsc::channel(2);
+        for created in [1_700_000_000, 1_700_000_001] {
+            tx.send(Ok(TextCompletionChunk {
+                id: "chunk".to_string(),
+                object: "text_completion.chunk".to_string(),
+                created,
+                model: "test-model".to_string(),
+                choices: vec![TextCompletionStreamChoice {
+                    index: 0,
+                    text: "Once".to_string(),
+                    finish_reason: None,
+                    ..Default::default()
+                }],
+            }))
+            .await
+            .unwrap();
+        }
+        drop(tx);
+        let collected = collect_text_completion_stream(create_text_completion_stream(rx))
+            .await
+            .unwrap();
+        assert_eq!(collected.created, 1_700_000_000);
+    }
+
+    #[tokio::test]
+    async fn test_sse_keep_alive_only_before_first_chunk() {
+        let (tx, rx) = mpsc::channel(4);
+        tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            tx.send(Ok(chat_chunk(0, Some("Hello"), None)))
+                .await
+                .unwrap();
+            // A pause between chunks longer than the interval sends no keep-alive
+            tokio::time::sleep(Duration::from_millis(100)).await;
+            tx.send(Ok(chat_chunk(0, Some(" world"), Some("stop"))))
+                .await
+                .unwrap();
+        });
+
+        let frames: Vec<String> = chat_completion_sse(
+            create_chat_completion_stream(rx),
+            Some(Duration::from_millis(20)),
+        )
+        .collect()
+        .await;
+
+        let first_data = frames
+            .iter()
+            .position(|frame| frame.starts_with("data: {"))
+            .unwrap();
+        assert!(first_data >= 2);
+        assert!(frames[..first_data]
+            .iter()
+            .all(|frame| frame == SSE_KEEP_ALIVE));
+        assert_eq!(frames.len(), first_data + 3);
+        assert!(frames[first_data].contains(r#""content":"Hello""#));
+        assert!(frames[first_data + 1].contains(r#""finish_reason":"stop""#));
+        assert_eq!(frames[first_data + 2], SSE_DONE);
+    }
+
+    #[tokio::test]
+    async fn test_collect_chat_stream_keeps_reasoning_separate() {
+        let (tx, rx) = mpsc::channel(4);
+        for data in [
+            r#"{"id":"c","object":"chat.completion.chunk","created":0,"model":"deepseek-r1","choices":[{"index":0,"delta":{"role":"assistant","reasoning_content":"The user greets. "},"finish_reason":null}]}"#,
+            r#"{"id":"c","object":"chat.completion.chunk","created":0,"model":"deepseek-r1","choices":[{"index":0,"delta":{"reasoning_content":"Greet back."},"finish_reason":null}]}"#,
+            r#"{"id":"c","object":"chat.completion.chunk","created":0,"model":"deepseek-r1","choices":[{"index":0,"delta":{"content":"Hi!"},"finish_reason":"stop"}]}"#,
+        ] {
+            let chunk: ChatCompletionChunk = serde_json::from_str(data).unwrap();
+            tx.send(Ok(chunk)).await.unwrap();
+        }
+        drop(tx);
+
+        let response = collect_chat_completion_stream(create_chat_completion_stream(rx))
+            .await
+            .unwrap();
+
+        let message = &response.choices[0].message;
+        assert_eq!(message.content, "Hi!");
+        assert_eq!(
+            message.reasoning_content.as_deref(),
+            Some("The user greets. Greet back.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_collect_chat_stream_aggregates_logprobs() {
+        let (tx, rx) = mpsc::channel(4);
+        for data in [
+            r#"{"id":"c","object":"chat.completion.chunk","created":0,"model":"llama","choices":[{"index":0,"delta":{"role":"assistant","content":""},"logprobs":null,"finish_reason":null}]}"#,
+            r#"{"id":"c","object":"chat.completion.chunk","created":0,"model":"llama","choices":[{"index":0,"delta":{"content":"Hello"},"logprobs":{"content":[{"token":"Hello","logprob":-0.25,"bytes":[72,101,108,108,111],"top_logprobs":[{"token":"Hello","logprob":-0.25},{"token":"Hi","logprob":-1.5}]}]},"finish_reason":null}]}"#,
+            r#"{"id":"c","object":"chat.completion.chunk","created":0,"model":"llama","choices":[{"index":0,"delta":{"content":"!"},"logprobs":{"content":[{"token":"!","logprob":-0.5,"top_logprobs":[]}]},"finish_reason":"stop"}]}"#,
+        ] {
+            let chunk: ChatCompletionChunk = serde_json::from_str(data).unwrap();
+            tx.send(Ok(chunk)).await.unwrap();
+        }
+        drop(tx);
+
+        let response = collect_chat_completion_stream(create_chat_completion_stream(rx))
+            .await
+            .unwrap();
+
+        let choice = &response.choices[0];
+        assert_eq!(choice.message.content, "Hello!");
+        let logprobs = &choice.logprobs.as_ref().unwrap().content;
+        assert_eq!(logprobs.len(), 2);
+        assert_eq!(logprobs[0].token, "Hello");
+        assert_eq!(logprobs[0].logprob, -0.25);
+        assert_eq!(logprobs[0].bytes.as_deref(), Some("Hello".as_bytes()));
+        assert_eq!(logprobs[0].top_logprobs[1].token, "Hi");
+        assert_eq!(logprobs[1].token, "!");
+        assert_eq!(logprobs[1].logprob, -0.5);
+    }
+
+    #[tokio::test]
+    async fn test_collect_text_stream_aggregates_logprobs() {
+        let (tx, rx) = mpsc::channel(4);
+        for data in [
+            r#"{"id":"c","object":"text_completion","created":0,"model":"llama","choices":[{"index":0,"text":"Once","logprobs":{"tokens":["Once"],"token_logprobs":[-0.1],"top_logprobs":[{"Once":-0.1,"In":-2.5}],"text_offset":[0]},"finish_reason":null}]}"#,
+            r#"{"id":"c","object":"text_completion","created":0,"model":"llama","choices":[{"index":0,"text":" upon","logprobs":{"tokens":[" upon"],"token_logprobs":[-0.2],"top_logprobs":[{" upon":-0.2}],"text_offset":[4]},"finish_reason":"length"}]}"#,
+        ] {
+            let chunk: TextCompletionChunk = serde_json::from_str(data).unwrap();
+            tx.send(Ok(chunk)).await.unwrap();
+        }
+        drop(tx);
+
+        let response = collect_text_completion_stream(create_text_completion_stream(rx))
+            .await
+            .unwrap();
+
+        let choice = &response.choices[0];
+        assert_eq!(choice.text, "Once upon");
+        let logprobs = choice.logprobs.as_ref().unwrap();
+        assert_eq!(logprobs.tokens, vec!["Once", " upon"]);
+        assert_eq!(logprobs.token_logprobs, vec![Some(-0.1), Some(-0.2)]);
+        assert_eq!(logprobs.top_logprobs[0].as_ref().unwrap()["In"], -2.5);
+        assert_eq!(logprobs.text_offset, vec![0, 4]);
+    }
+
+    #[tokio::test]
+    async fn test_collect_stream_without_logprobs_has_none() {
+        let (tx, rx) = mpsc::channel(4);
+        tx.send(Ok(chat_chunk(0, Some("Hello"), Some("stop"))))
+            .await
+            .unwrap();
+        drop(tx);
+
+        let response = collect_chat_completion_stream(create_chat_completion_stream(rx))
+            .await
+            .unwrap();
+
+        assert!(response.choices[0].logprobs.is_none());
+    }
+}
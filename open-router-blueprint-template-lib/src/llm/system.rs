@@ -0,0 +1,50 @@
+use std::time::Duration;
+
+use sysinfo::System;
+
+/// How often the template's `local` client samples the host's CPU and memory usage
+pub const SYSTEM_METRICS_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Samples the CPU and memory usage of the whole host
+///
+/// The usage is that of every process on the machine, not just the LLM backend, so a node
+/// sharing its host with other workloads reports their load too.
+pub struct SystemMetricsSampler {
+    system: System,
+}
+
+impl SystemMetricsSampler {
+    /// Create a sampler, taking the CPU baseline the first sample is measured against
+    pub fn new() -> Self {
+        let mut system = System::new();
+        system.refresh_cpu();
+        system.refresh_memory();
+        Self { system }
+    }
+
+    /// Time to wait after creating the sampler before the first CPU sample is meaningful
+    pub const fn warm_up() -> Duration {
+        sysinfo::MINIMUM_CPU_UPDATE_INTERVAL
+    }
+
+    /// CPU and memory utilization of the host (0.0 - 1.0) since the previous sample
+    pub fn sample(&mut self) -> (f32, f32) {
+        self.system.refresh_cpu();
+        self.system.refresh_memory();
+
+        let cpu = self.system.global_cpu_info().cpu_usage() / 100.0;
+        let total_memory = self.system.total_memory();
+        let memory = if total_memory == 0 {
+            0.0
+        } else {
+            self.system.used_memory() as f64 / total_memory as f64
+        };
+        (cpu.clamp(0.0, 1.0), (memory as f32).clamp(0.0, 1.0))
+    }
+}
+
+impl Default for SystemMetricsSampler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,87 @@
+use std::fmt;
+
+/// A backend version in `major.minor.patch` form, used to gate version-specific features
+///
+/// Only the leading numeric components are compared, so pre-release and build suffixes such
+/// as `0.6.3.post1` or `0.5.1+cu121` are ignored.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BackendVersion {
+    pub major: u64,
+    pub minor: u64,
+    pub patch: u64,
+}
+
+impl BackendVersion {
+    /// Create a version from its components
+    pub const fn new(major: u64, minor: u64, patch: u64) -> Self {
+        Self {
+            major,
+            minor,
+            patch,
+        }
+    }
+
+    /// Parse a version string such as `0.6.3` or `v0.1.32`
+    ///
+    /// Missing minor or patch components are zero. Returns `None` if the string does not
+    /// start with a number.
+    pub fn parse(version: &str) -> Option<Self> {
+        let version = version.trim();
+        let version = version.strip_prefix('v').unwrap_or(version);
+
+        let mut components = version.split('.').map(|component| {
+            let digits: String = component
+                .chars()
+                .take_while(|c| c.is_ascii_digit())
+                .collect();
+            digits.parse::<u64>().ok()
+        });
+
+        let major = components.next().flatten()?;
+        let minor = components.next().flatten().unwrap_or(0);
+        let patch = components.next().flatten().unwrap_or(0);
+        Some(Self::new(major, minor, patch))
+    }
+}
+
+impl fmt::Display for BackendVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_versions() {
+        assert_eq!(
+            BackendVersion::parse("0.6.3"),
+            Some(BackendVersion::new(0, 6, 3))
+        );
+        assert_eq!(
+            BackendVersion::parse("v0.1.32"),
+            Some(BackendVersion::new(0, 1, 32))
+        );
+        assert_eq!(
+            BackendVersion::parse("0.6.3.post1"),
+            Some(BackendVersion::new(0, 6, 3))
+        );
+        assert_eq!(
+            BackendVersion::parse("0.5.1+cu121"),
+            Some(BackendVersion::new(0, 5, 1))
+        );
+        assert_eq!(
+            BackendVersion::parse("1"),
+            Some(BackendVersion::new(1, 0, 0))
+        );
+        assert_eq!(BackendVersion::parse("dev"), None);
+    }
+
+    #[test]
+    fn test_versions_order_numerically() {
+        assert!(BackendVersion::new(0, 10, 0) > BackendVersion::new(0, 9, 9));
+        assert!(BackendVersion::parse("0.1.14") >= Some(BackendVersion::new(0, 1, 14)));
+    }
+}
@@ -0,0 +1,208 @@
+//! Circuit breakers that take chronically failing nodes out of rotation for a while
+//!
+//! Every node has a [`CircuitBreaker`] fed with the outcome of the requests it serves. While
+//! closed, it counts consecutive failures; once `failure_grace_count` of them followed each
+//! other within `failure_window_seconds`, the circuit opens and the node is marked failed.
+//! After `failure_cooldown_seconds` the circuit is half-open: a single trial request is let
+//! through, which closes the circuit again when it succeeds and reopens it when it fails.
+
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// State of a node's [`CircuitBreaker`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CircuitState {
+    /// Requests are sent to the node and its failures are counted
+    Closed,
+
+    /// The node failed too often and receives no requests until its cool-down has passed
+    Open,
+
+    /// The cool-down has passed and a trial request decides whether the circuit closes
+    HalfOpen,
+}
+
+/// Tracks the failures of a node to decide whether it may receive requests
+#[derive(Debug, Clone, Default)]
+pub struct CircuitBreaker {
+    /// Consecutive failures while closed, with the time of the first
+    streak: Option<(u32, Instant)>,
+
+    /// When the circuit last opened, unless it is closed
+    opened_at: Option<Instant>,
+
+    /// When the trial request of the half-open circuit was let through
+    trial_started: Option<Instant>,
+}
+
+impl CircuitBreaker {
+    /// The state of the circuit at `now` for a cool-down of `cool_down`
+    pub fn state(&self, now: Instant, cool_down: Duration) -> CircuitState {
+        match self.opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if now.duration_since(opened_at) < cool_down => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Consecutive failures counted while closed
+    pub fn consecutive_failures(&self) -> u32 {
+        self.streak.map_or(0, |(failures, _)| failures)
+    }
+
+    /// Whether the circuit is half-open with no trial request under way
+    ///
+    /// A trial request that never reported back is given up after another cool-down.
+    pub fn allows_trial(&self, now: Instant, cool_down: Duration) -> bool {
+        self.state(now, cool_down) == CircuitState::HalfOpen
+            && self
+                .trial_started
+                .is_none_or(|started| now.duration_since(started) >= cool_down)
+    }
+
+    /// Let the trial request of a half-open circuit through, returning whether it was one
+    pub fn start_trial(&mut self, now: Instant, cool_down: Duration) -> bool {
+        if !self.allows_trial(now, cool_down) {
+            return false;
+        }
+        self.trial_started = Some(now);
+        true
+    }
+
+    /// Record a failed request, returning whether it opened the circuit
+    ///
+    /// A closed circuit opens on the `threshold`th failure in a row, with the streak starting
+    /// over when its first failure is older than `window`. A half-open circuit reopens on any
+    /// failure, and failures of requests sent before an open circuit opened are ignored.
+    pub fn record_failure(
+        &mut self,
+        now: Instant,
+        threshold: u32,
+        window: Duration,
+        cool_down: Duration,
+    ) -> bool {
+        match self.state(now, cool_down) {
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => {
+                self.open(now);
+                true
+            }
+            CircuitState::Closed => {
+                let (failures, first_failure) = match self.streak {
+                    Some((failures, first)) if now.duration_since(first) <= window => {
+                        (failures + 1, first)
+                    }
+                    _ => (1, now),
+                };
+                if failures >= threshold.max(1) {
+                    self.open(now);
+                    return true;
+                }
+                self.streak = Some((failures, first_failure));
+                false
+            }
+        }
+    }
+
+    /// Record a successful request, returning whether it closed the circuit
+    ///
+    /// A success starts the failure streak of a closed circuit over and closes a half-open
+    /// one. Successes of requests sent before an open circuit opened are ignored.
+    pub fn record_success(&mut self, now: Instant, cool_down: Duration) -> bool {
+        match self.state(now, cool_down) {
+            CircuitState::Open => false,
+            CircuitState::HalfOpen => {
+                self.reset();
+                true
+            }
+            CircuitState::Closed => {
+                self.streak = None;
+                false
+            }
+        }
+    }
+
+    /// Close the circuit and forget its failures
+    pub fn reset(&mut self) {
+        *self = Self::default();
+    }
+
+    fn open(&mut self, now: Instant) {
+        self.streak = None;
+        self.opened_at = Some(now);
+        self.trial_started = None;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WINDOW: Duration = Duration::from_secs(60);
+    const COOL_DOWN: Duration = Duration::from_secs(30);
+
+    #[test]
+    fn test_circuit_opens_after_threshold() {
+        let mut breaker = CircuitBreaker::default();
+        let now = Instant::now();
+
+        assert!(!breaker.record_failure(now, 3, WINDOW, COOL_DOWN));
+        assert!(!breaker.record_failure(now, 3, WINDOW, COOL_DOWN));
+        assert_eq!(breaker.consecutive_failures(), 2);
+        assert!(breaker.record_failure(now, 3, WINDOW, COOL_DOWN));
+        assert_eq!(breaker.state(now, COOL_DOWN), CircuitState::Open);
+
+        // Late failures of an open circuit don't extend its cool-down
+        let later = now + Duration::from_secs(10);
+        assert!(!breaker.record_failure(later, 3, WINDOW, COOL_DOWN));
+        assert_eq!(
+            breaker.state(now + COOL_DOWN, COOL_DOWN),
+            CircuitState::HalfOpen
+        );
+    }
+
+    #[test]
+    fn test_streak_starts_over_after_window() {
+        let mut breaker = CircuitBreaker::default();
+        let now = Instant::now();
+
+        assert!(!breaker.record_failure(now, 2, WINDOW, COOL_DOWN));
+        let later = now + WINDOW + Duration::from_secs(1);
+        assert!(!breaker.record_failure(later, 2, WINDOW, COOL_DOWN));
+        assert_eq!(breaker.consecutive_failures(), 1);
+        assert_eq!(breaker.state(later, COOL_DOWN), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_half_open_allows_one_trial() {
+        let mut breaker = CircuitBreaker::default();
+        let now = Instant::now();
+        assert!(breaker.record_failure(now, 1, WINDOW, COOL_DOWN));
+        assert!(!breaker.allows_trial(now, COOL_DOWN));
+
+        let half_open = now + COOL_DOWN;
+        assert!(breaker.start_trial(half_open, COOL_DOWN));
+        assert!(!breaker.allows_trial(half_open, COOL_DOWN));
+
+        // A failed trial reopens the circuit for another cool-down
+        assert!(breaker.record_failure(half_open, 1, WINDOW, COOL_DOWN));
+        assert_eq!(breaker.state(half_open, COOL_DOWN), CircuitState::Open);
+
+        // A successful one closes it
+        let half_open = half_open + COOL_DOWN;
+        assert!(breaker.start_trial(half_open, COOL_DOWN));
+        assert!(breaker.record_success(half_open, COOL_DOWN));
+        assert_eq!(breaker.state(half_open, COOL_DOWN), CircuitState::Closed);
+    }
+
+    #[test]
+    fn test_abandoned_trial_is_given_up() {
+        let mut breaker = CircuitBreaker::default();
+        let now = Instant::now();
+        breaker.record_failure(now, 1, WINDOW, COOL_DOWN);
+
+        assert!(breaker.start_trial(now + COOL_DOWN, COOL_DOWN));
+        assert!(!breaker.allows_trial(now + COOL_DOWN, COOL_DOWN));
+        assert!(breaker.allows_trial(now + COOL_DOWN * 2, COOL_DOWN));
+    }
+}
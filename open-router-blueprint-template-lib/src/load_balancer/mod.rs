@@ -1,16 +1,44 @@
-use std::collections::HashMap;
-use std::sync::Arc;
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, HashSet};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, Weak};
+use std::time::Duration;
 use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio::time::Instant;
 
+use rand::seq::SliceRandom;
+use rand::Rng;
 use serde::{Deserialize, Serialize};
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
-use crate::llm::{LlmClient, ModelInfo, NodeMetrics};
+use crate::llm::{LlmClient, LlmError, LlmRequest, NodeMetrics, Operation, RequestPriority};
+
+mod circuit_breaker;
+
+pub use circuit_breaker::{CircuitBreaker, CircuitState};
+
+/// How often a draining node is checked for in-flight requests
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(25);
+
+/// Split a `model@provider` id into the model and the provider its request is pinned to
+///
+/// Ids without a non-empty model and provider around their last `@` are not pinned.
+pub fn split_provider_suffix(model: &str) -> (&str, Option<&str>) {
+    match model.rsplit_once('@') {
+        Some((model, provider)) if !model.is_empty() && !provider.is_empty() => {
+            (model, Some(provider))
+        }
+        _ => (model, None),
+    }
+}
 
 /// Load balancing strategy
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
 pub enum LoadBalancingStrategy {
     /// Round-robin strategy
+    #[default]
     RoundRobin,
 
     /// Least-loaded strategy (based on active requests)
@@ -21,11 +49,63 @@ pub enum LoadBalancingStrategy {
 
     /// Latency-based strategy (route to nodes with lowest response time)
     LatencyBased,
+
+    /// Random strategy (pick uniformly among eligible nodes)
+    ///
+    /// Every pick is independent, so the spread is only even on average: over a few requests
+    /// a node may be picked repeatedly or not at all. Use `RoundRobin` for a strict rotation.
+    Random,
+
+    /// Weighted round-robin strategy (rotate through nodes in proportion to their weight)
+    ///
+    /// Uses smooth weighted round-robin, which interleaves the picks: with weights 3 and 1 the
+    /// rotation is `a a b a` rather than `a a a b`.
+    WeightedRoundRobin,
+
+    /// Power-of-two-choices strategy (pick the less loaded of two random nodes)
+    ///
+    /// Sampling two nodes avoids the herding of `LeastLoaded`, where every request goes to the
+    /// same node until its metrics are refreshed, while still steering away from busy nodes.
+    PowerOfTwo,
+
+    /// Session affinity strategy (keep the requests of a chat session on one node)
+    ///
+    /// Chat requests with a `session_id` go to the node with the highest rendezvous hash of
+    /// the session and the node's id among the candidates, so adding or removing a node only
+    /// moves the sessions of that node. Requests without a session use round-robin.
+    Affinity,
 }
 
-impl Default for LoadBalancingStrategy {
-    fn default() -> Self {
-        Self::RoundRobin
+impl LoadBalancingStrategy {
+    /// The cargo feature that compiles this strategy in, if it is optional
+    ///
+    /// `RoundRobin`, `LeastLoaded`, `Random`, `WeightedRoundRobin`, `PowerOfTwo` and `Affinity`
+    /// are always available.
+    pub fn required_feature(&self) -> Option<&'static str> {
+        match self {
+            Self::RoundRobin
+            | Self::LeastLoaded
+            | Self::Random
+            | Self::WeightedRoundRobin
+            | Self::PowerOfTwo
+            | Self::Affinity => None,
+            Self::CapabilityBased => Some("strategy-capability"),
+            Self::LatencyBased => Some("strategy-latency"),
+        }
+    }
+
+    /// Whether this strategy was compiled into this build
+    pub fn is_enabled(&self) -> bool {
+        match self {
+            Self::RoundRobin
+            | Self::LeastLoaded
+            | Self::Random
+            | Self::WeightedRoundRobin
+            | Self::PowerOfTwo
+            | Self::Affinity => true,
+            Self::CapabilityBased => cfg!(feature = "strategy-capability"),
+            Self::LatencyBased => cfg!(feature = "strategy-latency"),
+        }
     }
 }
 
@@ -40,6 +120,26 @@ pub struct LoadBalancerConfig {
 
     /// Timeout for node selection in milliseconds
     pub selection_timeout_ms: u64,
+
+    /// How the capability-based strategy scores nodes
+    #[serde(default)]
+    pub capability_weights: CapabilityScoreWeights,
+
+    /// Consecutive failures after which a node is marked failed
+    #[serde(default = "default_failure_grace_count")]
+    pub failure_grace_count: u32,
+
+    /// Seconds within which failures must follow each other to count as consecutive
+    #[serde(default = "default_failure_window_seconds")]
+    pub failure_window_seconds: u64,
+
+    /// Seconds a node's circuit stays open before a trial request is sent to it
+    #[serde(default = "default_failure_cooldown_seconds")]
+    pub failure_cooldown_seconds: u64,
+
+    /// Delays between the retries of a request on other nodes
+    #[serde(default)]
+    pub backoff: BackoffConfig,
 }
 
 impl Default for LoadBalancerConfig {
@@ -48,6 +148,140 @@ impl Default for LoadBalancerConfig {
             strategy: LoadBalancingStrategy::default(),
             max_retries: 3,
             selection_timeout_ms: 1000,
+            capability_weights: CapabilityScoreWeights::default(),
+            failure_grace_count: default_failure_grace_count(),
+            failure_window_seconds: default_failure_window_seconds(),
+            failure_cooldown_seconds: default_failure_cooldown_seconds(),
+            backoff: BackoffConfig::default(),
+        }
+    }
+}
+
+fn default_failure_grace_count() -> u32 {
+    1
+}
+
+fn default_failure_window_seconds() -> u64 {
+    60
+}
+
+fn default_failure_cooldown_seconds() -> u64 {
+    30
+}
+
+/// Weights of the capability-based score of a node for a model
+///
+/// A node starts at 1, gains `context_weight` per 10,000 tokens of the model's context length,
+/// and loses each penalty times its utilization (0.0 - 1.0) or its number of active requests.
+/// Nodes that report no GPU utilization take no GPU penalty.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct CapabilityScoreWeights {
+    /// Score per 10,000 tokens of context length
+    pub context_weight: f32,
+
+    /// Penalty for full CPU utilization
+    pub cpu_penalty: f32,
+
+    /// Penalty for full memory utilization
+    pub memory_penalty: f32,
+
+    /// Penalty per active request
+    pub active_request_penalty: f32,
+
+    /// Penalty for full GPU utilization
+    pub gpu_penalty: f32,
+}
+
+impl CapabilityScoreWeights {
+    /// Whether every weight is a finite, non-negative number
+    pub fn is_valid(&self) -> bool {
+        [
+            self.context_weight,
+            self.cpu_penalty,
+            self.memory_penalty,
+            self.active_request_penalty,
+            self.gpu_penalty,
+        ]
+        .iter()
+        .all(|w| w.is_finite() && *w >= 0.0)
+    }
+}
+
+impl Default for CapabilityScoreWeights {
+    fn default() -> Self {
+        Self {
+            context_weight: 1.0,
+            cpu_penalty: 0.5,
+            memory_penalty: 0.5,
+            active_request_penalty: 0.1,
+            gpu_penalty: 0.5,
+        }
+    }
+}
+
+/// Delays between the retries of a request that failed on a node
+///
+/// Retry `n` waits `initial_ms * multiplier^(n - 1)` milliseconds, at most `max_ms`. With
+/// `jitter`, the wait is drawn uniformly between 0 and that delay ("full jitter"), so that the
+/// retries of requests that failed together do not reach a recovering backend together.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct BackoffConfig {
+    /// Delay before the first retry in milliseconds
+    pub initial_ms: u64,
+
+    /// Upper bound of the delay in milliseconds
+    pub max_ms: u64,
+
+    /// Factor by which the delay grows with every retry
+    pub multiplier: f64,
+
+    /// Whether to wait a random part of the delay
+    pub jitter: bool,
+}
+
+impl BackoffConfig {
+    /// Whether the delays grow and stay within `max_ms`
+    pub fn is_valid(&self) -> bool {
+        self.multiplier.is_finite() && self.multiplier >= 1.0 && self.initial_ms <= self.max_ms
+    }
+
+    /// The delay before retry `retry`, counting from 1, without jitter
+    pub fn base_delay(&self, retry: u32) -> Duration {
+        if self.initial_ms == 0 {
+            return Duration::ZERO;
+        }
+        let exponent = i32::try_from(retry.saturating_sub(1)).unwrap_or(i32::MAX);
+        let delay_ms = self.initial_ms as f64 * self.multiplier.powi(exponent);
+        Duration::from_millis(delay_ms.min(self.max_ms as f64) as u64)
+    }
+
+    /// The delay before retry `retry`, counting from 1
+    ///
+    /// With `jitter` enabled, `jitter` picks the delay in milliseconds given the base delay;
+    /// picks above the base delay are capped to it.
+    pub fn delay(&self, retry: u32, jitter: impl FnOnce(u64) -> u64) -> Duration {
+        let base_ms = self.base_delay(retry).as_millis() as u64;
+        if !self.jitter {
+            return Duration::from_millis(base_ms);
+        }
+        Duration::from_millis(jitter(base_ms).min(base_ms))
+    }
+
+    /// The delay before retry `retry`, with jitter drawn from the thread's random generator
+    pub fn random_delay(&self, retry: u32) -> Duration {
+        self.delay(retry, |max_ms| rand::thread_rng().gen_range(0..=max_ms))
+    }
+}
+
+impl Default for BackoffConfig {
+    fn default() -> Self {
+        Self {
+            initial_ms: 50,
+            max_ms: 2000,
+            multiplier: 2.0,
+            jitter: true,
         }
     }
 }
@@ -66,6 +300,62 @@ pub struct LoadBalancerNode {
 
     /// Whether this node is active
     pub active: bool,
+
+    /// Whether this node was marked as failed; failed nodes are not selected until reset,
+    /// except for the trial request of a half-open circuit
+    pub failed: bool,
+
+    /// Circuit breaker fed with the outcome of the node's requests
+    pub circuit: CircuitBreaker,
+
+    /// Provider serving this node, e.g. `vllm`, which `model@provider` requests are pinned to
+    pub provider: Option<String>,
+
+    /// Relative share of traffic under weighted round-robin, at least 1
+    pub weight: u32,
+
+    /// Requests dispatched to this node that have not completed, shared by its clones
+    in_flight: Arc<AtomicU32>,
+}
+
+impl LoadBalancerNode {
+    /// Whether this node's client can serve streaming requests
+    pub fn can_stream(&self) -> bool {
+        self.client.get_capabilities().supports_streaming
+    }
+
+    /// Whether this node may receive requests, given the cool-down of its circuit
+    ///
+    /// A failed node is only selectable for the trial request of its half-open circuit; nodes
+    /// marked failed with `mark_node_failed` keep a closed circuit and are never tried.
+    pub fn is_selectable(&self, cool_down: Duration) -> bool {
+        self.active && (!self.failed || self.circuit.allows_trial(Instant::now(), cool_down))
+    }
+
+    /// Count a request dispatched to this node as in flight until the returned guard drops
+    pub fn start_request(&self) -> InFlightRequest {
+        self.in_flight.fetch_add(1, Ordering::SeqCst);
+        InFlightRequest {
+            in_flight: self.in_flight.clone(),
+        }
+    }
+
+    /// Number of requests dispatched to this node that have not completed
+    pub fn in_flight_requests(&self) -> u32 {
+        self.in_flight.load(Ordering::SeqCst)
+    }
+}
+
+/// A request a node is serving, counted in its in-flight requests until dropped
+#[derive(Debug)]
+pub struct InFlightRequest {
+    in_flight: Arc<AtomicU32>,
+}
+
+impl Drop for InFlightRequest {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::SeqCst);
+    }
 }
 
 impl std::fmt::Debug for LoadBalancerNode {
@@ -75,44 +365,129 @@ impl std::fmt::Debug for LoadBalancerNode {
             .field("client", &"<dyn LlmClient>")
             .field("metrics", &self.metrics)
             .field("active", &self.active)
+            .field("failed", &self.failed)
+            .field("circuit", &self.circuit)
+            .field("provider", &self.provider)
+            .field("weight", &self.weight)
+            .field("in_flight", &self.in_flight_requests())
             .finish()
     }
 }
 
+/// How a node left the load balancer in [`LoadBalancer::drain_node`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DrainOutcome {
+    /// The node finished its in-flight requests before it was removed
+    Drained,
+
+    /// The drain timed out and the node was removed with requests still in flight
+    ForceRemoved {
+        /// Requests still in flight when the node was removed
+        active_requests: u32,
+    },
+
+    /// No node has the id
+    NotFound,
+}
+
 /// Load balancer for distributing requests across multiple LLM nodes
+///
+/// The configuration can be changed while requests are being routed. Its lock is only held
+/// to copy or replace the configuration, never while waiting for the node or round-robin
+/// locks, so readers of the configuration cannot deadlock with node updates.
 pub struct LoadBalancer {
     /// Configuration for the load balancer
-    config: LoadBalancerConfig,
+    config: RwLock<LoadBalancerConfig>,
 
     /// Nodes in the load balancer
     nodes: RwLock<HashMap<String, LoadBalancerNode>>,
 
     /// Current round-robin index
     round_robin_index: RwLock<usize>,
+
+    /// Current weight of each node in the weighted round-robin rotation
+    current_weights: RwLock<HashMap<String, i64>>,
+
+    /// Ids of the nodes an operator excluded from selection
+    excluded: RwLock<HashSet<String>>,
+
+    /// Background task running the periodic health checks, if started
+    health_checks: Mutex<Option<JoinHandle<()>>>,
 }
 
 impl LoadBalancer {
     /// Create a new load balancer with the given configuration
     pub fn new(config: LoadBalancerConfig) -> Self {
         Self {
-            config,
+            config: RwLock::new(config),
             nodes: RwLock::new(HashMap::new()),
             round_robin_index: RwLock::new(0),
+            current_weights: RwLock::new(HashMap::new()),
+            excluded: RwLock::new(HashSet::new()),
+            health_checks: Mutex::new(None),
+        }
+    }
+
+    /// Create a load balancer already populated with the given nodes, all active
+    pub async fn with_nodes(
+        config: LoadBalancerConfig,
+        nodes: Vec<(String, Arc<dyn LlmClient>)>,
+    ) -> Self {
+        let load_balancer = Self::new(config);
+        for (id, client) in nodes {
+            load_balancer.add_node(id, client).await;
+        }
+        load_balancer
+    }
+
+    /// The configuration currently in use
+    pub async fn config(&self) -> LoadBalancerConfig {
+        self.config.read().await.clone()
+    }
+
+    /// Replace the configuration used for subsequent selections
+    pub async fn set_config(&self, new_config: LoadBalancerConfig) {
+        let mut config = self.config.write().await;
+        if config.strategy != new_config.strategy {
+            info!(
+                "Changing load balancing strategy from {:?} to {:?}",
+                config.strategy, new_config.strategy
+            );
         }
+        *config = new_config;
+    }
+
+    /// The load balancing strategy currently in use
+    pub async fn strategy(&self) -> LoadBalancingStrategy {
+        self.config.read().await.strategy
     }
 
     /// Add a node to the load balancer
+    ///
+    /// The node is tagged with the backend its client reports as provider, if any, and
+    /// weighted by the number of concurrent requests its client can serve.
     pub async fn add_node(&self, id: String, client: Arc<dyn LlmClient>) {
         let metrics = client.get_metrics();
+        let provider = Some(client.get_node_info().backend).filter(|b| !b.is_empty());
+        let weight = client
+            .get_capabilities()
+            .max_concurrent_requests
+            .clamp(1, u32::MAX as usize) as u32;
         let node = LoadBalancerNode {
             id: id.clone(),
             client,
             metrics,
             active: true,
+            failed: false,
+            circuit: CircuitBreaker::default(),
+            provider,
+            weight,
+            in_flight: Arc::default(),
         };
 
         let mut nodes = self.nodes.write().await;
         nodes.insert(id.clone(), node);
+        self.clamp_round_robin_index(nodes.len()).await;
 
         info!("Added node to load balancer: {}", id);
     }
@@ -123,6 +498,8 @@ impl LoadBalancer {
         let removed = nodes.remove(id).is_some();
 
         if removed {
+            self.clamp_round_robin_index(nodes.len()).await;
+            self.current_weights.write().await.remove(id);
             info!("Removed node from load balancer: {}", id);
         } else {
             debug!("Attempted to remove non-existent node: {}", id);
@@ -131,6 +508,79 @@ impl LoadBalancer {
         removed
     }
 
+    /// Remove a node once its in-flight requests have drained
+    ///
+    /// Returns whether the node existed; see [`LoadBalancer::drain_node`].
+    pub async fn remove_node_graceful(&self, id: &str, drain_timeout: Duration) -> bool {
+        self.drain_node(id, drain_timeout).await != DrainOutcome::NotFound
+    }
+
+    /// Drain a node of its in-flight requests, then remove it
+    ///
+    /// The node is marked inactive so it receives no new requests, then removed as soon as
+    /// the requests dispatched to it completed or `timeout` elapses, whichever is first.
+    /// Requests are in flight while the [`InFlightRequest`] of their dispatch is held; see
+    /// [`LoadBalancerNode::start_request`].
+    pub async fn drain_node(&self, id: &str, timeout: Duration) -> DrainOutcome {
+        let in_flight = {
+            let mut nodes = self.nodes.write().await;
+            match nodes.get_mut(id) {
+                Some(node) => {
+                    node.active = false;
+                    node.in_flight.clone()
+                }
+                None => {
+                    debug!("Attempted to drain non-existent node: {}", id);
+                    return DrainOutcome::NotFound;
+                }
+            }
+        };
+
+        info!("Draining node before removal: {}", id);
+        let deadline = Instant::now() + timeout;
+        let outcome = loop {
+            let active_requests = in_flight.load(Ordering::SeqCst);
+            if active_requests == 0 {
+                break DrainOutcome::Drained;
+            }
+            if Instant::now() >= deadline {
+                warn!(
+                    "Drain timeout reached for node {} with {} active requests",
+                    id, active_requests
+                );
+                break DrainOutcome::ForceRemoved { active_requests };
+            }
+            tokio::time::sleep(DRAIN_POLL_INTERVAL).await;
+        };
+
+        // The node may have been removed by someone else while it drained
+        if self.remove_node(id).await {
+            outcome
+        } else {
+            DrainOutcome::NotFound
+        }
+    }
+
+    /// Restart the round-robin rotation from the first node
+    ///
+    /// Adding or removing nodes already keeps the rotation in bounds; call this after changing
+    /// node availability in other ways (e.g. with `set_node_active`) to redistribute evenly.
+    pub async fn rebalance(&self) {
+        *self.round_robin_index.write().await = 0;
+        self.current_weights.write().await.clear();
+        debug!("Reset round-robin rotation");
+    }
+
+    /// Keep the round-robin index within the bounds of `node_count` nodes
+    async fn clamp_round_robin_index(&self, node_count: usize) {
+        let mut index = self.round_robin_index.write().await;
+        *index = if node_count == 0 {
+            0
+        } else {
+            *index % node_count
+        };
+    }
+
     /// Update the metrics for a node
     pub async fn update_node_metrics(&self, id: &str, metrics: NodeMetrics) -> bool {
         let mut nodes = self.nodes.write().await;
@@ -160,6 +610,286 @@ impl LoadBalancer {
         }
     }
 
+    /// Tag a node with the provider serving it, replacing the one its client reported
+    pub async fn set_node_provider(&self, id: &str, provider: Option<String>) -> bool {
+        let mut nodes = self.nodes.write().await;
+
+        if let Some(node) = nodes.get_mut(id) {
+            node.provider = provider;
+            true
+        } else {
+            debug!("Attempted to set provider for non-existent node: {}", id);
+            false
+        }
+    }
+
+    /// Set the weight of a node under weighted round-robin, replacing the one derived from its
+    /// capabilities; a weight of 0 is raised to 1
+    pub async fn set_node_weight(&self, id: &str, weight: u32) -> bool {
+        let mut nodes = self.nodes.write().await;
+
+        if let Some(node) = nodes.get_mut(id) {
+            node.weight = weight.max(1);
+            true
+        } else {
+            debug!("Attempted to set weight for non-existent node: {}", id);
+            false
+        }
+    }
+
+    /// Mark a node as failed, excluding it from selection until `reset_node_failure`
+    ///
+    /// Unlike deactivating a node, this records that the node misbehaved rather than that an
+    /// operator took it out of rotation. Unlike a node whose circuit opened, it is not sent a
+    /// trial request after the cool-down.
+    pub async fn mark_node_failed(&self, id: &str) -> bool {
+        self.set_node_failed(id, true).await
+    }
+
+    /// Clear the failure mark of a node and close its circuit, making it selectable again if
+    /// it is active
+    pub async fn reset_node_failure(&self, id: &str) -> bool {
+        self.set_node_failed(id, false).await
+    }
+
+    /// Record a failed request of a node, returning whether it opened the node's circuit
+    ///
+    /// The circuit opens, marking the node failed, once the node failed `failure_grace_count`
+    /// times in a row, with the streak starting over when its first failure is older than
+    /// `failure_window_seconds`. A failed trial request reopens it for another cool-down.
+    /// Failures of nodes marked failed with `mark_node_failed` are not counted.
+    pub async fn record_node_failure(&self, id: &str) -> bool {
+        let (grace_count, window, cool_down) = {
+            let config = self.config.read().await;
+            (
+                config.failure_grace_count.max(1),
+                Duration::from_secs(config.failure_window_seconds),
+                Duration::from_secs(config.failure_cooldown_seconds),
+            )
+        };
+
+        let mut nodes = self.nodes.write().await;
+        let Some(node) = nodes.get_mut(id) else {
+            debug!("Attempted to record a failure of non-existent node: {}", id);
+            return false;
+        };
+        let now = Instant::now();
+        let state = node.circuit.state(now, cool_down);
+        if node.failed && state == CircuitState::Closed {
+            return false;
+        }
+
+        let opened = node
+            .circuit
+            .record_failure(now, grace_count, window, cool_down);
+        if !opened {
+            debug!(
+                "Node {} failed {} of {} times in a row",
+                id,
+                node.circuit.consecutive_failures(),
+                grace_count
+            );
+        } else if state == CircuitState::HalfOpen {
+            warn!(
+                "Trial request of node {} failed, reopening its circuit for {:?}",
+                id, cool_down
+            );
+        } else {
+            warn!(
+                "Marked node as failed: {}, opening its circuit for {:?}",
+                id, cool_down
+            );
+        }
+        node.failed |= opened;
+        opened
+    }
+
+    /// Record a successful request of a node, starting its failure streak over
+    ///
+    /// A successful trial request closes the node's circuit and clears its failure mark.
+    pub async fn record_node_success(&self, id: &str) {
+        let cool_down = self.failure_cooldown().await;
+        let mut nodes = self.nodes.write().await;
+        if let Some(node) = nodes.get_mut(id) {
+            if node.circuit.record_success(Instant::now(), cool_down) {
+                node.failed = false;
+                info!(
+                    "Trial request of node {} succeeded, closing its circuit",
+                    id
+                );
+            }
+        }
+    }
+
+    /// How long a node's circuit stays open
+    async fn failure_cooldown(&self) -> Duration {
+        Duration::from_secs(self.config.read().await.failure_cooldown_seconds)
+    }
+
+    /// Let `node` through as the trial request of its half-open circuit if it is failed
+    ///
+    /// The nodes to select from are a snapshot, so the circuit is checked again under the
+    /// write lock: when a concurrent selection already took the trial, no node is returned.
+    async fn start_trial(&self, node: Option<LoadBalancerNode>) -> Option<LoadBalancerNode> {
+        let node = node?;
+        if !node.failed {
+            return Some(node);
+        }
+        let cool_down = self.failure_cooldown().await;
+        if let Some(n) = self.nodes.write().await.get_mut(&node.id) {
+            if n.failed {
+                if !n.circuit.start_trial(Instant::now(), cool_down) {
+                    debug!("Trial request of node {} already sent", node.id);
+                    return None;
+                }
+                info!(
+                    "Sending a trial request to node {} with a half-open circuit",
+                    node.id
+                );
+            }
+        }
+        Some(node)
+    }
+
+    async fn set_node_failed(&self, id: &str, failed: bool) -> bool {
+        let mut nodes = self.nodes.write().await;
+
+        if let Some(node) = nodes.get_mut(id) {
+            if node.failed != failed {
+                if failed {
+                    warn!("Marked node as failed: {}", id);
+                } else {
+                    info!("Reset failure of node: {}", id);
+                }
+            }
+            node.failed = failed;
+            node.circuit.reset();
+            true
+        } else {
+            debug!(
+                "Attempted to set failure state for non-existent node: {}",
+                id
+            );
+            false
+        }
+    }
+
+    /// Exclude a node from selection until `include_node` is called for it
+    ///
+    /// Unlike deactivating or failing a node, an exclusion is only lifted by an operator: it
+    /// survives health checks and failure resets, and applies to a node added later under the
+    /// same id. Returns whether the node was not excluded already.
+    pub async fn exclude_node(&self, id: &str) -> bool {
+        let excluded = self.excluded.write().await.insert(id.to_string());
+        if excluded {
+            warn!("Excluded node from selection: {}", id);
+        }
+        excluded
+    }
+
+    /// Lift the exclusion of a node, returning whether it was excluded
+    pub async fn include_node(&self, id: &str) -> bool {
+        let included = self.excluded.write().await.remove(id);
+        if included {
+            info!("Included node in selection again: {}", id);
+        }
+        included
+    }
+
+    /// Ids of the nodes excluded from selection, sorted
+    pub async fn excluded_nodes(&self) -> Vec<String> {
+        let mut excluded: Vec<_> = self.excluded.read().await.iter().cloned().collect();
+        excluded.sort();
+        excluded
+    }
+
+    /// Start checking the health of every node each `interval` in a background task
+    ///
+    /// A node failing its check, or not answering within `interval`, is deactivated and
+    /// reactivated once it passes again. Nodes deactivated by other means, with
+    /// `set_node_active` or while draining, are left alone. Replaces checks already running;
+    /// the task stops with `stop_health_checks` or once the load balancer is dropped.
+    pub fn start_health_checks(self: Arc<Self>, interval: Duration) {
+        let load_balancer = Arc::downgrade(&self);
+        let task = tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // Nodes deactivated by these checks, which are the only ones they reactivate
+            let mut unhealthy = HashSet::new();
+            loop {
+                ticker.tick().await;
+                let Some(load_balancer) = Weak::upgrade(&load_balancer) else {
+                    break;
+                };
+                load_balancer
+                    .run_health_checks(interval, &mut unhealthy)
+                    .await;
+            }
+        });
+
+        let previous = self.health_checks.lock().unwrap().replace(task);
+        if let Some(previous) = previous {
+            previous.abort();
+        }
+        info!("Started node health checks every {:?}", interval);
+    }
+
+    /// Stop the health checks started with `start_health_checks`, if running
+    ///
+    /// Nodes keep the active state the last check gave them.
+    pub fn stop_health_checks(&self) {
+        if let Some(task) = self.health_checks.lock().unwrap().take() {
+            task.abort();
+            info!("Stopped node health checks");
+        }
+    }
+
+    /// Check every node once, updating the active state of the ones that changed health
+    async fn run_health_checks(&self, timeout: Duration, unhealthy: &mut HashSet<String>) {
+        let clients: Vec<_> = self
+            .nodes
+            .read()
+            .await
+            .values()
+            .map(|node| (node.id.clone(), node.client.clone()))
+            .collect();
+
+        let results =
+            futures::future::join_all(clients.into_iter().map(|(id, client)| async move {
+                let result = match tokio::time::timeout(timeout, client.health_check()).await {
+                    Ok(result) => result,
+                    Err(_) => Err(LlmError::Timeout(timeout)),
+                };
+                (id, result)
+            }))
+            .await;
+
+        let mut nodes = self.nodes.write().await;
+        unhealthy.retain(|id| nodes.contains_key(id));
+        for (id, result) in results {
+            // The node may have been removed while it was being checked
+            let Some(node) = nodes.get_mut(&id) else {
+                continue;
+            };
+            match result {
+                Ok(()) if unhealthy.remove(&id) => {
+                    node.active = true;
+                    info!("Node {} passed its health check, reactivating it", id);
+                }
+                Ok(()) => {}
+                Err(e) if node.active => {
+                    node.active = false;
+                    unhealthy.insert(id.clone());
+                    warn!(
+                        "Node {} failed its health check, deactivating it: {}",
+                        id, e
+                    );
+                }
+                Err(e) => debug!("Inactive node {} failed its health check: {}", id, e),
+            }
+        }
+    }
+
     /// Get a node by ID
     pub async fn get_node(&self, id: &str) -> Option<LoadBalancerNode> {
         let nodes = self.nodes.read().await;
@@ -172,45 +902,321 @@ impl LoadBalancer {
         nodes.values().cloned().collect()
     }
 
-    /// Get all active nodes
+    /// Get all nodes keyed by ID
+    pub async fn get_nodes(&self) -> HashMap<String, LoadBalancerNode> {
+        self.nodes.read().await.clone()
+    }
+
+    /// Get all active nodes not excluded from selection
     pub async fn get_active_nodes(&self) -> Vec<LoadBalancerNode> {
+        let excluded = self.excluded.read().await.clone();
+        let nodes = self.nodes.read().await;
+        nodes
+            .values()
+            .filter(|n| n.active && !excluded.contains(&n.id))
+            .cloned()
+            .collect()
+    }
+
+    /// Active nodes not marked as failed nor excluded, ordered by id
+    ///
+    /// Failed nodes whose circuit is half-open are included for their trial request.
+    async fn selectable_nodes(&self) -> Vec<LoadBalancerNode> {
+        let cool_down = self.failure_cooldown().await;
+        let excluded = self.excluded.read().await.clone();
         let nodes = self.nodes.read().await;
-        nodes.values().filter(|n| n.active).cloned().collect()
+        let mut selectable: Vec<_> = nodes
+            .values()
+            .filter(|n| n.is_selectable(cool_down) && !excluded.contains(&n.id))
+            .cloned()
+            .collect();
+
+        // Keep a stable node order so the round-robin rotation is fair across calls
+        selectable.sort_by(|a, b| a.id.cmp(&b.id));
+        selectable
+    }
+
+    /// Select any node using the configured strategy, regardless of the models it serves
+    pub async fn select_node(&self) -> Option<LoadBalancerNode> {
+        let nodes = self.selectable_nodes().await;
+        if nodes.is_empty() {
+            debug!("No active nodes available for selection");
+            return None;
+        }
+
+        let node = self.select_from(&nodes, None, None).await;
+        self.start_trial(node).await
     }
 
     /// Select a node for the given model using the configured strategy
     pub async fn select_node_for_model(&self, model: &str) -> Option<LoadBalancerNode> {
-        let active_nodes = self.get_active_nodes().await;
+        self.select_node_for_model_from(model, None).await
+    }
 
-        if active_nodes.is_empty() {
-            debug!("No active nodes available for selection");
+    /// Select a node for the given model among the nodes of `provider`, or among all nodes
+    /// without one
+    pub async fn select_node_for_model_from(
+        &self,
+        model: &str,
+        provider: Option<&str>,
+    ) -> Option<LoadBalancerNode> {
+        self.select_matching_node(
+            model,
+            provider,
+            None,
+            false,
+            RequestPriority::Normal,
+            None,
+            &HashSet::new(),
+        )
+        .await
+    }
+
+    /// Select a node whose entry for the given model supports `operation`
+    pub async fn select_node_for_operation(
+        &self,
+        model: &str,
+        operation: Operation,
+    ) -> Option<LoadBalancerNode> {
+        self.select_matching_node(
+            model,
+            None,
+            Some(operation),
+            false,
+            RequestPriority::Normal,
+            None,
+            &HashSet::new(),
+        )
+        .await
+    }
+
+    /// Select a node to serve `request` with `model`, among the nodes of `provider` if given
+    ///
+    /// The node's entry for `model` must support the request's operation, and streaming
+    /// requests prefer streaming-capable nodes. High-priority requests go to the least-loaded
+    /// candidate whatever the strategy, and the affinity strategy routes by the request's
+    /// `session_id`. `model` stands in for the request's own so that fallback models can be
+    /// tried without changing the request.
+    pub async fn node_for_request(
+        &self,
+        model: &str,
+        request: &LlmRequest,
+        provider: Option<&str>,
+    ) -> Option<LoadBalancerNode> {
+        self.node_for_request_excluding(model, request, provider, &HashSet::new())
+            .await
+    }
+
+    /// Select a node to serve `request` with `model` like [`LoadBalancer::node_for_request`],
+    /// other than the nodes with an id in `excluded`
+    ///
+    /// Used to fail a request over to a node it was not tried on yet.
+    pub async fn node_for_request_excluding(
+        &self,
+        model: &str,
+        request: &LlmRequest,
+        provider: Option<&str>,
+        excluded: &HashSet<String>,
+    ) -> Option<LoadBalancerNode> {
+        self.select_matching_node(
+            model,
+            provider,
+            Some(request.operation()),
+            request.is_streaming(),
+            request.priority(),
+            request.session_id(),
+            excluded,
+        )
+        .await
+    }
+
+    /// Select a streaming-capable node for the given model using the configured strategy
+    ///
+    /// Falls back to any node serving the model when none of them can stream, since the
+    /// job can still answer the request without streaming.
+    pub async fn select_streaming_node_for_model(&self, model: &str) -> Option<LoadBalancerNode> {
+        self.select_streaming_node_for_model_from(model, None).await
+    }
+
+    /// Select a streaming-capable node for the given model among the nodes of `provider`, or
+    /// among all nodes without one
+    pub async fn select_streaming_node_for_model_from(
+        &self,
+        model: &str,
+        provider: Option<&str>,
+    ) -> Option<LoadBalancerNode> {
+        self.select_matching_node(
+            model,
+            provider,
+            None,
+            true,
+            RequestPriority::Normal,
+            None,
+            &HashSet::new(),
+        )
+        .await
+    }
+
+    /// Select a node for the given model, of `provider` and supporting `operation` if given,
+    /// other than the nodes with an id in `excluded`
+    ///
+    /// With `prefer_streaming`, streaming-capable nodes are picked over the others, which are
+    /// still used when none of the candidates can stream. A `High` priority skips the
+    /// configured strategy for the least-loaded candidate.
+    #[allow(clippy::too_many_arguments)]
+    async fn select_matching_node(
+        &self,
+        model: &str,
+        provider: Option<&str>,
+        operation: Option<Operation>,
+        prefer_streaming: bool,
+        priority: RequestPriority,
+        session_id: Option<&str>,
+        excluded: &HashSet<String>,
+    ) -> Option<LoadBalancerNode> {
+        let mut supporting_nodes = self.supporting_nodes(model, provider, operation).await;
+        supporting_nodes.retain(|n| !excluded.contains(&n.id));
+        if supporting_nodes.is_empty() {
             return None;
         }
+        if !prefer_streaming {
+            return self
+                .select_with_priority(&supporting_nodes, model, priority, session_id)
+                .await;
+        }
+
+        let streaming_nodes: Vec<_> = supporting_nodes
+            .iter()
+            .filter(|n| n.can_stream())
+            .cloned()
+            .collect();
+        if streaming_nodes.is_empty() {
+            debug!(
+                "No streaming-capable nodes support the requested model: {}, using any node",
+                model
+            );
+            return self
+                .select_with_priority(&supporting_nodes, model, priority, session_id)
+                .await;
+        }
+
+        self.select_with_priority(&streaming_nodes, model, priority, session_id)
+            .await
+    }
+
+    /// Select one of the given nodes for a request of `priority`
+    ///
+    /// High-priority requests take the least-loaded node, since a rotation may otherwise put
+    /// them behind a busy node's queue; others use the configured strategy.
+    async fn select_with_priority(
+        &self,
+        nodes: &[LoadBalancerNode],
+        model: &str,
+        priority: RequestPriority,
+        session_id: Option<&str>,
+    ) -> Option<LoadBalancerNode> {
+        let node = if priority == RequestPriority::High {
+            debug!("Selecting the least-loaded node for a high-priority request");
+            self.select_least_loaded(nodes)
+        } else {
+            self.select_from(nodes, Some(model), session_id).await
+        };
+        self.start_trial(node).await
+    }
+
+    /// Selectable nodes that support the given model, of `provider` and for `operation` if
+    /// given, ordered by id
+    async fn supporting_nodes(
+        &self,
+        model: &str,
+        provider: Option<&str>,
+        operation: Option<Operation>,
+    ) -> Vec<LoadBalancerNode> {
+        let selectable_nodes = self.selectable_nodes().await;
+
+        if selectable_nodes.is_empty() {
+            debug!("No active nodes available for selection");
+            return Vec::new();
+        }
 
         // Filter nodes that support the requested model
-        let supporting_nodes: Vec<_> = active_nodes
+        let supporting_nodes: Vec<_> = selectable_nodes
             .into_iter()
+            .filter(|n| provider.is_none() || n.provider.as_deref() == provider)
             .filter(|n| {
-                n.client
-                    .get_supported_models()
-                    .iter()
-                    .any(|m| m.id == model)
+                n.client.get_supported_models().iter().any(|m| {
+                    m.id == model && operation.is_none_or(|operation| m.supports(operation))
+                })
             })
             .collect();
 
         if supporting_nodes.is_empty() {
-            debug!("No nodes support the requested model: {}", model);
-            return None;
+            match (provider, operation) {
+                (Some(provider), _) => debug!(
+                    "No {} nodes support the requested model: {}",
+                    provider, model
+                ),
+                (None, Some(operation)) => debug!(
+                    "No nodes support {:?} requests for model: {}",
+                    operation, model
+                ),
+                (None, None) => debug!("No nodes support the requested model: {}", model),
+            }
+        }
+        supporting_nodes
+    }
+
+    /// Select one of the given nodes using the configured strategy
+    ///
+    /// With a `model`, every node supports it. Capability-based selection scores nodes for a
+    /// model, so without one it picks the least-loaded node instead. Affinity pins
+    /// `session_id` to a node, and rotates like round-robin without one. A single candidate is
+    /// returned as is, since every strategy would pick it.
+    async fn select_from(
+        &self,
+        supporting_nodes: &[LoadBalancerNode],
+        model: Option<&str>,
+        session_id: Option<&str>,
+    ) -> Option<LoadBalancerNode> {
+        // The common single-node deployment needs no strategy lookup, rotation or scoring
+        if let [node] = supporting_nodes {
+            return Some(node.clone());
         }
 
         // Select a node based on the configured strategy
-        match self.config.strategy {
-            LoadBalancingStrategy::RoundRobin => self.select_round_robin(&supporting_nodes).await,
-            LoadBalancingStrategy::LeastLoaded => self.select_least_loaded(&supporting_nodes),
-            LoadBalancingStrategy::CapabilityBased => {
-                self.select_capability_based(&supporting_nodes, model)
+        let strategy = self.strategy().await;
+        match strategy {
+            LoadBalancingStrategy::RoundRobin => self.select_round_robin(supporting_nodes).await,
+            LoadBalancingStrategy::LeastLoaded => self.select_least_loaded(supporting_nodes),
+            LoadBalancingStrategy::Random => self.select_random(supporting_nodes),
+            LoadBalancingStrategy::WeightedRoundRobin => {
+                self.select_weighted_round_robin(supporting_nodes).await
+            }
+            LoadBalancingStrategy::PowerOfTwo => self.select_power_of_two(supporting_nodes),
+            LoadBalancingStrategy::Affinity => match session_id {
+                Some(session_id) => self.select_by_affinity(supporting_nodes, session_id),
+                None => self.select_round_robin(supporting_nodes).await,
+            },
+            #[cfg(feature = "strategy-capability")]
+            LoadBalancingStrategy::CapabilityBased => match model {
+                Some(model) => {
+                    let weights = self.config.read().await.capability_weights;
+                    self.select_capability_based(supporting_nodes, model, &weights)
+                }
+                None => self.select_least_loaded(supporting_nodes),
+            },
+            #[cfg(feature = "strategy-latency")]
+            LoadBalancingStrategy::LatencyBased => self.select_latency_based(supporting_nodes),
+            #[allow(unreachable_patterns)]
+            strategy => {
+                // Rejected by config validation, but a LoadBalancerConfig can be built directly
+                warn!(
+                    "Load balancing strategy {:?} is disabled in this build, using round-robin for model {}",
+                    strategy,
+                    model.unwrap_or("<any>")
+                );
+                self.select_round_robin(supporting_nodes).await
             }
-            LoadBalancingStrategy::LatencyBased => self.select_latency_based(&supporting_nodes),
         }
     }
 
@@ -227,6 +1233,33 @@ impl LoadBalancer {
         Some(nodes[selected_index].clone())
     }
 
+    /// Select a node using smooth weighted round-robin
+    ///
+    /// Every node's current weight grows by its weight, then the node with the highest current
+    /// weight is picked and set back by the total weight of the candidates.
+    async fn select_weighted_round_robin(
+        &self,
+        nodes: &[LoadBalancerNode],
+    ) -> Option<LoadBalancerNode> {
+        let total: i64 = nodes.iter().map(|n| i64::from(n.weight)).sum();
+        let mut current_weights = self.current_weights.write().await;
+
+        let mut selected: Option<(&LoadBalancerNode, i64)> = None;
+        for node in nodes {
+            let current = current_weights.entry(node.id.clone()).or_insert(0);
+            *current += i64::from(node.weight);
+            if selected.is_none_or(|(_, best)| *current > best) {
+                selected = Some((node, *current));
+            }
+        }
+
+        let (node, _) = selected?;
+        if let Some(current) = current_weights.get_mut(&node.id) {
+            *current -= total;
+        }
+        Some(node.clone())
+    }
+
     /// Select a node using the least-loaded strategy
     fn select_least_loaded(&self, nodes: &[LoadBalancerNode]) -> Option<LoadBalancerNode> {
         if nodes.is_empty() {
@@ -239,11 +1272,47 @@ impl LoadBalancer {
             .cloned()
     }
 
+    /// Select a node uniformly at random
+    fn select_random(&self, nodes: &[LoadBalancerNode]) -> Option<LoadBalancerNode> {
+        nodes.choose(&mut rand::thread_rng()).cloned()
+    }
+
+    /// Select the node with fewer active requests out of two picked at random
+    ///
+    /// A single node is returned as is.
+    fn select_power_of_two(&self, nodes: &[LoadBalancerNode]) -> Option<LoadBalancerNode> {
+        nodes
+            .choose_multiple(&mut rand::thread_rng(), 2)
+            .min_by_key(|n| n.metrics.active_requests)
+            .cloned()
+    }
+
+    /// Select the node of `session_id` by rendezvous hashing
+    ///
+    /// Every node is scored by a hash of the session and its id, which does not depend on the
+    /// other candidates, so a session only moves when its node leaves the candidates.
+    fn select_by_affinity(
+        &self,
+        nodes: &[LoadBalancerNode],
+        session_id: &str,
+    ) -> Option<LoadBalancerNode> {
+        nodes
+            .iter()
+            .max_by_key(|n| {
+                let mut hasher = DefaultHasher::new();
+                (session_id, n.id.as_str()).hash(&mut hasher);
+                hasher.finish()
+            })
+            .cloned()
+    }
+
     /// Select a node using the capability-based strategy
+    #[cfg(feature = "strategy-capability")]
     fn select_capability_based(
         &self,
         nodes: &[LoadBalancerNode],
         model: &str,
+        weights: &CapabilityScoreWeights,
     ) -> Option<LoadBalancerNode> {
         if nodes.is_empty() {
             return None;
@@ -257,7 +1326,7 @@ impl LoadBalancer {
                 let model_info = supported_models.iter().find(|m| m.id == model)?;
 
                 // Score the node based on its capabilities
-                let score = self.calculate_capability_score(n, model_info);
+                let score = self.calculate_capability_score(n, model_info, weights);
                 Some((n, score))
             })
             .collect();
@@ -270,24 +1339,34 @@ impl LoadBalancer {
     }
 
     /// Calculate a capability score for a node and model
-    fn calculate_capability_score(&self, node: &LoadBalancerNode, model_info: &ModelInfo) -> f32 {
+    #[cfg(feature = "strategy-capability")]
+    fn calculate_capability_score(
+        &self,
+        node: &LoadBalancerNode,
+        model_info: &crate::llm::ModelInfo,
+        weights: &CapabilityScoreWeights,
+    ) -> f32 {
         // Base score
         let mut score = 1.0;
 
         // Adjust score based on context length
-        score += (model_info.max_context_length as f32) / 10000.0;
+        score += (model_info.max_context_length as f32) / 10000.0 * weights.context_weight;
 
         // Adjust score based on node metrics
-        score -= node.metrics.cpu_utilization * 0.5;
-        score -= node.metrics.memory_utilization * 0.5;
+        score -= node.metrics.cpu_utilization * weights.cpu_penalty;
+        score -= node.metrics.memory_utilization * weights.memory_penalty;
+        if let Some(gpu_utilization) = node.metrics.gpu_utilization {
+            score -= gpu_utilization * weights.gpu_penalty;
+        }
 
         // Penalize nodes with high active requests
-        score -= (node.metrics.active_requests as f32) * 0.1;
+        score -= (node.metrics.active_requests as f32) * weights.active_request_penalty;
 
         score
     }
 
     /// Select a node using the latency-based strategy
+    #[cfg(feature = "strategy-latency")]
     fn select_latency_based(&self, nodes: &[LoadBalancerNode]) -> Option<LoadBalancerNode> {
         if nodes.is_empty() {
             return None;
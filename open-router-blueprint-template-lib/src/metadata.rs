@@ -0,0 +1,22 @@
+//! The blueprint metadata registered on chain
+//!
+//! Built by the binary's build script into `blueprint.json`, and printed by the binary with
+//! `--print-blueprint`, from the same definition.
+
+use blueprint_sdk::tangle::blueprint;
+use blueprint_sdk::tangle::metadata::macros::ext::serde_json;
+
+use crate::jobs::process_llm_request;
+
+/// The blueprint metadata as pretty-printed JSON, the content of `blueprint.json`
+pub fn blueprint_json() -> Result<String, String> {
+    let blueprint = blueprint! {
+        name: "open-router-blueprint",
+        master_manager_revision: "Latest",
+        manager: { Evm = "OpenRouterBlueprint" },
+        jobs: [process_llm_request]
+    }
+    .map_err(|e| format!("{e:?}"))?;
+
+    serde_json::to_string_pretty(&blueprint).map_err(|e| e.to_string())
+}
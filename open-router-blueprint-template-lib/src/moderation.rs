@@ -0,0 +1,165 @@
+//! Content moderation for prompts and responses
+//!
+//! A [`Moderator`] decides whether a piece of text may pass through the gateway. The
+//! built-in [`KeywordModerator`] blocks configured keywords and regular expressions; operators
+//! with other needs (e.g. a hosted moderation API) can install their own implementation with
+//! `OpenRouterContext::set_moderator`.
+
+use async_trait::async_trait;
+use regex::Regex;
+
+use crate::config::ModerationConfig;
+use crate::llm::{LlmRequest, LlmResponse};
+
+/// Outcome of moderating a piece of text
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ModerationResult {
+    /// The text may pass
+    Allowed,
+
+    /// The text violates the policy; `reason` is for logs and is not returned to callers
+    Blocked { reason: String },
+}
+
+impl ModerationResult {
+    /// Whether the text was blocked
+    pub fn is_blocked(&self) -> bool {
+        matches!(self, Self::Blocked { .. })
+    }
+}
+
+/// A content policy applied to prompts and, optionally, responses
+#[async_trait]
+pub trait Moderator: Send + Sync {
+    /// Check a single piece of text against the policy
+    async fn check(&self, text: &str) -> ModerationResult;
+
+    /// Check every prompt text of a request, stopping at the first blocked one
+    async fn check_request(&self, request: &LlmRequest) -> ModerationResult {
+        for text in request_texts(request) {
+            let result = self.check(text).await;
+            if result.is_blocked() {
+                return result;
+            }
+        }
+        ModerationResult::Allowed
+    }
+
+    /// Check every generated text of a response, stopping at the first blocked one
+    async fn check_response(&self, response: &LlmResponse) -> ModerationResult {
+        for text in response_texts(response) {
+            let result = self.check(text).await;
+            if result.is_blocked() {
+                return result;
+            }
+        }
+        ModerationResult::Allowed
+    }
+}
+
+/// Blocks text containing any configured keyword or matching any configured pattern
+#[derive(Debug, Clone)]
+pub struct KeywordModerator {
+    keywords: Vec<String>,
+    patterns: Vec<Regex>,
+}
+
+impl KeywordModerator {
+    /// Create a moderator for the given keywords and regular expressions
+    pub fn new(keywords: &[String], patterns: &[String]) -> Result<Self, regex::Error> {
+        Ok(Self {
+            keywords: keywords
+                .iter()
+                .filter(|k| !k.is_empty())
+                .map(|k| k.to_lowercase())
+                .collect(),
+            patterns: patterns
+                .iter()
+                .map(|p| Regex::new(p))
+                .collect::<Result<_, _>>()?,
+        })
+    }
+
+    /// Create a moderator from the `api.moderation` configuration
+    pub fn from_config(config: &ModerationConfig) -> Result<Self, regex::Error> {
+        Self::new(&config.blocked_keywords, &config.blocked_patterns)
+    }
+}
+
+#[async_trait]
+impl Moderator for KeywordModerator {
+    async fn check(&self, text: &str) -> ModerationResult {
+        let lowercase = text.to_lowercase();
+        if let Some(keyword) = self
+            .keywords
+            .iter()
+            .find(|k| lowercase.contains(k.as_str()))
+        {
+            return ModerationResult::Blocked {
+                reason: format!("contains blocked keyword {:?}", keyword),
+            };
+        }
+        if let Some(pattern) = self.patterns.iter().find(|p| p.is_match(text)) {
+            return ModerationResult::Blocked {
+                reason: format!("matches blocked pattern {:?}", pattern.as_str()),
+            };
+        }
+        ModerationResult::Allowed
+    }
+}
+
+/// The caller-supplied texts of a request
+fn request_texts(request: &LlmRequest) -> Vec<&str> {
+    match request {
+        LlmRequest::ChatCompletion(req) => {
+            req.messages.iter().map(|m| m.content.as_str()).collect()
+        }
+        LlmRequest::TextCompletion(req) => vec![req.prompt.as_str()],
+        LlmRequest::Embedding(req) => req.input.iter().map(String::as_str).collect(),
+    }
+}
+
+/// The generated texts of a response
+fn response_texts(response: &LlmResponse) -> Vec<&str> {
+    match response {
+        LlmResponse::ChatCompletion(resp) => resp
+            .choices
+            .iter()
+            .map(|c| c.message.content.as_str())
+            .collect(),
+        LlmResponse::TextCompletion(resp) => resp.choices.iter().map(|c| c.text.as_str()).collect(),
+        LlmResponse::Embedding(_) => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_keywords_match_case_insensitively() {
+        let moderator = KeywordModerator::new(&["Forbidden Topic".to_string()], &[]).unwrap();
+
+        assert_eq!(
+            moderator.check("tell me about the weather").await,
+            ModerationResult::Allowed
+        );
+        assert!(moderator
+            .check("tell me about the FORBIDDEN topic")
+            .await
+            .is_blocked());
+    }
+
+    #[tokio::test]
+    async fn test_patterns_block_matching_text() {
+        let moderator =
+            KeywordModerator::new(&[], &[r"\b\d{3}-\d{2}-\d{4}\b".to_string()]).unwrap();
+
+        assert!(moderator
+            .check("my number is 123-45-6789")
+            .await
+            .is_blocked());
+        assert!(!moderator.check("my number is 12345").await.is_blocked());
+        assert!(KeywordModerator::new(&[], &["(".to_string()]).is_err());
+    }
+}
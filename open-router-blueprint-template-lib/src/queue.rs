@@ -0,0 +1,213 @@
+//! Admission queue bounding the requests this node dispatches at the same time
+//!
+//! Requests beyond `llm.max_concurrent_requests` wait for a slot instead of piling onto the
+//! backends. The queue depth and the time spent waiting are reported in `NodeMetrics`, so
+//! saturation is visible on chain and not only as active load.
+//!
+//! There is one queue for the whole operator rather than one per backend node: the limit
+//! bounds the requests dispatched to all nodes together, while the load balancer spreads
+//! them over the nodes. The queue is resized when the configuration is reloaded.
+
+use std::sync::atomic::{AtomicU32, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+use tracing::debug;
+
+/// A bounded set of dispatch slots with wait-time accounting
+#[derive(Debug)]
+pub struct RequestQueue {
+    slots: Arc<Semaphore>,
+    /// Number of slots, changed by [`RequestQueue::resize`]
+    capacity: Mutex<usize>,
+    /// Slots in use to retire when they are released, after shrinking below their number
+    retiring: AtomicUsize,
+    queued: AtomicU32,
+    admitted: AtomicU64,
+    total_wait_ms: AtomicU64,
+}
+
+/// A dispatch slot, released when dropped
+#[derive(Debug)]
+pub struct QueueSlot {
+    permit: Option<OwnedSemaphorePermit>,
+    queue: Arc<RequestQueue>,
+}
+
+impl Drop for QueueSlot {
+    fn drop(&mut self) {
+        if let Some(permit) = self.permit.take() {
+            if self.queue.take_retiring() {
+                permit.forget();
+            }
+        }
+    }
+}
+
+/// Counts a request as queued until dropped, including when the waiting request is cancelled
+struct Waiting<'a>(&'a AtomicU32);
+
+impl<'a> Waiting<'a> {
+    fn new(queued: &'a AtomicU32) -> Self {
+        queued.fetch_add(1, Ordering::SeqCst);
+        Self(queued)
+    }
+}
+
+impl Drop for Waiting<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+impl RequestQueue {
+    /// Create a queue admitting at most `max_concurrent` requests at a time
+    pub fn new(max_concurrent: usize) -> Self {
+        let max_concurrent = max_concurrent.max(1);
+        Self {
+            slots: Arc::new(Semaphore::new(max_concurrent)),
+            capacity: Mutex::new(max_concurrent),
+            retiring: AtomicUsize::new(0),
+            queued: AtomicU32::new(0),
+            admitted: AtomicU64::new(0),
+            total_wait_ms: AtomicU64::new(0),
+        }
+    }
+
+    /// Wait for a dispatch slot, which is released when the returned slot is dropped
+    pub async fn acquire(self: &Arc<Self>) -> QueueSlot {
+        let started = Instant::now();
+        let permit = {
+            let _waiting = Waiting::new(&self.queued);
+            // The semaphore is never closed, so acquiring cannot fail
+            self.slots
+                .clone()
+                .acquire_owned()
+                .await
+                .expect("request queue semaphore is never closed")
+        };
+
+        let waited = started.elapsed();
+        self.admitted.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_ms
+            .fetch_add(waited.as_millis() as u64, Ordering::Relaxed);
+        if !waited.is_zero() {
+            debug!("Request waited {:?} for a dispatch slot", waited);
+        }
+        QueueSlot {
+            permit: Some(permit),
+            queue: self.clone(),
+        }
+    }
+
+    /// Admit at most `max_concurrent` requests at a time from now on
+    ///
+    /// When shrinking, requests already admitted keep their slots; the extra slots are
+    /// retired as they are released.
+    pub fn resize(&self, max_concurrent: usize) {
+        let max_concurrent = max_concurrent.max(1);
+        let mut capacity = self.capacity.lock().unwrap_or_else(|e| e.into_inner());
+        if max_concurrent > *capacity {
+            let mut added = max_concurrent - *capacity;
+            // Keep slots that were about to be retired before adding new ones
+            while added > 0 && self.take_retiring() {
+                added -= 1;
+            }
+            self.slots.add_permits(added);
+        } else {
+            let removed = *capacity - max_concurrent;
+            let forgotten = self.slots.forget_permits(removed);
+            self.retiring
+                .fetch_add(removed - forgotten, Ordering::SeqCst);
+        }
+        if *capacity != max_concurrent {
+            debug!(
+                "Resized the request queue from {} to {} slots",
+                *capacity, max_concurrent
+            );
+        }
+        *capacity = max_concurrent;
+    }
+
+    /// Whether a released slot is retired instead of returned, counting it as retired if so
+    fn take_retiring(&self) -> bool {
+        self.retiring
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| n.checked_sub(1))
+            .is_ok()
+    }
+
+    /// Number of requests currently waiting for a slot
+    pub fn queued_requests(&self) -> u32 {
+        self.queued.load(Ordering::SeqCst)
+    }
+
+    /// Average time admitted requests waited for a slot, in milliseconds
+    pub fn avg_wait_ms(&self) -> u64 {
+        let admitted = self.admitted.load(Ordering::Relaxed);
+        if admitted == 0 {
+            return 0;
+        }
+        self.total_wait_ms.load(Ordering::Relaxed) / admitted
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_waiting_requests_are_counted() {
+        let queue = Arc::new(RequestQueue::new(1));
+        let permit = queue.acquire().await;
+
+        let waiter = {
+            let queue = queue.clone();
+            tokio::spawn(async move {
+                let _permit = queue.acquire().await;
+            })
+        };
+        while queue.queued_requests() == 0 {
+            tokio::task::yield_now().await;
+        }
+        assert_eq!(queue.queued_requests(), 1);
+
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        drop(permit);
+        waiter.await.unwrap();
+
+        assert_eq!(queue.queued_requests(), 0);
+        // One request did not wait and one waited at least 20ms
+        assert!(queue.avg_wait_ms() >= 10);
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_waiter_is_no_longer_counted() {
+        let queue = Arc::new(RequestQueue::new(1));
+        let _slot = queue.acquire().await;
+
+        let timed_out = tokio::time::timeout(Duration::from_millis(20), queue.acquire()).await;
+
+        assert!(timed_out.is_err());
+        assert_eq!(queue.queued_requests(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_resize_changes_the_number_of_slots() {
+        let queue = Arc::new(RequestQueue::new(1));
+        let first = queue.acquire().await;
+
+        queue.resize(2);
+        let second = queue.acquire().await;
+
+        // Shrinking retires the slots in use as they are released
+        queue.resize(1);
+        drop(first);
+        let waiting = tokio::time::timeout(Duration::from_millis(20), queue.acquire()).await;
+        assert!(waiting.is_err());
+        drop(second);
+        let _third = queue.acquire().await;
+    }
+}
@@ -0,0 +1,364 @@
+//! Node-wide and per-model request rate limiting
+//!
+//! The [`RateLimiter`] admits at most `api.max_requests_per_minute` requests in total, and
+//! enforces the request rates of `api.model_rate_limits` on top of that with a token bucket per
+//! limited model. Models without a limit of their own only count against the total, so callers
+//! cannot get past it by varying the model, and no bucket is kept for them. Models are limited
+//! under the id they resolve to, see `OpenRouterContext::try_acquire_rate_limit`.
+//!
+//! A bucket holds up to a minute's worth of requests and refills at a sixtieth of its limit per
+//! second, so bursts up to the limit are admitted and the sustained rate cannot exceed it.
+//! Limits of individual principals are enforced by the
+//! [`UsageTracker`](crate::usage::UsageTracker) instead.
+
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+use crate::config::ApiConfig;
+use crate::llm::LlmError;
+
+#[derive(Debug)]
+struct Bucket {
+    /// Requests admitted per minute, also the most tokens the bucket holds
+    requests_per_minute: u32,
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl Bucket {
+    /// A full bucket
+    fn new(requests_per_minute: u32, now: Instant) -> Self {
+        Self {
+            requests_per_minute,
+            tokens: f64::from(requests_per_minute),
+            last_refill: now,
+        }
+    }
+
+    /// Add the tokens earned since the last refill, up to the capacity
+    fn refill(&mut self, now: Instant) {
+        let elapsed = now.saturating_duration_since(self.last_refill).as_secs_f64();
+        let capacity = f64::from(self.requests_per_minute);
+        self.tokens = (self.tokens + elapsed * capacity / 60.0).min(capacity);
+        self.last_refill = now;
+    }
+
+    /// Change the limit, keeping the tokens left up to the new capacity
+    fn set_limit(&mut self, requests_per_minute: u32, now: Instant) {
+        self.refill(now);
+        self.requests_per_minute = requests_per_minute;
+        self.tokens = self.tokens.min(f64::from(requests_per_minute));
+    }
+}
+
+#[derive(Debug)]
+struct Limits {
+    enabled: bool,
+    max_requests_per_minute: u32,
+    model_limits: HashMap<String, u32>,
+}
+
+#[derive(Debug)]
+struct State {
+    limits: Limits,
+    /// Bucket of every request, holding `max_requests_per_minute`
+    global: Bucket,
+    /// Buckets of the models in `model_limits` that were requested
+    buckets: HashMap<String, Bucket>,
+}
+
+/// Token buckets limiting the requests the node admits per minute, in total and per model
+#[derive(Debug)]
+pub struct RateLimiter {
+    state: Mutex<State>,
+}
+
+impl RateLimiter {
+    /// Create a limiter admitting `max_requests_per_minute` in total
+    pub fn new(max_requests_per_minute: u32) -> Self {
+        Self::with_limits(Limits {
+            enabled: true,
+            max_requests_per_minute,
+            model_limits: HashMap::new(),
+        })
+    }
+
+    /// Create a limiter admitting every request
+    pub fn disabled() -> Self {
+        Self::with_limits(Limits {
+            enabled: false,
+            max_requests_per_minute: 0,
+            model_limits: HashMap::new(),
+        })
+    }
+
+    /// Create the limiter the `api` section of a configuration asks for
+    pub fn from_config(config: &ApiConfig) -> Self {
+        Self::with_limits(limits_of(config))
+    }
+
+    fn with_limits(limits: Limits) -> Self {
+        let global = Bucket::new(limits.max_requests_per_minute, Instant::now());
+        Self {
+            state: Mutex::new(State {
+                limits,
+                global,
+                buckets: HashMap::new(),
+            }),
+        }
+    }
+
+    /// Admit at most `requests_per_minute` for `model`, within the total limit
+    pub fn with_model_limit(self, model: impl Into<String>, requests_per_minute: u32) -> Self {
+        {
+            let mut state = self.state.lock().unwrap();
+            let model = model.into();
+            if let Some(bucket) = state.buckets.get_mut(&model) {
+                bucket.set_limit(requests_per_minute, Instant::now());
+            }
+            state.limits.model_limits.insert(model, requests_per_minute);
+        }
+        self
+    }
+
+    /// Apply the limits of a reloaded configuration
+    ///
+    /// The tokens left in every bucket are kept, up to its new capacity, so a reload doesn't
+    /// reset the window. Buckets of models that lost their limit are dropped.
+    pub fn reconfigure(&self, config: &ApiConfig) {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        state.limits = limits_of(config);
+        let State {
+            limits,
+            global,
+            buckets,
+        } = &mut *state;
+        global.set_limit(limits.max_requests_per_minute, now);
+        buckets.retain(|model, bucket| match limits.model_limits.get(model) {
+            Some(&requests_per_minute) => {
+                bucket.set_limit(requests_per_minute, now);
+                true
+            }
+            None => false,
+        });
+    }
+
+    /// Whether requests are limited at all
+    pub fn is_enabled(&self) -> bool {
+        self.state.lock().unwrap().limits.enabled
+    }
+
+    /// Admit a request for `model`, taking a token of the total bucket and of the model's if
+    /// it has a limit, or fail with [`LlmError::RateLimited`]
+    pub fn try_acquire(&self, model: &str) -> Result<(), LlmError> {
+        self.try_acquire_at(model, Instant::now())
+    }
+
+    /// Admit a request for `model` arriving at `now`, or fail with
+    /// [`LlmError::RateLimited`]
+    ///
+    /// A rejected request takes no token of either bucket.
+    pub fn try_acquire_at(&self, model: &str, now: Instant) -> Result<(), LlmError> {
+        let mut state = self.state.lock().unwrap();
+        if !state.limits.enabled {
+            return Ok(());
+        }
+        let State {
+            limits,
+            global,
+            buckets,
+        } = &mut *state;
+
+        let model_bucket = match limits.model_limits.get(model) {
+            Some(&requests_per_minute) => {
+                let bucket = buckets
+                    .entry(model.to_string())
+                    .or_insert_with(|| Bucket::new(requests_per_minute, now));
+                bucket.refill(now);
+                if bucket.tokens < 1.0 {
+                    return Err(LlmError::RateLimited(format!(
+                        "limit of {} requests per minute for model {} reached",
+                        bucket.requests_per_minute, model
+                    )));
+                }
+                Some(bucket)
+            }
+            None => None,
+        };
+
+        global.refill(now);
+        if global.tokens < 1.0 {
+            return Err(LlmError::RateLimited(format!(
+                "limit of {} requests per minute reached",
+                global.requests_per_minute
+            )));
+        }
+        global.tokens -= 1.0;
+        if let Some(bucket) = model_bucket {
+            bucket.tokens -= 1.0;
+        }
+        Ok(())
+    }
+}
+
+fn limits_of(config: &ApiConfig) -> Limits {
+    Limits {
+        enabled: config.rate_limiting_enabled,
+        max_requests_per_minute: config.max_requests_per_minute,
+        model_limits: config.model_rate_limits.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const MODEL: &str = "test-model";
+
+    #[test]
+    fn test_rejects_requests_over_limit_until_refilled() {
+        let limiter = RateLimiter::new(60);
+        let start = Instant::now();
+
+        for _ in 0..60 {
+            assert!(limiter.try_acquire_at(MODEL, start).is_ok());
+        }
+        assert!(matches!(
+            limiter.try_acquire_at(MODEL, start),
+            Err(LlmError::RateLimited(_))
+        ));
+
+        // 60 requests per minute earn a token every second
+        assert!(limiter
+            .try_acquire_at(MODEL, start + Duration::from_millis(500))
+            .is_err());
+        assert!(limiter
+            .try_acquire_at(MODEL, start + Duration::from_secs(1))
+            .is_ok());
+        assert!(limiter
+            .try_acquire_at(MODEL, start + Duration::from_secs(1))
+            .is_err());
+    }
+
+    #[test]
+    fn test_bucket_holds_at_most_a_minute_of_requests() {
+        let limiter = RateLimiter::new(2);
+        let start = Instant::now();
+        assert!(limiter.try_acquire_at(MODEL, start).is_ok());
+
+        let later = start + Duration::from_secs(600);
+        assert!(limiter.try_acquire_at(MODEL, later).is_ok());
+        assert!(limiter.try_acquire_at(MODEL, later).is_ok());
+        assert!(limiter.try_acquire_at(MODEL, later).is_err());
+    }
+
+    #[test]
+    fn test_model_limits_apply_within_total_limit() {
+        let limiter = RateLimiter::new(3).with_model_limit("large-model", 1);
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire_at("large-model", now).is_ok());
+        assert!(limiter.try_acquire_at("large-model", now).is_err());
+
+        // Models without a limit of their own share what is left of the total
+        assert!(limiter.try_acquire_at("small-model", now).is_ok());
+        assert!(limiter.try_acquire_at("other-model", now).is_ok());
+        assert!(limiter.try_acquire_at("small-model", now).is_err());
+        assert!(limiter.try_acquire_at("yet-another-model", now).is_err());
+    }
+
+    #[test]
+    fn test_varying_the_model_does_not_get_past_the_total() {
+        let limiter = RateLimiter::new(5);
+        let now = Instant::now();
+
+        for i in 0..5 {
+            assert!(limiter.try_acquire_at(&format!("model-{}", i), now).is_ok());
+        }
+        assert!(limiter.try_acquire_at("model-5", now).is_err());
+
+        // Only limited models get a bucket
+        assert!(limiter.state.lock().unwrap().buckets.is_empty());
+    }
+
+    #[test]
+    fn test_rejected_request_takes_no_token() {
+        let limiter = RateLimiter::new(2).with_model_limit("large-model", 1);
+        let now = Instant::now();
+
+        assert!(limiter.try_acquire_at("large-model", now).is_ok());
+        // Rejected by the model's limit, which leaves the total's last token
+        assert!(limiter.try_acquire_at("large-model", now).is_err());
+        assert!(limiter.try_acquire_at("small-model", now).is_ok());
+    }
+
+    #[test]
+    fn test_disabled_limiter_admits_everything() {
+        let limiter = RateLimiter::from_config(&ApiConfig {
+            rate_limiting_enabled: false,
+            max_requests_per_minute: 1,
+            ..Default::default()
+        });
+        let now = Instant::now();
+
+        assert!(!limiter.is_enabled());
+        for _ in 0..100 {
+            assert!(limiter.try_acquire_at(MODEL, now).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_reconfigure_applies_new_limits() {
+        let limiter = RateLimiter::new(10).with_model_limit(MODEL, 10);
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(MODEL, now).is_ok());
+
+        limiter.reconfigure(&ApiConfig {
+            rate_limiting_enabled: true,
+            max_requests_per_minute: 10,
+            model_rate_limits: HashMap::from([(MODEL.to_string(), 2)]),
+            ..Default::default()
+        });
+
+        // The bucket keeps its tokens up to the new capacity
+        assert!(limiter.try_acquire_at(MODEL, now).is_ok());
+        assert!(limiter.try_acquire_at(MODEL, now).is_ok());
+        assert!(limiter.try_acquire_at(MODEL, now).is_err());
+    }
+
+    #[test]
+    fn test_reconfigure_applies_new_total_limit() {
+        let limiter = RateLimiter::new(10);
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(MODEL, now).is_ok());
+
+        limiter.reconfigure(&ApiConfig {
+            rate_limiting_enabled: true,
+            max_requests_per_minute: 2,
+            ..Default::default()
+        });
+
+        assert!(limiter.try_acquire_at(MODEL, now).is_ok());
+        assert!(limiter.try_acquire_at(MODEL, now).is_ok());
+        assert!(limiter.try_acquire_at(MODEL, now).is_err());
+    }
+
+    #[test]
+    fn test_reconfigure_drops_buckets_of_unlimited_models() {
+        let limiter = RateLimiter::new(10).with_model_limit(MODEL, 1);
+        let now = Instant::now();
+        assert!(limiter.try_acquire_at(MODEL, now).is_ok());
+        assert!(limiter.try_acquire_at(MODEL, now).is_err());
+
+        limiter.reconfigure(&ApiConfig {
+            rate_limiting_enabled: true,
+            max_requests_per_minute: 10,
+            ..Default::default()
+        });
+        assert!(limiter.state.lock().unwrap().buckets.is_empty());
+        assert!(limiter.try_acquire_at(MODEL, now).is_ok());
+    }
+}
@@ -0,0 +1,151 @@
+//! Structured logging of LLM requests and their responses
+//!
+//! With `api.request_logging`, every `process_llm_request` call emits one `INFO` event with
+//! target [`REQUEST_LOG_TARGET`] once it completes. The event carries the model, the estimated
+//! prompt tokens, the latency, the finish reason and the outcome as fields, so log pipelines
+//! can filter and aggregate on them. The prompt and the generated output are logged as well,
+//! replaced by [`REDACTED`] while `api.redact_content` is set so personal data in them never
+//! reaches the logs.
+
+use std::time::Instant;
+
+use tracing::info;
+
+use crate::config::ApiConfig;
+use crate::llm::{estimate_tokens, LlmRequest, LlmResponse};
+
+/// Tracing target of the request log events
+pub const REQUEST_LOG_TARGET: &str = "openrouter::request_log";
+
+/// Placeholder logged instead of redacted content
+pub const REDACTED: &str = "[redacted]";
+
+/// Emits a structured log event per LLM request
+#[derive(Debug, Clone, Copy)]
+pub struct RequestLogger {
+    redact_content: bool,
+}
+
+impl RequestLogger {
+    /// Create a logger, logging message content unless `redact_content` is set
+    pub fn new(redact_content: bool) -> Self {
+        Self { redact_content }
+    }
+
+    /// The logger the `api` section of a configuration asks for, if request logging is enabled
+    pub fn from_config(config: &ApiConfig) -> Option<Self> {
+        config
+            .request_logging
+            .then(|| Self::new(config.redact_content))
+    }
+
+    /// Start timing `request`, to be logged with [`RequestLog::finish`] once it completes
+    pub fn start(&self, request: &LlmRequest) -> RequestLog {
+        RequestLog {
+            model: request.model().to_string(),
+            prompt_tokens: prompt_tokens(request),
+            prompt: (!self.redact_content).then(|| prompt_content(request)),
+            redact_content: self.redact_content,
+            started: Instant::now(),
+        }
+    }
+}
+
+/// A request being timed for the request log
+#[derive(Debug)]
+pub struct RequestLog {
+    model: String,
+    prompt_tokens: usize,
+    /// The prompt, unless redacted
+    prompt: Option<String>,
+    redact_content: bool,
+    started: Instant,
+}
+
+impl RequestLog {
+    /// Emit the log event of the request, with its outcome
+    pub fn finish<E: std::fmt::Display>(self, result: &Result<LlmResponse, E>) {
+        let latency_ms = self.started.elapsed().as_millis() as u64;
+        let prompt = self.prompt.as_deref().unwrap_or(REDACTED);
+        match result {
+            Ok(response) => {
+                let output = if self.redact_content {
+                    REDACTED.to_string()
+                } else {
+                    output_content(response)
+                };
+                info!(
+                    target: REQUEST_LOG_TARGET,
+                    model = %self.model,
+                    prompt_tokens = self.prompt_tokens,
+                    latency_ms,
+                    finish_reason = finish_reason(response).unwrap_or("none"),
+                    status = "ok",
+                    prompt,
+                    output = %output,
+                    "LLM request completed"
+                );
+            }
+            Err(e) => info!(
+                target: REQUEST_LOG_TARGET,
+                model = %self.model,
+                prompt_tokens = self.prompt_tokens,
+                latency_ms,
+                finish_reason = "error",
+                status = "error",
+                prompt,
+                error = %e,
+                "LLM request completed"
+            ),
+        }
+    }
+}
+
+/// Estimated tokens of the prompt, or of the input of an embedding request
+fn prompt_tokens(request: &LlmRequest) -> usize {
+    match request {
+        LlmRequest::ChatCompletion(request) => request.estimated_prompt_tokens(),
+        LlmRequest::TextCompletion(request) => estimate_tokens(&request.prompt),
+        LlmRequest::Embedding(request) => request.input.iter().map(|i| estimate_tokens(i)).sum(),
+    }
+}
+
+/// The prompt of a request, with chat messages on their own lines prefixed by their role
+fn prompt_content(request: &LlmRequest) -> String {
+    match request {
+        LlmRequest::ChatCompletion(request) => request
+            .messages
+            .iter()
+            .map(|m| format!("{}: {}", m.role, m.content))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        LlmRequest::TextCompletion(request) => request.prompt.clone(),
+        LlmRequest::Embedding(request) => request.input.join("\n"),
+    }
+}
+
+/// The output of the first choice of a completion; embeddings have none
+fn output_content(response: &LlmResponse) -> String {
+    match response {
+        LlmResponse::ChatCompletion(response) => response
+            .choices
+            .first()
+            .map(|c| c.message.content.clone())
+            .unwrap_or_default(),
+        LlmResponse::TextCompletion(response) => response
+            .choices
+            .first()
+            .map(|c| c.text.clone())
+            .unwrap_or_default(),
+        LlmResponse::Embedding(_) => String::new(),
+    }
+}
+
+/// The finish reason of the first choice of a completion
+fn finish_reason(response: &LlmResponse) -> Option<&str> {
+    match response {
+        LlmResponse::ChatCompletion(response) => response.choices.first()?.finish_reason.as_deref(),
+        LlmResponse::TextCompletion(response) => response.choices.first()?.finish_reason.as_deref(),
+        LlmResponse::Embedding(_) => None,
+    }
+}
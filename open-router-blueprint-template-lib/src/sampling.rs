@@ -0,0 +1,49 @@
+//! Sampling for per-request log lines
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Decides which requests log their summary line
+///
+/// Sampling is deterministic: with a rate of 0.25 exactly every fourth request is sampled,
+/// so no random number generator is needed on the request path.
+#[derive(Debug, Default)]
+pub struct LogSampler {
+    seen: AtomicU64,
+}
+
+impl LogSampler {
+    /// Create a sampler that has not seen any requests yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Whether the next request should be logged at the given sample rate
+    pub fn sample(&self, rate: f32) -> bool {
+        if rate >= 1.0 {
+            return true;
+        }
+        if rate <= 0.0 {
+            return false;
+        }
+
+        // Sample whenever the running total of `rate` crosses an integer
+        let n = self.seen.fetch_add(1, Ordering::Relaxed);
+        let rate = f64::from(rate);
+        ((n + 1) as f64 * rate).floor() > (n as f64 * rate).floor()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sampler_logs_the_configured_fraction() {
+        let sampler = LogSampler::new();
+        let sampled = (0..100).filter(|_| sampler.sample(0.25)).count();
+        assert_eq!(sampled, 25);
+
+        assert!((0..10).all(|_| sampler.sample(1.0)));
+        assert!(!(0..10).any(|_| sampler.sample(0.0)));
+    }
+}
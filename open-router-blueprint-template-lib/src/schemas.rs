@@ -0,0 +1,138 @@
+//! JSON schemas for the request and response types
+//!
+//! Only available with the `schema` feature. The schemas are generated from the same types the
+//! blueprint serializes, so clients generated from them stay in sync with the wire format.
+
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+use schemars::schema::RootSchema;
+use schemars::schema_for;
+
+use crate::llm::{
+    BatchItemResult, ChatCompletionRequest, ChatCompletionResponse, EmbeddingRequest,
+    EmbeddingResponse, LlmRequest, LlmResponse, TextCompletionRequest, TextCompletionResponse,
+};
+
+/// Schema for [`ChatCompletionRequest`]
+pub fn chat_completion_request_schema() -> RootSchema {
+    schema_for!(ChatCompletionRequest)
+}
+
+/// Schema for [`ChatCompletionResponse`]
+pub fn chat_completion_response_schema() -> RootSchema {
+    schema_for!(ChatCompletionResponse)
+}
+
+/// Schema for [`TextCompletionRequest`]
+pub fn text_completion_request_schema() -> RootSchema {
+    schema_for!(TextCompletionRequest)
+}
+
+/// Schema for [`TextCompletionResponse`]
+pub fn text_completion_response_schema() -> RootSchema {
+    schema_for!(TextCompletionResponse)
+}
+
+/// Schema for [`EmbeddingRequest`]
+pub fn embedding_request_schema() -> RootSchema {
+    schema_for!(EmbeddingRequest)
+}
+
+/// Schema for [`EmbeddingResponse`]
+pub fn embedding_response_schema() -> RootSchema {
+    schema_for!(EmbeddingResponse)
+}
+
+/// Schema for the unified [`LlmRequest`] job input
+pub fn llm_request_schema() -> RootSchema {
+    schema_for!(LlmRequest)
+}
+
+/// Schema for the unified [`LlmResponse`] job output
+pub fn llm_response_schema() -> RootSchema {
+    schema_for!(LlmResponse)
+}
+
+/// Schema for the batch job input, a list of [`LlmRequest`]s
+pub fn llm_batch_request_schema() -> RootSchema {
+    schema_for!(Vec<LlmRequest>)
+}
+
+/// Schema for the batch job output, one [`BatchItemResult`] per request
+pub fn llm_batch_response_schema() -> RootSchema {
+    schema_for!(Vec<BatchItemResult>)
+}
+
+/// All schemas, keyed by the file stem they are written under
+pub fn all_schemas() -> Vec<(&'static str, RootSchema)> {
+    vec![
+        ("chat_completion_request", chat_completion_request_schema()),
+        (
+            "chat_completion_response",
+            chat_completion_response_schema(),
+        ),
+        ("text_completion_request", text_completion_request_schema()),
+        (
+            "text_completion_response",
+            text_completion_response_schema(),
+        ),
+        ("embedding_request", embedding_request_schema()),
+        ("embedding_response", embedding_response_schema()),
+        ("llm_request", llm_request_schema()),
+        ("llm_response", llm_response_schema()),
+        ("llm_batch_request", llm_batch_request_schema()),
+        ("llm_batch_response", llm_batch_response_schema()),
+    ]
+}
+
+/// Write every schema to `<dir>/<name>.schema.json`, creating `dir` if needed
+///
+/// Returns the paths of the written files.
+pub fn write_schemas(dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+
+    all_schemas()
+        .into_iter()
+        .map(|(name, schema)| {
+            let path = dir.join(format!("{name}.schema.json"));
+            let json = serde_json::to_string_pretty(&schema)?;
+            fs::write(&path, json)?;
+            Ok(path)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_chat_completion_request_schema_requires_model_and_messages() {
+        let schema = serde_json::to_value(chat_completion_request_schema()).unwrap();
+
+        let required: Vec<&str> = schema["required"]
+            .as_array()
+            .expect("schema should list required fields")
+            .iter()
+            .filter_map(|v| v.as_str())
+            .collect();
+        assert!(required.contains(&"model"));
+        assert!(required.contains(&"messages"));
+        assert!(!required.contains(&"temperature"));
+        assert_eq!(schema["properties"]["messages"]["type"], "array");
+    }
+
+    #[test]
+    fn test_write_schemas() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let paths = write_schemas(dir.path()).unwrap();
+
+        assert_eq!(paths.len(), all_schemas().len());
+        let written: serde_json::Value =
+            serde_json::from_str(&fs::read_to_string(&paths[0]).unwrap()).unwrap();
+        assert_eq!(written["title"], "ChatCompletionRequest");
+    }
+}
@@ -0,0 +1,384 @@
+//! Streaming LLM output through Tangle job results
+//!
+//! A Tangle job returns a single result, so `process_llm_request` can only return a streamed
+//! completion once it is complete. The `start_llm_stream` job instead generates the completion
+//! in the background and returns its stream id right away. Each `poll_llm_stream` call then
+//! returns the chunks produced since the sequence number it asks for, as a [`StreamPage`].
+//! Clients feed the pages into a [`StreamReassembler`], in whatever order their results
+//! arrive, to rebuild the complete response.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use serde::{Deserialize, Serialize};
+
+use crate::llm::{
+    collect_chat_completion_stream, collect_text_completion_stream, ChatCompletionChunk,
+    LlmError, LlmResponse, Result, TextCompletionChunk, TextCompletionResponse,
+    TextCompletionStreamChoice,
+};
+
+/// How long a stream is kept after its last chunk for clients to poll it
+pub const STREAM_RETENTION: Duration = Duration::from_secs(600);
+
+/// Most chunks returned by one `poll_llm_stream` call, bounding the size of a job result
+pub const MAX_CHUNKS_PER_PAGE: usize = 256;
+
+/// A chunk of a streamed completion
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum StreamChunk {
+    #[serde(rename = "chat.completion.chunk")]
+    Chat(ChatCompletionChunk),
+
+    #[serde(rename = "text.completion.chunk")]
+    Text(TextCompletionChunk),
+}
+
+/// A chunk with its position in the stream
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SequencedChunk {
+    /// Position of the chunk in the stream, starting at 0
+    pub sequence: u64,
+
+    /// The chunk
+    pub chunk: StreamChunk,
+}
+
+/// Input of the `poll_llm_stream` job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamPoll {
+    /// Id of the stream, as returned by `start_llm_stream`
+    pub stream_id: String,
+
+    /// Sequence number of the first chunk to return
+    #[serde(default)]
+    pub from_sequence: u64,
+}
+
+/// Result of the `poll_llm_stream` job
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StreamPage {
+    /// Id of the stream
+    pub stream_id: String,
+
+    /// Chunks from the requested sequence number on, in order
+    pub chunks: Vec<SequencedChunk>,
+
+    /// Sequence number to poll from next
+    pub next_sequence: u64,
+
+    /// Whether the stream has ended and `chunks` reaches its end
+    pub done: bool,
+
+    /// Why the stream ended early, if it failed
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug)]
+struct StreamBuffer {
+    chunks: Vec<StreamChunk>,
+    ended: bool,
+    error: Option<String>,
+    updated: Instant,
+}
+
+/// The streams started by `start_llm_stream`, buffered for clients to poll
+///
+/// A stream is kept for [`STREAM_RETENTION`] after its last chunk, so clients can poll it
+/// again; streams older than that are dropped whenever a stream is opened.
+#[derive(Debug)]
+pub struct StreamRegistry {
+    streams: Mutex<HashMap<String, StreamBuffer>>,
+    retention: Duration,
+}
+
+impl StreamRegistry {
+    /// Create a registry keeping streams for `retention` after their last chunk
+    pub fn new(retention: Duration) -> Self {
+        Self {
+            streams: Mutex::new(HashMap::new()),
+            retention,
+        }
+    }
+
+    /// Open an empty stream, returning `false` if a stream with this id exists already
+    pub fn open(&self, stream_id: &str) -> bool {
+        let mut streams = self.streams.lock().unwrap();
+        let retention = self.retention;
+        streams.retain(|_, stream| stream.updated.elapsed() < retention);
+        if streams.contains_key(stream_id) {
+            return false;
+        }
+        streams.insert(
+            stream_id.to_string(),
+            StreamBuffer {
+                chunks: Vec::new(),
+                ended: false,
+                error: None,
+                updated: Instant::now(),
+            },
+        );
+        true
+    }
+
+    /// Append a chunk to an open stream
+    pub fn push(&self, stream_id: &str, chunk: StreamChunk) {
+        if let Some(stream) = self.streams.lock().unwrap().get_mut(stream_id) {
+            if !stream.ended {
+                stream.chunks.push(chunk);
+                stream.updated = Instant::now();
+            }
+        }
+    }
+
+    /// End a stream, with the error that ended it early if it failed
+    pub fn finish(&self, stream_id: &str, error: Option<String>) {
+        if let Some(stream) = self.streams.lock().unwrap().get_mut(stream_id) {
+            stream.ended = true;
+            stream.error = error;
+            stream.updated = Instant::now();
+        }
+    }
+
+    /// The chunks of a stream from `from_sequence` on, at most [`MAX_CHUNKS_PER_PAGE`] of them,
+    /// or `None` for an unknown stream
+    pub fn page(&self, stream_id: &str, from_sequence: u64) -> Option<StreamPage> {
+        let streams = self.streams.lock().unwrap();
+        let stream = streams.get(stream_id)?;
+
+        let start = (from_sequence as usize).min(stream.chunks.len());
+        let end = (start + MAX_CHUNKS_PER_PAGE).min(stream.chunks.len());
+        let chunks = stream.chunks[start..end]
+            .iter()
+            .enumerate()
+            .map(|(offset, chunk)| SequencedChunk {
+                sequence: (start + offset) as u64,
+                chunk: chunk.clone(),
+            })
+            .collect();
+        let done = stream.ended && end == stream.chunks.len();
+        Some(StreamPage {
+            stream_id: stream_id.to_string(),
+            chunks,
+            next_sequence: end as u64,
+            done,
+            error: if done { stream.error.clone() } else { None },
+        })
+    }
+}
+
+impl Default for StreamRegistry {
+    fn default() -> Self {
+        Self::new(STREAM_RETENTION)
+    }
+}
+
+/// Rebuilds a complete response from the pages of a stream
+///
+/// Pages may arrive in any order and overlap; every chunk is kept once, by its sequence
+/// number.
+#[derive(Debug, Default)]
+pub struct StreamReassembler {
+    stream_id: Option<String>,
+    chunks: BTreeMap<u64, StreamChunk>,
+    /// Number of chunks in the stream, known once its last page arrived
+    length: Option<u64>,
+    error: Option<String>,
+}
+
+impl StreamReassembler {
+    /// Create a reassembler that has not seen any page yet
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add the chunks of a page, failing if it belongs to another stream than earlier pages
+    pub fn push(&mut self, page: StreamPage) -> Result<()> {
+        match &self.stream_id {
+            Some(stream_id) if *stream_id != page.stream_id => {
+                return Err(LlmError::InvalidRequest(format!(
+                    "page of stream {} pushed to the reassembler of stream {}",
+                    page.stream_id, stream_id
+                )));
+            }
+            Some(_) => {}
+            None => self.stream_id = Some(page.stream_id),
+        }
+
+        if page.done {
+            self.length = Some(page.next_sequence);
+            self.error = page.error;
+        }
+        for chunk in page.chunks {
+            self.chunks.entry(chunk.sequence).or_insert(chunk.chunk);
+        }
+        Ok(())
+    }
+
+    /// Sequence number of the first chunk not received yet, to poll from next
+    pub fn next_sequence(&self) -> u64 {
+        let mut next = 0;
+        while self.chunks.contains_key(&next) {
+            next += 1;
+        }
+        next
+    }
+
+    /// Whether every chunk of the stream has been received
+    pub fn is_complete(&self) -> bool {
+        self.length
+            .is_some_and(|length| self.next_sequence() >= length)
+    }
+
+    /// The complete response of the stream
+    ///
+    /// Fails if the stream failed, or if chunks are still missing.
+    pub async fn into_response(self) -> Result<LlmResponse> {
+        if let Some(error) = self.error {
+            return Err(LlmError::RequestFailed(error));
+        }
+        if !self.is_complete() {
+            return Err(LlmError::Internal(format!(
+                "stream is incomplete, chunks from {} on are missing",
+                self.next_sequence()
+            )));
+        }
+
+        let mut chat_chunks = Vec::new();
+        let mut text_chunks = Vec::new();
+        for chunk in self.chunks.into_values() {
+            match chunk {
+                StreamChunk::Chat(chunk) => chat_chunks.push(Ok(chunk)),
+                StreamChunk::Text(chunk) => text_chunks.push(Ok(chunk)),
+            }
+        }
+        match (chat_chunks.is_empty(), text_chunks.is_empty()) {
+            (false, true) => Ok(LlmResponse::ChatCompletion(
+                collect_chat_completion_stream(Box::pin(futures::stream::iter(chat_chunks)))
+                    .await?,
+            )),
+            (true, false) => Ok(LlmResponse::TextCompletion(
+                collect_text_completion_stream(Box::pin(futures::stream::iter(text_chunks)))
+                    .await?,
+            )),
+            (true, true) => Err(LlmError::ResponseParseError(
+                "stream has no chunks".to_string(),
+            )),
+            (false, false) => Err(LlmError::ResponseParseError(
+                "stream mixes chat and text completion chunks".to_string(),
+            )),
+        }
+    }
+}
+
+/// A text completion as a single chunk, for clients that cannot stream
+pub fn text_chunk_from_response(response: TextCompletionResponse) -> TextCompletionChunk {
+    TextCompletionChunk {
+        id: response.id,
+        object: "text_completion.chunk".to_string(),
+        created: response.created,
+        model: response.model,
+        choices: response
+            .choices
+            .into_iter()
+            .map(|choice| TextCompletionStreamChoice {
+                index: choice.index,
+                text: choice.text,
+                finish_reason: choice.finish_reason,
+                logprobs: choice.logprobs,
+            })
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text_chunk(text: &str, finish_reason: Option<&str>) -> StreamChunk {
+        StreamChunk::Text(TextCompletionChunk {
+            id: "cmpl-1".to_string(),
+            object: "text_completion.chunk".to_string(),
+            created: 1_700_000_000,
+            model: "test-model".to_string(),
+            choices: vec![TextCompletionStreamChoice {
+                index: 0,
+                text: text.to_string(),
+                finish_reason: finish_reason.map(str::to_string),
+                ..Default::default()
+            }],
+        })
+    }
+
+    #[tokio::test]
+    async fn test_pages_reassemble_in_any_order() {
+        let registry = StreamRegistry::default();
+        assert!(registry.open("stream"));
+        assert!(!registry.open("stream"));
+        for word in ["Once ", "upon ", "a "] {
+            registry.push("stream", text_chunk(word, None));
+        }
+        let first = registry.page("stream", 0).unwrap();
+        assert_eq!(first.next_sequence, 3);
+        assert!(!first.done);
+
+        registry.push("stream", text_chunk("time", Some("stop")));
+        registry.finish("stream", None);
+        let rest = registry.page("stream", 2).unwrap();
+        assert_eq!(rest.chunks.len(), 2);
+        assert!(rest.done);
+
+        let mut reassembler = StreamReassembler::new();
+        reassembler.push(rest).unwrap();
+        assert!(!reassembler.is_complete());
+        assert_eq!(reassembler.next_sequence(), 0);
+        reassembler.push(first).unwrap();
+        assert!(reassembler.is_complete());
+
+        let LlmResponse::TextCompletion(response) = reassembler.into_response().await.unwrap()
+        else {
+            panic!("expected a text completion");
+        };
+        assert_eq!(response.choices[0].text, "Once upon a time");
+        assert_eq!(response.choices[0].finish_reason.as_deref(), Some("stop"));
+    }
+
+    #[tokio::test]
+    async fn test_failed_stream() {
+        let registry = StreamRegistry::default();
+        registry.open("stream");
+        registry.push("stream", text_chunk("Once", None));
+        registry.finish("stream", Some("backend went away".to_string()));
+
+        let page = registry.page("stream", 0).unwrap();
+        assert_eq!(page.error.as_deref(), Some("backend went away"));
+        let mut reassembler = StreamReassembler::new();
+        reassembler.push(page).unwrap();
+        assert!(matches!(
+            reassembler.into_response().await,
+            Err(LlmError::RequestFailed(_))
+        ));
+
+        assert!(registry.page("unknown", 0).is_none());
+    }
+
+    #[test]
+    fn test_pages_are_bounded() {
+        let registry = StreamRegistry::default();
+        registry.open("stream");
+        for _ in 0..MAX_CHUNKS_PER_PAGE + 1 {
+            registry.push("stream", text_chunk("a", None));
+        }
+        registry.finish("stream", None);
+
+        let page = registry.page("stream", 0).unwrap();
+        assert_eq!(page.chunks.len(), MAX_CHUNKS_PER_PAGE);
+        assert!(!page.done);
+        let page = registry.page("stream", page.next_sequence).unwrap();
+        assert_eq!(page.chunks.len(), 1);
+        assert!(page.done);
+    }
+}
@@ -0,0 +1,43 @@
+//! OpenTelemetry export of the node's tracing spans
+//!
+//! Only available with the `otel` feature. The node already records its work as `tracing`
+//! spans: `llm_request` for each request, with `node_selection` and `backend_call` children
+//! carrying the model and node id. Adding the layer from [`otlp_layer`] to the subscriber turns
+//! them into OpenTelemetry spans exported over OTLP/HTTP. The exporter is configured by the
+//! standard `OTEL_EXPORTER_OTLP_*` environment variables and the service name by
+//! `OTEL_SERVICE_NAME`.
+
+use opentelemetry::trace::{TraceError, TracerProvider as _};
+use opentelemetry_sdk::runtime;
+use opentelemetry_sdk::trace::{Tracer, TracerProvider};
+use tracing::Subscriber;
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::registry::LookupSpan;
+
+/// Name of the tracer the node's spans are recorded with
+pub const TRACER_NAME: &str = "open-router-blueprint";
+
+/// A tracing layer exporting spans over OTLP, with the provider behind it
+///
+/// Spans are exported in batches on the Tokio runtime; call `shutdown` on the provider before
+/// exiting to flush the last batch.
+pub fn otlp_layer<S>() -> Result<(OpenTelemetryLayer<S, Tracer>, TracerProvider), TraceError>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .build()?;
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .build();
+    Ok((layer_for_provider(&provider), provider))
+}
+
+/// A tracing layer recording spans with the tracer of `provider`
+pub fn layer_for_provider<S>(provider: &TracerProvider) -> OpenTelemetryLayer<S, Tracer>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    tracing_opentelemetry::layer().with_tracer(provider.tracer(TRACER_NAME))
+}
@@ -3,15 +3,22 @@
 //! This module contains tests for the LLM client functionality.
 
 use std::sync::Arc;
-use tokio::test;
+use std::time::Duration;
 
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use futures::StreamExt;
+
+use crate::context::OpenRouterContext;
+use crate::jobs::process_llm_request;
 use crate::llm::{
-    ChatCompletionRequest, ChatMessage, EmbeddingRequest, LlmClient, StreamingLlmClient,
-    TextCompletionRequest,
+    LlmClient, LlmClientExt, LlmRequest, LlmResponse, LocalLlmClient, LocalLlmConfig,
+    StreamingLlmClient,
 };
 use crate::tests::{
-    MockLlmClient, MockStreamingLlmClient, create_test_chat_request, create_test_text_request,
-    create_test_embedding_request,
+    create_test_chat_request, create_test_embedding_request, create_test_text_request,
+    MockLlmClient, MockStreamingLlmClient, MOCK_STREAMED_REPLY,
 };
 
 /// Test that verifies the basic LLM client functionality works correctly
@@ -19,18 +26,18 @@ use crate::tests::{
 async fn test_llm_client_basic() {
     // Create a mock LLM client
     let client = MockLlmClient::new();
-    
+
     // Verify supported models
     let models = client.get_supported_models();
     assert_eq!(models.len(), 1);
     assert_eq!(models[0].id, "test-model");
-    
+
     // Verify capabilities
     let capabilities = client.get_capabilities();
     assert!(capabilities.supports_streaming);
     assert_eq!(capabilities.max_concurrent_requests, 10);
     assert!(!capabilities.supports_batching);
-    
+
     // Verify metrics
     let metrics = client.get_metrics();
     assert_eq!(metrics.cpu_utilization, 0.5);
@@ -41,23 +48,33 @@ async fn test_llm_client_basic() {
     assert_eq!(metrics.active_requests, 5);
 }
 
+/// Test that the default health check requires the client to serve a model
+#[tokio::test]
+async fn test_default_health_check() {
+    let mut client = MockLlmClient::new();
+    assert!(client.health_check().await.is_ok());
+
+    client.models.clear();
+    assert!(client.health_check().await.is_err());
+}
+
 /// Test that verifies the chat completion functionality works correctly
 #[tokio::test]
 async fn test_chat_completion() {
     // Create a mock LLM client
     let client = MockLlmClient::new();
-    
+
     // Create a chat completion request
     let request = create_test_chat_request();
-    
+
     // Send the request
     let response = client.chat_completion(request.clone()).await;
-    
+
     // Verify the response
     assert!(response.is_ok());
     let response = response.unwrap();
     assert_eq!(response.model, request.model);
-    
+
     // Test with a failing client
     let failing_client = MockLlmClient::new().with_failure();
     let response = failing_client.chat_completion(request).await;
@@ -69,18 +86,18 @@ async fn test_chat_completion() {
 async fn test_text_completion() {
     // Create a mock LLM client
     let client = MockLlmClient::new();
-    
+
     // Create a text completion request
     let request = create_test_text_request();
-    
+
     // Send the request
     let response = client.text_completion(request.clone()).await;
-    
+
     // Verify the response
     assert!(response.is_ok());
     let response = response.unwrap();
     assert_eq!(response.model, request.model);
-    
+
     // Test with a failing client
     let failing_client = MockLlmClient::new().with_failure();
     let response = failing_client.text_completion(request).await;
@@ -92,18 +109,18 @@ async fn test_text_completion() {
 async fn test_embeddings() {
     // Create a mock LLM client
     let client = MockLlmClient::new();
-    
+
     // Create an embedding request
     let request = create_test_embedding_request();
-    
+
     // Send the request
     let response = client.embeddings(request.clone()).await;
-    
+
     // Verify the response
     assert!(response.is_ok());
     let response = response.unwrap();
     assert_eq!(response.model, request.model);
-    
+
     // Test with a failing client
     let failing_client = MockLlmClient::new().with_failure();
     let response = failing_client.embeddings(request).await;
@@ -115,18 +132,18 @@ async fn test_embeddings() {
 async fn test_streaming_chat_completion() {
     // Create a mock streaming LLM client
     let client = MockStreamingLlmClient::new();
-    
+
     // Create a chat completion request
     let mut request = create_test_chat_request();
     request.stream = Some(true);
-    
+
     // Send the request
     let response = client.streaming_chat_completion(request.clone()).await;
-    
+
     // Verify the response
     assert!(response.is_ok());
     let mut stream = response.unwrap();
-    
+
     // Read from the stream
     let chunk = stream.next().await;
     assert!(chunk.is_some());
@@ -134,7 +151,7 @@ async fn test_streaming_chat_completion() {
     assert!(chunk.is_ok());
     let chunk = chunk.unwrap();
     assert_eq!(chunk.model, request.model);
-    
+
     // Test with a failing client
     let failing_client = MockStreamingLlmClient::new().with_failure();
     let response = failing_client.streaming_chat_completion(request).await;
@@ -146,18 +163,18 @@ async fn test_streaming_chat_completion() {
 async fn test_streaming_text_completion() {
     // Create a mock streaming LLM client
     let client = MockStreamingLlmClient::new();
-    
+
     // Create a text completion request
     let mut request = create_test_text_request();
     request.stream = Some(true);
-    
+
     // Send the request
     let response = client.streaming_text_completion(request.clone()).await;
-    
+
     // Verify the response
     assert!(response.is_ok());
     let mut stream = response.unwrap();
-    
+
     // Read from the stream
     let chunk = stream.next().await;
     assert!(chunk.is_some());
@@ -165,7 +182,7 @@ async fn test_streaming_text_completion() {
     assert!(chunk.is_ok());
     let chunk = chunk.unwrap();
     assert_eq!(chunk.model, request.model);
-    
+
     // Test with a failing client
     let failing_client = MockStreamingLlmClient::new().with_failure();
     let response = failing_client.streaming_text_completion(request).await;
@@ -177,13 +194,13 @@ async fn test_streaming_text_completion() {
 async fn test_llm_client_arc() {
     // Create a mock LLM client
     let client = Arc::new(MockLlmClient::new());
-    
+
     // Create a chat completion request
     let request = create_test_chat_request();
-    
+
     // Send the request
     let response = client.chat_completion(request).await;
-    
+
     // Verify the response
     assert!(response.is_ok());
 }
@@ -193,14 +210,80 @@ async fn test_llm_client_arc() {
 async fn test_streaming_llm_client_arc() {
     // Create a mock streaming LLM client
     let client = Arc::new(MockStreamingLlmClient::new());
-    
+
     // Create a chat completion request
     let mut request = create_test_chat_request();
     request.stream = Some(true);
-    
+
     // Send the request
     let response = client.streaming_chat_completion(request).await;
-    
+
     // Verify the response
     assert!(response.is_ok());
 }
+
+/// Test that the metrics collector samples the host's CPU and memory usage
+#[tokio::test]
+async fn test_metrics_collector() {
+    let client = LocalLlmClient::new(LocalLlmConfig::default());
+    client.metrics.write().unwrap().last_updated = 0;
+
+    let collector = client.spawn_metrics_collector(Duration::from_secs(60));
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while client.get_metrics().last_updated == 0 {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+        }
+    })
+    .await
+    .expect("the collector should sample within 5 seconds");
+    collector.abort();
+
+    let metrics = client.get_metrics();
+    assert!((0.0..=1.0).contains(&metrics.cpu_utilization));
+    assert!((0.0..=1.0).contains(&metrics.memory_utilization));
+    assert!(metrics.memory_utilization > 0.0);
+    assert!(metrics.last_updated > 0);
+}
+
+/// Test that a streaming client is found through an `Arc<dyn LlmClient>`, and a plain one is not
+#[test]
+fn test_as_streaming() {
+    let streaming: Arc<dyn LlmClient> = Arc::new(MockStreamingLlmClient::new());
+    let plain: Arc<dyn LlmClient> = Arc::new(MockLlmClient::new());
+
+    assert!(streaming.as_streaming().is_some());
+    // The mock advertises streaming but does not implement it
+    assert!(plain.as_streaming().is_none());
+}
+
+/// Test that a streaming request is served through the streaming client when one is registered
+#[tokio::test]
+async fn test_process_llm_request_uses_streaming_client() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .add_llm_node(
+            "streaming".to_string(),
+            Arc::new(MockStreamingLlmClient::new()),
+        )
+        .await?;
+
+    let mut request = create_test_chat_request();
+    request.stream = Some(true);
+    let response = process_llm_request(
+        Context(context),
+        CallId(1),
+        TangleArg(LlmRequest::ChatCompletion(request)),
+    )
+    .await?;
+
+    // `chat_completion` of the mock returns no choices; the collected chunks carry the reply
+    let LlmResponse::ChatCompletion(response) = response.0 else {
+        panic!("Unexpected response type: {:?}", response.0);
+    };
+    assert_eq!(response.choices.len(), 1);
+    assert_eq!(
+        response.choices[0].message.content,
+        MOCK_STREAMED_REPLY.concat()
+    );
+    Ok(())
+}
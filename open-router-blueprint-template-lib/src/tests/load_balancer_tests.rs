@@ -2,11 +2,19 @@
 //!
 //! This module contains tests for the load balancing functionality.
 
+use std::collections::HashMap;
+use std::sync::atomic::Ordering;
 use std::sync::Arc;
-use tokio::test;
+use std::time::Duration;
 
-use crate::load_balancer::{LoadBalancer, LoadBalancerConfig, LoadBalancingStrategy};
-use crate::tests::{MockLlmClient, create_test_load_balancer, add_mock_clients};
+use tokio::time::Instant;
+
+use crate::llm::{ChatCompletionRequest, LlmClient, LlmRequest, Operation};
+use crate::load_balancer::{
+    split_provider_suffix, BackoffConfig, CircuitState, DrainOutcome, LoadBalancer,
+    LoadBalancerConfig, LoadBalancingStrategy,
+};
+use crate::tests::{add_mock_clients, create_test_load_balancer, MockLlmClient};
 
 /// Test that verifies adding and removing nodes from the load balancer works correctly
 #[tokio::test]
@@ -88,6 +96,11 @@ async fn test_round_robin_strategy() {
         strategy: LoadBalancingStrategy::RoundRobin,
         max_retries: 3,
         selection_timeout_ms: 1000,
+        capability_weights: Default::default(),
+        failure_grace_count: 1,
+        failure_window_seconds: 60,
+        failure_cooldown_seconds: 30,
+        backoff: Default::default(),
     };
     let load_balancer = Arc::new(LoadBalancer::new(config));
     
@@ -117,6 +130,11 @@ async fn test_least_loaded_strategy() {
         strategy: LoadBalancingStrategy::LeastLoaded,
         max_retries: 3,
         selection_timeout_ms: 1000,
+        capability_weights: Default::default(),
+        failure_grace_count: 1,
+        failure_window_seconds: 60,
+        failure_cooldown_seconds: 30,
+        backoff: Default::default(),
     };
     let load_balancer = Arc::new(LoadBalancer::new(config));
     
@@ -169,6 +187,11 @@ async fn test_random_strategy() {
         strategy: LoadBalancingStrategy::Random,
         max_retries: 3,
         selection_timeout_ms: 1000,
+        capability_weights: Default::default(),
+        failure_grace_count: 1,
+        failure_window_seconds: 60,
+        failure_cooldown_seconds: 30,
+        backoff: Default::default(),
     };
     let load_balancer = Arc::new(LoadBalancer::new(config));
     
@@ -235,6 +258,50 @@ async fn test_select_node_for_model() {
     assert!(selected.is_none());
 }
 
+/// Test that verifies selection for an operation skips nodes whose model cannot serve it
+#[tokio::test]
+async fn test_select_node_for_operation() {
+    let load_balancer = create_test_load_balancer();
+
+    // Both nodes serve the same model id, one for chat and one for embeddings
+    let model = |supports_chat: bool| crate::llm::ModelInfo {
+        id: "shared".to_string(),
+        name: "Shared".to_string(),
+        max_context_length: 4096,
+        supports_chat,
+        supports_text: supports_chat,
+        supports_embeddings: !supports_chat,
+        parameters: Default::default(),
+    };
+    let mut chat_client = MockLlmClient::new();
+    chat_client.models = vec![model(true)];
+    let mut embedding_client = MockLlmClient::new();
+    embedding_client.models = vec![model(false)];
+    load_balancer
+        .add_node("chat".to_string(), Arc::new(chat_client))
+        .await;
+    load_balancer
+        .add_node("embedding".to_string(), Arc::new(embedding_client))
+        .await;
+
+    for _ in 0..4 {
+        let selected = load_balancer
+            .select_node_for_operation("shared", Operation::Embedding)
+            .await
+            .unwrap();
+        assert_eq!(selected.id, "embedding");
+        let selected = load_balancer
+            .select_node_for_operation("shared", Operation::ChatCompletion)
+            .await
+            .unwrap();
+        assert_eq!(selected.id, "chat");
+    }
+    let selected = load_balancer
+        .select_node_for_operation("other", Operation::Embedding)
+        .await;
+    assert!(selected.is_none());
+}
+
 /// Test that verifies the load balancer handles node failures correctly
 #[tokio::test]
 async fn test_node_failure_handling() {
@@ -269,3 +336,820 @@ async fn test_node_failure_handling() {
     assert!(selected_ids.contains("failing"));
     assert!(selected_ids.contains("working"));
 }
+
+/// Test that a node is only marked failed after its grace count of consecutive failures
+#[tokio::test]
+async fn test_failure_grace_count() {
+    let config = LoadBalancerConfig {
+        failure_grace_count: 3,
+        ..Default::default()
+    };
+    let load_balancer = Arc::new(LoadBalancer::new(config));
+    load_balancer
+        .add_node("flaky".to_string(), Arc::new(MockLlmClient::new()))
+        .await;
+
+    // A single error leaves the node selectable
+    assert!(!load_balancer.record_node_failure("flaky").await);
+    assert_eq!(load_balancer.select_node().await.unwrap().id, "flaky");
+
+    // A success starts the streak over
+    load_balancer.record_node_success("flaky").await;
+    assert!(!load_balancer.record_node_failure("flaky").await);
+    assert!(!load_balancer.record_node_failure("flaky").await);
+    assert!(load_balancer.get_node("flaky").await.is_some_and(|node| !node.failed));
+
+    // The third failure in a row marks it failed
+    assert!(load_balancer.record_node_failure("flaky").await);
+    assert!(load_balancer.get_node("flaky").await.unwrap().failed);
+    assert!(load_balancer.select_node().await.is_none());
+}
+
+/// Test that an excluded node is never selected until it is included again
+#[tokio::test]
+async fn test_node_exclusion() {
+    let load_balancer = create_test_load_balancer();
+    load_balancer
+        .add_node("excluded".to_string(), Arc::new(MockLlmClient::new()))
+        .await;
+    load_balancer
+        .add_node("included".to_string(), Arc::new(MockLlmClient::new()))
+        .await;
+
+    assert!(load_balancer.exclude_node("excluded").await);
+    assert!(!load_balancer.exclude_node("excluded").await);
+    assert_eq!(load_balancer.excluded_nodes().await, vec!["excluded"]);
+
+    // Failure resets and reactivation don't lift the exclusion
+    load_balancer.reset_node_failure("excluded").await;
+    load_balancer.set_node_active("excluded", true).await;
+
+    for _ in 0..10 {
+        let node = load_balancer.select_node().await.unwrap();
+        assert_eq!(node.id, "included");
+        let node = load_balancer
+            .select_node_for_model("test-model")
+            .await
+            .unwrap();
+        assert_eq!(node.id, "included");
+    }
+    let active: Vec<_> = load_balancer
+        .get_active_nodes()
+        .await
+        .into_iter()
+        .map(|node| node.id)
+        .collect();
+    assert_eq!(active, vec!["included"]);
+
+    // The excluded node is kept and selected again once included
+    assert!(load_balancer.get_node("excluded").await.is_some());
+    assert!(load_balancer.include_node("excluded").await);
+    assert!(!load_balancer.include_node("excluded").await);
+    let mut selected_ids = std::collections::HashSet::new();
+    for _ in 0..10 {
+        selected_ids.insert(load_balancer.select_node().await.unwrap().id);
+    }
+    assert!(selected_ids.contains("excluded"));
+}
+
+/// Test that only ids with a model and provider around their last `@` are pinned
+#[test]
+fn test_split_provider_suffix() {
+    assert_eq!(
+        split_provider_suffix("llama3@vllm"),
+        ("llama3", Some("vllm"))
+    );
+    assert_eq!(
+        split_provider_suffix("org/model@v2@ollama"),
+        ("org/model@v2", Some("ollama"))
+    );
+    assert_eq!(split_provider_suffix("llama3"), ("llama3", None));
+    assert_eq!(split_provider_suffix("llama3@"), ("llama3@", None));
+    assert_eq!(split_provider_suffix("@vllm"), ("@vllm", None));
+}
+
+/// A client serving `test-model` one request at a time with `active_requests` in flight
+fn mock_client(active_requests: u32) -> MockLlmClient {
+    let mut client = MockLlmClient::new().with_active_requests(active_requests);
+    client.capabilities.supports_streaming = false;
+    client.capabilities.max_concurrent_requests = 1;
+    client
+}
+
+/// An idle client that can stream `test-model`
+fn streaming_client() -> MockLlmClient {
+    let mut client = mock_client(0);
+    client.capabilities.supports_streaming = true;
+    client
+}
+
+/// Idle nodes named `node1` to `node{count}`
+fn idle_nodes(count: usize) -> Vec<(String, Arc<dyn LlmClient>)> {
+    (1..=count)
+        .map(|i| {
+            let client: Arc<dyn LlmClient> = Arc::new(mock_client(0));
+            (format!("node{}", i), client)
+        })
+        .collect()
+}
+
+#[tokio::test]
+async fn test_with_nodes_adds_all_nodes_as_active() {
+    let lb = LoadBalancer::with_nodes(LoadBalancerConfig::default(), idle_nodes(3)).await;
+
+    let mut ids: Vec<String> = lb
+        .get_active_nodes()
+        .await
+        .into_iter()
+        .map(|n| n.id)
+        .collect();
+    ids.sort();
+    assert_eq!(ids, vec!["node1", "node2", "node3"]);
+    assert_eq!(lb.get_all_nodes().await.len(), 3);
+}
+
+#[tokio::test]
+async fn test_remove_node_graceful_waits_for_in_flight_requests() {
+    let lb = Arc::new(LoadBalancer::new(LoadBalancerConfig::default()));
+    lb.add_node("node1".to_string(), Arc::new(mock_client(0)))
+        .await;
+    let request = lb.get_node("node1").await.unwrap().start_request();
+
+    let removal = {
+        let lb = lb.clone();
+        tokio::spawn(async move {
+            lb.remove_node_graceful("node1", Duration::from_secs(5))
+                .await
+        })
+    };
+
+    // While draining, the node stays registered but is no longer selected
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!removal.is_finished());
+    assert!(lb.get_node("node1").await.is_some());
+    assert!(lb.select_node_for_model("test-model").await.is_none());
+
+    drop(request);
+    assert!(removal.await.unwrap());
+    assert!(lb.get_node("node1").await.is_none());
+}
+
+#[tokio::test]
+async fn test_remove_node_graceful_gives_up_after_timeout() {
+    let lb = LoadBalancer::new(LoadBalancerConfig::default());
+    lb.add_node("node1".to_string(), Arc::new(mock_client(0)))
+        .await;
+    let _request = lb.get_node("node1").await.unwrap().start_request();
+
+    let started = Instant::now();
+    assert!(
+        lb.remove_node_graceful("node1", Duration::from_millis(100))
+            .await
+    );
+    assert!(started.elapsed() >= Duration::from_millis(100));
+    assert!(lb.get_node("node1").await.is_none());
+    assert!(
+        !lb.remove_node_graceful("node1", Duration::from_millis(100))
+            .await
+    );
+}
+
+#[tokio::test]
+async fn test_drain_node_reports_outcome() {
+    let lb = Arc::new(LoadBalancer::new(LoadBalancerConfig::default()));
+    lb.add_node("node1".to_string(), Arc::new(mock_client(0)))
+        .await;
+    let mut requests: Vec<_> = {
+        let node = lb.get_node("node1").await.unwrap();
+        (0..2).map(|_| node.start_request()).collect()
+    };
+
+    let drain = {
+        let lb = lb.clone();
+        tokio::spawn(async move { lb.drain_node("node1", Duration::from_secs(5)).await })
+    };
+
+    // The drain waits while requests are in flight, whatever the client reports
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!drain.is_finished());
+    requests.pop();
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!drain.is_finished());
+
+    drop(requests);
+    assert_eq!(drain.await.unwrap(), DrainOutcome::Drained);
+    assert!(lb.get_node("node1").await.is_none());
+
+    lb.add_node("node2".to_string(), Arc::new(mock_client(0)))
+        .await;
+    let node = lb.get_node("node2").await.unwrap();
+    let _requests = [node.start_request(), node.start_request()];
+    assert_eq!(
+        lb.drain_node("node2", Duration::from_millis(50)).await,
+        DrainOutcome::ForceRemoved { active_requests: 2 }
+    );
+    assert_eq!(
+        lb.drain_node("node2", Duration::from_millis(50)).await,
+        DrainOutcome::NotFound
+    );
+}
+
+#[tokio::test]
+async fn test_health_checks_toggle_node_active_state() {
+    let lb = Arc::new(LoadBalancer::new(LoadBalancerConfig::default()));
+    let client = Arc::new(mock_client(0));
+    lb.add_node("node1".to_string(), client.clone()).await;
+    lb.add_node("node2".to_string(), Arc::new(mock_client(0)))
+        .await;
+    lb.clone().start_health_checks(Duration::from_millis(10));
+    let is_active = |id: &'static str| {
+        let lb = lb.clone();
+        async move { lb.get_node(id).await.unwrap().active }
+    };
+
+    client.healthy.store(false, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!is_active("node1").await);
+    assert!(is_active("node2").await);
+
+    client.healthy.store(true, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(is_active("node1").await);
+
+    // Once stopped, a failing node keeps its state
+    lb.stop_health_checks();
+    client.healthy.store(false, Ordering::SeqCst);
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(is_active("node1").await);
+}
+
+#[tokio::test]
+async fn test_health_checks_leave_manually_deactivated_nodes_inactive() {
+    let lb = Arc::new(LoadBalancer::with_nodes(LoadBalancerConfig::default(), idle_nodes(1)).await);
+    lb.set_node_active("node1", false).await;
+
+    lb.clone().start_health_checks(Duration::from_millis(10));
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!lb.get_node("node1").await.unwrap().active);
+    lb.stop_health_checks();
+}
+
+#[tokio::test]
+async fn test_round_robin_stays_even_after_node_removed_mid_rotation() {
+    let lb = LoadBalancer::with_nodes(LoadBalancerConfig::default(), idle_nodes(3)).await;
+
+    // Advance the rotation to the last node, then remove a node
+    for _ in 0..2 {
+        lb.select_node_for_model("test-model").await.unwrap();
+    }
+    assert!(lb.remove_node("node2").await);
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for _ in 0..6 {
+        let node = lb.select_node_for_model("test-model").await.unwrap();
+        *counts.entry(node.id).or_default() += 1;
+    }
+    assert_eq!(counts.len(), 2);
+    assert_eq!(counts["node1"], 3);
+    assert_eq!(counts["node3"], 3);
+}
+
+#[tokio::test]
+async fn test_rebalance_restarts_rotation() {
+    let lb = LoadBalancer::with_nodes(LoadBalancerConfig::default(), idle_nodes(3)).await;
+    for _ in 0..2 {
+        lb.select_node_for_model("test-model").await.unwrap();
+    }
+
+    lb.set_node_active("node3", false).await;
+    lb.rebalance().await;
+
+    let mut selected = Vec::new();
+    for _ in 0..4 {
+        selected.push(lb.select_node_for_model("test-model").await.unwrap().id);
+    }
+    assert_eq!(selected, vec!["node1", "node2", "node1", "node2"]);
+}
+
+#[tokio::test]
+async fn test_config_changes_apply_at_runtime() {
+    let lb = Arc::new(LoadBalancer::new(LoadBalancerConfig::default()));
+    lb.add_node("node1".to_string(), Arc::new(mock_client(5)))
+        .await;
+    lb.add_node("node2".to_string(), Arc::new(mock_client(0)))
+        .await;
+    assert_eq!(
+        lb.select_node_for_model("test-model").await.unwrap().id,
+        "node1"
+    );
+
+    // Flip the strategy while other tasks keep selecting and updating nodes
+    let workers: Vec<_> = (0..4)
+        .map(|i| {
+            let lb = lb.clone();
+            tokio::spawn(async move {
+                for _ in 0..50 {
+                    lb.select_node_for_model("test-model").await.unwrap();
+                    lb.set_node_active("node1", true).await;
+                    if i == 0 {
+                        for strategy in [
+                            LoadBalancingStrategy::LeastLoaded,
+                            LoadBalancingStrategy::RoundRobin,
+                        ] {
+                            lb.set_config(LoadBalancerConfig {
+                                strategy,
+                                ..Default::default()
+                            })
+                            .await;
+                        }
+                    }
+                }
+            })
+        })
+        .collect();
+    tokio::time::timeout(Duration::from_secs(5), futures::future::join_all(workers))
+        .await
+        .expect("selections and config changes should not deadlock");
+
+    lb.set_config(LoadBalancerConfig {
+        strategy: LoadBalancingStrategy::LeastLoaded,
+        max_retries: 1,
+        selection_timeout_ms: 250,
+        ..Default::default()
+    })
+    .await;
+
+    let config = lb.config().await;
+    assert_eq!(config.strategy, LoadBalancingStrategy::LeastLoaded);
+    assert_eq!(config.max_retries, 1);
+    assert_eq!(config.selection_timeout_ms, 250);
+    for _ in 0..3 {
+        assert_eq!(
+            lb.select_node_for_model("test-model").await.unwrap().id,
+            "node2"
+        );
+    }
+}
+
+#[tokio::test]
+async fn test_streaming_selection_prefers_streaming_nodes() {
+    let lb = LoadBalancer::new(LoadBalancerConfig::default());
+    lb.add_node("node1".to_string(), Arc::new(mock_client(0)))
+        .await;
+    lb.add_node("node2".to_string(), Arc::new(streaming_client()))
+        .await;
+    lb.add_node("node3".to_string(), Arc::new(mock_client(0)))
+        .await;
+
+    for _ in 0..3 {
+        let node = lb
+            .select_streaming_node_for_model("test-model")
+            .await
+            .unwrap();
+        assert_eq!(node.id, "node2");
+        assert!(node.can_stream());
+    }
+
+    // Without a streaming-capable node, any node serving the model is used
+    lb.remove_node("node2").await;
+    let node = lb
+        .select_streaming_node_for_model("test-model")
+        .await
+        .unwrap();
+    assert!(!node.can_stream());
+}
+
+/// Two nodes serving `test-model` whose circuits open after 2 failures for 30 seconds
+async fn circuit_breaker_lb() -> LoadBalancer {
+    let config = LoadBalancerConfig {
+        failure_grace_count: 2,
+        failure_cooldown_seconds: 30,
+        ..Default::default()
+    };
+    let nodes: Vec<(String, Arc<dyn LlmClient>)> = vec![
+        ("node1".to_string(), Arc::new(mock_client(0))),
+        ("node2".to_string(), Arc::new(mock_client(0))),
+    ];
+    LoadBalancer::with_nodes(config, nodes).await
+}
+
+/// Ids of the nodes picked by `count` selections for `test-model`
+async fn picks(lb: &LoadBalancer, count: usize) -> Vec<String> {
+    let mut picks = Vec::new();
+    for _ in 0..count {
+        picks.push(lb.select_node_for_model("test-model").await.unwrap().id);
+    }
+    picks
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_circuit_opens_and_recovers_after_cool_down() {
+    let lb = circuit_breaker_lb().await;
+    let cool_down = Duration::from_secs(30);
+
+    // Repeated failures open the circuit of node1, which is then left out of selection
+    assert!(!lb.record_node_failure("node1").await);
+    assert!(lb.record_node_failure("node1").await);
+    let node = lb.get_node("node1").await.unwrap();
+    assert!(node.failed);
+    assert_eq!(
+        node.circuit.state(Instant::now(), cool_down),
+        CircuitState::Open
+    );
+    assert!(picks(&lb, 4).await.iter().all(|id| id == "node2"));
+
+    // After the cool-down, node1 receives a single trial request
+    tokio::time::advance(cool_down).await;
+    let trials = picks(&lb, 4).await;
+    assert_eq!(trials.iter().filter(|id| *id == "node1").count(), 1);
+
+    // Its success closes the circuit and node1 is back in the rotation
+    lb.record_node_success("node1").await;
+    let node = lb.get_node("node1").await.unwrap();
+    assert!(!node.failed);
+    assert_eq!(
+        node.circuit.state(Instant::now(), cool_down),
+        CircuitState::Closed
+    );
+    let picks = picks(&lb, 4).await;
+    assert_eq!(picks.iter().filter(|id| *id == "node1").count(), 2);
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_failed_trial_reopens_circuit() {
+    let lb = circuit_breaker_lb().await;
+    let cool_down = Duration::from_secs(30);
+    lb.record_node_failure("node1").await;
+    lb.record_node_failure("node1").await;
+
+    tokio::time::advance(cool_down).await;
+    assert!(picks(&lb, 2).await.contains(&"node1".to_string()));
+
+    // A single failure of the trial request reopens the circuit for another cool-down
+    assert!(lb.record_node_failure("node1").await);
+    assert!(picks(&lb, 4).await.iter().all(|id| id == "node2"));
+    tokio::time::advance(cool_down).await;
+    assert!(picks(&lb, 2).await.contains(&"node1".to_string()));
+}
+
+#[tokio::test(start_paused = true)]
+async fn test_manually_failed_node_gets_no_trial() {
+    let lb = circuit_breaker_lb().await;
+    assert!(lb.mark_node_failed("node1").await);
+
+    // Failures of a node marked failed by hand don't open a circuit to recover from
+    assert!(!lb.record_node_failure("node1").await);
+    tokio::time::advance(Duration::from_secs(60)).await;
+    assert!(picks(&lb, 4).await.iter().all(|id| id == "node2"));
+}
+
+#[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+async fn test_concurrent_selections_send_a_single_trial() {
+    let config = LoadBalancerConfig {
+        failure_cooldown_seconds: 1,
+        ..Default::default()
+    };
+    let nodes: Vec<(String, Arc<dyn LlmClient>)> =
+        vec![("node1".to_string(), Arc::new(mock_client(0)))];
+    let lb = Arc::new(LoadBalancer::with_nodes(config, nodes).await);
+    assert!(lb.record_node_failure("node1").await);
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    // Selections racing for the half-open node only let a single trial request through
+    let barrier = Arc::new(tokio::sync::Barrier::new(8));
+    let selections: Vec<_> = (0..8)
+        .map(|_| {
+            let (lb, barrier) = (lb.clone(), barrier.clone());
+            tokio::spawn(async move {
+                barrier.wait().await;
+                lb.select_node_for_model("test-model").await
+            })
+        })
+        .collect();
+    let mut trials = 0;
+    for selection in futures::future::join_all(selections).await {
+        if selection.unwrap().is_some() {
+            trials += 1;
+        }
+    }
+    assert_eq!(trials, 1);
+}
+
+#[tokio::test]
+async fn test_select_node_skips_failed_nodes_until_reset() {
+    let lb = LoadBalancer::with_nodes(LoadBalancerConfig::default(), idle_nodes(2)).await;
+
+    assert!(lb.mark_node_failed("node1").await);
+    assert!(lb.get_nodes().await["node1"].failed);
+    for _ in 0..3 {
+        assert_eq!(lb.select_node().await.unwrap().id, "node2");
+        assert_eq!(
+            lb.select_node_for_model("test-model").await.unwrap().id,
+            "node2"
+        );
+    }
+
+    // A failed node is still active and listed, just not selected
+    assert_eq!(lb.get_active_nodes().await.len(), 2);
+    assert!(lb.reset_node_failure("node1").await);
+    let mut selected: Vec<String> = Vec::new();
+    for _ in 0..2 {
+        selected.push(lb.select_node().await.unwrap().id);
+    }
+    selected.sort();
+    assert_eq!(selected, vec!["node1", "node2"]);
+
+    assert!(!lb.mark_node_failed("node3").await);
+    lb.mark_node_failed("node1").await;
+    lb.mark_node_failed("node2").await;
+    assert!(lb.select_node().await.is_none());
+}
+
+#[tokio::test]
+async fn test_random_selection_only_picks_eligible_nodes() {
+    let config = LoadBalancerConfig {
+        strategy: LoadBalancingStrategy::Random,
+        ..Default::default()
+    };
+    let lb = LoadBalancer::with_nodes(config, idle_nodes(3)).await;
+    lb.mark_node_failed("node3").await;
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for _ in 0..200 {
+        let node = lb.select_node_for_model("test-model").await.unwrap();
+        *counts.entry(node.id).or_default() += 1;
+    }
+
+    // Each of the two eligible nodes is picked with probability 1/2 per request
+    assert_eq!(counts.len(), 2);
+    assert!(!counts.contains_key("node3"));
+    assert!(counts.values().all(|&count| count > 50));
+}
+
+#[tokio::test]
+async fn test_weighted_round_robin_follows_node_weights() {
+    let config = LoadBalancerConfig {
+        strategy: LoadBalancingStrategy::WeightedRoundRobin,
+        ..Default::default()
+    };
+    let lb = LoadBalancer::with_nodes(config, idle_nodes(2)).await;
+    // The mock clients serve one request at a time
+    assert_eq!(lb.get_node("node1").await.unwrap().weight, 1);
+    assert!(lb.set_node_weight("node1", 3).await);
+
+    let picks: Vec<String> = {
+        let mut picks = Vec::new();
+        for _ in 0..400 {
+            picks.push(lb.select_node_for_model("test-model").await.unwrap().id);
+        }
+        picks
+    };
+
+    // Heavy nodes do not get their share in bursts
+    assert_eq!(picks[..4], ["node1", "node1", "node2", "node1"]);
+    let heavy = picks.iter().filter(|id| *id == "node1").count();
+    let ratio = heavy as f64 / (picks.len() - heavy) as f64;
+    assert!((ratio - 3.0).abs() < 0.1, "observed ratio {}", ratio);
+}
+
+#[tokio::test]
+async fn test_power_of_two_prefers_less_loaded_sample() {
+    let config = LoadBalancerConfig {
+        strategy: LoadBalancingStrategy::PowerOfTwo,
+        ..Default::default()
+    };
+    let nodes: Vec<(String, Arc<dyn LlmClient>)> = [0, 5, 10]
+        .into_iter()
+        .enumerate()
+        .map(|(i, active)| {
+            let client: Arc<dyn LlmClient> = Arc::new(mock_client(active));
+            (format!("node{}", i + 1), client)
+        })
+        .collect();
+    let lb = LoadBalancer::with_nodes(config, nodes).await;
+
+    let mut counts: HashMap<String, usize> = HashMap::new();
+    for _ in 0..300 {
+        let node = lb.select_node_for_model("test-model").await.unwrap();
+        *counts.entry(node.id).or_default() += 1;
+    }
+
+    // The busiest node loses every comparison; the idle one wins the 2 in 3 it is sampled in
+    assert!(!counts.contains_key("node3"));
+    assert!(counts["node1"] > 150, "observed counts {:?}", counts);
+    assert!(counts["node2"] > 50, "observed counts {:?}", counts);
+}
+
+#[tokio::test]
+async fn test_priority_service_tier_selects_least_loaded_node() {
+    let nodes: Vec<(String, Arc<dyn LlmClient>)> = vec![
+        ("node1".to_string(), Arc::new(mock_client(5))),
+        ("node2".to_string(), Arc::new(mock_client(0))),
+    ];
+    let lb = LoadBalancer::with_nodes(LoadBalancerConfig::default(), nodes).await;
+    let mut request = ChatCompletionRequest {
+        model: "test-model".to_string(),
+        ..Default::default()
+    };
+
+    // Round-robin rotates through both nodes for normal requests
+    let mut picks = Vec::new();
+    for _ in 0..2 {
+        let request = LlmRequest::ChatCompletion(request.clone());
+        let node = lb.node_for_request("test-model", &request, None).await;
+        picks.push(node.unwrap().id);
+    }
+    assert_eq!(picks, ["node1", "node2"]);
+
+    request.service_tier = Some("priority".to_string());
+    let request = LlmRequest::ChatCompletion(request);
+    for _ in 0..3 {
+        let node = lb.node_for_request("test-model", &request, None).await;
+        assert_eq!(node.unwrap().id, "node2");
+    }
+}
+
+#[test]
+fn test_backoff_follows_configured_schedule() {
+    let backoff = BackoffConfig {
+        initial_ms: 100,
+        max_ms: 1000,
+        multiplier: 3.0,
+        jitter: false,
+    };
+    let delays: Vec<u64> = (1..=5)
+        .map(|retry| backoff.delay(retry, |_| 0).as_millis() as u64)
+        .collect();
+    assert_eq!(delays, [100, 300, 900, 1000, 1000]);
+}
+
+#[test]
+fn test_backoff_jitter_stays_within_schedule() {
+    let backoff = BackoffConfig {
+        initial_ms: 100,
+        max_ms: 1000,
+        multiplier: 2.0,
+        jitter: true,
+    };
+
+    // A fixed jitter source taking half of every delay halves the schedule
+    let delays: Vec<u64> = (1..=5)
+        .map(|retry| backoff.delay(retry, |max_ms| max_ms / 2).as_millis() as u64)
+        .collect();
+    assert_eq!(delays, [50, 100, 200, 400, 500]);
+
+    // Picks beyond the delay are capped, and random picks stay within it
+    assert_eq!(backoff.delay(2, |_| u64::MAX), Duration::from_millis(200));
+    for retry in 1..=5 {
+        assert!(backoff.random_delay(retry) <= backoff.base_delay(retry));
+    }
+}
+
+#[test]
+fn test_backoff_validation() {
+    assert!(BackoffConfig::default().is_valid());
+    let shrinking = BackoffConfig {
+        multiplier: 0.5,
+        ..Default::default()
+    };
+    assert!(!shrinking.is_valid());
+    let inverted = BackoffConfig {
+        initial_ms: 5000,
+        max_ms: 100,
+        ..Default::default()
+    };
+    assert!(!inverted.is_valid());
+}
+
+#[tokio::test]
+async fn test_affinity_keeps_session_on_one_node() {
+    let config = LoadBalancerConfig {
+        strategy: LoadBalancingStrategy::Affinity,
+        ..Default::default()
+    };
+    let nodes: Vec<(String, Arc<dyn LlmClient>)> = (1..=4)
+        .map(|i| {
+            let client: Arc<dyn LlmClient> = Arc::new(mock_client(0));
+            (format!("node{}", i), client)
+        })
+        .collect();
+    let lb = LoadBalancer::with_nodes(config, nodes).await;
+    let session = |id: &str| {
+        LlmRequest::ChatCompletion(ChatCompletionRequest {
+            model: "test-model".to_string(),
+            session_id: Some(id.to_string()),
+            ..Default::default()
+        })
+    };
+
+    let mut assigned = HashMap::new();
+    for i in 0..20 {
+        let request = session(&format!("session-{}", i));
+        let first = lb.node_for_request("test-model", &request, None).await;
+        let first = first.unwrap().id;
+        for _ in 0..3 {
+            let node = lb.node_for_request("test-model", &request, None).await;
+            assert_eq!(node.unwrap().id, first);
+        }
+        assigned.insert(i, first);
+    }
+
+    // Only the sessions of the removed node move, and they move to the remaining nodes
+    assert!(lb.remove_node("node1").await);
+    for (i, before) in &assigned {
+        let request = session(&format!("session-{}", i));
+        let after = lb.node_for_request("test-model", &request, None).await;
+        let after = after.unwrap().id;
+        if before == "node1" {
+            assert_ne!(after, "node1");
+        } else {
+            assert_eq!(&after, before);
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_affinity_without_session_rotates() {
+    let config = LoadBalancerConfig {
+        strategy: LoadBalancingStrategy::Affinity,
+        ..Default::default()
+    };
+    let nodes: Vec<(String, Arc<dyn LlmClient>)> = vec![
+        ("node1".to_string(), Arc::new(mock_client(0))),
+        ("node2".to_string(), Arc::new(mock_client(0))),
+    ];
+    let lb = LoadBalancer::with_nodes(config, nodes).await;
+    let request = LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: "test-model".to_string(),
+        ..Default::default()
+    });
+
+    let mut picks = Vec::new();
+    for _ in 0..4 {
+        let node = lb.node_for_request("test-model", &request, None).await;
+        picks.push(node.unwrap().id);
+    }
+    assert_eq!(picks, ["node1", "node2", "node1", "node2"]);
+}
+
+#[cfg(feature = "strategy-capability")]
+#[tokio::test]
+async fn test_capability_weights_decide_between_cpu_and_request_load() {
+    let mut config = LoadBalancerConfig {
+        strategy: LoadBalancingStrategy::CapabilityBased,
+        ..Default::default()
+    };
+    let nodes: Vec<(String, Arc<dyn LlmClient>)> = vec![
+        ("busy-cpu".to_string(), Arc::new(mock_client(0))),
+        ("busy-requests".to_string(), Arc::new(mock_client(5))),
+    ];
+    let lb = LoadBalancer::with_nodes(config.clone(), nodes).await;
+    for (id, cpu_utilization) in [("busy-cpu", 0.9), ("busy-requests", 0.1)] {
+        let mut metrics = lb.get_node(id).await.unwrap().metrics;
+        metrics.cpu_utilization = cpu_utilization;
+        lb.update_node_metrics(id, metrics).await;
+    }
+
+    // By default five active requests weigh more than the CPU load
+    let node = lb.select_node_for_model("test-model").await.unwrap();
+    assert_eq!(node.id, "busy-cpu");
+
+    config.capability_weights.cpu_penalty = 2.0;
+    lb.set_config(config).await;
+    let node = lb.select_node_for_model("test-model").await.unwrap();
+    assert_eq!(node.id, "busy-requests");
+}
+
+#[tokio::test]
+async fn test_single_node_is_selected_without_strategy() {
+    let strategies = [
+        LoadBalancingStrategy::RoundRobin,
+        LoadBalancingStrategy::LeastLoaded,
+        LoadBalancingStrategy::CapabilityBased,
+        LoadBalancingStrategy::LatencyBased,
+        LoadBalancingStrategy::Random,
+        LoadBalancingStrategy::WeightedRoundRobin,
+        LoadBalancingStrategy::PowerOfTwo,
+        LoadBalancingStrategy::Affinity,
+    ];
+    for strategy in strategies.into_iter().filter(|s| s.is_enabled()) {
+        let config = LoadBalancerConfig {
+            strategy,
+            ..Default::default()
+        };
+        let client = Arc::new(mock_client(3));
+        let lb =
+            LoadBalancer::with_nodes(config, vec![("node1".to_string(), client.clone())]).await;
+
+        for _ in 0..3 {
+            assert_eq!(
+                lb.select_node_for_model("test-model").await.unwrap().id,
+                "node1"
+            );
+        }
+        assert_eq!(lb.select_node().await.unwrap().id, "node1");
+
+        // Models are only listed to filter the node; capability scoring would list them again
+        assert_eq!(client.model_lookups.load(Ordering::SeqCst), 3);
+    }
+}
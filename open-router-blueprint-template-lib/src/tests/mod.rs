@@ -2,19 +2,21 @@
 //!
 //! This module contains tests for the core functionality of the OpenRouter Blueprint.
 
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
 use std::sync::Arc;
 
 use crate::llm::{
-    ChatCompletionRequest, ChatCompletionResponse, ChatMessage, EmbeddingRequest, EmbeddingResponse,
-    LlmCapabilities, LlmClient, LlmError, ModelInfo, NodeMetrics, Result, StreamingLlmClient,
-    TextCompletionRequest, TextCompletionResponse,
+    ChatCompletionRequest, ChatCompletionResponse, ChatMessage, EmbeddingRequest,
+    EmbeddingResponse, LlmCapabilities, LlmClient, LlmError, ModelInfo, NodeMetrics, Result,
+    StreamingLlmClient, TextCompletionRequest, TextCompletionResponse,
 };
 use crate::load_balancer::{LoadBalancer, LoadBalancerConfig, LoadBalancingStrategy};
-use crate::config::BlueprintConfig;
 
 mod config_tests;
-mod load_balancer_tests;
 mod llm_tests;
+// Keeps the layout the load balancer tests were written in
+#[rustfmt::skip]
+mod load_balancer_tests;
 
 /// A mock LLM client for testing
 pub struct MockLlmClient {
@@ -22,22 +24,27 @@ pub struct MockLlmClient {
     pub capabilities: LlmCapabilities,
     pub metrics: NodeMetrics,
     pub should_fail: bool,
+    /// Requests reported in flight, which a test may change while the client is shared
+    pub active_requests: AtomicU32,
+    /// Whether the backend is reachable; an unreachable mock lists no models, failing the
+    /// default health check
+    pub healthy: AtomicBool,
+    /// Number of times the supported models were listed
+    pub model_lookups: AtomicU32,
 }
 
 impl MockLlmClient {
     pub fn new() -> Self {
         Self {
-            models: vec![
-                ModelInfo {
-                    id: "test-model".to_string(),
-                    name: "Test Model".to_string(),
-                    max_context_length: 4096,
-                    supports_chat: true,
-                    supports_text: true,
-                    supports_embeddings: true,
-                    parameters: Default::default(),
-                },
-            ],
+            models: vec![ModelInfo {
+                id: "test-model".to_string(),
+                name: "Test Model".to_string(),
+                max_context_length: 4096,
+                supports_chat: true,
+                supports_text: true,
+                supports_embeddings: true,
+                parameters: Default::default(),
+            }],
             capabilities: LlmCapabilities {
                 supports_streaming: true,
                 max_concurrent_requests: 10,
@@ -50,38 +57,56 @@ impl MockLlmClient {
                 gpu_utilization: Some(0.7),
                 requests_per_minute: 100,
                 average_response_time_ms: 200,
-                active_requests: 5,
-                last_updated: 0,
+                ..Default::default()
             },
             should_fail: false,
+            active_requests: AtomicU32::new(5),
+            healthy: AtomicBool::new(true),
+            model_lookups: AtomicU32::new(0),
         }
     }
-    
+
     pub fn with_failure(mut self) -> Self {
         self.should_fail = true;
         self
     }
+
+    pub fn with_active_requests(self, active_requests: u32) -> Self {
+        self.active_requests
+            .store(active_requests, Ordering::SeqCst);
+        self
+    }
 }
 
 #[async_trait::async_trait]
 impl LlmClient for MockLlmClient {
     fn get_supported_models(&self) -> Vec<ModelInfo> {
+        self.model_lookups.fetch_add(1, Ordering::SeqCst);
+        if !self.healthy.load(Ordering::SeqCst) {
+            return Vec::new();
+        }
         self.models.clone()
     }
-    
+
     fn get_capabilities(&self) -> LlmCapabilities {
         self.capabilities.clone()
     }
-    
+
     fn get_metrics(&self) -> NodeMetrics {
-        self.metrics.clone()
+        NodeMetrics {
+            active_requests: self.active_requests.load(Ordering::SeqCst),
+            ..self.metrics.clone()
+        }
     }
-    
-    async fn chat_completion(&self, request: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+
+    async fn chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse> {
         if self.should_fail {
             return Err(LlmError::RequestFailed("Mock failure".to_string()));
         }
-        
+
         Ok(ChatCompletionResponse {
             id: "mock-id".to_string(),
             object: "chat.completion".to_string(),
@@ -89,14 +114,19 @@ impl LlmClient for MockLlmClient {
             model: request.model,
             choices: vec![],
             usage: None,
+            cost: None,
+            correlation_id: None,
         })
     }
-    
-    async fn text_completion(&self, request: TextCompletionRequest) -> Result<TextCompletionResponse> {
+
+    async fn text_completion(
+        &self,
+        request: TextCompletionRequest,
+    ) -> Result<TextCompletionResponse> {
         if self.should_fail {
             return Err(LlmError::RequestFailed("Mock failure".to_string()));
         }
-        
+
         Ok(TextCompletionResponse {
             id: "mock-id".to_string(),
             object: "text_completion".to_string(),
@@ -104,23 +134,29 @@ impl LlmClient for MockLlmClient {
             model: request.model,
             choices: vec![],
             usage: None,
+            cost: None,
+            correlation_id: None,
         })
     }
-    
+
     async fn embeddings(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
         if self.should_fail {
             return Err(LlmError::RequestFailed("Mock failure".to_string()));
         }
-        
+
         Ok(EmbeddingResponse {
             object: "list".to_string(),
             model: request.model,
             data: vec![],
             usage: None,
+            correlation_id: None,
         })
     }
 }
 
+/// Content of the chunks a [`MockStreamingLlmClient`] streams for a chat completion
+pub const MOCK_STREAMED_REPLY: [&str; 2] = ["Streamed ", "reply"];
+
 /// A mock streaming LLM client for testing
 pub struct MockStreamingLlmClient {
     pub base: MockLlmClient,
@@ -132,7 +168,7 @@ impl MockStreamingLlmClient {
             base: MockLlmClient::new(),
         }
     }
-    
+
     pub fn with_failure(mut self) -> Self {
         self.base.should_fail = true;
         self
@@ -144,63 +180,94 @@ impl LlmClient for MockStreamingLlmClient {
     fn get_supported_models(&self) -> Vec<ModelInfo> {
         self.base.get_supported_models()
     }
-    
+
     fn get_capabilities(&self) -> LlmCapabilities {
         self.base.get_capabilities()
     }
-    
+
     fn get_metrics(&self) -> NodeMetrics {
         self.base.get_metrics()
     }
-    
-    async fn chat_completion(&self, request: ChatCompletionRequest) -> Result<ChatCompletionResponse> {
+
+    async fn chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse> {
         self.base.chat_completion(request).await
     }
-    
-    async fn text_completion(&self, request: TextCompletionRequest) -> Result<TextCompletionResponse> {
+
+    async fn text_completion(
+        &self,
+        request: TextCompletionRequest,
+    ) -> Result<TextCompletionResponse> {
         self.base.text_completion(request).await
     }
-    
+
     async fn embeddings(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
         self.base.embeddings(request).await
     }
+
+    fn streaming_client(&self) -> Option<&dyn StreamingLlmClient> {
+        Some(self)
+    }
 }
 
 #[async_trait::async_trait]
 impl StreamingLlmClient for MockStreamingLlmClient {
-    async fn streaming_chat_completion(&self, request: ChatCompletionRequest) -> Result<crate::llm::ChatCompletionStream> {
+    async fn streaming_chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<crate::llm::ChatCompletionStream> {
         if self.base.should_fail {
             return Err(LlmError::RequestFailed("Mock failure".to_string()));
         }
-        
-        // Create an empty stream
-        let (tx, rx) = tokio::sync::mpsc::channel(1);
-        let _ = tx.send(Ok(crate::llm::ChatCompletionChunk {
-            id: "mock-id".to_string(),
-            object: "chat.completion.chunk".to_string(),
-            created: 0,
-            model: request.model,
-            choices: vec![],
-        })).await;
-        
+
+        // Stream the reply in two chunks, so it can be told apart from `chat_completion`
+        let (tx, rx) = tokio::sync::mpsc::channel(MOCK_STREAMED_REPLY.len());
+        for content in MOCK_STREAMED_REPLY {
+            let _ = tx
+                .send(Ok(crate::llm::ChatCompletionChunk {
+                    id: "mock-id".to_string(),
+                    object: "chat.completion.chunk".to_string(),
+                    created: 0,
+                    model: request.model.clone(),
+                    choices: vec![crate::llm::ChatCompletionStreamChoice {
+                        index: 0,
+                        delta: crate::llm::ChatMessageDelta {
+                            role: None,
+                            content: Some(content.to_string()),
+                            ..Default::default()
+                        },
+                        finish_reason: None,
+                        ..Default::default()
+                    }],
+                }))
+                .await;
+        }
+
         Ok(crate::llm::create_chat_completion_stream(rx))
     }
-    
-    async fn streaming_text_completion(&self, request: TextCompletionRequest) -> Result<crate::llm::TextCompletionStream> {
+
+    async fn streaming_text_completion(
+        &self,
+        request: TextCompletionRequest,
+    ) -> Result<crate::llm::TextCompletionStream> {
         if self.base.should_fail {
             return Err(LlmError::RequestFailed("Mock failure".to_string()));
         }
-        
+
         // Create an empty stream
         let (tx, rx) = tokio::sync::mpsc::channel(1);
-        let _ = tx.send(Ok(crate::llm::TextCompletionChunk {
-            id: "mock-id".to_string(),
-            object: "text_completion.chunk".to_string(),
-            created: 0,
-            model: request.model,
-            choices: vec![],
-        })).await;
-        
+        let _ = tx
+            .send(Ok(crate::llm::TextCompletionChunk {
+                id: "mock-id".to_string(),
+                object: "text_completion.chunk".to_string(),
+                created: 0,
+                model: request.model,
+                choices: vec![],
+            }))
+            .await;
+
         Ok(crate::llm::create_text_completion_stream(rx))
     }
 }
@@ -211,8 +278,13 @@ pub fn create_test_load_balancer() -> LoadBalancer {
         strategy: LoadBalancingStrategy::RoundRobin,
         max_retries: 3,
         selection_timeout_ms: 1000,
+        capability_weights: Default::default(),
+        failure_grace_count: 1,
+        failure_window_seconds: 60,
+        failure_cooldown_seconds: 30,
+        backoff: Default::default(),
     };
-    
+
     LoadBalancer::new(config)
 }
 
@@ -228,15 +300,19 @@ pub async fn add_mock_clients(load_balancer: &LoadBalancer, count: usize) {
 pub fn create_test_chat_request() -> ChatCompletionRequest {
     ChatCompletionRequest {
         model: "test-model".to_string(),
-        messages: vec![
-            ChatMessage {
-                role: "user".to_string(),
-                content: "Hello, world!".to_string(),
-                name: None,
-            },
-        ],
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Hello, world!".to_string(),
+            ..Default::default()
+        }],
         temperature: Some(0.7),
         top_p: Some(1.0),
+        stop: None,
+        n: None,
+        service_tier: None,
+        session_id: None,
+        tools: None,
+        tool_choice: None,
         max_tokens: Some(100),
         stream: Some(false),
         additional_params: Default::default(),
@@ -250,6 +326,8 @@ pub fn create_test_text_request() -> TextCompletionRequest {
         prompt: "Hello, world!".to_string(),
         temperature: Some(0.7),
         top_p: Some(1.0),
+        stop: None,
+        n: None,
         max_tokens: Some(100),
         stream: Some(false),
         additional_params: Default::default(),
@@ -0,0 +1,147 @@
+//! Token counting for checking requests against the context of their model
+//!
+//! `process_llm_request` counts the tokens a completion needs with the context's
+//! [`TokenCounter`] to reject requests that overflow their model's `max_context_length`
+//! before they reach a backend (see `llm.reject_context_overflow`). The default
+//! [`ApproximateTokenCounter`] assumes about four characters per token; blueprints whose
+//! models use a known tokenizer can install an exact counter with
+//! `OpenRouterContext::set_token_counter`, such as the [`TiktokenCounter`] of the `tiktoken`
+//! feature.
+
+use crate::llm::{estimate_tokens, LlmRequest};
+
+/// Counts the tokens of prompts the way a model's tokenizer would
+pub trait TokenCounter: Send + Sync {
+    /// Number of tokens in `text`
+    fn count(&self, text: &str) -> usize;
+
+    /// Number of prompt tokens of a request: its messages, prompt or embedding inputs
+    fn count_prompt(&self, request: &LlmRequest) -> usize {
+        match request {
+            LlmRequest::ChatCompletion(request) => request
+                .messages
+                .iter()
+                .map(|message| self.count(&message.content))
+                .sum(),
+            LlmRequest::TextCompletion(request) => self.count(&request.prompt),
+            LlmRequest::Embedding(request) => {
+                request.input.iter().map(|input| self.count(input)).sum()
+            }
+        }
+    }
+
+    /// Number of context tokens a completion needs: its prompt and `max_tokens`
+    ///
+    /// Always 0 for embedding requests, which are not checked against a context.
+    fn count_context(&self, request: &LlmRequest) -> usize {
+        let max_tokens = match request {
+            LlmRequest::ChatCompletion(request) => request.max_tokens,
+            LlmRequest::TextCompletion(request) => request.max_tokens,
+            LlmRequest::Embedding(_) => return 0,
+        };
+        self.count_prompt(request) + max_tokens.unwrap_or(0) as usize
+    }
+}
+
+/// Estimates about four characters per token, see [`estimate_tokens`]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct ApproximateTokenCounter;
+
+impl TokenCounter for ApproximateTokenCounter {
+    fn count(&self, text: &str) -> usize {
+        estimate_tokens(text)
+    }
+}
+
+/// Counts tokens exactly with an OpenAI tokenizer from `tiktoken-rs`
+#[cfg(feature = "tiktoken")]
+pub struct TiktokenCounter {
+    bpe: tiktoken_rs::CoreBPE,
+}
+
+#[cfg(feature = "tiktoken")]
+impl TiktokenCounter {
+    /// A counter for the `cl100k_base` encoding of GPT-3.5 and GPT-4 models
+    pub fn cl100k_base() -> crate::llm::Result<Self> {
+        tiktoken_rs::cl100k_base()
+            .map(|bpe| Self { bpe })
+            .map_err(|e| crate::llm::LlmError::Internal(e.to_string()))
+    }
+
+    /// A counter for the encoding of the OpenAI model `model`
+    pub fn for_model(model: &str) -> crate::llm::Result<Self> {
+        tiktoken_rs::get_bpe_from_model(model)
+            .map(|bpe| Self { bpe })
+            .map_err(|e| crate::llm::LlmError::Internal(format!("{}: {}", model, e)))
+    }
+}
+
+#[cfg(feature = "tiktoken")]
+impl TokenCounter for TiktokenCounter {
+    fn count(&self, text: &str) -> usize {
+        self.bpe.encode_with_special_tokens(text).len()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::llm::{ChatCompletionRequest, ChatMessage, EmbeddingRequest};
+
+    /// A chat request of `content` asking for up to `max_tokens` tokens
+    fn chat_request(content: &str, max_tokens: u32) -> LlmRequest {
+        LlmRequest::ChatCompletion(ChatCompletionRequest {
+            model: "test-model".to_string(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: content.to_string(),
+                ..Default::default()
+            }],
+            max_tokens: Some(max_tokens),
+            ..Default::default()
+        })
+    }
+
+    #[test]
+    fn test_approximate_count() {
+        let counter = ApproximateTokenCounter;
+        assert_eq!(counter.count(""), 0);
+        assert_eq!(counter.count("abcd"), 1);
+        assert_eq!(counter.count("abcde"), 2);
+    }
+
+    #[test]
+    fn test_prompt_fits_small_context() {
+        // 40 characters of prompt and 20 tokens of output fit 32 tokens of context
+        let request = chat_request(&"a".repeat(40), 20);
+        let needed = ApproximateTokenCounter.count_context(&request);
+        assert_eq!(needed, 30);
+        assert!(needed <= 32);
+    }
+
+    #[test]
+    fn test_prompt_overflows_small_context() {
+        let request = chat_request(&"a".repeat(100), 20);
+        let needed = ApproximateTokenCounter.count_context(&request);
+        assert_eq!(needed, 45);
+        assert!(needed > 32);
+    }
+
+    #[test]
+    fn test_embeddings_need_no_context() {
+        let request = LlmRequest::Embedding(EmbeddingRequest {
+            model: "test-model".to_string(),
+            input: vec!["abcd".to_string(), "efgh".to_string()],
+            ..Default::default()
+        });
+        assert_eq!(ApproximateTokenCounter.count_prompt(&request), 2);
+        assert_eq!(ApproximateTokenCounter.count_context(&request), 0);
+    }
+
+    #[cfg(feature = "tiktoken")]
+    #[test]
+    fn test_tiktoken_count() {
+        let counter = TiktokenCounter::cl100k_base().unwrap();
+        assert_eq!(counter.count("hello world"), 2);
+    }
+}
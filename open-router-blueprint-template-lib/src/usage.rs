@@ -0,0 +1,253 @@
+//! Per-principal usage accounting and limits
+//!
+//! The [`UsageTracker`] counts the requests and tokens of every authenticated principal and
+//! enforces the [`PrincipalLimits`] issued with its key: a requests-per-minute rate and a
+//! token budget per calendar month (UTC). Counters live in memory and start from zero when the
+//! node restarts.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Mutex;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+use crate::auth::{Principal, PrincipalLimits};
+use crate::llm::{LlmError, UsageInfo};
+
+/// Length of the window `requests_per_minute` is counted over
+const RATE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Usage of a principal since the node started
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PrincipalUsage {
+    /// Requests admitted
+    pub requests: u64,
+
+    /// Requests rejected for exceeding a limit
+    pub rejected_requests: u64,
+
+    /// Prompt tokens used
+    pub prompt_tokens: u64,
+
+    /// Completion tokens used
+    pub completion_tokens: u64,
+
+    /// Total tokens used
+    pub total_tokens: u64,
+
+    /// Tokens used in the current calendar month, counted against `monthly_token_budget`
+    pub month_tokens: u64,
+}
+
+#[derive(Debug)]
+struct UsageEntry {
+    usage: PrincipalUsage,
+    /// Calendar month `usage.month_tokens` belongs to, as months since January 1970
+    month: u32,
+    window_started: Instant,
+    window_requests: u32,
+}
+
+impl UsageEntry {
+    fn new() -> Self {
+        Self {
+            usage: PrincipalUsage::default(),
+            month: current_month(),
+            window_started: Instant::now(),
+            window_requests: 0,
+        }
+    }
+
+    /// Start a new month or rate window once the current one has passed
+    fn roll_over(&mut self) {
+        let month = current_month();
+        if month != self.month {
+            self.month = month;
+            self.usage.month_tokens = 0;
+        }
+        if self.window_started.elapsed() >= RATE_WINDOW {
+            self.window_started = Instant::now();
+            self.window_requests = 0;
+        }
+    }
+
+    /// The limit a new request would exceed, if any
+    fn exceeded_limit(&self, limits: &PrincipalLimits) -> Option<String> {
+        if let Some(budget) = limits.monthly_token_budget {
+            if self.usage.month_tokens >= budget {
+                return Some(format!("monthly token budget of {} exhausted", budget));
+            }
+        }
+        if let Some(rate) = limits.requests_per_minute {
+            if self.window_requests >= rate {
+                return Some(format!("limit of {} requests per minute reached", rate));
+            }
+        }
+        None
+    }
+}
+
+/// Request and token counters for every principal, with limit enforcement
+#[derive(Debug, Default)]
+pub struct UsageTracker {
+    entries: Mutex<HashMap<String, UsageEntry>>,
+}
+
+impl UsageTracker {
+    /// Create a tracker without any usage
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Admit a request of `principal`, counting it, or fail with [`LlmError::RateLimited`]
+    ///
+    /// The token budget is checked against the tokens already used, so the request that
+    /// crosses it still completes and only later requests are rejected.
+    pub fn admit(&self, principal: &Principal) -> Result<(), LlmError> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .entry(principal.id.clone())
+            .or_insert_with(UsageEntry::new);
+        entry.roll_over();
+
+        if let Some(limit) = entry.exceeded_limit(&principal.limits) {
+            entry.usage.rejected_requests += 1;
+            return Err(LlmError::RateLimited(format!(
+                "principal '{}': {}",
+                principal.id, limit
+            )));
+        }
+        entry.usage.requests += 1;
+        entry.window_requests += 1;
+        Ok(())
+    }
+
+    /// Count the tokens of a completed request of `principal`
+    pub fn record_tokens(&self, principal: &Principal, usage: &UsageInfo) {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries
+            .entry(principal.id.clone())
+            .or_insert_with(UsageEntry::new);
+        entry.roll_over();
+
+        entry.usage.prompt_tokens += u64::from(usage.prompt_tokens);
+        entry.usage.completion_tokens += u64::from(usage.completion_tokens);
+        entry.usage.total_tokens += u64::from(usage.total_tokens);
+        entry.usage.month_tokens += u64::from(usage.total_tokens);
+    }
+
+    /// The usage of the principal with the given id, if it made any requests
+    pub fn usage(&self, principal_id: &str) -> Option<PrincipalUsage> {
+        let mut entries = self.entries.lock().unwrap();
+        let entry = entries.get_mut(principal_id)?;
+        entry.roll_over();
+        Some(entry.usage.clone())
+    }
+
+    /// The usage of every principal, ordered by id
+    pub fn report(&self) -> BTreeMap<String, PrincipalUsage> {
+        let mut entries = self.entries.lock().unwrap();
+        entries
+            .iter_mut()
+            .map(|(id, entry)| {
+                entry.roll_over();
+                (id.clone(), entry.usage.clone())
+            })
+            .collect()
+    }
+}
+
+/// The current calendar month (UTC) as months since January 1970
+fn current_month() -> u32 {
+    let days = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        / 86_400;
+    let (year, month) = civil_month(days as i64);
+    ((year - 1970) * 12 + (month - 1)) as u32
+}
+
+/// Year and month (1-12) of the day `days` after 1970-01-01
+///
+/// Howard Hinnant's `civil_from_days` algorithm, restricted to the year and month.
+fn civil_month(days: i64) -> (i64, i64) {
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let month = if shifted_month < 10 {
+        shifted_month + 3
+    } else {
+        shifted_month - 9
+    };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+    (year, month)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn limited(id: &str, requests_per_minute: Option<u32>, budget: Option<u64>) -> Principal {
+        Principal::new(id).with_limits(PrincipalLimits {
+            requests_per_minute,
+            monthly_token_budget: budget,
+        })
+    }
+
+    #[test]
+    fn test_requests_per_minute() {
+        let tracker = UsageTracker::new();
+        let principal = limited("team-a", Some(2), None);
+
+        assert!(tracker.admit(&principal).is_ok());
+        assert!(tracker.admit(&principal).is_ok());
+        assert!(matches!(
+            tracker.admit(&principal),
+            Err(LlmError::RateLimited(_))
+        ));
+
+        let usage = tracker.usage("team-a").unwrap();
+        assert_eq!(usage.requests, 2);
+        assert_eq!(usage.rejected_requests, 1);
+    }
+
+    #[test]
+    fn test_monthly_token_budget() {
+        let tracker = UsageTracker::new();
+        let principal = limited("team-a", None, Some(100));
+        let usage = UsageInfo {
+            prompt_tokens: 40,
+            completion_tokens: 30,
+            total_tokens: 70,
+            prompt_tokens_cached: None,
+        };
+
+        // The request crossing the budget completes; the next one is rejected
+        for _ in 0..2 {
+            tracker.admit(&principal).unwrap();
+            tracker.record_tokens(&principal, &usage);
+        }
+        let error = tracker.admit(&principal).unwrap_err();
+        assert!(error.to_string().contains("monthly token budget of 100"));
+
+        let report = tracker.report();
+        assert_eq!(report["team-a"].total_tokens, 140);
+        assert_eq!(report["team-a"].month_tokens, 140);
+        assert_eq!(report["team-a"].prompt_tokens, 80);
+    }
+
+    #[test]
+    fn test_civil_month() {
+        assert_eq!(civil_month(0), (1970, 1));
+        assert_eq!(civil_month(31), (1970, 2));
+        // 2000-02-29 and 2024-12-31
+        assert_eq!(civil_month(11_016), (2000, 2));
+        assert_eq!(civil_month(20_088), (2024, 12));
+        assert_eq!(civil_month(20_089), (2025, 1));
+    }
+}
@@ -0,0 +1,371 @@
+//! Validation of request bodies received over the HTTP API
+//!
+//! Deserializing a malformed body stops at its first problem with a terse serde message. The
+//! `parse_*` functions check the JSON body against the request format first and report every
+//! offending field along with the reason, as a [`ValidationError`] the API answers with
+//! HTTP 400 and [`ValidationError::to_response_body`].
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Map, Value};
+use thiserror::Error;
+
+use crate::llm::{ChatCompletionRequest, EmbeddingRequest, LlmError, TextCompletionRequest};
+
+/// Roles a chat message may have
+const MESSAGE_ROLES: [&str; 6] = ["system", "developer", "user", "assistant", "tool", "function"];
+
+/// A field of a request body that is invalid, and why
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FieldError {
+    /// Path of the field, e.g. `messages[0].role`, or `body` for the body as a whole
+    pub field: String,
+
+    /// Why the field is invalid
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+/// A request body that failed validation, with every offending field
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("invalid request body: {}", describe(.errors))]
+pub struct ValidationError {
+    pub errors: Vec<FieldError>,
+}
+
+impl ValidationError {
+    /// HTTP status code the API answers invalid bodies with
+    pub const STATUS_CODE: u16 = 400;
+
+    /// The error body of the HTTP response, in the OpenAI error format with the offending
+    /// fields listed under `fields`
+    pub fn to_response_body(&self) -> Value {
+        json!({
+            "error": {
+                "message": self.to_string(),
+                "type": "invalid_request_error",
+                "code": Self::STATUS_CODE,
+                "fields": self.errors,
+            }
+        })
+    }
+}
+
+impl From<ValidationError> for LlmError {
+    fn from(e: ValidationError) -> Self {
+        LlmError::InvalidRequest(describe(&e.errors))
+    }
+}
+
+fn describe(errors: &[FieldError]) -> String {
+    errors
+        .iter()
+        .map(|e| format!("{}: {}", e.field, e.message))
+        .collect::<Vec<_>>()
+        .join("; ")
+}
+
+/// Parse and validate the body of a chat completion request
+pub fn parse_chat_completion_request(
+    body: &[u8],
+) -> Result<ChatCompletionRequest, ValidationError> {
+    parse_request(body, check_chat_completion)
+}
+
+/// Parse and validate the body of a text completion request
+pub fn parse_text_completion_request(
+    body: &[u8],
+) -> Result<TextCompletionRequest, ValidationError> {
+    parse_request(body, check_text_completion)
+}
+
+/// Parse and validate the body of an embedding request
+pub fn parse_embedding_request(body: &[u8]) -> Result<EmbeddingRequest, ValidationError> {
+    parse_request(body, check_embedding)
+}
+
+fn parse_request<T: DeserializeOwned>(
+    body: &[u8],
+    check: fn(&Map<String, Value>, &mut Vec<FieldError>),
+) -> Result<T, ValidationError> {
+    let invalid = |field: &str, message: String| ValidationError {
+        errors: vec![FieldError::new(field, message)],
+    };
+
+    let value: Value = serde_json::from_slice(body)
+        .map_err(|e| invalid("body", format!("is not valid JSON: {}", e)))?;
+    let Value::Object(object) = &value else {
+        return Err(invalid("body", "must be a JSON object".to_string()));
+    };
+
+    let mut errors = Vec::new();
+    check(object, &mut errors);
+    if !errors.is_empty() {
+        return Err(ValidationError { errors });
+    }
+
+    // Anything the checks above let through is still caught here
+    serde_json::from_value(value).map_err(|e| invalid("body", e.to_string()))
+}
+
+fn check_chat_completion(body: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    check_model(body, errors);
+    match body.get("messages") {
+        None => errors.push(FieldError::new("messages", "is required")),
+        Some(Value::Array(messages)) if messages.is_empty() => {
+            errors.push(FieldError::new("messages", "must contain at least one message"))
+        }
+        Some(Value::Array(messages)) => {
+            for (i, message) in messages.iter().enumerate() {
+                check_message(&format!("messages[{}]", i), message, errors);
+            }
+        }
+        Some(_) => errors.push(FieldError::new("messages", "must be an array of messages")),
+    }
+    check_sampling(body, errors);
+    check_optional(body, "tools", errors, |v| {
+        v.is_array().then_some(()).ok_or("must be an array of tools")
+    });
+}
+
+fn check_text_completion(body: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    check_model(body, errors);
+    match body.get("prompt") {
+        None => errors.push(FieldError::new("prompt", "is required")),
+        Some(Value::String(_)) => {}
+        Some(_) => errors.push(FieldError::new("prompt", "must be a string")),
+    }
+    check_sampling(body, errors);
+}
+
+fn check_embedding(body: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    check_model(body, errors);
+    match body.get("input") {
+        None => errors.push(FieldError::new("input", "is required")),
+        Some(Value::Array(input)) if input.is_empty() => {
+            errors.push(FieldError::new("input", "must contain at least one input"))
+        }
+        Some(Value::Array(input)) => {
+            for (i, item) in input.iter().enumerate() {
+                if !item.is_string() {
+                    errors.push(FieldError::new(format!("input[{}]", i), "must be a string"));
+                }
+            }
+        }
+        Some(_) => errors.push(FieldError::new("input", "must be an array of strings")),
+    }
+}
+
+fn check_model(body: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    match body.get("model") {
+        None => errors.push(FieldError::new("model", "is required")),
+        Some(Value::String(model)) if model.trim().is_empty() => {
+            errors.push(FieldError::new("model", "must not be empty"))
+        }
+        Some(Value::String(_)) => {}
+        Some(_) => errors.push(FieldError::new("model", "must be a string")),
+    }
+}
+
+fn check_message(path: &str, message: &Value, errors: &mut Vec<FieldError>) {
+    let Value::Object(message) = message else {
+        errors.push(FieldError::new(path, "must be an object"));
+        return;
+    };
+    match message.get("role") {
+        None => errors.push(FieldError::new(format!("{}.role", path), "is required")),
+        Some(Value::String(role)) if MESSAGE_ROLES.contains(&role.as_str()) => {}
+        Some(_) => errors.push(FieldError::new(
+            format!("{}.role", path),
+            format!("must be one of {}", MESSAGE_ROLES.join(", ")),
+        )),
+    }
+    match message.get("content") {
+        None | Some(Value::Null) | Some(Value::String(_)) => {}
+        Some(_) => errors.push(FieldError::new(
+            format!("{}.content", path),
+            "must be a string",
+        )),
+    }
+}
+
+/// Check the sampling parameters shared by chat and text completions
+fn check_sampling(body: &Map<String, Value>, errors: &mut Vec<FieldError>) {
+    check_optional(body, "temperature", errors, |v| {
+        number_in(v, 0.0, 2.0, "must be between 0 and 2")
+    });
+    check_optional(body, "top_p", errors, |v| {
+        number_in(v, 0.0, 1.0, "must be between 0 and 1")
+    });
+    check_optional(body, "max_tokens", errors, |v| {
+        v.as_u64()
+            .filter(|&n| n <= u64::from(u32::MAX))
+            .map(|_| ())
+            .ok_or("must be a non-negative integer")
+    });
+    check_optional(body, "n", errors, |v| {
+        v.as_u64()
+            .filter(|n| (1..=u64::from(u32::MAX)).contains(n))
+            .map(|_| ())
+            .ok_or("must be a positive integer")
+    });
+    check_optional(body, "stop", errors, |v| match v {
+        Value::Array(stop) if stop.iter().all(Value::is_string) => Ok(()),
+        _ => Err("must be an array of strings"),
+    });
+    check_optional(body, "stream", errors, |v| {
+        v.is_boolean().then_some(()).ok_or("must be a boolean")
+    });
+}
+
+/// Check `field` with `check` if it is present and not `null`
+fn check_optional(
+    body: &Map<String, Value>,
+    field: &str,
+    errors: &mut Vec<FieldError>,
+    check: impl Fn(&Value) -> Result<(), &'static str>,
+) {
+    match body.get(field) {
+        None | Some(Value::Null) => {}
+        Some(value) => {
+            if let Err(message) = check(value) {
+                errors.push(FieldError::new(field, message));
+            }
+        }
+    }
+}
+
+/// Check that `value` is a number between `min` and `max`, failing with `out_of_range` if not
+fn number_in(
+    value: &Value,
+    min: f64,
+    max: f64,
+    out_of_range: &'static str,
+) -> Result<(), &'static str> {
+    match value.as_f64() {
+        Some(n) if (min..=max).contains(&n) => Ok(()),
+        Some(_) => Err(out_of_range),
+        None => Err("must be a number"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat_errors(body: Value) -> Vec<FieldError> {
+        parse_chat_completion_request(body.to_string().as_bytes())
+            .unwrap_err()
+            .errors
+    }
+
+    #[test]
+    fn test_valid_chat_request() {
+        let body = json!({
+            "model": "llama3",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "temperature": 0.7,
+            "max_tokens": 16,
+        });
+        let request = parse_chat_completion_request(body.to_string().as_bytes()).unwrap();
+        assert_eq!(request.model, "llama3");
+        assert_eq!(request.temperature, Some(0.7));
+    }
+
+    #[test]
+    fn test_missing_model() {
+        let errors = chat_errors(json!({"messages": [{"role": "user", "content": "Hi"}]}));
+        assert_eq!(errors, vec![FieldError::new("model", "is required")]);
+    }
+
+    #[test]
+    fn test_wrong_typed_temperature() {
+        let errors = chat_errors(json!({
+            "model": "llama3",
+            "messages": [{"role": "user", "content": "Hi"}],
+            "temperature": "hot",
+        }));
+        assert_eq!(errors, vec![FieldError::new("temperature", "must be a number")]);
+    }
+
+    #[test]
+    fn test_empty_messages() {
+        let errors = chat_errors(json!({"model": "llama3", "messages": []}));
+        assert_eq!(
+            errors,
+            vec![FieldError::new(
+                "messages",
+                "must contain at least one message"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_every_offending_field_is_reported() {
+        let error = parse_chat_completion_request(
+            json!({
+                "messages": [{"content": "Hi"}, {"role": "user", "content": 3}],
+                "top_p": 1.5,
+                "stream": "yes",
+            })
+            .to_string()
+            .as_bytes(),
+        )
+        .unwrap_err();
+        let fields: Vec<_> = error.errors.iter().map(|e| e.field.as_str()).collect();
+        assert_eq!(
+            fields,
+            vec![
+                "model",
+                "messages[0].role",
+                "messages[1].content",
+                "top_p",
+                "stream"
+            ]
+        );
+
+        let body = error.to_response_body();
+        assert_eq!(body["error"]["type"], "invalid_request_error");
+        assert_eq!(body["error"]["fields"][0]["field"], "model");
+        assert_eq!(body["error"]["fields"][0]["message"], "is required");
+    }
+
+    #[test]
+    fn test_malformed_json() {
+        let error = parse_chat_completion_request(b"{\"model\": ").unwrap_err();
+        assert_eq!(error.errors.len(), 1);
+        assert_eq!(error.errors[0].field, "body");
+
+        let error = parse_embedding_request(b"[]").unwrap_err();
+        assert_eq!(
+            error.errors,
+            vec![FieldError::new("body", "must be a JSON object")]
+        );
+    }
+
+    #[test]
+    fn test_text_and_embedding_requests() {
+        let error = parse_text_completion_request(json!({"model": "m"}).to_string().as_bytes())
+            .unwrap_err();
+        assert_eq!(error.errors, vec![FieldError::new("prompt", "is required")]);
+
+        let error = parse_embedding_request(
+            json!({"model": "m", "input": ["a", 1]})
+                .to_string()
+                .as_bytes(),
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.errors,
+            vec![FieldError::new("input[1]", "must be a string")]
+        );
+    }
+}
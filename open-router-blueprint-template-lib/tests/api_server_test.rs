@@ -0,0 +1,370 @@
+use std::path::Path;
+
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use open_router_blueprint_template_lib::api::{serve, ApiServer};
+use open_router_blueprint_template_lib::context::OpenRouterContext;
+use reqwest::StatusCode;
+use serde_json::{json, Value};
+
+/// Serve the API of `context` on a free local port
+fn serve_locally(context: &OpenRouterContext) -> ApiServer {
+    serve(context.clone(), "127.0.0.1:0".parse().unwrap()).unwrap()
+}
+
+fn url(server: &ApiServer, path: &str) -> String {
+    format!("http://{}{}", server.local_addr(), path)
+}
+
+/// A context whose configuration file is `config`
+async fn context_with_config(
+    data_dir: &Path,
+    config: Value,
+) -> Result<OpenRouterContext, blueprint_sdk::Error> {
+    std::fs::write(data_dir.join("config.json"), config.to_string()).unwrap();
+    let mut env = BlueprintEnvironment::default();
+    env.data_dir = Some(data_dir.to_path_buf());
+    OpenRouterContext::new(env).await
+}
+
+/// Test that a chat completion posted over HTTP is answered with an OpenAI chat completion
+#[tokio::test]
+async fn test_chat_completion_over_http() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let server = serve_locally(&context);
+
+    let response = reqwest::Client::new()
+        .post(url(&server, "/v1/chat/completions"))
+        .json(&json!({
+            "model": "gpt-3.5-turbo",
+            "messages": [{"role": "user", "content": "Hello, HTTP!"}],
+        }))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"].to_str()?,
+        "application/json"
+    );
+
+    // The local client echoes the prompt back
+    let body: Value = response.json().await?;
+    assert!(body["id"].as_str().is_some_and(|id| !id.is_empty()));
+    assert_eq!(body["object"], "chat.completion");
+    assert_eq!(body["model"], "gpt-3.5-turbo");
+    assert_eq!(body["choices"][0]["message"]["role"], "assistant");
+    assert_eq!(body["choices"][0]["message"]["content"], "Hello, HTTP!");
+    assert!(body["usage"]["total_tokens"].is_u64());
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that a chat completion with `stream: true` is answered with server-sent events
+#[tokio::test]
+async fn test_streaming_chat_completion_over_http() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let server = serve_locally(&context);
+
+    let response = reqwest::Client::new()
+        .post(url(&server, "/v1/chat/completions"))
+        .json(&json!({
+            "model": "gpt-3.5-turbo",
+            "messages": [{"role": "user", "content": "Hello, stream!"}],
+            "stream": true,
+        }))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()["content-type"].to_str()?,
+        "text/event-stream"
+    );
+
+    // Reassemble the message from the deltas of the events
+    let body = response.text().await?;
+    let events: Vec<&str> = body
+        .split("\n\n")
+        .filter_map(|event| event.strip_prefix("data: "))
+        .collect();
+    assert_eq!(events.last(), Some(&"[DONE]"));
+
+    let mut role = None;
+    let mut content = String::new();
+    let mut finish_reason = None;
+    for event in &events[..events.len() - 1] {
+        let chunk: Value = serde_json::from_str(event)?;
+        assert_eq!(chunk["object"], "chat.completion.chunk");
+        let choice = &chunk["choices"][0];
+        if let Some(delta_role) = choice["delta"]["role"].as_str() {
+            role = Some(delta_role.to_string());
+        }
+        if let Some(delta) = choice["delta"]["content"].as_str() {
+            content.push_str(delta);
+        }
+        if let Some(reason) = choice["finish_reason"].as_str() {
+            finish_reason = Some(reason.to_string());
+        }
+    }
+    assert_eq!(role.as_deref(), Some("assistant"));
+    assert_eq!(content, "Hello, stream!");
+    assert!(finish_reason.is_some());
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that text completions, embeddings and models are served too
+#[tokio::test]
+async fn test_other_endpoints_over_http() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let server = serve_locally(&context);
+    let client = reqwest::Client::new();
+
+    let body: Value = client
+        .post(url(&server, "/v1/completions"))
+        .json(&json!({"model": "text-davinci-003", "prompt": "Once upon a time"}))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(body["choices"][0]["text"], "Once upon a time");
+
+    let body: Value = client
+        .post(url(&server, "/v1/embeddings"))
+        .json(&json!({"model": "gpt-3.5-turbo", "input": ["Hello", "World"]}))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert_eq!(body["data"].as_array().map(Vec::len), Some(2));
+
+    let body: Value = client
+        .get(url(&server, "/v1/models"))
+        .send()
+        .await?
+        .json()
+        .await?;
+    assert!(body["data"]
+        .as_array()
+        .is_some_and(|models| models.iter().any(|m| m["id"] == "gpt-3.5-turbo")));
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that invalid requests are answered with OpenAI error bodies
+#[tokio::test]
+async fn test_errors_over_http() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let server = serve_locally(&context);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(url(&server, "/v1/chat/completions"))
+        .json(&json!({"messages": []}))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body: Value = response.json().await?;
+    assert_eq!(body["error"]["type"], "invalid_request_error");
+    assert_eq!(body["error"]["fields"][0]["field"], "model");
+
+    let response = client
+        .get(url(&server, "/v1/models/no-such-model"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: Value = response.json().await?;
+    assert_eq!(body["error"]["code"], "model_not_found");
+
+    let response = client.get(url(&server, "/v1/unknown")).send().await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    let body: Value = response.json().await?;
+    assert_eq!(body["error"]["code"], "not_found");
+
+    let response = client
+        .get(url(&server, "/v1/chat/completions"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::METHOD_NOT_ALLOWED);
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that callers without a valid key are rejected when authentication is enabled
+#[tokio::test]
+async fn test_authentication_over_http() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    let context = context_with_config(
+        data_dir.path(),
+        json!({"api": {"auth_enabled": true, "api_key": "sk-test"}}),
+    )
+    .await?;
+    let server = serve_locally(&context);
+    let client = reqwest::Client::new();
+    let request = json!({
+        "model": "gpt-3.5-turbo",
+        "messages": [{"role": "user", "content": "Hi"}],
+    });
+
+    let response = client
+        .post(url(&server, "/v1/chat/completions"))
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let body: Value = response.json().await?;
+    assert_eq!(body["error"]["type"], "authentication_error");
+
+    let response = client
+        .post(url(&server, "/v1/chat/completions"))
+        .bearer_auth("sk-test")
+        .json(&request)
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that the usage report is served to callers presenting the admin token
+#[tokio::test]
+async fn test_admin_usage_over_http() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    let context = context_with_config(
+        data_dir.path(),
+        json!({"api": {"auth_token": "admin-secret"}}),
+    )
+    .await?;
+    let server = serve_locally(&context);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(url(&server, "/v1/chat/completions"))
+        .json(&json!({
+            "model": "gpt-3.5-turbo",
+            "messages": [{"role": "user", "content": "Hi"}],
+        }))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+
+    let response = client.get(url(&server, "/admin/usage")).send().await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    let response = client
+        .get(url(&server, "/admin/usage"))
+        .bearer_auth("not-the-admin-token")
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+    let response = client
+        .get(url(&server, "/admin/usage"))
+        .bearer_auth("admin-secret")
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await?;
+    assert_eq!(body["default"]["requests"], 1);
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that nodes are excluded from routing and included again by the admin endpoints
+#[tokio::test]
+async fn test_admin_node_exclusion_over_http() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    let context = context_with_config(
+        data_dir.path(),
+        json!({"api": {"auth_token": "admin-secret"}}),
+    )
+    .await?;
+    let server = serve_locally(&context);
+    let client = reqwest::Client::new();
+
+    let response = client
+        .post(url(&server, "/admin/nodes/node-1/exclude"))
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert!(context.excluded_nodes().await.is_empty());
+
+    let response = client
+        .post(url(&server, "/admin/nodes/node-1/exclude"))
+        .bearer_auth("admin-secret")
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await?;
+    assert_eq!(body, json!({"excluded": ["node-1"]}));
+    assert_eq!(context.excluded_nodes().await, vec!["node-1"]);
+
+    let response = client
+        .get(url(&server, "/admin/nodes/excluded"))
+        .bearer_auth("admin-secret")
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await?;
+    assert_eq!(body, json!({"excluded": ["node-1"]}));
+
+    let response = client
+        .post(url(&server, "/admin/nodes/node-1/include"))
+        .bearer_auth("admin-secret")
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::OK);
+    let body: Value = response.json().await?;
+    assert_eq!(body, json!({"excluded": []}));
+    assert!(context.excluded_nodes().await.is_empty());
+
+    let response = client
+        .post(url(&server, "/admin/nodes/node-1/restart"))
+        .bearer_auth("admin-secret")
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that the admin endpoints are disabled without an admin token
+#[tokio::test]
+async fn test_admin_endpoints_disabled_without_token() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let server = serve_locally(&context);
+
+    let response = reqwest::Client::new()
+        .get(url(&server, "/admin/usage"))
+        .bearer_auth("admin-secret")
+        .send()
+        .await?;
+    assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    let body: Value = response.json().await?;
+    assert_eq!(body["error"]["code"], "admin_disabled");
+
+    server.shutdown().await?;
+    Ok(())
+}
+
+/// Test that the server binds the configured address and is not started when disabled
+#[tokio::test]
+async fn test_serve_api_honors_config() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    {
+        let mut config = context.blueprint_config.write().await;
+        config.api.host = "127.0.0.1".to_string();
+        config.api.port = 0;
+    }
+    let server = context.serve_api().await?.expect("the API is enabled");
+    assert!(server.local_addr().ip().is_loopback());
+    server.shutdown().await?;
+
+    context.blueprint_config.write().await.api.enabled = false;
+    assert!(context.serve_api().await?.is_none());
+    Ok(())
+}
@@ -0,0 +1,70 @@
+use std::path::Path;
+
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use open_router_blueprint_template_lib::auth::{Principal, DEFAULT_PRINCIPAL};
+use open_router_blueprint_template_lib::context::OpenRouterContext;
+
+/// A context whose configuration file enables authentication with `keys_file`
+async fn context_with_keys_file(
+    data_dir: &Path,
+    keys_file: &Path,
+) -> Result<OpenRouterContext, blueprint_sdk::Error> {
+    let config = serde_json::json!({
+        "api": { "auth_enabled": true, "keys_file": keys_file }
+    });
+    std::fs::write(data_dir.join("config.json"), config.to_string()).unwrap();
+    let mut env = BlueprintEnvironment::default();
+    env.data_dir = Some(data_dir.to_path_buf());
+    OpenRouterContext::new(env).await
+}
+
+/// Test that keys from the keys file authenticate as their principals and others are rejected
+#[tokio::test]
+async fn test_keys_file_authenticates_principals() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    let keys_file = data_dir.path().join("keys.json");
+    std::fs::write(
+        &keys_file,
+        r#"[
+            { "key": "sk-tenant-a", "principal": "tenant-a" },
+            { "key": "sk-tenant-b", "principal": "tenant-b" }
+        ]"#,
+    )?;
+    let context = context_with_keys_file(data_dir.path(), &keys_file).await?;
+
+    assert_eq!(
+        context.authenticate(Some("sk-tenant-a")).await,
+        Some(Principal::new("tenant-a"))
+    );
+    assert_eq!(
+        context.authenticate(Some("sk-tenant-b")).await,
+        Some(Principal::new("tenant-b"))
+    );
+    assert_eq!(context.authenticate(Some("sk-unknown")).await, None);
+    assert_eq!(context.authenticate(None).await, None);
+    Ok(())
+}
+
+/// Test that every caller is the default principal while authentication is disabled
+#[tokio::test]
+async fn test_disabled_authentication_accepts_everyone() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+
+    assert_eq!(
+        context.authenticate(None).await,
+        Some(Principal::new(DEFAULT_PRINCIPAL))
+    );
+    Ok(())
+}
+
+/// Test that a missing keys file fails the context instead of disabling authentication
+#[tokio::test]
+async fn test_missing_keys_file_fails_closed() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    let keys_file = data_dir.path().join("missing.json");
+
+    let result = context_with_keys_file(data_dir.path(), &keys_file).await;
+
+    assert!(result.is_err());
+    Ok(())
+}
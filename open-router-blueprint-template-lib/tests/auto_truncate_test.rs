@@ -0,0 +1,96 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{message, model_info, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{estimate_tokens, ChatCompletionRequest, LlmRequest, TextCompletionRequest},
+};
+
+const SMALL_MODEL: &str = "small-model";
+const CONTEXT_LENGTH: usize = 100;
+
+async fn context_with_truncation(
+    auto_truncate: bool,
+) -> color_eyre::Result<(OpenRouterContext, Arc<MockBackend>)> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.blueprint_config.write().await.llm.auto_truncate = auto_truncate;
+    let client =
+        Arc::new(MockBackend::new().serving_model(model_info(SMALL_MODEL, CONTEXT_LENGTH)));
+    context
+        .add_llm_node("small".to_string(), client.clone())
+        .await?;
+    Ok((context, client))
+}
+
+/// A conversation of about 210 tokens: a system prompt and ten 20-token turns
+fn long_conversation() -> LlmRequest {
+    let mut messages = vec![message("system", "Be brief.")];
+    for turn in 0..10 {
+        let role = if turn % 2 == 0 { "user" } else { "assistant" };
+        messages.push(message(role, &format!("{:0>80}", turn)));
+    }
+    LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: SMALL_MODEL.to_string(),
+        messages,
+        max_tokens: Some(20),
+        ..Default::default()
+    })
+}
+
+/// Test that an over-length conversation loses its oldest turns but keeps its system prompt
+#[tokio::test]
+async fn test_over_length_conversation_is_truncated() -> color_eyre::Result<()> {
+    let (context, client) = context_with_truncation(true).await?;
+
+    process_llm_request(Context(context), CallId(1), TangleArg(long_conversation())).await?;
+
+    let requests = client.chat_requests();
+    assert_eq!(requests.len(), 1);
+    let request = &requests[0];
+    assert_eq!(request.messages[0].role, "system");
+    assert_eq!(request.messages[0].content, "Be brief.");
+    assert_eq!(
+        request.messages.last().unwrap().content,
+        format!("{:0>80}", 9)
+    );
+    assert!(request.messages.len() < 11);
+    assert!(request.estimated_prompt_tokens() + 20 <= CONTEXT_LENGTH);
+    Ok(())
+}
+
+/// Test that requests are passed on whole unless truncation is enabled
+#[tokio::test]
+async fn test_conversation_is_not_truncated_by_default() -> color_eyre::Result<()> {
+    let (context, client) = context_with_truncation(false).await?;
+
+    process_llm_request(Context(context), CallId(1), TangleArg(long_conversation())).await?;
+
+    assert_eq!(client.chat_requests()[0].messages.len(), 11);
+    Ok(())
+}
+
+/// Test that an over-length text prompt loses its head
+#[tokio::test]
+async fn test_over_length_prompt_is_truncated() -> color_eyre::Result<()> {
+    let (context, client) = context_with_truncation(true).await?;
+    let prompt = format!("{}The question is", "a".repeat(1000));
+    let request = LlmRequest::TextCompletion(TextCompletionRequest {
+        model: SMALL_MODEL.to_string(),
+        prompt,
+        max_tokens: Some(50),
+        ..Default::default()
+    });
+
+    process_llm_request(Context(context), CallId(1), TangleArg(request)).await?;
+
+    let requests = client.text_requests();
+    assert!(requests[0].prompt.ends_with("The question is"));
+    assert!(estimate_tokens(&requests[0].prompt) + 50 <= CONTEXT_LENGTH);
+    Ok(())
+}
@@ -0,0 +1,106 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_request, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_batch,
+    llm::{LlmError, LlmResponse},
+    rate_limit::RateLimiter,
+};
+
+const BATCH_MODEL: &str = "batch-model";
+
+/// A backend serving `BATCH_MODEL` that answers with the last message
+fn echo_backend() -> MockBackend {
+    MockBackend::new()
+        .serving(BATCH_MODEL)
+        .replying(str::to_string)
+}
+
+/// Test that a request for an unsupported model fails on its own without failing the batch
+#[tokio::test]
+async fn test_batch_keeps_per_request_outcomes() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .add_llm_node("echo".to_string(), Arc::new(echo_backend()))
+        .await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .strict_model_catalog = true;
+
+    let requests = vec![
+        chat_request(BATCH_MODEL, "first"),
+        chat_request("unsupported-model", "second"),
+        chat_request(BATCH_MODEL, "third"),
+    ];
+    let items = process_llm_batch(Context(context), CallId(7), TangleArg(requests))
+        .await?
+        .0;
+
+    assert_eq!(items.len(), 3);
+    for (position, item) in items.iter().enumerate() {
+        assert_eq!(item.index, position);
+    }
+
+    assert!(!items[1].is_ok());
+    assert!(items[1]
+        .error
+        .as_deref()
+        .unwrap()
+        .contains(&LlmError::ModelNotSupported("unsupported-model".to_string()).to_string()));
+
+    for (item, expected) in [(&items[0], "first"), (&items[2], "third")] {
+        assert!(item.error.is_none());
+        match item.response.as_ref().unwrap() {
+            LlmResponse::ChatCompletion(response) => {
+                assert_eq!(response.choices[0].message.content, expected);
+                assert!(response.correlation_id.is_some());
+            }
+            other => panic!("Unexpected response type: {:?}", other),
+        }
+    }
+
+    // Partial results survive a serialization round trip
+    let json = serde_json::to_string(&items)?;
+    let decoded: Vec<serde_json::Value> = serde_json::from_str(&json)?;
+    assert!(decoded[1].get("response").is_none());
+    assert!(decoded[0].get("error").is_none());
+    Ok(())
+}
+
+/// Test that every request of a batch counts against the rate limit on its own
+#[tokio::test]
+async fn test_batch_requests_over_rate_limit_fail_on_their_own() -> color_eyre::Result<()> {
+    let mut context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.rate_limiter = Arc::new(RateLimiter::new(2));
+    let backend = Arc::new(echo_backend());
+    context
+        .add_llm_node("echo".to_string(), backend.clone())
+        .await?;
+
+    let requests = (0..3)
+        .map(|i| chat_request(BATCH_MODEL, &i.to_string()))
+        .collect();
+    let items = process_llm_batch(Context(context), CallId(7), TangleArg(requests))
+        .await?
+        .0;
+
+    assert_eq!(items.len(), 3);
+    let rejected: Vec<_> = items.iter().filter(|item| !item.is_ok()).collect();
+    assert_eq!(rejected.len(), 1);
+    assert!(rejected[0]
+        .error
+        .as_deref()
+        .unwrap()
+        .contains("Rate limit exceeded"));
+    assert_eq!(backend.request_count(), 2);
+    Ok(())
+}
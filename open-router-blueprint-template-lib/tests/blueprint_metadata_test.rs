@@ -0,0 +1,19 @@
+use open_router_blueprint_template_lib::{metadata::blueprint_json, PROCESS_LLM_REQUEST_JOB_ID};
+
+/// Test that the printed metadata registers the LLM request job under its job id
+#[test]
+fn test_blueprint_json_lists_registered_jobs() {
+    let json = blueprint_json().unwrap();
+    let blueprint: serde_json::Value = serde_json::from_str(&json).unwrap();
+
+    // Jobs are registered on chain under their index in `jobs`
+    let jobs = blueprint["jobs"].as_array().expect("the blueprint lists its jobs");
+    let job = &jobs[PROCESS_LLM_REQUEST_JOB_ID as usize];
+    assert!(
+        job.to_string().contains("process_llm_request"),
+        "unexpected job {}: {}",
+        PROCESS_LLM_REQUEST_JOB_ID,
+        job
+    );
+    assert!(json.contains("open-router-blueprint"));
+}
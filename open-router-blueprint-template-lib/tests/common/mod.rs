@@ -0,0 +1,351 @@
+//! Configurable in-process backend shared by the integration tests
+//!
+//! Each test configures the mock for the behavior it needs instead of implementing
+//! `LlmClient` again, so new trait methods and fields only have to be handled here.
+
+#![allow(dead_code)]
+
+pub mod trace;
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use open_router_blueprint_template_lib::llm::{
+    ChatCompletionChoice, ChatCompletionRequest, ChatCompletionResponse, ChatMessage,
+    EmbeddingRequest, EmbeddingResponse, LlmCapabilities, LlmClient, LlmError, LlmRequest,
+    ModelInfo, NodeInfo, NodeMetrics, Result, TextCompletionChoice, TextCompletionRequest,
+    TextCompletionResponse, UsageInfo,
+};
+use tokio::sync::Semaphore;
+
+type ChatHandler =
+    Box<dyn Fn(&ChatCompletionRequest) -> Result<ChatCompletionResponse> + Send + Sync>;
+type TextHandler =
+    Box<dyn Fn(&TextCompletionRequest) -> Result<TextCompletionResponse> + Send + Sync>;
+type EmbeddingHandler = Box<dyn Fn(&EmbeddingRequest) -> Result<EmbeddingResponse> + Send + Sync>;
+
+/// A backend answering "OK" to every completion and recording the requests it receives
+///
+/// Embeddings are not implemented unless a handler is set with [`MockBackend::on_embeddings`].
+pub struct MockBackend {
+    models: Vec<ModelInfo>,
+    capabilities: LlmCapabilities,
+    provider: Option<String>,
+    active_requests: u32,
+    healthy: bool,
+    delay: Option<Duration>,
+    gate: Option<Semaphore>,
+    usage: Option<UsageInfo>,
+    chat: ChatHandler,
+    text: TextHandler,
+    embeddings: EmbeddingHandler,
+    chat_requests: Mutex<Vec<ChatCompletionRequest>>,
+    text_requests: Mutex<Vec<TextCompletionRequest>>,
+}
+
+impl Default for MockBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl MockBackend {
+    /// A backend serving no models
+    pub fn new() -> Self {
+        Self {
+            models: Vec::new(),
+            capabilities: LlmCapabilities {
+                supports_streaming: false,
+                max_concurrent_requests: 1,
+                supports_batching: false,
+                features: Default::default(),
+            },
+            provider: None,
+            active_requests: 0,
+            healthy: true,
+            delay: None,
+            gate: None,
+            usage: None,
+            chat: Box::new(|request| Ok(chat_reply(&request.model, "OK"))),
+            text: Box::new(|request| Ok(text_reply(&request.model, "OK"))),
+            embeddings: Box::new(|_| Err(LlmError::NotImplemented("embeddings".to_string()))),
+            chat_requests: Mutex::new(Vec::new()),
+            text_requests: Mutex::new(Vec::new()),
+        }
+    }
+
+    /// Also serve `id`, with a 4096-token context
+    pub fn serving(self, id: &str) -> Self {
+        self.serving_model(model_info(id, 4096))
+    }
+
+    /// Also serve `model`
+    pub fn serving_model(mut self, model: ModelInfo) -> Self {
+        self.models.push(model);
+        self
+    }
+
+    /// Report `capabilities` instead of a single non-streaming slot
+    pub fn with_capabilities(mut self, capabilities: LlmCapabilities) -> Self {
+        self.capabilities = capabilities;
+        self
+    }
+
+    /// Report `provider` as the backend in the node info
+    pub fn with_provider(mut self, provider: &str) -> Self {
+        self.provider = Some(provider.to_string());
+        self
+    }
+
+    /// Report `active_requests` requests in flight in the metrics
+    pub fn with_active_requests(mut self, active_requests: u32) -> Self {
+        self.active_requests = active_requests;
+        self
+    }
+
+    /// Fail health checks, as a backend nothing listens for would
+    pub fn unhealthy(mut self) -> Self {
+        self.healthy = false;
+        self
+    }
+
+    /// Wait `delay` before answering each completion
+    pub fn with_delay(mut self, delay: Duration) -> Self {
+        self.delay = Some(delay);
+        self
+    }
+
+    /// Hold each completion until the test calls [`MockBackend::release`]
+    pub fn gated(mut self) -> Self {
+        self.gate = Some(Semaphore::new(0));
+        self
+    }
+
+    /// Report `usage` on every completion
+    pub fn with_usage(mut self, usage: UsageInfo) -> Self {
+        self.usage = Some(usage);
+        self
+    }
+
+    /// Answer chat completions with the last message as transformed by `reply`
+    pub fn replying(self, reply: impl Fn(&str) -> String + Send + Sync + 'static) -> Self {
+        self.on_chat(move |request| {
+            let last = request.messages.last().map_or("", |m| m.content.as_str());
+            Ok(chat_reply(&request.model, &reply(last)))
+        })
+    }
+
+    /// Answer chat completions with `handler`
+    pub fn on_chat(
+        mut self,
+        handler: impl Fn(&ChatCompletionRequest) -> Result<ChatCompletionResponse>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.chat = Box::new(handler);
+        self
+    }
+
+    /// Answer text completions with `handler`
+    pub fn on_text(
+        mut self,
+        handler: impl Fn(&TextCompletionRequest) -> Result<TextCompletionResponse>
+            + Send
+            + Sync
+            + 'static,
+    ) -> Self {
+        self.text = Box::new(handler);
+        self
+    }
+
+    /// Answer embedding requests with `handler`
+    pub fn on_embeddings(
+        mut self,
+        handler: impl Fn(&EmbeddingRequest) -> Result<EmbeddingResponse> + Send + Sync + 'static,
+    ) -> Self {
+        self.embeddings = Box::new(handler);
+        self
+    }
+
+    /// Fail every completion with the error `error` returns
+    pub fn failing(self, error: fn() -> LlmError) -> Self {
+        self.on_chat(move |_| Err(error()))
+            .on_text(move |_| Err(error()))
+            .on_embeddings(move |_| Err(error()))
+    }
+
+    /// Let `count` held completions answer
+    pub fn release(&self, count: usize) {
+        if let Some(gate) = &self.gate {
+            gate.add_permits(count);
+        }
+    }
+
+    /// The chat requests received so far
+    pub fn chat_requests(&self) -> Vec<ChatCompletionRequest> {
+        self.chat_requests.lock().unwrap().clone()
+    }
+
+    /// The text requests received so far
+    pub fn text_requests(&self) -> Vec<TextCompletionRequest> {
+        self.text_requests.lock().unwrap().clone()
+    }
+
+    /// The number of completions requested so far
+    pub fn request_count(&self) -> usize {
+        self.chat_requests.lock().unwrap().len() + self.text_requests.lock().unwrap().len()
+    }
+
+    /// The models the chat requests received so far were for
+    pub fn received_models(&self) -> Vec<String> {
+        self.chat_requests
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|request| request.model.clone())
+            .collect()
+    }
+
+    async fn wait_turn(&self) -> Result<()> {
+        if let Some(delay) = self.delay {
+            tokio::time::sleep(delay).await;
+        }
+        if let Some(gate) = &self.gate {
+            gate.acquire()
+                .await
+                .map_err(|e| LlmError::Internal(e.to_string()))?
+                .forget();
+        }
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl LlmClient for MockBackend {
+    fn get_supported_models(&self) -> Vec<ModelInfo> {
+        self.models.clone()
+    }
+
+    fn get_capabilities(&self) -> LlmCapabilities {
+        self.capabilities.clone()
+    }
+
+    fn get_metrics(&self) -> NodeMetrics {
+        NodeMetrics {
+            active_requests: self.active_requests,
+            ..Default::default()
+        }
+    }
+
+    fn get_node_info(&self) -> NodeInfo {
+        match &self.provider {
+            Some(provider) => NodeInfo {
+                backend: provider.clone(),
+                version: None,
+            },
+            None => NodeInfo::default(),
+        }
+    }
+
+    async fn health_check(&self) -> Result<()> {
+        if self.healthy {
+            Ok(())
+        } else {
+            Err(LlmError::RequestFailed("connection refused".to_string()))
+        }
+    }
+
+    async fn chat_completion(
+        &self,
+        request: ChatCompletionRequest,
+    ) -> Result<ChatCompletionResponse> {
+        self.chat_requests.lock().unwrap().push(request.clone());
+        self.wait_turn().await?;
+        let mut response = (self.chat)(&request)?;
+        if self.usage.is_some() {
+            response.usage = self.usage.clone();
+        }
+        Ok(response)
+    }
+
+    async fn text_completion(
+        &self,
+        request: TextCompletionRequest,
+    ) -> Result<TextCompletionResponse> {
+        self.text_requests.lock().unwrap().push(request.clone());
+        self.wait_turn().await?;
+        let mut response = (self.text)(&request)?;
+        if self.usage.is_some() {
+            response.usage = self.usage.clone();
+        }
+        Ok(response)
+    }
+
+    async fn embeddings(&self, request: EmbeddingRequest) -> Result<EmbeddingResponse> {
+        (self.embeddings)(&request)
+    }
+}
+
+/// A chat and text model `id` with a `max_context_length`-token context
+pub fn model_info(id: &str, max_context_length: usize) -> ModelInfo {
+    ModelInfo {
+        id: id.to_string(),
+        name: id.to_string(),
+        max_context_length,
+        supports_chat: true,
+        supports_text: true,
+        supports_embeddings: false,
+        parameters: Default::default(),
+    }
+}
+
+/// A message from `role`
+pub fn message(role: &str, content: &str) -> ChatMessage {
+    ChatMessage {
+        role: role.to_string(),
+        content: content.to_string(),
+        ..Default::default()
+    }
+}
+
+/// A chat request for `model` with a single user message
+pub fn chat_request(model: &str, content: &str) -> LlmRequest {
+    LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: model.to_string(),
+        messages: vec![message("user", content)],
+        ..Default::default()
+    })
+}
+
+/// A finished chat completion answering `content`
+pub fn chat_reply(model: &str, content: &str) -> ChatCompletionResponse {
+    ChatCompletionResponse {
+        id: "mock".to_string(),
+        object: "chat.completion".to_string(),
+        model: model.to_string(),
+        choices: vec![ChatCompletionChoice {
+            index: 0,
+            message: message("assistant", content),
+            finish_reason: Some("stop".to_string()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
+
+/// A finished text completion answering `text`
+pub fn text_reply(model: &str, text: &str) -> TextCompletionResponse {
+    TextCompletionResponse {
+        id: "mock".to_string(),
+        object: "text_completion".to_string(),
+        model: model.to_string(),
+        choices: vec![TextCompletionChoice {
+            index: 0,
+            text: text.to_string(),
+            finish_reason: Some("stop".to_string()),
+            ..Default::default()
+        }],
+        ..Default::default()
+    }
+}
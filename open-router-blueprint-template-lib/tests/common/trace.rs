@@ -0,0 +1,86 @@
+//! Tracing layer recording the spans and events of a test
+
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use tracing::field::{Field, Visit};
+use tracing::span::{Attributes, Id};
+use tracing::{Event, Level};
+use tracing_subscriber::layer::Context;
+use tracing_subscriber::Layer;
+
+/// Name and fields of a recorded span
+type RecordedSpan = (String, Vec<(String, String)>);
+
+/// A tracing layer that records the name and fields of every new span, and the level and
+/// message of every event
+#[derive(Clone, Default)]
+pub struct TraceRecorder {
+    spans: Arc<Mutex<Vec<RecordedSpan>>>,
+    events: Arc<Mutex<Vec<(Level, String)>>>,
+}
+
+impl TraceRecorder {
+    /// The value of `field_name` on the first `span_name` span recording it
+    pub fn field(&self, span_name: &str, field_name: &str) -> Option<String> {
+        self.spans
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(name, _)| name == span_name)
+            .flat_map(|(_, fields)| fields.iter())
+            .find(|(name, _)| name == field_name)
+            .map(|(_, value)| value.clone())
+    }
+
+    /// The messages of the events logged at `level`
+    pub fn messages_at(&self, level: Level) -> Vec<String> {
+        self.events
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(l, _)| *l == level)
+            .map(|(_, message)| message.clone())
+            .collect()
+    }
+}
+
+#[derive(Default)]
+struct FieldVisitor(Vec<(String, String)>);
+
+impl FieldVisitor {
+    fn message(&self) -> String {
+        self.0
+            .iter()
+            .find(|(name, _)| name == "message")
+            .map(|(_, value)| value.clone())
+            .unwrap_or_default()
+    }
+}
+
+impl Visit for FieldVisitor {
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{:?}", value)));
+    }
+}
+
+impl<S: tracing::Subscriber> Layer<S> for TraceRecorder {
+    fn on_new_span(&self, attrs: &Attributes<'_>, _id: &Id, _ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        attrs.record(&mut visitor);
+        self.spans
+            .lock()
+            .unwrap()
+            .push((attrs.metadata().name().to_string(), visitor.0));
+    }
+
+    fn on_event(&self, event: &Event<'_>, _ctx: Context<'_, S>) {
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        self.events
+            .lock()
+            .unwrap()
+            .push((*event.metadata().level(), visitor.message()));
+    }
+}
@@ -0,0 +1,137 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use common::MockBackend;
+use open_router_blueprint_template_lib::{
+    config::ConfigEvent, context::OpenRouterContext, load_balancer::LoadBalancingStrategy,
+};
+
+const RELOAD_MODEL: &str = "reload-model";
+
+/// A node serving `RELOAD_MODEL` with a fixed number of active requests
+fn loaded_node(active_requests: u32) -> Arc<MockBackend> {
+    Arc::new(
+        MockBackend::new()
+            .serving(RELOAD_MODEL)
+            .with_active_requests(active_requests),
+    )
+}
+
+/// A context reading its configuration from `data_dir`
+async fn context_with_data_dir(
+    data_dir: &std::path::Path,
+) -> color_eyre::Result<OpenRouterContext> {
+    let mut env = BlueprintEnvironment::default();
+    env.data_dir = Some(data_dir.to_path_buf());
+    Ok(OpenRouterContext::new(env).await?)
+}
+
+/// Test that a successful reload is broadcast and its strategy applied to the load balancer
+#[tokio::test]
+async fn test_reload_emits_event_and_applies_strategy() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    let context = context_with_data_dir(data_dir.path()).await?;
+    assert_eq!(
+        context.load_balancer.strategy().await,
+        LoadBalancingStrategy::RoundRobin
+    );
+
+    let mut events = context.subscribe_config_events();
+    std::fs::write(
+        data_dir.path().join("config.json"),
+        r#"{ "load_balancer": { "strategy": "LeastLoaded" } }"#,
+    )?;
+    context
+        .reload_config()
+        .await
+        .map_err(|e| color_eyre::eyre::eyre!(e))?;
+
+    match events.recv().await? {
+        ConfigEvent::Reloaded(config) => {
+            assert_eq!(
+                config.load_balancer.strategy,
+                LoadBalancingStrategy::LeastLoaded
+            );
+        }
+        other => panic!("Unexpected config event: {:?}", other),
+    }
+    assert_eq!(
+        context.load_balancer.strategy().await,
+        LoadBalancingStrategy::LeastLoaded
+    );
+    Ok(())
+}
+
+/// Test that a failed reload is broadcast and leaves the current configuration in place
+#[tokio::test]
+async fn test_failed_reload_emits_event() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    let context = context_with_data_dir(data_dir.path()).await?;
+
+    let mut events = context.subscribe_config_events();
+    std::fs::write(data_dir.path().join("config.json"), "{ not json")?;
+    let result = context.reload_config().await;
+
+    assert!(result.is_err());
+    match events.recv().await? {
+        ConfigEvent::ReloadFailed(message) => {
+            assert!(message.starts_with("Failed to load configuration"));
+        }
+        other => panic!("Unexpected config event: {:?}", other),
+    }
+    assert_eq!(
+        context.load_balancer.strategy().await,
+        LoadBalancingStrategy::RoundRobin
+    );
+    Ok(())
+}
+
+/// Ids of the nodes picked for `RELOAD_MODEL` by four consecutive selections
+async fn select_four(context: &OpenRouterContext) -> Vec<String> {
+    let mut selected = Vec::new();
+    for _ in 0..4 {
+        let node = context
+            .load_balancer
+            .select_node_for_model(RELOAD_MODEL)
+            .await
+            .expect("a node serves the model");
+        selected.push(node.id);
+    }
+    selected
+}
+
+/// Test that selections made after a reload follow the reloaded strategy
+#[tokio::test]
+async fn test_reloaded_strategy_applies_to_selection() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    let context = context_with_data_dir(data_dir.path()).await?;
+    context
+        .add_llm_node("busy".to_string(), loaded_node(5))
+        .await?;
+    context
+        .add_llm_node("idle".to_string(), loaded_node(0))
+        .await?;
+
+    // Round-robin alternates regardless of load
+    assert_eq!(
+        select_four(&context).await,
+        ["busy", "idle", "busy", "idle"]
+    );
+
+    std::fs::write(
+        data_dir.path().join("config.json"),
+        r#"{ "load_balancer": { "strategy": "LeastLoaded" } }"#,
+    )?;
+    context
+        .reload_config()
+        .await
+        .map_err(|e| color_eyre::eyre::eyre!(e))?;
+
+    assert_eq!(
+        select_four(&context).await,
+        ["idle", "idle", "idle", "idle"]
+    );
+    Ok(())
+}
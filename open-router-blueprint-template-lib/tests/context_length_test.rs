@@ -0,0 +1,111 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{message, model_info, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{ChatCompletionRequest, LlmRequest},
+    tokens::TokenCounter,
+};
+
+const SHORT_MODEL: &str = "short-model";
+const LONG_MODEL: &str = "long-model";
+
+/// A backend serving a 100-token and a 1000-token model
+fn multi_model_backend() -> Arc<MockBackend> {
+    Arc::new(
+        MockBackend::new()
+            .serving_model(model_info(SHORT_MODEL, 100))
+            .serving_model(model_info(LONG_MODEL, 1000)),
+    )
+}
+
+/// A chat request for `model` needing about 300 context tokens
+fn request_for(model: &str) -> LlmRequest {
+    LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: model.to_string(),
+        messages: vec![message("user", &"a".repeat(800))],
+        max_tokens: Some(100),
+        ..Default::default()
+    })
+}
+
+/// Test that each model of a multi-model node is validated against its own context length
+#[tokio::test]
+async fn test_requests_are_validated_against_their_model_context() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .reject_context_overflow = true;
+    context
+        .add_llm_node("multi".to_string(), multi_model_backend())
+        .await?;
+
+    let error = process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(request_for(SHORT_MODEL)),
+    )
+    .await
+    .expect_err("the request overflows the short model's context");
+    assert!(error.to_string().contains("100-token context"));
+
+    process_llm_request(
+        Context(context),
+        CallId(2),
+        TangleArg(request_for(LONG_MODEL)),
+    )
+    .await?;
+    Ok(())
+}
+
+/// Counts every character as a token, as a tokenizer far less compact than the estimate
+struct CharTokenCounter;
+
+impl TokenCounter for CharTokenCounter {
+    fn count(&self, text: &str) -> usize {
+        text.chars().count()
+    }
+}
+
+/// Test that requests are checked with the token counter installed on the context
+#[tokio::test]
+async fn test_custom_token_counter_decides_overflow() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .reject_context_overflow = true;
+    context
+        .add_llm_node("multi".to_string(), multi_model_backend())
+        .await?;
+    context.set_token_counter(Arc::new(CharTokenCounter)).await;
+
+    // 800 prompt tokens and 100 output tokens still fit the long model
+    process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(request_for(LONG_MODEL)),
+    )
+    .await?;
+
+    let mut request = request_for(LONG_MODEL);
+    if let LlmRequest::ChatCompletion(request) = &mut request {
+        request.max_tokens = Some(300);
+    }
+    let error = process_llm_request(Context(context), CallId(2), TangleArg(request))
+        .await
+        .expect_err("the request overflows the long model's context");
+    assert!(error.to_string().contains("needs about 1100 tokens"));
+    Ok(())
+}
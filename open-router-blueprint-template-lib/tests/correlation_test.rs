@@ -0,0 +1,55 @@
+mod common;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::trace::TraceRecorder;
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{ChatCompletionRequest, ChatMessage, LlmRequest, LlmResponse},
+};
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Test that the Tangle call id is recorded on the request span and returned in the response
+#[tokio::test]
+async fn test_call_id_propagates_into_span_and_response() -> color_eyre::Result<()> {
+    let recorder = TraceRecorder::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let request = ChatCompletionRequest {
+        model: "gpt-3.5-turbo".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+
+    let result = process_llm_request(
+        Context(context),
+        CallId(42),
+        TangleArg(LlmRequest::ChatCompletion(request)),
+    )
+    .await?;
+
+    assert_eq!(
+        recorder.field("llm_request", "call_id").as_deref(),
+        Some("42")
+    );
+    assert_eq!(
+        recorder.field("llm_request", "correlation_id").as_deref(),
+        Some("tangle-call-42")
+    );
+    match result.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.correlation_id.as_deref(), Some("tangle-call-42"));
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+
+    Ok(())
+}
@@ -0,0 +1,119 @@
+mod common;
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_request, model_info, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{ChatCompletionResponse, LlmResponse, ModelInfo, Pricing, UsageInfo},
+};
+use rust_decimal::Decimal;
+
+const PRICED_MODEL: &str = "priced-model";
+
+/// A backend serving `PRICED_MODEL` at OpenRouter prices, reporting 1000 prompt and 500
+/// completion tokens per request
+fn priced_backend() -> Arc<MockBackend> {
+    let model = ModelInfo {
+        parameters: HashMap::from([
+            ("pricing_prompt".to_string(), "0.000002".to_string()),
+            ("pricing_completion".to_string(), "0.00001".to_string()),
+            ("pricing_request".to_string(), "0.001".to_string()),
+        ]),
+        ..model_info(PRICED_MODEL, 4096)
+    };
+    Arc::new(
+        MockBackend::new()
+            .serving_model(model)
+            .with_usage(UsageInfo {
+                prompt_tokens: 1000,
+                completion_tokens: 500,
+                total_tokens: 1500,
+                prompt_tokens_cached: None,
+            }),
+    )
+}
+
+async fn priced_completion(include_cost: bool) -> color_eyre::Result<ChatCompletionResponse> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.blueprint_config.write().await.api.include_cost = include_cost;
+    completion_of(context, PRICED_MODEL).await
+}
+
+async fn completion_of(
+    context: OpenRouterContext,
+    model: &str,
+) -> color_eyre::Result<ChatCompletionResponse> {
+    context
+        .add_llm_node("priced".to_string(), priced_backend())
+        .await?;
+
+    let request = chat_request(model, "Hello");
+    let response = process_llm_request(Context(context), CallId(1), TangleArg(request)).await?;
+    match response.0 {
+        LlmResponse::ChatCompletion(response) => Ok(response),
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+}
+
+/// Test that a completion reports its cost from the model's pricing and its usage
+#[tokio::test]
+async fn test_completion_reports_cost_when_enabled() -> color_eyre::Result<()> {
+    let response = priced_completion(true).await?;
+
+    // 1000 * 0.000002 + 500 * 0.00001 + 0.001
+    assert_eq!(response.cost, Some(Decimal::from_str("0.008")?));
+    Ok(())
+}
+
+/// Test that no cost is reported unless enabled
+#[tokio::test]
+async fn test_completion_omits_cost_by_default() -> color_eyre::Result<()> {
+    let response = priced_completion(false).await?;
+
+    assert_eq!(response.cost, None);
+    assert!(serde_json::to_value(&response)?.get("cost").is_none());
+    Ok(())
+}
+
+/// Test that configured pricing takes precedence over the pricing the node advertises
+#[tokio::test]
+async fn test_configured_pricing_takes_precedence() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    {
+        let mut config = context.blueprint_config.write().await;
+        config.api.include_cost = true;
+        config.llm.model_pricing.insert(
+            PRICED_MODEL.to_string(),
+            Pricing {
+                prompt: Decimal::from_str("0.000001")?,
+                completion: Decimal::from_str("0.000002")?,
+                ..Default::default()
+            },
+        );
+    }
+    let response = completion_of(context, PRICED_MODEL).await?;
+
+    // 1000 * 0.000001 + 500 * 0.000002
+    assert_eq!(response.cost, Some(Decimal::from_str("0.002")?));
+    Ok(())
+}
+
+/// Test that a completion of a model without known pricing reports no cost
+#[tokio::test]
+async fn test_completion_without_pricing_omits_cost() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.blueprint_config.write().await.api.include_cost = true;
+
+    // No node serves the model, so the default client answers it
+    let response = completion_of(context, "unpriced-model").await?;
+
+    assert_eq!(response.cost, None);
+    Ok(())
+}
@@ -0,0 +1,81 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{message, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{ChatCompletionRequest, LlmRequest},
+};
+
+const MODEL: &str = "greedy-model";
+
+async fn context_with_recording_backend(
+) -> color_eyre::Result<(OpenRouterContext, Arc<MockBackend>)> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let client = Arc::new(
+        MockBackend::new()
+            .serving(MODEL)
+            .replying(|_| "Paris".to_string()),
+    );
+    context
+        .add_llm_node("recording".to_string(), client.clone())
+        .await?;
+    Ok((context, client))
+}
+
+fn greedy_request() -> LlmRequest {
+    LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: MODEL.to_string(),
+        messages: vec![message("user", "What is the capital of France?")],
+        temperature: Some(0.0),
+        top_p: Some(1.0),
+        ..Default::default()
+    })
+}
+
+/// Test that a temperature 0 request reaches the backend without top_p
+#[tokio::test]
+async fn test_temperature_zero_omits_top_p() -> color_eyre::Result<()> {
+    let (context, client) = context_with_recording_backend().await?;
+
+    process_llm_request(Context(context), CallId(1), TangleArg(greedy_request())).await?;
+
+    let requests = client.chat_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].temperature, Some(0.0));
+    assert_eq!(requests[0].top_p, None);
+    Ok(())
+}
+
+/// Test that a repeated temperature 0 request is answered from the cache
+#[cfg(feature = "response-cache")]
+#[tokio::test]
+async fn test_repeated_temperature_zero_request_is_cached() -> color_eyre::Result<()> {
+    use open_router_blueprint_template_lib::llm::LlmResponse;
+
+    let (context, client) = context_with_recording_backend().await?;
+
+    process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(greedy_request()),
+    )
+    .await?;
+    let second =
+        process_llm_request(Context(context), CallId(2), TangleArg(greedy_request())).await?;
+
+    assert_eq!(client.request_count(), 1);
+    match second.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.choices[0].message.content, "Paris");
+            assert_eq!(response.correlation_id.as_deref(), Some("tangle-call-2"));
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    Ok(())
+}
@@ -1,14 +1,19 @@
 use blueprint_sdk::tangle::filters::MatchesServiceId;
 use blueprint_sdk::tangle::layers::TangleLayer;
-use blueprint_sdk::tangle::serde::to_field;
+use blueprint_sdk::tangle::serde::{from_field, to_field};
 use blueprint_sdk::testing::tempfile;
 use blueprint_sdk::testing::utils::setup_log;
 use blueprint_sdk::testing::utils::tangle::TangleTestHarness;
 use blueprint_sdk::Job;
 use blueprint_sdk::Router;
-use open_router_blueprint_template_lib::llm::{LlmRequest, TextCompletionRequest};
+use open_router_blueprint_template_lib::correlation::correlation_id_for_call;
+use open_router_blueprint_template_lib::llm::{LlmRequest, LlmResponse, TextCompletionRequest};
+use open_router_blueprint_template_lib::stream_results::{
+    StreamPage, StreamPoll, StreamReassembler,
+};
 use open_router_blueprint_template_lib::{
-    process_llm_request, OpenRouterContext, PROCESS_LLM_REQUEST_JOB_ID,
+    poll_llm_stream, process_llm_request, start_llm_stream, OpenRouterContext,
+    POLL_LLM_STREAM_JOB_ID, PROCESS_LLM_REQUEST_JOB_ID, START_LLM_STREAM_JOB_ID,
 };
 use std::collections::HashMap;
 use tower::filter::FilterLayer;
@@ -37,7 +42,7 @@ async fn test_blueprint() -> color_eyre::Result<()> {
     test_env.initialize().await?;
 
     // Create a Router and register the job with it
-    let router = Router::new()
+    let _router = Router::new()
         .route(
             PROCESS_LLM_REQUEST_JOB_ID,
             process_llm_request.layer(TangleLayer),
@@ -56,6 +61,8 @@ async fn test_blueprint() -> color_eyre::Result<()> {
         max_tokens: Some(10),
         temperature: None, // Avoid using f32 values which might cause serialization issues
         top_p: None,       // Avoid using f32 values which might cause serialization issues
+        stop: None,
+        n: None,
         stream: Some(false),
         additional_params: HashMap::new(),
     });
@@ -71,3 +78,70 @@ async fn test_blueprint() -> color_eyre::Result<()> {
     assert_eq!(results.service_id, service_id);
     Ok(())
 }
+
+#[tokio::test]
+async fn test_streaming_through_job_results() -> color_eyre::Result<()> {
+    setup_log();
+
+    let current_dir = std::env::current_dir()?;
+    let root_dir = current_dir
+        .parent()
+        .ok_or_else(|| color_eyre::eyre::eyre!("Failed to find root directory"))?;
+    std::env::set_current_dir(root_dir)?;
+
+    let temp_dir = tempfile::TempDir::new()?;
+    let context =
+        OpenRouterContext::new(blueprint_sdk::runner::config::BlueprintEnvironment::default())
+            .await?;
+    let harness = TangleTestHarness::setup(temp_dir).await?;
+
+    let (mut test_env, service_id, _) = harness.setup_services::<N>(false).await?;
+
+    test_env.initialize().await?;
+
+    let _router = Router::new()
+        .route(START_LLM_STREAM_JOB_ID, start_llm_stream.layer(TangleLayer))
+        .route(POLL_LLM_STREAM_JOB_ID, poll_llm_stream.layer(TangleLayer))
+        .layer(FilterLayer::new(MatchesServiceId(service_id)));
+
+    test_env.start(context).await?;
+
+    let request = LlmRequest::TextCompletion(TextCompletionRequest {
+        model: "gpt-3.5-turbo".to_string(),
+        prompt: "Hello, world!".to_string(),
+        stream: Some(true),
+        ..Default::default()
+    });
+    let job = harness
+        .submit_job(
+            service_id,
+            START_LLM_STREAM_JOB_ID,
+            vec![to_field(request).unwrap()],
+        )
+        .await?;
+    let stream_id = correlation_id_for_call(job.call_id);
+    harness.wait_for_job_execution(service_id, job).await?;
+
+    // Poll the chunked results until the stream is complete
+    let mut reassembler = StreamReassembler::new();
+    while !reassembler.is_complete() {
+        let poll = StreamPoll {
+            stream_id: stream_id.clone(),
+            from_sequence: reassembler.next_sequence(),
+        };
+        let job = harness
+            .submit_job(service_id, POLL_LLM_STREAM_JOB_ID, vec![to_field(poll).unwrap()])
+            .await?;
+        let results = harness.wait_for_job_execution(service_id, job).await?;
+        assert_eq!(results.service_id, service_id);
+
+        let page: StreamPage = from_field(results.result[0].clone())?;
+        reassembler.push(page)?;
+    }
+
+    let LlmResponse::TextCompletion(response) = reassembler.into_response().await? else {
+        color_eyre::eyre::bail!("expected a text completion");
+    };
+    assert_eq!(response.choices[0].text, "Hello, world!");
+    Ok(())
+}
@@ -0,0 +1,146 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_request, model_info, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{
+        ChatCompletionResponse, LlmRequest, LlmResponse, ModelInfo, TextCompletionResponse,
+        UsageInfo,
+    },
+};
+
+const EMPTY_MODEL: &str = "empty-model";
+
+/// A backend serving `model` that answers every completion without any choices, reporting
+/// `usage`
+fn empty_choices_backend(model: ModelInfo, usage: Option<UsageInfo>) -> MockBackend {
+    let text_usage = usage.clone();
+    MockBackend::new()
+        .serving_model(model)
+        .on_chat(move |request| {
+            Ok(ChatCompletionResponse {
+                id: "empty".to_string(),
+                object: "chat.completion".to_string(),
+                model: request.model.clone(),
+                usage: usage.clone(),
+                ..Default::default()
+            })
+        })
+        .on_text(move |request| {
+            Ok(TextCompletionResponse {
+                id: "empty".to_string(),
+                object: "text_completion".to_string(),
+                model: request.model.clone(),
+                usage: text_usage.clone(),
+                ..Default::default()
+            })
+        })
+}
+
+async fn context_with_backend(client: MockBackend) -> color_eyre::Result<OpenRouterContext> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .add_llm_node("empty".to_string(), Arc::new(client))
+        .await?;
+    Ok(context)
+}
+
+async fn context_with_empty_backend() -> color_eyre::Result<OpenRouterContext> {
+    context_with_backend(empty_choices_backend(model_info(EMPTY_MODEL, 4096), None)).await
+}
+
+/// Usage of a request that generated no tokens
+fn prompt_only_usage() -> UsageInfo {
+    UsageInfo {
+        prompt_tokens: 12,
+        completion_tokens: 0,
+        total_tokens: 12,
+        prompt_tokens_cached: None,
+    }
+}
+
+fn empty_request() -> LlmRequest {
+    chat_request(EMPTY_MODEL, "Hello")
+}
+
+/// Test that a response without choices becomes a clean error instead of a panic
+#[tokio::test]
+async fn test_empty_choices_returns_error() -> color_eyre::Result<()> {
+    let context = context_with_empty_backend().await?;
+
+    let result = process_llm_request(Context(context), CallId(1), TangleArg(empty_request())).await;
+
+    let error = result.expect_err("empty choices should fail");
+    assert!(error.to_string().contains("empty response from backend"));
+    Ok(())
+}
+
+/// Test that the configured fallback content fills in for missing choices
+#[tokio::test]
+async fn test_empty_choices_uses_configured_fallback() -> color_eyre::Result<()> {
+    let context = context_with_empty_backend().await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .empty_response_fallback = Some("Sorry, no answer.".to_string());
+
+    let result =
+        process_llm_request(Context(context), CallId(1), TangleArg(empty_request())).await?;
+
+    match result.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.choices.len(), 1);
+            assert_eq!(response.choices[0].message.content, "Sorry, no answer.");
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    Ok(())
+}
+
+/// Test that a usage-only response from a classifier model passes through unchanged
+#[tokio::test]
+async fn test_usage_only_response_passes_through() -> color_eyre::Result<()> {
+    let classifier = ModelInfo {
+        parameters: HashMap::from([("task".to_string(), "classify".to_string())]),
+        ..model_info(EMPTY_MODEL, 4096)
+    };
+    let context =
+        context_with_backend(empty_choices_backend(classifier, Some(prompt_only_usage()))).await?;
+
+    let result =
+        process_llm_request(Context(context), CallId(1), TangleArg(empty_request())).await?;
+
+    match result.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert!(response.choices.is_empty());
+            assert_eq!(response.usage.map(|u| u.prompt_tokens), Some(12));
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    Ok(())
+}
+
+/// Test that a generative model answering with usage but no choices still fails
+#[tokio::test]
+async fn test_usage_only_response_from_generative_model_returns_error() -> color_eyre::Result<()> {
+    let context = context_with_backend(empty_choices_backend(
+        model_info(EMPTY_MODEL, 4096),
+        Some(prompt_only_usage()),
+    ))
+    .await?;
+
+    let result = process_llm_request(Context(context), CallId(1), TangleArg(empty_request())).await;
+
+    let error = result.expect_err("empty choices should fail");
+    assert!(error.to_string().contains("empty response from backend"));
+    Ok(())
+}
@@ -0,0 +1,113 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_reply, message, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{
+        ChatCompletionChoice, ChatCompletionRequest, ChatCompletionResponse, ExtraChoicesPolicy,
+        LlmRequest, LlmResponse,
+    },
+};
+
+const GENEROUS_MODEL: &str = "generous-model";
+
+/// A backend that answers every chat completion with three choices, whatever `n` was
+fn extra_choices_backend() -> MockBackend {
+    MockBackend::new()
+        .serving(GENEROUS_MODEL)
+        .on_chat(|request| {
+            // Out of order, so truncation has to keep the lowest indexes, not the first entries
+            let choices = [2, 0, 1]
+                .into_iter()
+                .map(|index| ChatCompletionChoice {
+                    index,
+                    message: message("assistant", &format!("Answer {}", index)),
+                    finish_reason: Some("stop".to_string()),
+                    ..Default::default()
+                })
+                .collect();
+            Ok(ChatCompletionResponse {
+                choices,
+                ..chat_reply(&request.model, "")
+            })
+        })
+}
+
+async fn context_with_policy(policy: ExtraChoicesPolicy) -> color_eyre::Result<OpenRouterContext> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .extra_choices_policy = policy;
+    context
+        .add_llm_node("generous".to_string(), Arc::new(extra_choices_backend()))
+        .await?;
+    Ok(context)
+}
+
+fn chat_request(n: Option<u64>) -> LlmRequest {
+    let mut request = ChatCompletionRequest {
+        model: GENEROUS_MODEL.to_string(),
+        messages: vec![message("user", "Hello")],
+        ..Default::default()
+    };
+    if let Some(n) = n {
+        request.additional_params.insert("n".to_string(), n.into());
+    }
+    LlmRequest::ChatCompletion(request)
+}
+
+/// The contents of the choices of a chat completion response, in order
+fn choice_contents(response: LlmResponse) -> Vec<String> {
+    match response {
+        LlmResponse::ChatCompletion(response) => response
+            .choices
+            .into_iter()
+            .map(|choice| choice.message.content)
+            .collect(),
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+}
+
+/// Test that choices beyond the requested `n` are dropped under the truncate policy
+#[tokio::test]
+async fn test_extra_choices_are_truncated() -> color_eyre::Result<()> {
+    let context = context_with_policy(ExtraChoicesPolicy::Truncate).await?;
+
+    let single = process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(chat_request(None)),
+    )
+    .await?;
+    let pair = process_llm_request(
+        Context(context),
+        CallId(2),
+        TangleArg(chat_request(Some(2))),
+    )
+    .await?;
+
+    assert_eq!(choice_contents(single.0), vec!["Answer 0"]);
+    assert_eq!(choice_contents(pair.0), vec!["Answer 0", "Answer 1"]);
+    Ok(())
+}
+
+/// Test that every choice is returned under the passthrough policy
+#[tokio::test]
+async fn test_extra_choices_pass_through() -> color_eyre::Result<()> {
+    let context = context_with_policy(ExtraChoicesPolicy::Passthrough).await?;
+
+    let response =
+        process_llm_request(Context(context), CallId(1), TangleArg(chat_request(None))).await?;
+
+    assert_eq!(choice_contents(response.0).len(), 3);
+    Ok(())
+}
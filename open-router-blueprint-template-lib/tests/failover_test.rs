@@ -0,0 +1,228 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_request, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{LlmError, LlmResponse},
+    load_balancer::{LoadBalancerConfig, LoadBalancingStrategy},
+};
+
+const MODEL: &str = "failover-model";
+
+/// A backend answering chat requests with its name, or failing them with `error`
+fn node(name: &'static str, error: Option<fn() -> LlmError>) -> Arc<MockBackend> {
+    let backend = MockBackend::new()
+        .serving(MODEL)
+        .replying(move |_| name.to_string());
+    Arc::new(match error {
+        Some(error) => backend.failing(error),
+        None => backend,
+    })
+}
+
+/// A context with the two nodes, `a` being the first one selected
+async fn context_with_nodes(
+    a: Arc<MockBackend>,
+    b: Arc<MockBackend>,
+) -> color_eyre::Result<OpenRouterContext> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.add_llm_node("a".to_string(), a).await?;
+    context.add_llm_node("b".to_string(), b).await?;
+    Ok(context)
+}
+
+/// Test that a request failed by one node is retried on another one
+#[tokio::test]
+async fn test_failed_request_is_retried_on_another_node() -> color_eyre::Result<()> {
+    let failing = node(
+        "failing",
+        Some(|| LlmError::RequestFailed("backend unavailable".to_string())),
+    );
+    let working = node("working", None);
+    let context = context_with_nodes(failing.clone(), working.clone()).await?;
+
+    let response = process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(chat_request(MODEL, "Hello")),
+    )
+    .await?;
+
+    match response.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.choices[0].message.content, "working")
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    assert_eq!(failing.request_count(), 1);
+    assert_eq!(working.request_count(), 1);
+    assert!(context.load_balancer.get_node("a").await.unwrap().failed);
+    Ok(())
+}
+
+/// Test that a request failing as invalid is not retried
+#[tokio::test]
+async fn test_invalid_request_is_not_retried() -> color_eyre::Result<()> {
+    let rejecting = node(
+        "rejecting",
+        Some(|| LlmError::InvalidRequest("bad parameters".to_string())),
+    );
+    let working = node("working", None);
+    let context = context_with_nodes(rejecting.clone(), working.clone()).await?;
+
+    let result = process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(chat_request(MODEL, "Hello")),
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert_eq!(rejecting.request_count(), 1);
+    assert_eq!(working.request_count(), 0);
+    assert!(!context.load_balancer.get_node("a").await.unwrap().failed);
+    Ok(())
+}
+
+/// Test that a failing node registered as "default" is failed over from like any other node
+#[tokio::test]
+async fn test_failing_default_node_is_failed_over() -> color_eyre::Result<()> {
+    let failing = node(
+        "failing",
+        Some(|| LlmError::RequestFailed("backend unavailable".to_string())),
+    );
+    let working = node("working", None);
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .add_llm_node("default".to_string(), failing.clone())
+        .await?;
+    context
+        .add_llm_node("fallback".to_string(), working.clone())
+        .await?;
+
+    let response = process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(chat_request(MODEL, "Hello")),
+    )
+    .await?;
+
+    match response.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.choices[0].message.content, "working")
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    assert_eq!(failing.request_count(), 1);
+    assert_eq!(working.request_count(), 1);
+    assert!(
+        context
+            .load_balancer
+            .get_node("default")
+            .await
+            .unwrap()
+            .failed
+    );
+    Ok(())
+}
+
+/// Test that a failure is recorded against its node even without retries left
+#[tokio::test]
+async fn test_failure_without_retries_opens_circuit() -> color_eyre::Result<()> {
+    let failing = node(
+        "failing",
+        Some(|| LlmError::RequestFailed("backend unavailable".to_string())),
+    );
+    let working = node("working", None);
+    let context = context_with_nodes(failing.clone(), working.clone()).await?;
+    context
+        .load_balancer
+        .set_config(LoadBalancerConfig {
+            max_retries: 0,
+            ..Default::default()
+        })
+        .await;
+
+    let result = process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(chat_request(MODEL, "Hello")),
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert_eq!(failing.request_count(), 1);
+    assert_eq!(working.request_count(), 0);
+    assert!(context.load_balancer.get_node("a").await.unwrap().failed);
+    Ok(())
+}
+
+/// Test that a failed request is retried on another node while the failed one is still within
+/// its grace count
+#[tokio::test]
+async fn test_retry_skips_node_already_tried() -> color_eyre::Result<()> {
+    let failing = node(
+        "failing",
+        Some(|| LlmError::RequestFailed("backend unavailable".to_string())),
+    );
+    let working = node("working", None);
+    let context = context_with_nodes(failing.clone(), working.clone()).await?;
+    // Least-loaded picks the first of the idle nodes, so `a` would be picked again
+    context
+        .load_balancer
+        .set_config(LoadBalancerConfig {
+            failure_grace_count: 2,
+            strategy: LoadBalancingStrategy::LeastLoaded,
+            ..Default::default()
+        })
+        .await;
+
+    let response = process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(chat_request(MODEL, "Hello")),
+    )
+    .await?;
+
+    match response.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.choices[0].message.content, "working")
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    assert_eq!(failing.request_count(), 1);
+    assert_eq!(working.request_count(), 1);
+    assert!(!context.load_balancer.get_node("a").await.unwrap().failed);
+    Ok(())
+}
+
+/// Test that the failure of the only node serving a request is recorded against it
+#[tokio::test]
+async fn test_failure_of_only_node_opens_circuit() -> color_eyre::Result<()> {
+    let failing = node(
+        "failing",
+        Some(|| LlmError::RequestFailed("backend unavailable".to_string())),
+    );
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .add_llm_node("only".to_string(), failing.clone())
+        .await?;
+
+    let result = process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(chat_request(MODEL, "Hello")),
+    )
+    .await;
+
+    assert!(result.is_err());
+    assert_eq!(failing.request_count(), 1);
+    assert!(context.load_balancer.get_node("only").await.unwrap().failed);
+    Ok(())
+}
@@ -0,0 +1,157 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_reply, message, MockBackend};
+use open_router_blueprint_template_lib::{
+    auth::Principal,
+    context::OpenRouterContext,
+    jobs::{process_llm_request, process_request_for_principal},
+    llm::{ChatCompletionRequest, LlmRequest, LlmResponse},
+};
+
+const MODEL: &str = "metered-model";
+
+/// A metered backend numbering the completions it generates
+fn metered_node(delay: Duration) -> Arc<MockBackend> {
+    let generated = AtomicU32::new(0);
+    Arc::new(
+        MockBackend::new()
+            .serving(MODEL)
+            .with_delay(delay)
+            .on_chat(move |request| {
+                assert!(
+                    !request.additional_params.contains_key("idempotency_key"),
+                    "the idempotency key must not reach the backend"
+                );
+                let number = generated.fetch_add(1, Ordering::SeqCst) + 1;
+                Ok(chat_reply(
+                    &request.model,
+                    &format!("completion {}", number),
+                ))
+            }),
+    )
+}
+
+fn chat_request(idempotency_key: &str) -> LlmRequest {
+    chat_request_saying("Hello", idempotency_key)
+}
+
+fn chat_request_saying(content: &str, idempotency_key: &str) -> LlmRequest {
+    LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: MODEL.to_string(),
+        messages: vec![message("user", content)],
+        additional_params: HashMap::from([(
+            "idempotency_key".to_string(),
+            serde_json::json!(idempotency_key),
+        )]),
+        ..Default::default()
+    })
+}
+
+async fn context_with_node(client: Arc<MockBackend>) -> color_eyre::Result<OpenRouterContext> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.add_llm_node("metered".to_string(), client).await?;
+    Ok(context)
+}
+
+async fn completion_content(
+    context: &OpenRouterContext,
+    call_id: u64,
+    request: LlmRequest,
+) -> color_eyre::Result<String> {
+    let response = process_llm_request(
+        Context(context.clone()),
+        CallId(call_id),
+        TangleArg(request),
+    )
+    .await?;
+    match response.0 {
+        LlmResponse::ChatCompletion(response) => Ok(response.choices[0].message.content.clone()),
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+}
+
+/// Test that a request sent again after its response was lost is not generated twice
+#[tokio::test]
+async fn test_lost_response_is_returned_again_for_same_key() -> color_eyre::Result<()> {
+    let client = metered_node(Duration::ZERO);
+    let context = context_with_node(client.clone()).await?;
+
+    // The first response never reaches the caller, which sends the request again
+    let _lost = completion_content(&context, 1, chat_request("order-42")).await?;
+    let retried = completion_content(&context, 2, chat_request("order-42")).await?;
+
+    assert_eq!(retried, "completion 1");
+    assert_eq!(client.request_count(), 1);
+
+    // Another key is generated anew
+    let other = completion_content(&context, 3, chat_request("order-43")).await?;
+    assert_eq!(other, "completion 2");
+    Ok(())
+}
+
+/// Test that a retry arriving while the first request is still served waits for its result
+#[tokio::test]
+async fn test_retry_in_flight_waits_for_first_result() -> color_eyre::Result<()> {
+    let client = metered_node(Duration::from_millis(200));
+    let context = context_with_node(client.clone()).await?;
+
+    let (first, retried) = tokio::join!(
+        completion_content(&context, 1, chat_request("order-42")),
+        async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            completion_content(&context, 2, chat_request("order-42")).await
+        }
+    );
+
+    assert_eq!(first?, "completion 1");
+    assert_eq!(retried?, "completion 1");
+    assert_eq!(client.request_count(), 1);
+    Ok(())
+}
+
+/// Test that a key sent again with a different request is rejected
+#[tokio::test]
+async fn test_key_reused_for_another_request_is_rejected() -> color_eyre::Result<()> {
+    let client = metered_node(Duration::ZERO);
+    let context = context_with_node(client.clone()).await?;
+
+    completion_content(&context, 1, chat_request_saying("Hello", "order-42")).await?;
+    let result = completion_content(&context, 2, chat_request_saying("Goodbye", "order-42")).await;
+
+    let error = result.unwrap_err().to_string();
+    assert!(error.contains("idempotency key order-42"), "{}", error);
+    assert_eq!(client.request_count(), 1);
+    Ok(())
+}
+
+/// Test that principals sending the same key do not receive each other's responses
+#[tokio::test]
+async fn test_keys_are_scoped_to_the_principal() -> color_eyre::Result<()> {
+    let client = metered_node(Duration::ZERO);
+    let context = context_with_node(client.clone()).await?;
+    let (alice, bob) = (Principal::new("alice"), Principal::new("bob"));
+
+    let mut contents = Vec::new();
+    for principal in [&alice, &bob, &alice] {
+        let response =
+            process_request_for_principal(&context, principal, chat_request("order-42")).await?;
+        match response {
+            LlmResponse::ChatCompletion(response) => {
+                contents.push(response.choices[0].message.content.clone())
+            }
+            other => panic!("Unexpected response type: {:?}", other),
+        }
+    }
+
+    assert_eq!(contents, ["completion 1", "completion 2", "completion 1"]);
+    assert_eq!(client.request_count(), 2);
+    Ok(())
+}
@@ -0,0 +1,145 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_reply, chat_request, text_reply, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{LlmRequest, LlmResponse, TextCompletionRequest, UsageInfo},
+};
+
+const TERSE_MODEL: &str = "terse-model";
+
+/// The parts a completion is cut into; every part but the last stops at the token limit
+const PARTS: [&str; 3] = ["Once upon ", "a time, ", "the end."];
+
+/// The part following `output`, and its finish reason
+fn next_part(output: &str) -> (&'static str, &'static str) {
+    let written = PARTS
+        .iter()
+        .scan(String::new(), |prefix, part| {
+            prefix.push_str(part);
+            Some(prefix.clone())
+        })
+        .position(|prefix| output.ends_with(&prefix))
+        .map_or(0, |index| index + 1);
+    let finish_reason = if written + 1 == PARTS.len() {
+        "stop"
+    } else {
+        "length"
+    };
+    (PARTS[written], finish_reason)
+}
+
+/// A backend that answers with one part per request, picking up after the output it is given
+fn truncating_backend() -> MockBackend {
+    MockBackend::new()
+        .serving(TERSE_MODEL)
+        .with_usage(UsageInfo {
+            prompt_tokens: 10,
+            completion_tokens: 4,
+            total_tokens: 14,
+            prompt_tokens_cached: None,
+        })
+        .on_chat(|request| {
+            let output = match request.messages.last() {
+                Some(message) if message.role == "assistant" => message.content.as_str(),
+                _ => "",
+            };
+            let (content, finish_reason) = next_part(output);
+            let mut response = chat_reply(&request.model, content);
+            response.choices[0].finish_reason = Some(finish_reason.to_string());
+            Ok(response)
+        })
+        .on_text(|request| {
+            let (text, finish_reason) = next_part(&request.prompt);
+            let mut response = text_reply(&request.model, text);
+            response.choices[0].finish_reason = Some(finish_reason.to_string());
+            Ok(response)
+        })
+}
+
+async fn context_with_continuations(
+    max_continuations: usize,
+) -> color_eyre::Result<(OpenRouterContext, Arc<MockBackend>)> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    {
+        let mut config = context.blueprint_config.write().await;
+        config.llm.auto_continue_on_length = true;
+        config.llm.max_continuations = max_continuations;
+    }
+    let client = Arc::new(truncating_backend());
+    context
+        .add_llm_node("terse".to_string(), client.clone())
+        .await?;
+    Ok((context, client))
+}
+
+fn story_request() -> LlmRequest {
+    chat_request(TERSE_MODEL, "Tell me a story")
+}
+
+/// Test that a truncated chat completion is continued until it finishes
+#[tokio::test]
+async fn test_truncated_chat_completion_is_continued() -> color_eyre::Result<()> {
+    let (context, client) = context_with_continuations(3).await?;
+
+    let response =
+        process_llm_request(Context(context), CallId(1), TangleArg(story_request())).await?;
+
+    match response.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.choices.len(), 1);
+            assert_eq!(response.choices[0].message.content, PARTS.concat());
+            assert_eq!(response.choices[0].finish_reason.as_deref(), Some("stop"));
+            assert_eq!(response.usage.map(|u| u.total_tokens), Some(42));
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    assert_eq!(client.request_count(), 3);
+    Ok(())
+}
+
+/// Test that a truncated text completion is continued until it finishes
+#[tokio::test]
+async fn test_truncated_text_completion_is_continued() -> color_eyre::Result<()> {
+    let (context, _client) = context_with_continuations(3).await?;
+    let request = LlmRequest::TextCompletion(TextCompletionRequest {
+        model: TERSE_MODEL.to_string(),
+        prompt: "Tell me a story: ".to_string(),
+        ..Default::default()
+    });
+
+    let response = process_llm_request(Context(context), CallId(1), TangleArg(request)).await?;
+
+    match response.0 {
+        LlmResponse::TextCompletion(response) => {
+            assert_eq!(response.choices[0].text, PARTS.concat());
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    Ok(())
+}
+
+/// Test that no more than `max_continuations` continuations are requested
+#[tokio::test]
+async fn test_continuations_are_capped() -> color_eyre::Result<()> {
+    let (context, client) = context_with_continuations(1).await?;
+
+    let response =
+        process_llm_request(Context(context), CallId(1), TangleArg(story_request())).await?;
+
+    match response.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.choices[0].message.content, PARTS[..2].concat());
+            assert_eq!(response.choices[0].finish_reason.as_deref(), Some("length"));
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    assert_eq!(client.request_count(), 2);
+    Ok(())
+}
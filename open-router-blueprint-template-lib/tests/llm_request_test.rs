@@ -1,14 +1,12 @@
-use std::sync::Arc;
-
 use blueprint_sdk::runner::config::BlueprintEnvironment;
-use blueprint_sdk::tangle::extract::TangleArg;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
 use blueprint_sdk::testing::utils::setup_log;
 use blueprint_sdk::{extract::Context, IntoJobResult, JobResult};
 use open_router_blueprint_template_lib::{
     context::OpenRouterContext,
     jobs::process_llm_request,
     llm::{
-        ChatCompletionRequest, ChatMessage, EmbeddingRequest, LlmRequest, ModelInfo,
+        ChatCompletionRequest, ChatMessage, EmbeddingRequest, LlmRequest, LlmResponse,
         TextCompletionRequest,
     },
 };
@@ -38,17 +36,23 @@ async fn test_process_chat_completion_request() -> color_eyre::Result<()> {
             ChatMessage {
                 role: "system".to_string(),
                 content: "You are a helpful assistant.".to_string(),
-                name: None,
+                ..Default::default()
             },
             ChatMessage {
                 role: "user".to_string(),
                 content: "Hello, how are you?".to_string(),
-                name: None,
+                ..Default::default()
             },
         ],
         max_tokens: Some(100),
         temperature: Some(0.7),
         top_p: None,
+        stop: None,
+        n: None,
+        service_tier: None,
+        session_id: None,
+        tools: None,
+        tool_choice: None,
         stream: None,
         additional_params: Default::default(),
     };
@@ -57,7 +61,7 @@ async fn test_process_chat_completion_request() -> color_eyre::Result<()> {
     let llm_request = LlmRequest::ChatCompletion(request);
 
     // Process the request
-    let result = process_llm_request(Context(context), TangleArg(llm_request)).await?;
+    let result = process_llm_request(Context(context), CallId(1), TangleArg(llm_request)).await?;
 
     // Convert the result to a JobResult
     let job_result = result.into_job_result().unwrap();
@@ -94,6 +98,8 @@ async fn test_process_text_completion_request() -> color_eyre::Result<()> {
         max_tokens: Some(100),
         temperature: Some(0.7),
         top_p: None,
+        stop: None,
+        n: None,
         stream: None,
         additional_params: Default::default(),
     };
@@ -102,7 +108,7 @@ async fn test_process_text_completion_request() -> color_eyre::Result<()> {
     let llm_request = LlmRequest::TextCompletion(request);
 
     // Process the request
-    let result = process_llm_request(Context(context), TangleArg(llm_request)).await?;
+    let result = process_llm_request(Context(context), CallId(1), TangleArg(llm_request)).await?;
 
     // Convert the result to a JobResult
     let job_result = result.into_job_result().unwrap();
@@ -143,7 +149,7 @@ async fn test_process_embedding_request() -> color_eyre::Result<()> {
     let llm_request = LlmRequest::Embedding(request);
 
     // Process the request
-    let result = process_llm_request(Context(context), TangleArg(llm_request)).await?;
+    let result = process_llm_request(Context(context), CallId(1), TangleArg(llm_request)).await?;
 
     // Convert the result to a JobResult
     let job_result = result.into_job_result().unwrap();
@@ -154,3 +160,50 @@ async fn test_process_embedding_request() -> color_eyre::Result<()> {
         JobResult::Err(error) => Err(color_eyre::eyre::eyre!("Job failed: {}", error)),
     }
 }
+
+/// Test that verifies the default template client echoes the last user message
+#[tokio::test]
+async fn test_default_client_echoes_last_user_message() -> color_eyre::Result<()> {
+    setup_log();
+
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+
+    let request = ChatCompletionRequest {
+        model: "gpt-3.5-turbo".to_string(),
+        messages: vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: "You are a helpful assistant.".to_string(),
+                ..Default::default()
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: "Echo me, please".to_string(),
+                ..Default::default()
+            },
+        ],
+        ..Default::default()
+    };
+
+    let result = process_llm_request(
+        Context(context),
+        CallId(7),
+        TangleArg(LlmRequest::ChatCompletion(request)),
+    )
+    .await?;
+
+    match result.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.correlation_id.as_deref(), Some("tangle-call-7"));
+            assert_eq!(response.choices.len(), 1);
+            assert_eq!(response.choices[0].message.role, "assistant");
+            assert_eq!(response.choices[0].message.content, "Echo me, please");
+            assert_eq!(response.choices[0].finish_reason.as_deref(), Some("stop"));
+            Ok(())
+        }
+        other => Err(color_eyre::eyre::eyre!(
+            "Unexpected response type: {:?}",
+            other
+        )),
+    }
+}
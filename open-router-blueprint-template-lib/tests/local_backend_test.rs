@@ -0,0 +1,198 @@
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use hyper::service::{make_service_fn, service_fn};
+use hyper::{Body, Request, Response, Server, StatusCode};
+use open_router_blueprint_template_lib::llm::{
+    ChatCompletionRequest, ChatMessage, EmbeddingRequest, LlmClient, LlmError, LocalLlmClient,
+    LocalLlmConfig, LocalReplyMode, ModelInfo, TextCompletionRequest,
+};
+use serde_json::{json, Value};
+
+const BACKEND_MODEL: &str = "backend-model";
+
+/// Requests received by the backend, as (path, JSON body)
+type Received = Arc<Mutex<Vec<(String, Value)>>>;
+
+/// Answer like an OpenAI-compatible backend, failing every request for `overloaded-model`
+async fn handle(request: Request<Body>, received: Received) -> Result<Response<Body>, Infallible> {
+    let path = request.uri().path().to_string();
+    let bytes = hyper::body::to_bytes(request.into_body()).await.unwrap();
+    let body: Value = serde_json::from_slice(&bytes).unwrap_or(Value::Null);
+    received.lock().unwrap().push((path.clone(), body.clone()));
+
+    if body["model"] == "overloaded-model" {
+        return Ok(Response::builder()
+            .status(StatusCode::SERVICE_UNAVAILABLE)
+            .body(Body::from(r#"{"error": {"message": "overloaded"}}"#))
+            .unwrap());
+    }
+
+    let usage = json!({ "prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7 });
+    let response = match path.as_str() {
+        "/v1/chat/completions" => json!({
+            "id": "chatcmpl-1",
+            "object": "chat.completion",
+            "created": 1700000000,
+            "model": body["model"],
+            "system_fingerprint": "fp-1",
+            "choices": [{
+                "index": 0,
+                "message": { "role": "assistant", "content": "Hi from the backend" },
+                "finish_reason": "stop"
+            }],
+            "usage": usage
+        }),
+        "/v1/completions" => json!({
+            "id": "cmpl-1",
+            "object": "text_completion",
+            "created": 1700000000,
+            "model": body["model"],
+            "choices": [{ "index": 0, "text": " world", "finish_reason": "length" }],
+            "usage": usage
+        }),
+        "/v1/embeddings" => json!({
+            "object": "list",
+            "model": body["model"],
+            "data": [
+                { "object": "embedding", "index": 1, "embedding": [0.0, 1.0] },
+                { "object": "embedding", "index": 0, "embedding": [1.0, 0.0] }
+            ],
+            "usage": { "prompt_tokens": 4, "total_tokens": 4, "completion_tokens": 0 }
+        }),
+        _ => {
+            return Ok(Response::builder()
+                .status(StatusCode::NOT_FOUND)
+                .body(Body::empty())
+                .unwrap())
+        }
+    };
+    Ok(Response::new(Body::from(response.to_string())))
+}
+
+/// Serve the backend on a free local port
+fn start_backend() -> (SocketAddr, Received) {
+    let received = Received::default();
+    let service_received = received.clone();
+    let make_svc = make_service_fn(move |_conn| {
+        let received = service_received.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |req| handle(req, received.clone()))) }
+    });
+
+    let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_svc);
+    let addr = server.local_addr();
+    tokio::spawn(server);
+    (addr, received)
+}
+
+fn backend_client(addr: SocketAddr) -> LocalLlmClient {
+    let models = [BACKEND_MODEL, "overloaded-model"]
+        .into_iter()
+        .map(|id| ModelInfo {
+            id: id.to_string(),
+            name: id.to_string(),
+            max_context_length: 4096,
+            supports_chat: true,
+            supports_text: true,
+            supports_embeddings: true,
+            parameters: Default::default(),
+        })
+        .collect();
+    LocalLlmClient::new(LocalLlmConfig {
+        api_url: format!("http://{}/", addr),
+        models,
+        ..Default::default()
+    })
+    .with_reply_mode(LocalReplyMode::Backend)
+}
+
+fn chat_request(model: &str) -> ChatCompletionRequest {
+    let mut request = ChatCompletionRequest {
+        model: model.to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        stream: Some(true),
+        ..Default::default()
+    };
+    request
+        .additional_params
+        .insert("presence_penalty".to_string(), json!(0.5));
+    request
+}
+
+/// Test that every operation is forwarded to the backend and its responses are parsed
+#[tokio::test]
+async fn test_backend_mode_forwards_requests() -> color_eyre::Result<()> {
+    let (addr, received) = start_backend();
+    let client = backend_client(addr);
+
+    let chat = client.chat_completion(chat_request(BACKEND_MODEL)).await?;
+    assert_eq!(chat.choices[0].message.content, "Hi from the backend");
+    assert_eq!(chat.usage.as_ref().map(|u| u.total_tokens), Some(7));
+
+    let text = client
+        .text_completion(TextCompletionRequest {
+            model: BACKEND_MODEL.to_string(),
+            prompt: "Hello".to_string(),
+            ..Default::default()
+        })
+        .await?;
+    assert_eq!(text.choices[0].text, " world");
+    assert_eq!(text.choices[0].finish_reason.as_deref(), Some("length"));
+
+    let embeddings = client
+        .embeddings(EmbeddingRequest {
+            model: BACKEND_MODEL.to_string(),
+            input: vec!["a".to_string(), "b".to_string()],
+            ..Default::default()
+        })
+        .await?;
+    let vectors: Vec<_> = embeddings
+        .data
+        .iter()
+        .map(|d| d.embedding.clone())
+        .collect();
+    assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+
+    let received = received.lock().unwrap().clone();
+    let paths: Vec<_> = received.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(
+        paths,
+        vec!["/v1/chat/completions", "/v1/completions", "/v1/embeddings"]
+    );
+    // Additional parameters are top-level fields, and the whole response is asked for
+    let chat_body = &received[0].1;
+    assert_eq!(chat_body["presence_penalty"], 0.5);
+    assert!(chat_body.get("additional_params").is_none());
+    assert!(chat_body.get("stream").is_none());
+
+    let metrics = client.get_metrics();
+    assert_eq!(metrics.requests_per_minute, 3);
+    assert_eq!(metrics.active_requests, 0);
+    Ok(())
+}
+
+/// Test that a backend error status fails the request
+#[tokio::test]
+async fn test_backend_errors_fail_the_request() {
+    let (addr, _received) = start_backend();
+    let client = backend_client(addr);
+
+    let error = client
+        .chat_completion(chat_request("overloaded-model"))
+        .await
+        .expect_err("the backend is overloaded");
+
+    match error {
+        LlmError::RequestFailed(message) => {
+            assert!(message.contains("503"), "{}", message);
+            assert!(message.contains("overloaded"), "{}", message);
+        }
+        other => panic!("Unexpected error: {:?}", other),
+    }
+    assert_eq!(client.get_metrics().active_requests, 0);
+}
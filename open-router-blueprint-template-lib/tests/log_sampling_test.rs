@@ -0,0 +1,45 @@
+mod common;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::chat_request;
+use common::trace::TraceRecorder;
+use open_router_blueprint_template_lib::{context::OpenRouterContext, jobs::process_llm_request};
+use tracing::Level;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// Test that a sample rate of 0.0 silences success lines but still logs errors
+#[tokio::test]
+async fn test_zero_sample_rate_only_logs_errors() -> color_eyre::Result<()> {
+    let recorder = TraceRecorder::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.blueprint_config.write().await.api.log_sample_rate = 0.0;
+
+    process_llm_request(
+        Context(context.clone()),
+        CallId(1),
+        TangleArg(chat_request("gpt-3.5-turbo", "Hello")),
+    )
+    .await?;
+    let result = process_llm_request(
+        Context(context),
+        CallId(2),
+        TangleArg(chat_request("unknown-model", "Hello")),
+    )
+    .await;
+    assert!(result.is_err());
+
+    assert!(!recorder
+        .messages_at(Level::INFO)
+        .iter()
+        .any(|message| message.contains("processed successfully")));
+    let errors = recorder.messages_at(Level::ERROR);
+    assert_eq!(errors.len(), 1);
+    assert!(errors[0].contains("LLM request failed"));
+
+    Ok(())
+}
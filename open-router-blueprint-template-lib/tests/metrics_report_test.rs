@@ -0,0 +1,50 @@
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::testing::utils::setup_log;
+use open_router_blueprint_template_lib::{context::OpenRouterContext, jobs::report_metrics};
+
+/// Test that metrics reports within the reporting interval are coalesced
+#[tokio::test]
+async fn test_report_metrics_coalesces_rapid_calls() -> color_eyre::Result<()> {
+    setup_log();
+
+    let env = BlueprintEnvironment::default();
+    let context = OpenRouterContext::new(env).await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .api
+        .metrics_interval_seconds = 60;
+
+    // The first report refreshes and records the metrics
+    let first = report_metrics(Context(context.clone())).await?;
+    let (first_reported_at, _) = context
+        .last_metrics_report
+        .read()
+        .await
+        .clone()
+        .expect("first report should be recorded");
+
+    // Metrics change before the interval elapses
+    context.metrics.write().await.active_requests = first.0.active_requests + 7;
+
+    // The second report reuses the last report instead of refreshing
+    let second = report_metrics(Context(context.clone())).await?;
+    let (second_reported_at, _) = context.last_metrics_report.read().await.clone().unwrap();
+    assert_eq!(second_reported_at, first_reported_at);
+    assert_eq!(second.0.active_requests, first.0.active_requests);
+
+    // Once the interval is over, reports are refreshed again
+    context
+        .blueprint_config
+        .write()
+        .await
+        .api
+        .metrics_interval_seconds = 0;
+    report_metrics(Context(context.clone())).await?;
+    let (third_reported_at, _) = context.last_metrics_report.read().await.clone().unwrap();
+    assert!(third_reported_at > first_reported_at);
+
+    Ok(())
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_request, model_info, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext, jobs::process_llm_request, llm::LlmResponse,
+};
+
+const ALIAS: &str = "meta-llama/Llama-3-8b";
+const LOCAL_MODEL: &str = "llama3";
+
+/// A context aliasing `ALIAS` to `LOCAL_MODEL`, with a node serving only `LOCAL_MODEL`
+async fn context_with_alias(
+    echo_requested_model: bool,
+) -> color_eyre::Result<(OpenRouterContext, Arc<MockBackend>)> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    {
+        let mut config = context.blueprint_config.write().await;
+        config
+            .llm
+            .model_aliases
+            .insert(ALIAS.to_string(), LOCAL_MODEL.to_string());
+        config.llm.echo_requested_model = echo_requested_model;
+    }
+    let client = Arc::new(MockBackend::new().serving_model(model_info(LOCAL_MODEL, 8192)));
+    context
+        .add_llm_node("llama".to_string(), client.clone())
+        .await?;
+    Ok((context, client))
+}
+
+fn response_model(response: LlmResponse) -> String {
+    match response {
+        LlmResponse::ChatCompletion(response) => response.model,
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+}
+
+/// Test that a request for an alias is served by the node serving the aliased model
+#[tokio::test]
+async fn test_alias_resolves_to_serving_node() -> color_eyre::Result<()> {
+    let (context, client) = context_with_alias(false).await?;
+
+    let result = process_llm_request(
+        Context(context),
+        CallId(1),
+        TangleArg(chat_request(ALIAS, "Hello")),
+    )
+    .await?;
+
+    assert_eq!(client.received_models(), vec![LOCAL_MODEL.to_string()]);
+    assert_eq!(response_model(result.0), LOCAL_MODEL);
+    Ok(())
+}
+
+/// Test that the requested alias is reported with `echo_requested_model`
+#[tokio::test]
+async fn test_alias_is_echoed_when_enabled() -> color_eyre::Result<()> {
+    let (context, client) = context_with_alias(true).await?;
+
+    let result = process_llm_request(
+        Context(context),
+        CallId(1),
+        TangleArg(chat_request(ALIAS, "Hello")),
+    )
+    .await?;
+
+    assert_eq!(client.received_models().len(), 1);
+    assert_eq!(response_model(result.0), ALIAS);
+    Ok(())
+}
+
+/// Test that a model without an alias is dispatched unchanged
+#[tokio::test]
+async fn test_unknown_alias_falls_through() -> color_eyre::Result<()> {
+    let (context, client) = context_with_alias(true).await?;
+
+    // No node serves the model, so the default client answers it under its own name
+    let result = process_llm_request(
+        Context(context),
+        CallId(1),
+        TangleArg(chat_request("meta-llama/Llama-3-70b", "Hello")),
+    )
+    .await?;
+
+    assert!(client.received_models().is_empty());
+    assert_eq!(response_model(result.0), "meta-llama/Llama-3-70b");
+    Ok(())
+}
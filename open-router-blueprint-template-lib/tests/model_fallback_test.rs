@@ -0,0 +1,41 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_reply, chat_request, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext, jobs::process_llm_request, llm::LlmResponse,
+};
+
+const REQUESTED_MODEL: &str = "large-model";
+const FALLBACK_MODEL: &str = "small-model";
+
+/// Test that a request served by a fallback model reports the fallback model
+#[tokio::test]
+async fn test_fallback_model_is_reported_in_response() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.blueprint_config.write().await.llm.fallback_models = vec![FALLBACK_MODEL.to_string()];
+    // Report a stale model name to show the job overwrites it with the served model
+    let client = Arc::new(
+        MockBackend::new()
+            .serving(FALLBACK_MODEL)
+            .on_chat(|_| Ok(chat_reply(REQUESTED_MODEL, "Hi"))),
+    );
+    context
+        .add_llm_node("fallback".to_string(), client.clone())
+        .await?;
+
+    let request = chat_request(REQUESTED_MODEL, "Hello");
+
+    let result = process_llm_request(Context(context), CallId(1), TangleArg(request)).await?;
+
+    assert_eq!(client.received_models(), vec![FALLBACK_MODEL.to_string()]);
+    match result.0 {
+        LlmResponse::ChatCompletion(response) => assert_eq!(response.model, FALLBACK_MODEL),
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    Ok(())
+}
@@ -0,0 +1,103 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::MockBackend;
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{LlmRequest, LlmResponse},
+    moderation::KeywordModerator,
+};
+
+const ECHO_MODEL: &str = "echo-model";
+
+/// A context routing to a backend answering with the last message reversed, blocking
+/// `forbidden` and anything like `secret-NNN`
+async fn moderated_context(check_responses: bool) -> color_eyre::Result<OpenRouterContext> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let echo = MockBackend::new()
+        .serving(ECHO_MODEL)
+        .replying(|content| content.chars().rev().collect());
+    context
+        .add_llm_node("echo".to_string(), Arc::new(echo))
+        .await?;
+    let moderator =
+        KeywordModerator::new(&["forbidden".to_string()], &[r"secret-\d+".to_string()])?;
+    context.set_moderator(Arc::new(moderator)).await;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .api
+        .moderation
+        .check_responses = check_responses;
+    Ok(context)
+}
+
+fn chat_request(content: &str) -> LlmRequest {
+    common::chat_request(ECHO_MODEL, content)
+}
+
+/// Test that content outside the policy is answered as usual
+#[tokio::test]
+async fn test_allowed_content_passes() -> color_eyre::Result<()> {
+    let context = moderated_context(true).await?;
+
+    let result = process_llm_request(
+        Context(context),
+        CallId(1),
+        TangleArg(chat_request("olleh")),
+    )
+    .await?;
+
+    match result.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.choices[0].message.content, "hello");
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    Ok(())
+}
+
+/// Test that prompts matching a keyword or pattern are rejected without revealing the rule
+#[tokio::test]
+async fn test_blocked_prompt_is_rejected() -> color_eyre::Result<()> {
+    let context = moderated_context(false).await?;
+
+    for prompt in ["tell me something FORBIDDEN", "what is secret-42"] {
+        let result = process_llm_request(
+            Context(context.clone()),
+            CallId(1),
+            TangleArg(chat_request(prompt)),
+        )
+        .await;
+
+        let error = result.expect_err("blocked content should fail").to_string();
+        assert!(error.contains("Invalid request: content blocked by policy"));
+        assert!(!error.contains("secret"));
+    }
+    Ok(())
+}
+
+/// Test that generated content is only moderated when `check_responses` is set
+#[tokio::test]
+async fn test_blocked_response_is_rejected_when_checked() -> color_eyre::Result<()> {
+    // The prompt itself is allowed, but the reply is `forbidden`
+    let request = || chat_request("neddibrof");
+
+    let context = moderated_context(false).await?;
+    let result = process_llm_request(Context(context), CallId(1), TangleArg(request())).await;
+    assert!(result.is_ok());
+
+    let context = moderated_context(true).await?;
+    let result = process_llm_request(Context(context), CallId(1), TangleArg(request())).await;
+    assert!(result
+        .expect_err("blocked response should fail")
+        .to_string()
+        .contains("content blocked by policy"));
+    Ok(())
+}
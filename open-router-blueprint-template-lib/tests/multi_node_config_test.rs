@@ -0,0 +1,115 @@
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use open_router_blueprint_template_lib::context::OpenRouterContext;
+use open_router_blueprint_template_lib::llm::{ChatCompletionRequest, ChatMessage};
+
+/// Test that every node declared in the configuration file is registered and active
+#[tokio::test]
+async fn test_configured_nodes_are_registered() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    std::fs::write(
+        data_dir.path().join("config.json"),
+        r#"{
+            "nodes": [
+                { "id": "gpu-1", "api_url": "http://10.0.0.1:8000", "weight": 3, "tags": ["a100"] },
+                { "id": "gpu-2", "provider": "local", "api_url": "http://10.0.0.2:8000" },
+                {
+                    "id": "cpu-1",
+                    "api_url": "http://10.0.0.3:8000",
+                    "models": [{
+                        "id": "small-model",
+                        "name": "Small Model",
+                        "max_context_length": 2048,
+                        "supports_chat": true,
+                        "supports_text": true,
+                        "supports_embeddings": false,
+                        "parameters": {}
+                    }]
+                }
+            ]
+        }"#,
+    )?;
+    let mut env = BlueprintEnvironment::default();
+    env.data_dir = Some(data_dir.path().to_path_buf());
+    let context = OpenRouterContext::new(env).await?;
+
+    let mut ids: Vec<String> = context
+        .load_balancer
+        .get_active_nodes()
+        .await
+        .into_iter()
+        .map(|node| node.id)
+        .collect();
+    ids.sort();
+    assert_eq!(ids, ["cpu-1", "default", "gpu-1", "gpu-2"]);
+
+    // Nodes without models serve the models of the `llm` section
+    let node = context.load_balancer.get_node("gpu-1").await.unwrap();
+    assert!(node
+        .client
+        .get_supported_models()
+        .iter()
+        .any(|model| model.id == "gpt-3.5-turbo"));
+
+    let node = context
+        .load_balancer
+        .select_node_for_model("small-model")
+        .await
+        .unwrap();
+    assert_eq!(node.id, "cpu-1");
+    Ok(())
+}
+
+/// Test that declared nodes forward requests to their backend instead of echoing them
+#[tokio::test]
+async fn test_configured_nodes_call_their_backend() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    // Nothing listens on the discard port
+    std::fs::write(
+        data_dir.path().join("config.json"),
+        r#"{
+            "nodes": [{ "id": "gpu-1", "api_url": "http://127.0.0.1:9" }]
+        }"#,
+    )?;
+    let mut env = BlueprintEnvironment::default();
+    env.data_dir = Some(data_dir.path().to_path_buf());
+    let context = OpenRouterContext::new(env).await?;
+
+    let request = ChatCompletionRequest {
+        model: "gpt-3.5-turbo".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    };
+    let node = context.load_balancer.get_node("gpu-1").await.unwrap();
+    assert!(node.client.chat_completion(request.clone()).await.is_err());
+
+    // The default client still answers as configured by `llm.local_reply_mode`
+    let node = context.load_balancer.get_node("default").await.unwrap();
+    assert!(node.client.chat_completion(request).await.is_ok());
+    Ok(())
+}
+
+/// Test that nodes of providers the template cannot build are skipped
+#[tokio::test]
+async fn test_nodes_of_unknown_providers_are_skipped() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    std::fs::write(
+        data_dir.path().join("config.json"),
+        r#"{
+            "nodes": [
+                { "id": "vllm-1", "provider": "vllm", "api_url": "http://10.0.0.1:8000" },
+                { "id": "local-1", "api_url": "http://10.0.0.2:8000" }
+            ]
+        }"#,
+    )?;
+    let mut env = BlueprintEnvironment::default();
+    env.data_dir = Some(data_dir.path().to_path_buf());
+    let context = OpenRouterContext::new(env).await?;
+
+    assert!(context.load_balancer.get_node("local-1").await.is_some());
+    assert!(context.load_balancer.get_node("vllm-1").await.is_none());
+    Ok(())
+}
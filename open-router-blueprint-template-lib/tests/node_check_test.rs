@@ -0,0 +1,65 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use common::MockBackend;
+use open_router_blueprint_template_lib::{context::OpenRouterContext, llm::LlmError};
+
+/// A client for a backend at a URL nothing listens on
+fn unreachable_client() -> Arc<MockBackend> {
+    Arc::new(
+        MockBackend::new()
+            .unhealthy()
+            .failing(|| LlmError::RequestFailed("connection refused".to_string())),
+    )
+}
+
+async fn context_requiring_healthy_nodes(require: bool) -> color_eyre::Result<OpenRouterContext> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .require_healthy_on_add = require;
+    Ok(context)
+}
+
+/// Test that an unreachable node is still added by default, only logging a warning
+#[tokio::test]
+async fn test_unhealthy_node_is_added_with_warning() -> color_eyre::Result<()> {
+    let context = context_requiring_healthy_nodes(false).await?;
+
+    context
+        .add_llm_node("unreachable".to_string(), unreachable_client())
+        .await?;
+
+    assert!(context
+        .load_balancer
+        .get_node("unreachable")
+        .await
+        .is_some());
+    Ok(())
+}
+
+/// Test that an unreachable node is rejected when healthy nodes are required
+#[tokio::test]
+async fn test_unhealthy_node_is_rejected_when_required() -> color_eyre::Result<()> {
+    let context = context_requiring_healthy_nodes(true).await?;
+
+    let result = context
+        .add_llm_node("unreachable".to_string(), unreachable_client())
+        .await;
+
+    assert!(result
+        .expect_err("unreachable node should be rejected")
+        .to_string()
+        .contains("connection refused"));
+    assert!(context
+        .load_balancer
+        .get_node("unreachable")
+        .await
+        .is_none());
+    Ok(())
+}
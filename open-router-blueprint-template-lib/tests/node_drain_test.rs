@@ -0,0 +1,103 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_request, MockBackend};
+use open_router_blueprint_template_lib::{
+    auth::Principal,
+    context::OpenRouterContext,
+    jobs::{process_llm_request, stream_chat_completion_for_principal},
+    llm::LlmRequest,
+    load_balancer::DrainOutcome,
+};
+
+const MODEL: &str = "drained-model";
+
+/// Test that draining a node waits for the requests dispatched to it
+///
+/// The backend reports no active requests, so only the requests counted by the dispatch
+/// hold the drain up.
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_drain_waits_for_dispatched_requests() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let client = Arc::new(MockBackend::new().serving(MODEL).gated());
+    context
+        .add_llm_node("gated".to_string(), client.clone())
+        .await?;
+
+    let request = {
+        let context = context.clone();
+        tokio::spawn(async move {
+            process_llm_request(
+                Context(context),
+                CallId(1),
+                TangleArg(chat_request(MODEL, "Hello")),
+            )
+            .await
+        })
+    };
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while client.request_count() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await?;
+
+    let drain = {
+        let context = context.clone();
+        tokio::spawn(async move {
+            context
+                .drain_llm_node("gated", Duration::from_secs(5))
+                .await
+        })
+    };
+    tokio::time::sleep(Duration::from_millis(100)).await;
+    assert!(!drain.is_finished());
+
+    client.release(1);
+    request.await??;
+    assert_eq!(drain.await?, DrainOutcome::Drained);
+    assert!(context.load_balancer.get_node("gated").await.is_none());
+    Ok(())
+}
+
+/// Test that a stream waiting for a dispatch slot does not hold up a drain of its node
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_drain_ignores_queued_streams() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let client = Arc::new(MockBackend::new().serving(MODEL));
+    context.add_llm_node("queued".to_string(), client).await?;
+    context.request_queue.resize(1);
+    let slot = context.request_queue.acquire().await;
+
+    let LlmRequest::ChatCompletion(request) = chat_request(MODEL, "Hello") else {
+        unreachable!("chat_request builds a chat completion");
+    };
+    let stream = {
+        let context = context.clone();
+        tokio::spawn(async move {
+            stream_chat_completion_for_principal(&context, &Principal::new("tenant"), request)
+                .await
+                .map(|_| ())
+        })
+    };
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while context.request_queue.queued_requests() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await?;
+
+    let outcome = context
+        .drain_llm_node("queued", Duration::from_secs(5))
+        .await;
+    assert_eq!(outcome, DrainOutcome::Drained);
+
+    drop(slot);
+    stream.abort();
+    Ok(())
+}
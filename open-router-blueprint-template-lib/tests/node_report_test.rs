@@ -0,0 +1,70 @@
+mod common;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use common::MockBackend;
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext, jobs::report_node, llm::LlmCapabilities,
+};
+
+/// A backend serving `models` with `capabilities`
+fn static_client(models: &[&str], capabilities: LlmCapabilities) -> Arc<MockBackend> {
+    let backend = models
+        .iter()
+        .fold(MockBackend::new(), |backend, id| backend.serving(id));
+    Arc::new(backend.with_capabilities(capabilities))
+}
+
+/// Test that the node report lists every served model once and combines capabilities
+#[tokio::test]
+async fn test_report_node_includes_models_and_capabilities() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    // Only the added nodes are reported
+    context.load_balancer.remove_node("default").await;
+    context
+        .add_llm_node(
+            "gpu-a".to_string(),
+            static_client(
+                &["llama3", "mistral"],
+                LlmCapabilities {
+                    supports_streaming: true,
+                    max_concurrent_requests: 4,
+                    supports_batching: false,
+                    features: HashMap::from([("tools".to_string(), false)]),
+                },
+            ),
+        )
+        .await?;
+    context
+        .add_llm_node(
+            "gpu-b".to_string(),
+            static_client(
+                &["mistral", "qwen"],
+                LlmCapabilities {
+                    supports_streaming: false,
+                    max_concurrent_requests: 2,
+                    supports_batching: true,
+                    features: HashMap::from([("tools".to_string(), true)]),
+                },
+            ),
+        )
+        .await?;
+
+    let report = report_node(Context(context.clone())).await?.0;
+
+    let ids: Vec<_> = report.models.iter().map(|m| m.id.as_str()).collect();
+    assert_eq!(ids, vec!["llama3", "mistral", "qwen"]);
+    assert!(report.capabilities.supports_streaming);
+    assert!(report.capabilities.supports_batching);
+    assert_eq!(report.capabilities.max_concurrent_requests, 6);
+    assert_eq!(report.capabilities.features.get("tools"), Some(&true));
+    assert_eq!(report.version, env!("CARGO_PKG_VERSION"));
+
+    // The metrics are those of the metrics report
+    let metrics = context.metrics.read().await.clone();
+    assert_eq!(report.metrics.last_updated, metrics.last_updated);
+    Ok(())
+}
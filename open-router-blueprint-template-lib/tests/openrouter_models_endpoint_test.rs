@@ -1,10 +1,13 @@
+mod common;
+
+use std::collections::HashMap;
 use std::net::SocketAddr;
 use std::sync::Arc;
-use std::time::{SystemTime, UNIX_EPOCH};
 
 use blueprint_sdk::runner::config::BlueprintEnvironment;
 use blueprint_sdk::testing::utils::setup_log;
 use color_eyre::Result;
+use common::{model_info, MockBackend};
 use hyper::service::{make_service_fn, service_fn};
 use hyper::{Body, Method, Request, Response, Server, StatusCode};
 use open_router_blueprint_template_lib::context::OpenRouterContext;
@@ -12,7 +15,7 @@ use open_router_blueprint_template_lib::llm::ModelInfo;
 use serde::{Deserialize, Serialize};
 use tokio::sync::oneshot;
 
-// OpenRouter model format
+// OpenRouter model format, as parsed by clients
 #[derive(Debug, Serialize, Deserialize)]
 struct OpenRouterModel {
     id: String,
@@ -24,7 +27,8 @@ struct OpenRouterModel {
     #[serde(skip_serializing_if = "Option::is_none")]
     max_completion_tokens: Option<usize>,
     quantization: String,
-    pricing: OpenRouterPricing,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pricing: Option<OpenRouterPricing>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -40,91 +44,40 @@ struct OpenRouterModelsResponse {
     data: Vec<OpenRouterModel>,
 }
 
-// Convert internal ModelInfo to OpenRouter format
-fn convert_to_openrouter_model(model: &ModelInfo) -> OpenRouterModel {
-    // Get current timestamp in seconds since epoch
-    let now = SystemTime::now()
-        .duration_since(UNIX_EPOCH)
-        .unwrap_or_default()
-        .as_secs();
-
-    // Extract max_completion_tokens if available in parameters
-    let max_completion_tokens = model
-        .parameters
-        .get("max_completion_tokens")
-        .and_then(|v| v.parse::<usize>().ok());
-
-    // Extract description if available in parameters
-    let description = model.parameters.get("description").cloned();
-
-    OpenRouterModel {
-        id: model.id.clone(),
-        name: model.name.clone(),
-        created: now,
-        description,
-        context_length: model.max_context_length,
-        max_completion_tokens,
-        // Default quantization to "none" if not specified
-        quantization: model
-            .parameters
-            .get("quantization")
-            .cloned()
-            .unwrap_or_else(|| "none".to_string()),
-        pricing: OpenRouterPricing {
-            prompt: model
-                .parameters
-                .get("pricing_prompt")
-                .cloned()
-                .unwrap_or_else(|| "0.000001".to_string()),
-            completion: model
-                .parameters
-                .get("pricing_completion")
-                .cloned()
-                .unwrap_or_else(|| "0.000002".to_string()),
-            image: model
-                .parameters
-                .get("pricing_image")
-                .cloned()
-                .unwrap_or_else(|| "0".to_string()),
-            request: model
-                .parameters
-                .get("pricing_request")
-                .cloned()
-                .unwrap_or_else(|| "0".to_string()),
-        },
-    }
+fn json_response<T: Serialize>(status: StatusCode, body: &T) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header("Content-Type", "application/json")
+        .body(Body::from(serde_json::to_string(body).unwrap_or_default()))
+        .unwrap()
 }
 
-// HTTP handler for the models endpoint
+// HTTP handler for the models endpoints
 async fn handle_request(
     req: Request<Body>,
     context: Arc<OpenRouterContext>,
 ) -> Result<Response<Body>, hyper::Error> {
     match (req.method(), req.uri().path()) {
         (&Method::GET, "/v1/models") => {
-            // Get models from the LLM client
-            let models = context.llm_client.get_supported_models();
-
-            // Convert to OpenRouter format
-            let openrouter_models = models
-                .iter()
-                .map(convert_to_openrouter_model)
-                .collect::<Vec<_>>();
-
-            // Create response
-            let response = OpenRouterModelsResponse {
-                data: openrouter_models,
-            };
-
-            // Serialize to JSON
-            let json = serde_json::to_string(&response).unwrap_or_default();
-
-            // Return response
-            Ok(Response::builder()
-                .status(StatusCode::OK)
-                .header("Content-Type", "application/json")
-                .body(Body::from(json))
-                .unwrap())
+            // List the models of all active nodes in OpenRouter format
+            let response = context.model_catalog().await.to_response();
+            Ok(json_response(StatusCode::OK, &response))
+        }
+        (&Method::GET, path) if path.starts_with("/v1/models/") => {
+            let id = &path["/v1/models/".len()..];
+            match context.model_catalog().await.get(id) {
+                Some(model) => Ok(json_response(StatusCode::OK, model)),
+                None => Ok(json_response(
+                    StatusCode::NOT_FOUND,
+                    &serde_json::json!({
+                        "error": {
+                            "message": format!("The model '{}' does not exist", id),
+                            "type": "invalid_request_error",
+                            "code": "model_not_found"
+                        }
+                    }),
+                )),
+            }
         }
         // Return 404 for any other path
         _ => {
@@ -143,8 +96,21 @@ async fn test_openrouter_models_endpoint() -> Result<()> {
     // Create a mock environment
     let env = BlueprintEnvironment::default();
 
-    // Create the context
+    // Create the context, with a node serving a priced model next to the unpriced defaults
     let context = Arc::new(OpenRouterContext::new(env).await?);
+    let priced_model = ModelInfo {
+        parameters: HashMap::from([
+            ("pricing_prompt".to_string(), "0.000001".to_string()),
+            ("pricing_completion".to_string(), "0.000002".to_string()),
+        ]),
+        ..model_info("priced-model", 4096)
+    };
+    context
+        .add_llm_node(
+            "priced".to_string(),
+            Arc::new(MockBackend::new().serving_model(priced_model)),
+        )
+        .await?;
 
     // Set up the HTTP server
     let addr = SocketAddr::from(([127, 0, 0, 1], 0)); // Use port 0 to let the OS assign a free port
@@ -178,7 +144,7 @@ async fn test_openrouter_models_endpoint() -> Result<()> {
     // Make a request to the models endpoint
     let client = reqwest::Client::new();
     let response = client
-        .get(&format!("http://{}/v1/models", actual_addr))
+        .get(format!("http://{}/v1/models", actual_addr))
         .send()
         .await?;
 
@@ -204,25 +170,61 @@ async fn test_openrouter_models_endpoint() -> Result<()> {
             "Context length should be positive"
         );
 
-        // Verify pricing fields
+        // Verify pricing fields, listed only for models that advertise pricing
+        let Some(pricing) = &model.pricing else {
+            continue;
+        };
         assert!(
-            !model.pricing.prompt.is_empty(),
+            !pricing.prompt.is_empty(),
             "Prompt pricing should not be empty"
         );
         assert!(
-            !model.pricing.completion.is_empty(),
+            !pricing.completion.is_empty(),
             "Completion pricing should not be empty"
         );
         assert!(
-            !model.pricing.image.is_empty(),
+            !pricing.image.is_empty(),
             "Image pricing should not be empty"
         );
         assert!(
-            !model.pricing.request.is_empty(),
+            !pricing.request.is_empty(),
             "Request pricing should not be empty"
         );
     }
 
+    // Verify that advertised prices are listed as is, and missing prices are not listed as free
+    let pricing_of = |id: &str| {
+        models_response
+            .data
+            .iter()
+            .find(|model| model.id == id)
+            .map(|model| model.pricing.as_ref().map(|p| p.prompt.clone()))
+    };
+    assert_eq!(
+        pricing_of("priced-model"),
+        Some(Some("0.000001".to_string()))
+    );
+    assert_eq!(pricing_of("gpt-3.5-turbo"), Some(None));
+
+    // Fetch a single model by id
+    let response = client
+        .get(format!("http://{}/v1/models/priced-model", actual_addr))
+        .send()
+        .await?;
+    assert_eq!(response.status(), reqwest::StatusCode::OK);
+    let model = response.json::<OpenRouterModel>().await?;
+    assert_eq!(model.id, "priced-model");
+    assert_eq!(model.pricing.unwrap().completion, "0.000002");
+
+    // Unknown models are not found
+    let response = client
+        .get(format!("http://{}/v1/models/unknown-model", actual_addr))
+        .send()
+        .await?;
+    assert_eq!(response.status(), reqwest::StatusCode::NOT_FOUND);
+    let error = response.json::<serde_json::Value>().await?;
+    assert_eq!(error["error"]["code"], "model_not_found");
+
     // Shut down the server
     let _ = tx.send(());
 
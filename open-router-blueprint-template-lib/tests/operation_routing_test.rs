@@ -0,0 +1,100 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_request, model_info, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{
+        EmbeddingData, EmbeddingRequest, EmbeddingResponse, LlmError, LlmRequest, LlmResponse,
+        ModelInfo,
+    },
+};
+
+const SHARED_MODEL: &str = "shared-model";
+
+/// A backend serving `SHARED_MODEL` for chat, failing embeddings
+fn chat_node() -> Arc<MockBackend> {
+    Arc::new(
+        MockBackend::new()
+            .serving(SHARED_MODEL)
+            .replying(|_| "chat".to_string()),
+    )
+}
+
+/// A backend serving `SHARED_MODEL` for embeddings, failing chat
+fn embedding_node() -> Arc<MockBackend> {
+    let model = ModelInfo {
+        supports_chat: false,
+        supports_text: false,
+        supports_embeddings: true,
+        ..model_info(SHARED_MODEL, 4096)
+    };
+    Arc::new(
+        MockBackend::new()
+            .serving_model(model)
+            .on_chat(|_| Err(LlmError::NotImplemented("chat".to_string())))
+            .on_embeddings(|_| {
+                Ok(EmbeddingResponse {
+                    object: "list".to_string(),
+                    model: "embedding".to_string(),
+                    data: vec![EmbeddingData {
+                        index: 0,
+                        embedding: vec![0.0; 4],
+                    }],
+                    usage: None,
+                    correlation_id: None,
+                })
+            }),
+    )
+}
+
+/// Test that chat and embedding requests for the same model reach the nodes serving them
+#[tokio::test]
+async fn test_requests_are_routed_by_operation() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .add_llm_node("chat".to_string(), chat_node())
+        .await?;
+    context
+        .add_llm_node("embedding".to_string(), embedding_node())
+        .await?;
+
+    // Enough calls that round-robin alone would hit the wrong node
+    for call_id in 0..4 {
+        let request = LlmRequest::Embedding(EmbeddingRequest {
+            model: SHARED_MODEL.to_string(),
+            input: vec!["Hello".to_string()],
+            ..Default::default()
+        });
+        let response = process_llm_request(
+            Context(context.clone()),
+            CallId(call_id),
+            TangleArg(request),
+        )
+        .await?;
+        match response.0 {
+            LlmResponse::Embedding(response) => assert_eq!(response.model, "embedding"),
+            other => panic!("Unexpected response type: {:?}", other),
+        }
+
+        let request = chat_request(SHARED_MODEL, "Hello");
+        let response = process_llm_request(
+            Context(context.clone()),
+            CallId(call_id),
+            TangleArg(request),
+        )
+        .await?;
+        match response.0 {
+            LlmResponse::ChatCompletion(response) => {
+                assert_eq!(response.choices[0].message.content, "chat")
+            }
+            other => panic!("Unexpected response type: {:?}", other),
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,75 @@
+#![cfg(feature = "otel")]
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{ChatCompletionRequest, ChatMessage, LlmRequest},
+    telemetry::layer_for_provider,
+};
+use opentelemetry_sdk::export::trace::SpanData;
+use opentelemetry_sdk::testing::trace::InMemorySpanExporter;
+use opentelemetry_sdk::trace::TracerProvider;
+use tracing_subscriber::layer::SubscriberExt;
+
+/// The value of the attribute `key` of `span`, if it has one
+fn attribute(span: &SpanData, key: &str) -> Option<String> {
+    span.attributes
+        .iter()
+        .find(|kv| kv.key.as_str() == key)
+        .map(|kv| kv.value.as_str().into_owned())
+}
+
+/// Test that processing a request exports its spans with their model and node attributes
+#[tokio::test]
+async fn test_request_spans_are_exported() -> color_eyre::Result<()> {
+    let exporter = InMemorySpanExporter::default();
+    let provider = TracerProvider::builder()
+        .with_simple_exporter(exporter.clone())
+        .build();
+    let _guard = tracing::subscriber::set_default(
+        tracing_subscriber::registry().with(layer_for_provider(&provider)),
+    );
+
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let request = LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: "gpt-3.5-turbo".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Hello".to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    });
+    process_llm_request(Context(context), CallId(7), TangleArg(request)).await?;
+
+    let spans = exporter.get_finished_spans()?;
+    let span = |name: &str| {
+        spans
+            .iter()
+            .find(|span| span.name == name)
+            .unwrap_or_else(|| panic!("No {} span was exported", name))
+    };
+    let request_span = span("llm_request");
+    let selection_span = span("node_selection");
+    let backend_span = span("backend_call");
+
+    assert_eq!(attribute(request_span, "call_id").as_deref(), Some("7"));
+    assert_eq!(
+        selection_span.parent_span_id,
+        request_span.span_context.span_id()
+    );
+    assert_eq!(
+        backend_span.parent_span_id,
+        request_span.span_context.span_id()
+    );
+    assert_eq!(
+        attribute(backend_span, "model").as_deref(),
+        Some("gpt-3.5-turbo")
+    );
+    let node = attribute(selection_span, "node").expect("the selected node is recorded");
+    assert_eq!(attribute(backend_span, "node"), Some(node));
+    Ok(())
+}
@@ -0,0 +1,86 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::MockBackend;
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{LlmRequest, LlmResponse},
+};
+
+const SHARED_MODEL: &str = "llama3";
+
+/// A backend of `provider` serving `SHARED_MODEL`
+fn provider_node(provider: &'static str) -> Arc<MockBackend> {
+    Arc::new(
+        MockBackend::new()
+            .serving(SHARED_MODEL)
+            .with_provider(provider)
+            .replying(move |_| format!("Hi from {}", provider)),
+    )
+}
+
+fn chat_request(model: &str) -> LlmRequest {
+    common::chat_request(model, "Hello")
+}
+
+/// Test that `model@vllm` requests only reach vLLM nodes, with the suffix stripped
+#[tokio::test]
+async fn test_provider_suffix_pins_node_selection() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let vllm = provider_node("vllm");
+    let ollama = provider_node("ollama");
+    context
+        .add_llm_node("a-ollama".to_string(), ollama.clone())
+        .await?;
+    context
+        .add_llm_node("b-vllm".to_string(), vllm.clone())
+        .await?;
+
+    // Round-robin would alternate between the two nodes without the pin
+    for call_id in 0..4 {
+        let result = process_llm_request(
+            Context(context.clone()),
+            CallId(call_id),
+            TangleArg(chat_request("llama3@vllm")),
+        )
+        .await?;
+        match result.0 {
+            LlmResponse::ChatCompletion(response) => {
+                assert_eq!(response.choices[0].message.content, "Hi from vllm");
+                assert_eq!(response.model, SHARED_MODEL);
+            }
+            other => panic!("Unexpected response type: {:?}", other),
+        }
+    }
+
+    assert_eq!(vllm.received_models(), vec![SHARED_MODEL; 4]);
+    assert!(ollama.received_models().is_empty());
+    Ok(())
+}
+
+/// Test that a request pinned to a provider without a node for the model is rejected
+#[tokio::test]
+async fn test_unserved_provider_is_rejected() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let ollama = provider_node("ollama");
+    context
+        .add_llm_node("ollama".to_string(), ollama.clone())
+        .await?;
+
+    let error = process_llm_request(
+        Context(context),
+        CallId(1),
+        TangleArg(chat_request("llama3@vllm")),
+    )
+    .await
+    .expect_err("no vLLM node serves the model");
+
+    assert!(error.to_string().contains("llama3@vllm"), "{}", error);
+    assert!(ollama.received_models().is_empty());
+    Ok(())
+}
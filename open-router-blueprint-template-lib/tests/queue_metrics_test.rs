@@ -0,0 +1,69 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_request, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::{process_llm_request, report_metrics},
+};
+
+const SLOW_MODEL: &str = "slow-model";
+
+/// Test that requests beyond the concurrency limit are reported as queued
+#[tokio::test(flavor = "multi_thread", worker_threads = 2)]
+async fn test_saturated_node_reports_queued_requests() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    std::fs::write(
+        data_dir.path().join("config.json"),
+        r#"{ "llm": { "max_concurrent_requests": 1 } }"#,
+    )?;
+    let mut env = BlueprintEnvironment::default();
+    env.data_dir = Some(data_dir.path().to_path_buf());
+    let context = OpenRouterContext::new(env).await?;
+
+    // A backend that holds every request until the test releases it
+    let client = Arc::new(MockBackend::new().serving(SLOW_MODEL).gated());
+    context
+        .add_llm_node("gated".to_string(), client.clone())
+        .await?;
+
+    let requests: Vec<_> = (0..3)
+        .map(|i| {
+            let context = context.clone();
+            tokio::spawn(async move {
+                process_llm_request(
+                    Context(context),
+                    CallId(i),
+                    TangleArg(chat_request(SLOW_MODEL, "Hello")),
+                )
+                .await
+            })
+        })
+        .collect();
+
+    // One request occupies the only slot, the other two wait behind it
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while context.request_queue.queued_requests() < 2 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+    })
+    .await?;
+    tokio::time::sleep(Duration::from_millis(20)).await;
+
+    let metrics = report_metrics(Context(context.clone())).await?.0;
+    assert_eq!(metrics.queued_requests, 2);
+
+    client.release(3);
+    for request in requests {
+        request.await??;
+    }
+
+    assert_eq!(context.request_queue.queued_requests(), 0);
+    assert!(context.request_queue.avg_wait_ms() > 0);
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{LlmRequest, TextCompletionRequest},
+    rate_limit::RateLimiter,
+};
+
+const MAX_REQUESTS_PER_MINUTE: u32 = 60;
+
+/// Models served by the default context
+const MODEL: &str = "gpt-3.5-turbo";
+const OTHER_MODEL: &str = "text-davinci-003";
+
+async fn send_for(
+    context: &OpenRouterContext,
+    call_id: u64,
+    model: &str,
+) -> Result<(), blueprint_sdk::Error> {
+    let request = LlmRequest::TextCompletion(TextCompletionRequest {
+        model: model.to_string(),
+        prompt: "Hello".to_string(),
+        ..Default::default()
+    });
+    process_llm_request(Context(context.clone()), CallId(call_id), TangleArg(request))
+        .await
+        .map(|_| ())
+}
+
+async fn send(context: &OpenRouterContext, call_id: u64) -> Result<(), blueprint_sdk::Error> {
+    send_for(context, call_id, MODEL).await
+}
+
+/// Test that the request over the limit is rejected, and admitted once a token is refilled
+#[tokio::test]
+async fn test_request_over_limit_is_rejected_until_refilled() -> color_eyre::Result<()> {
+    let mut context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.rate_limiter = Arc::new(RateLimiter::new(MAX_REQUESTS_PER_MINUTE));
+
+    for call_id in 0..u64::from(MAX_REQUESTS_PER_MINUTE) {
+        send(&context, call_id).await?;
+    }
+    let rejected = send(&context, 100).await;
+    let error = rejected.expect_err("the request over the limit must be rejected");
+    assert!(error.to_string().contains("Rate limit exceeded"));
+
+    // 60 requests per minute refill a token every second
+    tokio::time::sleep(Duration::from_millis(1100)).await;
+    send(&context, 101).await?;
+    Ok(())
+}
+
+/// Test that requests are not limited when rate limiting is disabled
+#[tokio::test]
+async fn test_disabled_rate_limiting_admits_every_request() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let mut config = context.blueprint_config.read().await.api.clone();
+    config.rate_limiting_enabled = false;
+    config.max_requests_per_minute = 1;
+    context.rate_limiter.reconfigure(&config);
+
+    for call_id in 0..5 {
+        send(&context, call_id).await?;
+    }
+    Ok(())
+}
+
+/// Test that exhausting the quota of one model leaves other models unaffected
+#[tokio::test]
+async fn test_model_quota_does_not_limit_other_models() -> color_eyre::Result<()> {
+    let mut context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.rate_limiter =
+        Arc::new(RateLimiter::new(MAX_REQUESTS_PER_MINUTE).with_model_limit(MODEL, 2));
+
+    send_for(&context, 0, MODEL).await?;
+    send_for(&context, 1, MODEL).await?;
+    let error = send_for(&context, 2, MODEL)
+        .await
+        .expect_err("the model's quota is exhausted");
+    assert!(error.to_string().contains("Rate limit exceeded"));
+
+    for call_id in 3..6 {
+        send_for(&context, call_id, OTHER_MODEL).await?;
+    }
+    Ok(())
+}
+
+/// Test that a model's limit applies to its `@provider` and aliased ids too
+#[tokio::test]
+async fn test_model_quota_applies_to_suffixed_and_aliased_ids() -> color_eyre::Result<()> {
+    let mut context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .model_aliases
+        .insert("turbo".to_string(), MODEL.to_string());
+    context.rate_limiter =
+        Arc::new(RateLimiter::new(MAX_REQUESTS_PER_MINUTE).with_model_limit(MODEL, 2));
+
+    context.try_acquire_rate_limit(MODEL).await?;
+    context
+        .try_acquire_rate_limit(&format!("{}@local", MODEL))
+        .await?;
+    assert!(context.try_acquire_rate_limit("turbo").await.is_err());
+    Ok(())
+}
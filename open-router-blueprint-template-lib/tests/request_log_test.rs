@@ -0,0 +1,127 @@
+use std::collections::HashMap;
+use std::fmt::Debug;
+use std::sync::{Arc, Mutex};
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{ChatCompletionRequest, ChatMessage, LlmRequest},
+    request_log::{REDACTED, REQUEST_LOG_TARGET},
+};
+use tracing::field::{Field, Visit};
+use tracing::Event;
+use tracing_subscriber::layer::{Context as LayerContext, SubscriberExt};
+use tracing_subscriber::Layer;
+
+const SECRET: &str = "my card number is 4111 1111 1111 1111";
+
+/// A tracing layer that records the fields of every request log event
+#[derive(Clone, Default)]
+struct RequestLogRecorder {
+    events: Arc<Mutex<Vec<HashMap<String, String>>>>,
+}
+
+#[derive(Default)]
+struct FieldVisitor(HashMap<String, String>);
+
+impl Visit for FieldVisitor {
+    fn record_str(&mut self, field: &Field, value: &str) {
+        self.0.insert(field.name().to_string(), value.to_string());
+    }
+
+    fn record_debug(&mut self, field: &Field, value: &dyn Debug) {
+        self.0
+            .insert(field.name().to_string(), format!("{:?}", value));
+    }
+}
+
+impl<S: tracing::Subscriber> Layer<S> for RequestLogRecorder {
+    fn on_event(&self, event: &Event<'_>, _ctx: LayerContext<'_, S>) {
+        if event.metadata().target() != REQUEST_LOG_TARGET {
+            return;
+        }
+        let mut visitor = FieldVisitor::default();
+        event.record(&mut visitor);
+        self.events.lock().unwrap().push(visitor.0);
+    }
+}
+
+/// Send a chat request with `SECRET` in its prompt, returning the request log events
+async fn logged_request(redact_content: bool) -> color_eyre::Result<Vec<HashMap<String, String>>> {
+    let recorder = RequestLogRecorder::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    {
+        let mut config = context.blueprint_config.write().await;
+        config.api.request_logging = true;
+        config.api.redact_content = redact_content;
+    }
+
+    let request = LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: "gpt-3.5-turbo".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: SECRET.to_string(),
+            ..Default::default()
+        }],
+        ..Default::default()
+    });
+    process_llm_request(Context(context), CallId(1), TangleArg(request)).await?;
+
+    let events = recorder.events.lock().unwrap().clone();
+    Ok(events)
+}
+
+/// Test that the request log event carries the request's details but not its content
+#[tokio::test]
+async fn test_request_log_redacts_content() -> color_eyre::Result<()> {
+    let events = logged_request(true).await?;
+
+    assert_eq!(events.len(), 1);
+    let event = &events[0];
+    assert_eq!(event["model"], "gpt-3.5-turbo");
+    assert_eq!(event["finish_reason"], "stop");
+    assert_eq!(event["status"], "ok");
+    assert!(event["prompt_tokens"].parse::<usize>()? > 0);
+    assert!(event.contains_key("latency_ms"));
+
+    assert_eq!(event["prompt"], REDACTED);
+    assert_eq!(event["output"], REDACTED);
+    assert!(!event.values().any(|value| value.contains("4111")));
+    Ok(())
+}
+
+/// Test that the content is logged when redaction is disabled
+#[tokio::test]
+async fn test_request_log_includes_content_unless_redacted() -> color_eyre::Result<()> {
+    let events = logged_request(false).await?;
+
+    assert_eq!(events.len(), 1);
+    assert!(events[0]["prompt"].contains(SECRET));
+    // The local client echoes the prompt back
+    assert!(events[0]["output"].contains(SECRET));
+    Ok(())
+}
+
+/// Test that no request log event is emitted unless enabled
+#[tokio::test]
+async fn test_request_log_is_disabled_by_default() -> color_eyre::Result<()> {
+    let recorder = RequestLogRecorder::default();
+    let _guard =
+        tracing::subscriber::set_default(tracing_subscriber::registry().with(recorder.clone()));
+
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let request = LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: "gpt-3.5-turbo".to_string(),
+        ..Default::default()
+    });
+    let _ = process_llm_request(Context(context), CallId(1), TangleArg(request)).await;
+
+    assert!(recorder.events.lock().unwrap().is_empty());
+    Ok(())
+}
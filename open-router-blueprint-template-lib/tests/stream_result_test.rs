@@ -0,0 +1,210 @@
+mod common;
+
+use std::sync::Arc;
+use std::time::Duration;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_request, message, MockBackend};
+use open_router_blueprint_template_lib::{
+    auth::Principal,
+    context::OpenRouterContext,
+    jobs::{poll_llm_stream, start_llm_stream, stream_chat_completion_for_principal},
+    llm::{
+        ChatCompletionRequest, ChatMessage, EmbeddingRequest, LlmError, LlmRequest, LlmResponse,
+        TextCompletionRequest,
+    },
+    stream_results::{StreamPoll, StreamReassembler},
+};
+
+/// Model served by the mock nodes
+const MODEL: &str = "stream-model";
+
+/// Poll a stream until its last page, reassembling its response
+async fn collect_stream(
+    context: &OpenRouterContext,
+    stream_id: &str,
+) -> color_eyre::Result<LlmResponse> {
+    let mut reassembler = StreamReassembler::new();
+    tokio::time::timeout(Duration::from_secs(5), async {
+        while !reassembler.is_complete() {
+            let poll = StreamPoll {
+                stream_id: stream_id.to_string(),
+                from_sequence: reassembler.next_sequence(),
+            };
+            let page = poll_llm_stream(Context(context.clone()), TangleArg(poll))
+                .await?
+                .0;
+            let done = page.done;
+            reassembler.push(page)?;
+            if done && !reassembler.is_complete() {
+                color_eyre::eyre::bail!("stream ended with chunks missing");
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        Ok::<_, color_eyre::Report>(())
+    })
+    .await??;
+    Ok(reassembler.into_response().await?)
+}
+
+/// Test that a chat completion streamed through job results reassembles into the response
+#[tokio::test]
+async fn test_chat_completion_streams_through_job_results() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let request = LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: "gpt-3.5-turbo".to_string(),
+        messages: vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Hello, stream!".to_string(),
+            ..Default::default()
+        }],
+        stream: Some(true),
+        ..Default::default()
+    });
+
+    let stream_id = start_llm_stream(Context(context.clone()), CallId(7), TangleArg(request))
+        .await?
+        .0;
+    assert_eq!(stream_id, "tangle-call-7");
+
+    // The local client echoes the prompt back
+    let LlmResponse::ChatCompletion(response) = collect_stream(&context, &stream_id).await? else {
+        panic!("expected a chat completion");
+    };
+    assert_eq!(response.choices[0].message.role, "assistant");
+    assert_eq!(response.choices[0].message.content, "Hello, stream!");
+
+    // The stream can be polled again once it finished
+    let poll = StreamPoll {
+        stream_id: stream_id.clone(),
+        from_sequence: 0,
+    };
+    let page = poll_llm_stream(Context(context.clone()), TangleArg(poll))
+        .await?
+        .0;
+    assert!(page.done);
+    assert!(!page.chunks.is_empty());
+    Ok(())
+}
+
+/// Test that a text completion streamed through job results reassembles into the response
+#[tokio::test]
+async fn test_text_completion_streams_through_job_results() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let request = LlmRequest::TextCompletion(TextCompletionRequest {
+        model: "text-davinci-003".to_string(),
+        prompt: "Once upon a time".to_string(),
+        ..Default::default()
+    });
+
+    let stream_id = start_llm_stream(Context(context.clone()), CallId(8), TangleArg(request))
+        .await?
+        .0;
+
+    let LlmResponse::TextCompletion(response) = collect_stream(&context, &stream_id).await? else {
+        panic!("expected a text completion");
+    };
+    assert_eq!(response.choices[0].text, "Once upon a time");
+    Ok(())
+}
+
+/// Test that embeddings and unknown streams are rejected
+#[tokio::test]
+async fn test_invalid_streams_are_rejected() -> color_eyre::Result<()> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    let request = LlmRequest::Embedding(EmbeddingRequest {
+        model: "gpt-3.5-turbo".to_string(),
+        input: vec!["Hello".to_string()],
+        ..Default::default()
+    });
+    assert!(
+        start_llm_stream(Context(context.clone()), CallId(9), TangleArg(request))
+            .await
+            .is_err()
+    );
+
+    let poll = StreamPoll {
+        stream_id: "tangle-call-9".to_string(),
+        from_sequence: 0,
+    };
+    assert!(poll_llm_stream(Context(context), TangleArg(poll))
+        .await
+        .is_err());
+    Ok(())
+}
+
+/// A context with `backend` as its only node serving `MODEL`
+async fn context_with_node(backend: Arc<MockBackend>) -> color_eyre::Result<OpenRouterContext> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.add_llm_node("node".to_string(), backend).await?;
+    Ok(context)
+}
+
+/// A backend serving `MODEL` that fails every completion as unavailable
+fn failing_backend() -> Arc<MockBackend> {
+    Arc::new(
+        MockBackend::new()
+            .serving(MODEL)
+            .failing(|| LlmError::RequestFailed("backend unavailable".to_string())),
+    )
+}
+
+/// Test that streamed requests are sent with the operator's default system prompt
+#[tokio::test]
+async fn test_streamed_request_gets_default_system_prompt() -> color_eyre::Result<()> {
+    let backend = Arc::new(MockBackend::new().serving(MODEL));
+    let context = context_with_node(backend.clone()).await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .default_system_prompt = Some("Be brief".to_string());
+
+    let request = chat_request(MODEL, "Hello");
+    let stream_id = start_llm_stream(Context(context.clone()), CallId(10), TangleArg(request))
+        .await?
+        .0;
+    collect_stream(&context, &stream_id).await?;
+
+    let requests = backend.chat_requests();
+    assert_eq!(requests.len(), 1);
+    assert_eq!(requests[0].messages[0].role, "system");
+    assert_eq!(requests[0].messages[0].content, "Be brief");
+    Ok(())
+}
+
+/// Test that a node failing a stream started as a job is recorded as failed
+#[tokio::test]
+async fn test_failed_stream_counts_against_its_node() -> color_eyre::Result<()> {
+    let context = context_with_node(failing_backend()).await?;
+
+    let request = chat_request(MODEL, "Hello");
+    let stream_id = start_llm_stream(Context(context.clone()), CallId(11), TangleArg(request))
+        .await?
+        .0;
+
+    assert!(collect_stream(&context, &stream_id).await.is_err());
+    assert!(context.load_balancer.get_node("node").await.unwrap().failed);
+    Ok(())
+}
+
+/// Test that a node failing a stream of an API caller is recorded as failed
+#[tokio::test]
+async fn test_failed_principal_stream_counts_against_its_node() -> color_eyre::Result<()> {
+    let context = context_with_node(failing_backend()).await?;
+    let request = ChatCompletionRequest {
+        model: MODEL.to_string(),
+        messages: vec![message("user", "Hello")],
+        ..Default::default()
+    };
+
+    let result =
+        stream_chat_completion_for_principal(&context, &Principal::new("alice"), request).await;
+
+    assert!(result.is_err());
+    assert!(context.load_balancer.get_node("node").await.unwrap().failed);
+    Ok(())
+}
@@ -0,0 +1,75 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{chat_request, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{LlmError, LlmRequest, LlmResponse},
+};
+
+const UNKNOWN_MODEL: &str = "unknown-model";
+
+/// A context whose only client is a permissive default client outside the load balancer,
+/// advertising no models but answering for any model name
+async fn context_with_permissive_default(strict: bool) -> color_eyre::Result<OpenRouterContext> {
+    let mut context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context.remove_llm_node("default").await;
+    context.llm_client = Arc::new(MockBackend::new().replying(|_| "Hi".to_string()));
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .strict_model_catalog = strict;
+    Ok(context)
+}
+
+fn unknown_model_request() -> LlmRequest {
+    chat_request(UNKNOWN_MODEL, "Hello")
+}
+
+/// Test that strict mode rejects a model no node serves instead of using the default client
+#[tokio::test]
+async fn test_strict_catalog_rejects_unknown_model() -> color_eyre::Result<()> {
+    let context = context_with_permissive_default(true).await?;
+
+    let result = process_llm_request(
+        Context(context),
+        CallId(1),
+        TangleArg(unknown_model_request()),
+    )
+    .await;
+
+    let error = result.expect_err("unknown model should be rejected");
+    assert!(error
+        .to_string()
+        .contains(&LlmError::ModelNotSupported(UNKNOWN_MODEL.to_string()).to_string()));
+    Ok(())
+}
+
+/// Test that permissive mode passes a model no node serves to the default client
+#[tokio::test]
+async fn test_permissive_catalog_falls_back_to_default_client() -> color_eyre::Result<()> {
+    let context = context_with_permissive_default(false).await?;
+
+    let result = process_llm_request(
+        Context(context),
+        CallId(1),
+        TangleArg(unknown_model_request()),
+    )
+    .await?;
+
+    match result.0 {
+        LlmResponse::ChatCompletion(response) => {
+            assert_eq!(response.model, UNKNOWN_MODEL);
+            assert_eq!(response.choices[0].message.content, "Hi");
+        }
+        other => panic!("Unexpected response type: {:?}", other),
+    }
+    Ok(())
+}
@@ -0,0 +1,85 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::extract::Context;
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use blueprint_sdk::tangle::extract::{CallId, TangleArg};
+use common::{message, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_llm_request,
+    llm::{ChatCompletionRequest, ChatMessage, LlmRequest},
+};
+
+const MODEL: &str = "prompted-model";
+const DEFAULT_PROMPT: &str = "You are a helpful assistant.";
+
+async fn context_with_default_system_prompt(
+) -> color_eyre::Result<(OpenRouterContext, Arc<MockBackend>)> {
+    let context = OpenRouterContext::new(BlueprintEnvironment::default()).await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .default_system_prompt = Some(DEFAULT_PROMPT.to_string());
+    let client = Arc::new(MockBackend::new().serving(MODEL));
+    context
+        .add_llm_node("recording".to_string(), client.clone())
+        .await?;
+    Ok((context, client))
+}
+
+fn chat_request(messages: Vec<ChatMessage>) -> LlmRequest {
+    LlmRequest::ChatCompletion(ChatCompletionRequest {
+        model: MODEL.to_string(),
+        messages,
+        ..Default::default()
+    })
+}
+
+/// Test that the default system prompt is sent first when the request has no system message
+#[tokio::test]
+async fn test_default_system_prompt_is_injected() -> color_eyre::Result<()> {
+    let (context, client) = context_with_default_system_prompt().await?;
+
+    let request = chat_request(vec![message("user", "Hi")]);
+    process_llm_request(Context(context), CallId(1), TangleArg(request)).await?;
+
+    let requests = client.chat_requests();
+    let messages = &requests[0].messages;
+    assert_eq!(messages.len(), 2);
+    assert_eq!(messages[0].role, "system");
+    assert_eq!(messages[0].content, DEFAULT_PROMPT);
+    assert_eq!(messages[1].content, "Hi");
+    Ok(())
+}
+
+/// Test that a request's own system message is kept unless overriding is configured
+#[tokio::test]
+async fn test_existing_system_message_is_kept() -> color_eyre::Result<()> {
+    let (context, client) = context_with_default_system_prompt().await?;
+    let request = || {
+        chat_request(vec![
+            message("system", "Answer in French."),
+            message("user", "Hi"),
+        ])
+    };
+
+    process_llm_request(Context(context.clone()), CallId(1), TangleArg(request())).await?;
+    context
+        .blueprint_config
+        .write()
+        .await
+        .llm
+        .override_system_prompt = true;
+    process_llm_request(Context(context), CallId(2), TangleArg(request())).await?;
+
+    let requests = client.chat_requests();
+    assert_eq!(requests[0].messages.len(), 2);
+    assert_eq!(requests[0].messages[0].content, "Answer in French.");
+    assert_eq!(requests[1].messages.len(), 2);
+    assert_eq!(requests[1].messages[0].content, DEFAULT_PROMPT);
+    Ok(())
+}
@@ -0,0 +1,79 @@
+mod common;
+
+use std::sync::Arc;
+
+use blueprint_sdk::runner::config::BlueprintEnvironment;
+use common::{chat_request, MockBackend};
+use open_router_blueprint_template_lib::{
+    context::OpenRouterContext,
+    jobs::process_request_for_principal,
+    llm::{LlmRequest, UsageInfo},
+};
+
+const METERED_MODEL: &str = "metered-model";
+
+/// A backend serving `METERED_MODEL` that reports 60 tokens per request
+fn metered_backend() -> Arc<MockBackend> {
+    Arc::new(
+        MockBackend::new()
+            .serving(METERED_MODEL)
+            .with_usage(UsageInfo {
+                prompt_tokens: 50,
+                completion_tokens: 10,
+                total_tokens: 60,
+                prompt_tokens_cached: None,
+            }),
+    )
+}
+
+fn metered_request() -> LlmRequest {
+    chat_request(METERED_MODEL, "Hello")
+}
+
+/// Test that a principal over its token budget is rejected while another one proceeds
+#[tokio::test]
+async fn test_budget_is_enforced_per_principal() -> color_eyre::Result<()> {
+    let data_dir = tempfile::tempdir()?;
+    let keys_file = data_dir.path().join("keys.json");
+    std::fs::write(
+        &keys_file,
+        r#"[
+            { "key": "sk-small", "principal": "small-tenant", "monthly_token_budget": 100 },
+            { "key": "sk-large", "principal": "large-tenant" }
+        ]"#,
+    )?;
+    let config = serde_json::json!({
+        "api": { "auth_enabled": true, "keys_file": keys_file }
+    });
+    std::fs::write(data_dir.path().join("config.json"), config.to_string())?;
+    let mut env = BlueprintEnvironment::default();
+    env.data_dir = Some(data_dir.path().to_path_buf());
+    let context = OpenRouterContext::new(env).await?;
+    context
+        .add_llm_node("metered".to_string(), metered_backend())
+        .await?;
+
+    let small = context.authenticate(Some("sk-small")).await.unwrap();
+    let large = context.authenticate(Some("sk-large")).await.unwrap();
+
+    // 60 tokens each: the second request crosses the budget of 100, the third is rejected
+    for _ in 0..2 {
+        process_request_for_principal(&context, &small, metered_request()).await?;
+    }
+    let error = process_request_for_principal(&context, &small, metered_request())
+        .await
+        .expect_err("budget is exhausted");
+    assert!(error.to_string().contains("Rate limit exceeded"));
+
+    for _ in 0..3 {
+        process_request_for_principal(&context, &large, metered_request()).await?;
+    }
+
+    let report = context.usage_report();
+    assert_eq!(report["small-tenant"].requests, 2);
+    assert_eq!(report["small-tenant"].rejected_requests, 1);
+    assert_eq!(report["small-tenant"].total_tokens, 120);
+    assert_eq!(report["large-tenant"].requests, 3);
+    assert_eq!(report["large-tenant"].total_tokens, 180);
+    Ok(())
+}